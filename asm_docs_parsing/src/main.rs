@@ -1,7 +1,7 @@
 use std::path::PathBuf;
 
 use ::asm_lsp::x86_parser::{populate_instructions, populate_registers};
-use asm_lsp::populate_directives;
+use asm_lsp::{populate_directives, Arch};
 
 use anyhow::{anyhow, Result};
 use clap::{Parser, Subcommand};
@@ -56,6 +56,15 @@ fn run(opts: &SerializeDocs) -> Result<()> {
                     "Failed to determine architecture -- Zero instructions read in"
                 ));
             };
+            // x86 hover links to the online docs through these URLs, including in offline
+            // mode, so don't bundle instructions without them
+            if matches!(arch, Arch::X86 | Arch::X86_64)
+                && instrs.iter().all(|instr| instr.urls.is_empty())
+            {
+                return Err(anyhow!(
+                    "Failed to link instructions to their documentation -- Check that the x86 docs page can be fetched"
+                ));
+            }
             let serialized = bincode::serialize(&instrs)?;
             let output_path: PathBuf = if let Some(ref path) = opts.output_path {
                 path.to_owned()
@@ -0,0 +1,120 @@
+use std::{collections::HashMap, str::FromStr};
+
+use asm_lsp::{
+    get_comp_resp, get_hover_resp, get_word_from_pos_params,
+    test_utils::{init_global_info, init_store, test_config},
+    TreeEntry,
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lsp_textdocument::FullTextDocument;
+use lsp_types::{
+    CompletionContext, CompletionParams, CompletionTriggerKind, HoverParams, PartialResultParams,
+    Position, TextDocumentIdentifier, TextDocumentPositionParams, Uri, WorkDoneProgressParams,
+};
+use tree_sitter::Parser;
+
+/// A short function touching instructions, registers, directives and labels
+const SOURCE: &str = "	.text
+	.globl	sum
+	.type	sum, @function
+sum:
+	xorl	%eax, %eax
+	testq	%rsi, %rsi
+	je	.Ldone
+.Lloop:
+	addl	(%rdi), %eax
+	addq	$4, %rdi
+	decq	%rsi
+	jne	.Lloop
+.Ldone:
+	ret
+	.size	sum, .-sum
+";
+
+fn tree_entry() -> TreeEntry {
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_asm::language()).unwrap();
+    let tree = parser.parse(SOURCE, None);
+    TreeEntry { tree, parser }
+}
+
+fn pos_params(line: u32, character: u32) -> TextDocumentPositionParams {
+    TextDocumentPositionParams {
+        text_document: TextDocumentIdentifier {
+            uri: Uri::from_str("file://").unwrap(),
+        },
+        position: Position { line, character },
+    }
+}
+
+fn bench_latency(c: &mut Criterion) {
+    let info = init_global_info(None).expect("Failed to load info");
+    let store = init_store(&info).expect("Failed to initialize store");
+    let config = test_config();
+    let doc = FullTextDocument::new("asm".to_string(), 1, SOURCE.to_string());
+
+    // hover `addq`
+    let hover_params = HoverParams {
+        text_document_position_params: pos_params(9, 3),
+        work_done_progress_params: WorkDoneProgressParams {
+            work_done_token: None,
+        },
+    };
+    let mut hover_tree = tree_entry();
+    c.bench_function("hover instruction", |b| {
+        b.iter(|| {
+            let params = &hover_params.text_document_position_params;
+            let word = get_word_from_pos_params(&doc, params, "@");
+            let file_word = get_word_from_pos_params(&doc, params, ".");
+            get_hover_resp(
+                black_box(&hover_params),
+                word,
+                file_word,
+                &doc,
+                Some(&mut hover_tree),
+                &store.names_to_instructions,
+                &store.names_to_registers,
+                &store.names_to_directives,
+                &store.same_operations,
+                &HashMap::new(),
+                &config,
+                &[],
+            )
+        });
+    });
+
+    // complete the `%` register operand of `addl`
+    let comp_params = CompletionParams {
+        text_document_position: pos_params(8, 15),
+        work_done_progress_params: WorkDoneProgressParams {
+            work_done_token: None,
+        },
+        partial_result_params: PartialResultParams {
+            partial_result_token: None,
+        },
+        context: Some(CompletionContext {
+            trigger_kind: CompletionTriggerKind::TRIGGER_CHARACTER,
+            trigger_character: Some(String::from("%")),
+        }),
+    };
+    let mut comp_tree = tree_entry();
+    c.bench_function("complete register", |b| {
+        b.iter(|| {
+            get_comp_resp(
+                SOURCE,
+                &mut comp_tree,
+                black_box(&comp_params),
+                &store.instr_completion_items,
+                &store.directive_completion_items,
+                &store.reg_completion_items,
+                &store.names_to_instructions,
+                &store.names_to_directives,
+                &config,
+                &[],
+            )
+        });
+    });
+}
+
+criterion_group!(benches, bench_latency);
+criterion_main!(benches);
@@ -1,16 +1,28 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 use asm_lsp::handle::{
+    handle_code_action_request, handle_code_lens_request, handle_code_lens_resolve_request,
     handle_completion_request, handle_diagnostics, handle_did_change_text_document_notification,
     handle_did_close_text_document_notification, handle_did_open_text_document_notification,
-    handle_document_symbols_request, handle_goto_def_request, handle_hover_request,
-    handle_references_request, handle_signature_help_request,
+    handle_document_highlight_request, handle_document_symbols_request,
+    handle_execute_command_request, handle_file_summary_request, handle_folding_range_request,
+    handle_goto_def_request, handle_hover_request, handle_incoming_calls_request,
+    handle_outgoing_calls_request, handle_prepare_call_hierarchy_request,
+    handle_references_request, handle_selection_range_request, handle_signature_help_request,
+    handle_type_definition_request, save_completion_frequencies, update_included_files,
+    PendingDiagnostics, PendingFrequenciesSave, WorkDoneProgressReporter,
 };
+use asm_lsp::x86_parser::get_cache_dir;
 use asm_lsp::{
-    get_compile_cmds, get_completes, get_include_dirs, get_target_config, instr_filter_targets,
-    populate_name_to_directive_map, populate_name_to_instruction_map,
-    populate_name_to_register_map, Arch, Assembler, Instruction, NameToInfoMaps, TreeStore,
+    arch_providers, data_hash, get_compile_cmds, get_completes, get_hover_html, get_include_dirs,
+    get_target_config, instr_filter_targets, load_directives, load_instructions,
+    populate_custom_instructions, populate_name_to_directive_map, populate_name_to_instruction_map,
+    populate_name_to_register_map, populate_prefix_index, populate_same_operation_map,
+    relink_z80_docs, Arch, Assembler, CompletionCase, CompletionFrequencies, Directive,
+    FileSummaryRequest, IncludeStore, Instruction, NameToInfoMaps, Register, TargetConfig,
+    TreeStore, COMPLETION_FREQUENCIES_FILE, RECORD_COMPLETION_COMMAND,
 };
 
 use compile_commands::{CompilationDatabase, SourceFile};
@@ -18,27 +30,68 @@ use lsp_types::notification::{
     DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
 };
 use lsp_types::request::{
-    Completion, DocumentDiagnosticRequest, DocumentSymbolRequest, GotoDefinition, HoverRequest,
-    References, SignatureHelpRequest,
+    CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare,
+    CodeActionRequest, CodeLensRequest, CodeLensResolve, Completion, DocumentDiagnosticRequest,
+    DocumentHighlightRequest, DocumentSymbolRequest, ExecuteCommand, FoldingRangeRequest,
+    GotoDefinition, GotoTypeDefinition, HoverRequest, References, SelectionRangeRequest,
+    SignatureHelpRequest,
 };
 use lsp_types::{
-    CompletionItem, CompletionItemKind, CompletionOptions, CompletionOptionsCompletionItem,
-    DiagnosticOptions, DiagnosticServerCapabilities, HoverProviderCapability, InitializeParams,
-    OneOf, PositionEncodingKind, ServerCapabilities, SignatureHelpOptions,
-    TextDocumentSyncCapability, TextDocumentSyncKind, WorkDoneProgressOptions,
+    CallHierarchyServerCapability, CodeActionProviderCapability, CodeLensOptions, CompletionItem,
+    CompletionItemKind, CompletionOptions, CompletionOptionsCompletionItem, DiagnosticOptions,
+    DiagnosticServerCapabilities, ExecuteCommandOptions, FoldingRangeProviderCapability,
+    HoverProviderCapability, InitializeParams, OneOf, PositionEncodingKind,
+    SelectionRangeProviderCapability, ServerCapabilities, SignatureHelpOptions,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TypeDefinitionProviderCapability, Uri,
+    WorkDoneProgressOptions,
 };
 
 use anyhow::Result;
-use log::{error, info};
+use log::{error, info, warn, LevelFilter};
 use lsp_server::{Connection, Message, Notification, Request, RequestId};
 use lsp_textdocument::TextDocuments;
 
+// bundled documentation --------------------------------------------------------------------------
+const X86_INSTRS: &[u8] = include_bytes!("../../docs_store/opcodes/serialized/x86");
+const X86_64_INSTRS: &[u8] = include_bytes!("../../docs_store/opcodes/serialized/x86_64");
+const Z80_INSTRS: &[u8] = include_bytes!("../../docs_store/opcodes/serialized/z80");
+const X86_REGS: &[u8] = include_bytes!("../../docs_store/registers/serialized/x86");
+const X86_64_REGS: &[u8] = include_bytes!("../../docs_store/registers/serialized/x86_64");
+const Z80_REGS: &[u8] = include_bytes!("../../docs_store/registers/serialized/z80");
+const AARCH64_REGS: &[u8] = include_bytes!("../../docs_store/registers/serialized/aarch64");
+const GAS_DIRS: &[u8] = include_bytes!("../../docs_store/directives/serialized/gas");
+
 // main -------------------------------------------------------------------------------------------
 pub fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--self-test") {
+        std::process::exit(i32::from(!run_self_test()));
+    }
+    if args.iter().any(|arg| arg == "--version-data") {
+        std::process::exit(i32::from(!run_version_data()));
+    }
+    if let Some(idx) = args.iter().position(|arg| arg == "--hover-html") {
+        let (arch, name) = (args.get(idx + 1), args.get(idx + 2));
+        std::process::exit(i32::from(!run_hover_html(arch, name)));
+    }
+
     // initialisation -----------------------------------------------------------------------------
     // Set up logging. Because `stdio_transport` gets a lock on stdout and stdin, we must have our
     // logging only write out to stderr.
-    flexi_logger::Logger::try_with_str("info")?.start()?;
+    let log_var = std::env::var("ASM_LSP_LOG").ok();
+    let log_level = log_var.as_deref().map(LevelFilter::from_str);
+    let log_spec = flexi_logger::LogSpecification::builder()
+        .default(match log_level {
+            Some(Ok(level)) => level,
+            _ => LevelFilter::Warn,
+        })
+        .build();
+    flexi_logger::Logger::with(log_spec).start()?;
+    if let (Some(val), Some(Err(_))) = (log_var, log_level) {
+        warn!(
+            "Ignoring invalid ASM_LSP_LOG value '{val}', expected one of off, error, warn, info, debug or trace"
+        );
+    }
 
     // LSP server initialisation ------------------------------------------------------------------
     info!("Starting asm_lsp...");
@@ -52,11 +105,26 @@ pub fn main() -> Result<()> {
     // Run the server and wait for the two threads to end (typically by trigger LSP Exit event).
     let hover_provider = Some(HoverProviderCapability::Simple(true));
 
+    // register and immediate prefixes of each architecture, then GAS directives and
+    // macro parameters
+    let mut trigger_characters: Vec<String> = Vec::new();
+    for prefix in arch_providers().iter().flat_map(|provider| {
+        provider
+            .register_prefix()
+            .into_iter()
+            .chain(provider.immediate_prefix())
+    }) {
+        if !trigger_characters.iter().any(|trigger| trigger == prefix) {
+            trigger_characters.push(prefix.to_string());
+        }
+    }
+    trigger_characters.extend([String::from("."), String::from("\\")]);
+
     let completion_provider = Some(CompletionOptions {
         completion_item: Some(CompletionOptionsCompletionItem {
             label_details_support: Some(true),
         }),
-        trigger_characters: Some(vec![String::from("%"), String::from(".")]),
+        trigger_characters: Some(trigger_characters),
         ..Default::default()
     });
 
@@ -91,34 +159,63 @@ pub fn main() -> Result<()> {
         completion_provider,
         signature_help_provider,
         definition_provider,
+        type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
         text_document_sync,
         document_symbol_provider: Some(OneOf::Left(true)),
+        document_highlight_provider: Some(OneOf::Left(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+        call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        code_lens_provider: Some(CodeLensOptions {
+            resolve_provider: Some(true),
+        }),
+        execute_command_provider: Some(ExecuteCommandOptions {
+            commands: vec![RECORD_COMPLETION_COMMAND.to_string()],
+            ..Default::default()
+        }),
         references_provider,
         diagnostic_provider,
         ..ServerCapabilities::default()
     };
     let server_capabilities = serde_json::to_value(capabilities).unwrap();
-    let initialization_params = connection.initialize(server_capabilities)?;
+    // The initialize response is held back until the documentation is loaded so that
+    // progress can be reported against the client's initialize work done token
+    let (initialize_id, initialization_params) = connection.initialize_start()?;
 
     let mut names_to_info = NameToInfoMaps::default();
     let params: InitializeParams = serde_json::from_value(initialization_params.clone()).unwrap();
     let target_config = get_target_config(&params);
     info!("Server Configuration: {:?}", target_config);
 
+    let progress = WorkDoneProgressReporter::new(
+        &connection,
+        params.work_done_progress_params.work_done_token.clone(),
+    );
+    progress.begin("Loading asm-lsp documentation")?;
+    progress.report("Loading instruction data…", 0)?;
+
+    // raw instruction XML in the cache directory overrides the bundled instructions
+    let cache_dir = get_cache_dir().ok();
+
     // create a map of &Instruction_name -> &Instruction - Use that in user queries
     // The Instruction(s) themselves are stored in a vector and we only keep references to the
     // former map
     let x86_instructions = if target_config.instruction_sets.x86 {
         let start = std::time::Instant::now();
-        let x86_instrs = include_bytes!("../../docs_store/opcodes/serialized/x86");
-        let instrs = bincode::deserialize::<Vec<Instruction>>(x86_instrs)?
-            .into_iter()
-            .map(|instruction| {
-                // filter out assemblers by user config
-                instr_filter_targets(&instruction, &target_config)
-            })
-            .filter(|instruction| !instruction.forms.is_empty())
-            .collect();
+        let instrs = load_instructions(
+            "x86",
+            X86_INSTRS,
+            cache_dir.as_deref(),
+            target_config.opts.offline,
+        )?
+        .into_iter()
+        .map(|instruction| {
+            // filter out assemblers by user config
+            instr_filter_targets(&instruction, &target_config)
+        })
+        .filter(|instruction| !instruction.forms.is_empty())
+        .collect();
         info!(
             "x86 instruction set loaded in {}ms",
             start.elapsed().as_millis()
@@ -130,15 +227,19 @@ pub fn main() -> Result<()> {
 
     let x86_64_instructions = if target_config.instruction_sets.x86_64 {
         let start = std::time::Instant::now();
-        let x86_64_instrs = include_bytes!("../../docs_store/opcodes/serialized/x86_64");
-        let instrs = bincode::deserialize::<Vec<Instruction>>(x86_64_instrs)?
-            .into_iter()
-            .map(|instruction| {
-                // filter out assemblers by user config
-                instr_filter_targets(&instruction, &target_config)
-            })
-            .filter(|instruction| !instruction.forms.is_empty())
-            .collect();
+        let instrs = load_instructions(
+            "x86_64",
+            X86_64_INSTRS,
+            cache_dir.as_deref(),
+            target_config.opts.offline,
+        )?
+        .into_iter()
+        .map(|instruction| {
+            // filter out assemblers by user config
+            instr_filter_targets(&instruction, &target_config)
+        })
+        .filter(|instruction| !instruction.forms.is_empty())
+        .collect();
         info!(
             "x86-64 instruction set loaded in {}ms",
             start.elapsed().as_millis()
@@ -150,15 +251,20 @@ pub fn main() -> Result<()> {
 
     let z80_instructions = if target_config.instruction_sets.z80 {
         let start = std::time::Instant::now();
-        let z80_instrs = include_bytes!("../../docs_store/opcodes/serialized/z80");
-        let instrs = bincode::deserialize::<Vec<Instruction>>(z80_instrs)?
-            .into_iter()
-            .map(|instruction| {
-                // filter out assemblers by user config
-                instr_filter_targets(&instruction, &target_config)
-            })
-            .filter(|instruction| !instruction.forms.is_empty())
-            .collect();
+        let mut instrs: Vec<Instruction> = load_instructions(
+            "z80",
+            Z80_INSTRS,
+            cache_dir.as_deref(),
+            target_config.opts.offline,
+        )?
+        .into_iter()
+        .map(|instruction| {
+            // filter out assemblers by user config
+            instr_filter_targets(&instruction, &target_config)
+        })
+        .filter(|instruction| !instruction.forms.is_empty())
+        .collect();
+        relink_z80_docs(&mut instrs, target_config.opts.z80_docs);
         info!(
             "z80 instruction set loaded in {}ms",
             start.elapsed().as_millis()
@@ -183,14 +289,21 @@ pub fn main() -> Result<()> {
         &z80_instructions,
         &mut names_to_info.instructions,
     );
+    let custom_instructions = target_config.custom_instructions();
+    populate_custom_instructions(&custom_instructions, &mut names_to_info.instructions);
+    populate_same_operation_map(
+        &names_to_info.instructions,
+        &mut names_to_info.same_operations,
+    );
+
+    progress.report("Loading register data…", 45)?;
 
     // create a map of &Register_name -> &Register - Use that in user queries
     // The Register(s) themselves are stored in a vector and we only keep references to the
     // former map
     let x86_registers = if target_config.instruction_sets.x86 {
         let start = std::time::Instant::now();
-        let regs_x86 = include_bytes!("../../docs_store/registers/serialized/x86");
-        let regs = bincode::deserialize(regs_x86)?;
+        let regs = bincode::deserialize(X86_REGS)?;
         info!(
             "x86 register set loaded in {}ms",
             start.elapsed().as_millis()
@@ -202,8 +315,7 @@ pub fn main() -> Result<()> {
 
     let x86_64_registers = if target_config.instruction_sets.x86_64 {
         let start = std::time::Instant::now();
-        let regs_x86_64 = include_bytes!("../../docs_store/registers/serialized/x86_64");
-        let regs = bincode::deserialize(regs_x86_64)?;
+        let regs = bincode::deserialize(X86_64_REGS)?;
         info!(
             "x86-64 register set loaded in {}ms",
             start.elapsed().as_millis()
@@ -215,8 +327,7 @@ pub fn main() -> Result<()> {
 
     let z80_registers = if target_config.instruction_sets.z80 {
         let start = std::time::Instant::now();
-        let regs_z80 = include_bytes!("../../docs_store/registers/serialized/z80");
-        let regs = bincode::deserialize(regs_z80)?;
+        let regs = bincode::deserialize(Z80_REGS)?;
         info!(
             "z80 register set loaded in {}ms",
             start.elapsed().as_millis()
@@ -226,6 +337,18 @@ pub fn main() -> Result<()> {
         Vec::new()
     };
 
+    let aarch64_registers = if target_config.instruction_sets.aarch64 {
+        let start = std::time::Instant::now();
+        let regs = bincode::deserialize(AARCH64_REGS)?;
+        info!(
+            "aarch64 register set loaded in {}ms",
+            start.elapsed().as_millis()
+        );
+        regs
+    } else {
+        Vec::new()
+    };
+
     populate_name_to_register_map(Arch::X86, &x86_registers, &mut names_to_info.registers);
     populate_name_to_register_map(
         Arch::X86_64,
@@ -233,11 +356,18 @@ pub fn main() -> Result<()> {
         &mut names_to_info.registers,
     );
     populate_name_to_register_map(Arch::Z80, &z80_registers, &mut names_to_info.registers);
+    populate_name_to_register_map(
+        Arch::AArch64,
+        &aarch64_registers,
+        &mut names_to_info.registers,
+    );
+
+    progress.report("Loading directive data…", 70)?;
 
-    let gas_directives = if target_config.assemblers.gas {
+    let directive_assemblers = target_config.directive_assemblers();
+    let gas_directives = if directive_assemblers.contains(&Assembler::Gas) {
         let start = std::time::Instant::now();
-        let gas_dirs = include_bytes!("../../docs_store/directives/serialized/gas");
-        let dirs = bincode::deserialize(gas_dirs)?;
+        let dirs = load_directives(GAS_DIRS, target_config.opts.extra_directives.as_deref())?;
         info!(
             "Gas directive set loaded in {}ms",
             start.elapsed().as_millis()
@@ -252,22 +382,48 @@ pub fn main() -> Result<()> {
         &gas_directives,
         &mut names_to_info.directives,
     );
+    populate_prefix_index(
+        &names_to_info.instructions,
+        &mut names_to_info.instruction_prefixes,
+    );
+    populate_prefix_index(
+        &names_to_info.registers,
+        &mut names_to_info.register_prefixes,
+    );
+    populate_prefix_index(
+        &names_to_info.directives,
+        &mut names_to_info.directive_prefixes,
+    );
 
+    let completion_case = target_config.opts.completion_case;
     let instr_completion_items = get_completes(
         &names_to_info.instructions,
         Some(CompletionItemKind::OPERATOR),
+        completion_case,
+    );
+    let reg_completion_items = get_completes(
+        &names_to_info.registers,
+        Some(CompletionItemKind::VARIABLE),
+        completion_case,
     );
-    let reg_completion_items =
-        get_completes(&names_to_info.registers, Some(CompletionItemKind::VARIABLE));
     let directive_completion_items = get_completes(
         &names_to_info.directives,
-        Some(CompletionItemKind::OPERATOR),
+        Some(CompletionItemKind::KEYWORD),
+        CompletionCase::AsIs,
     );
 
+    progress.report("Loading compile commands…", 90)?;
+
     let compile_cmds = get_compile_cmds(&params).unwrap_or_default();
     info!("Loaded compile commands: {:?}", compile_cmds);
     let include_dirs = get_include_dirs(&compile_cmds);
 
+    progress.end("Documentation loaded")?;
+    connection.initialize_finish(
+        initialize_id,
+        serde_json::json!({ "capabilities": server_capabilities }),
+    )?;
+
     main_loop(
         &connection,
         &names_to_info,
@@ -276,6 +432,7 @@ pub fn main() -> Result<()> {
         &reg_completion_items,
         &compile_cmds,
         &include_dirs,
+        &target_config,
     )?;
     io_threads.join()?;
 
@@ -283,6 +440,215 @@ pub fn main() -> Result<()> {
     Ok(())
 }
 
+/// Prints the hover for the instruction or register `name` of `arch` as an HTML fragment
+///
+/// Returns `false` if either argument is missing or invalid, or `arch` doesn't define `name`
+fn run_hover_html(arch: Option<&String>, name: Option<&String>) -> bool {
+    let (Some(arch_name), Some(name)) = (arch, name) else {
+        eprintln!("usage: asm-lsp --hover-html <arch> <name>");
+        return false;
+    };
+    // accept the config file's spelling (e.g. `x86_64`) too
+    let Ok(arch) =
+        Arch::from_str(arch_name).or_else(|_| Arch::from_str(&arch_name.replace('_', "-")))
+    else {
+        eprintln!("error: unknown architecture `{arch_name}`");
+        return false;
+    };
+
+    let (instrs, regs) = match arch {
+        Arch::X86 => (Some(X86_INSTRS), X86_REGS),
+        Arch::X86_64 => (Some(X86_64_INSTRS), X86_64_REGS),
+        Arch::Z80 => (Some(Z80_INSTRS), Z80_REGS),
+        Arch::AArch64 => (None, AARCH64_REGS),
+    };
+    let instrs: Vec<Instruction> = match instrs.map(bincode::deserialize).transpose() {
+        Ok(instrs) => instrs.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("error: failed to deserialize bundled {arch} instructions -- {e}");
+            return false;
+        }
+    };
+    let regs: Vec<Register> = match bincode::deserialize(regs) {
+        Ok(regs) => regs,
+        Err(e) => {
+            eprintln!("error: failed to deserialize bundled {arch} registers -- {e}");
+            return false;
+        }
+    };
+
+    let mut names_to_info = NameToInfoMaps::default();
+    populate_name_to_instruction_map(arch, &instrs, &mut names_to_info.instructions);
+    populate_name_to_register_map(arch, &regs, &mut names_to_info.registers);
+    if let Some(html) = get_hover_html(arch, name, &names_to_info) {
+        print!("{html}");
+        true
+    } else {
+        eprintln!("error: {arch} doesn't define `{name}`");
+        false
+    }
+}
+
+/// Prints the crate version along with the number of items and a content hash of each
+/// bundled blob, to identify the documentation data a build ships with when reporting
+/// outdated or wrong docs. Returns false if any of the data fails to deserialize
+fn run_version_data() -> bool {
+    fn report<T: serde::de::DeserializeOwned + serde::Serialize>(blob: &[u8], what: &str) -> bool {
+        match bincode::deserialize::<Vec<T>>(blob)
+            .map_err(anyhow::Error::from)
+            .and_then(|items| Ok((items.len(), data_hash(&items)?)))
+        {
+            Ok((count, hash)) => {
+                println!("{what}: {count} (hash {hash:016x})");
+                true
+            }
+            Err(e) => {
+                eprintln!("error: failed to load bundled {what} -- {e}");
+                false
+            }
+        }
+    }
+
+    println!("asm-lsp {}", env!("CARGO_PKG_VERSION"));
+    let mut ok = true;
+    for (arch, instrs) in [
+        (Arch::X86, X86_INSTRS),
+        (Arch::X86_64, X86_64_INSTRS),
+        (Arch::Z80, Z80_INSTRS),
+    ] {
+        ok &= report::<Instruction>(instrs, &format!("{arch} instructions"));
+    }
+    for (arch, regs) in [
+        (Arch::X86, X86_REGS),
+        (Arch::X86_64, X86_64_REGS),
+        (Arch::Z80, Z80_REGS),
+        (Arch::AArch64, AARCH64_REGS),
+    ] {
+        ok &= report::<Register>(regs, &format!("{arch} registers"));
+    }
+    ok &= report::<Directive>(GAS_DIRS, "Gas directives");
+
+    ok
+}
+
+/// Deserializes every bundled documentation blob, builds all of the name maps, and
+/// reports how many entries were loaded for each architecture/assembler, along with
+/// any names which were dropped because another entry already claimed them
+///
+/// Returns `false` if any blob fails to deserialize or comes back empty
+fn run_self_test() -> bool {
+    fn load<T: serde::de::DeserializeOwned>(blob: &[u8], what: &str) -> Option<Vec<T>> {
+        match bincode::deserialize::<Vec<T>>(blob) {
+            Ok(items) if !items.is_empty() => Some(items),
+            Ok(_) => {
+                eprintln!("error: bundled {what} are empty");
+                None
+            }
+            Err(e) => {
+                eprintln!("error: failed to deserialize bundled {what} -- {e}");
+                None
+            }
+        }
+    }
+
+    let mut ok = true;
+    let mut names_to_info = NameToInfoMaps::default();
+
+    // aarch64 only has system registers bundled so far
+    let instr_sets = [
+        (Arch::X86, Some(X86_INSTRS), X86_REGS),
+        (Arch::X86_64, Some(X86_64_INSTRS), X86_64_REGS),
+        (Arch::Z80, Some(Z80_INSTRS), Z80_REGS),
+        (Arch::AArch64, None, AARCH64_REGS),
+    ];
+    let loaded: Vec<(Arch, Vec<Instruction>, Vec<Register>)> = instr_sets
+        .iter()
+        .map(|(arch, instrs, regs)| {
+            let instrs = match instrs {
+                Some(instrs) => load::<Instruction>(instrs, &format!("{arch} instructions")),
+                None => Some(Vec::new()),
+            };
+            let regs = load::<Register>(regs, &format!("{arch} registers"));
+            ok &= instrs.is_some() && regs.is_some();
+            (*arch, instrs.unwrap_or_default(), regs.unwrap_or_default())
+        })
+        .collect();
+    let gas_directives = load::<Directive>(GAS_DIRS, "Gas directives");
+    ok &= gas_directives.is_some();
+    let gas_directives = gas_directives.unwrap_or_default();
+
+    for (arch, instrs, regs) in &loaded {
+        populate_name_to_instruction_map(*arch, instrs, &mut names_to_info.instructions);
+        populate_name_to_register_map(*arch, regs, &mut names_to_info.registers);
+        println!(
+            "{arch}: {} instructions, {} registers",
+            instrs.len(),
+            regs.len()
+        );
+    }
+    populate_name_to_directive_map(
+        Assembler::Gas,
+        &gas_directives,
+        &mut names_to_info.directives,
+    );
+    println!("Gas: {} directives", gas_directives.len());
+
+    // any name whose map entry isn't the item declaring it was silently dropped
+    let mut num_dropped = 0;
+    for (arch, instrs, regs) in &loaded {
+        for instr in instrs {
+            let mut names = instr.get_primary_names();
+            names.extend(instr.get_associated_names());
+            // forms frequently share a name, only report each one once
+            names.sort_unstable();
+            names.dedup();
+            for name in &names {
+                if let Some(entry) = names_to_info.instructions.get(&(*arch, *name)) {
+                    if !std::ptr::eq(*entry, instr) {
+                        println!(
+                            "warning: {arch} instruction name `{name}` of {} is taken by {}",
+                            instr.name, entry.name
+                        );
+                        num_dropped += 1;
+                    }
+                }
+            }
+        }
+        for reg in regs {
+            for name in &reg.get_associated_names() {
+                if let Some(entry) = names_to_info.registers.get(&(*arch, *name)) {
+                    if !std::ptr::eq(*entry, reg) {
+                        println!(
+                            "warning: {arch} register name `{name}` of {} is taken by {}",
+                            reg.name, entry.name
+                        );
+                        num_dropped += 1;
+                    }
+                }
+            }
+        }
+    }
+    for dir in &gas_directives {
+        for name in &dir.get_associated_names() {
+            if let Some(entry) = names_to_info.directives.get(&(Assembler::Gas, *name)) {
+                if !std::ptr::eq(*entry, dir) {
+                    println!(
+                        "warning: Gas directive name `{name}` of {} is taken by {}",
+                        dir.name, entry.name
+                    );
+                    num_dropped += 1;
+                }
+            }
+        }
+    }
+    if num_dropped > 0 {
+        println!("{num_dropped} name(s) dropped during map population");
+    }
+
+    println!("Self-test {}", if ok { "passed" } else { "failed" });
+    ok
+}
+
 #[allow(clippy::too_many_arguments)]
 fn main_loop(
     connection: &Connection,
@@ -292,16 +658,75 @@ fn main_loop(
     register_completion_items: &[CompletionItem],
     compile_cmds: &CompilationDatabase,
     include_dirs: &HashMap<SourceFile, Vec<PathBuf>>,
+    config: &TargetConfig,
 ) -> Result<()> {
     let mut text_store = TextDocuments::new();
     let mut tree_store = TreeStore::new();
+    let mut include_store = IncludeStore::new();
+    let frequencies_path = get_cache_dir()
+        .ok()
+        .map(|dir| dir.join(COMPLETION_FREQUENCIES_FILE));
+    let mut frequencies = frequencies_path
+        .as_deref()
+        .map(CompletionFrequencies::load)
+        .unwrap_or_default();
+
+    let mut pending_diagnostics = PendingDiagnostics::default();
+    let mut pending_frequencies_save = PendingFrequenciesSave::default();
+    let save_frequencies = |frequencies: &CompletionFrequencies| {
+        if let Some(path) = frequencies_path.as_deref() {
+            save_completion_frequencies(frequencies, path);
+        }
+    };
 
     info!("Starting asm_lsp loop...");
-    for msg in &connection.receiver {
+    loop {
+        let deadline = pending_diagnostics
+            .next_deadline()
+            .into_iter()
+            .chain(pending_frequencies_save.next_deadline())
+            .min();
+        let msg = match deadline {
+            Some(deadline) => match connection.receiver.recv_deadline(deadline) {
+                Ok(msg) => msg,
+                Err(e) if e.is_timeout() => {
+                    let now = std::time::Instant::now();
+                    if pending_frequencies_save.take_due(now) {
+                        save_frequencies(&frequencies);
+                    }
+                    for uri in pending_diagnostics.take_due(now) {
+                        let start = std::time::Instant::now();
+                        handle_diagnostics(
+                            connection,
+                            &uri,
+                            compile_cmds,
+                            &text_store,
+                            &mut tree_store,
+                            &names_to_info.instructions,
+                            &names_to_info.registers,
+                            config,
+                        )?;
+                        info!(
+                            "Published delayed diagnostics in {}ms",
+                            start.elapsed().as_millis()
+                        );
+                    }
+                    continue;
+                }
+                Err(_) => break,
+            },
+            None => match connection.receiver.recv() {
+                Ok(msg) => msg,
+                Err(_) => break,
+            },
+        };
         let start = std::time::Instant::now();
         match msg {
             Message::Request(req) => {
                 if connection.handle_shutdown(&req)? {
+                    if pending_frequencies_save.take() {
+                        save_frequencies(&frequencies);
+                    }
                     info!("Shutting down asm_lsp");
                     return Ok(());
                 } else if let Ok((id, params)) = cast_req::<HoverRequest>(req.clone()) {
@@ -310,8 +735,11 @@ fn main_loop(
                         id,
                         &params,
                         &text_store,
+                        &mut tree_store,
                         names_to_info,
                         include_dirs,
+                        &include_store,
+                        config,
                     )?;
                     info!(
                         "Hover request serviced in {}ms",
@@ -327,17 +755,62 @@ fn main_loop(
                         instruction_completion_items,
                         directive_completion_items,
                         register_completion_items,
+                        &names_to_info.instructions,
+                        &names_to_info.directives,
+                        &include_store,
+                        config,
+                        &frequencies,
                     )?;
                     info!(
                         "Completion request serviced in {}ms",
                         start.elapsed().as_millis()
                     );
+                } else if let Ok((id, params)) = cast_req::<ExecuteCommand>(req.clone()) {
+                    handle_execute_command_request(connection, id, &params, &mut frequencies)?;
+                    pending_frequencies_save.schedule(start);
+                    info!(
+                        "Execute command request serviced in {}ms",
+                        start.elapsed().as_millis()
+                    );
                 } else if let Ok((id, params)) = cast_req::<GotoDefinition>(req.clone()) {
                     handle_goto_def_request(connection, id, &params, &text_store, &mut tree_store)?;
                     info!(
                         "Goto definition request serviced in {}ms",
                         start.elapsed().as_millis()
                     );
+                } else if let Ok((id, params)) = cast_req::<GotoTypeDefinition>(req.clone()) {
+                    handle_type_definition_request(
+                        connection,
+                        id,
+                        &params,
+                        &text_store,
+                        &names_to_info.registers,
+                        config,
+                    )?;
+                    info!(
+                        "Type definition request serviced in {}ms",
+                        start.elapsed().as_millis()
+                    );
+                } else if let Ok((id, params)) = cast_req::<FileSummaryRequest>(req.clone()) {
+                    handle_file_summary_request(
+                        connection,
+                        id,
+                        &params,
+                        &text_store,
+                        &mut tree_store,
+                        &names_to_info.instructions,
+                        config,
+                    )?;
+                    info!(
+                        "File summary request serviced in {}ms",
+                        start.elapsed().as_millis()
+                    );
+                } else if let Ok((id, params)) = cast_req::<CodeActionRequest>(req.clone()) {
+                    handle_code_action_request(connection, id, &params, &text_store, config)?;
+                    info!(
+                        "Code action request serviced in {}ms",
+                        start.elapsed().as_millis()
+                    );
                 } else if let Ok((id, params)) = cast_req::<DocumentSymbolRequest>(req.clone()) {
                     handle_document_symbols_request(
                         connection,
@@ -350,6 +823,67 @@ fn main_loop(
                         "Document symbols request serviced in {}ms",
                         start.elapsed().as_millis()
                     );
+                } else if let Ok((id, params)) = cast_req::<FoldingRangeRequest>(req.clone()) {
+                    handle_folding_range_request(
+                        connection,
+                        id,
+                        &params,
+                        &text_store,
+                        &mut tree_store,
+                    )?;
+                    info!(
+                        "Folding range request serviced in {}ms",
+                        start.elapsed().as_millis()
+                    );
+                } else if let Ok((id, params)) = cast_req::<CodeLensRequest>(req.clone()) {
+                    handle_code_lens_request(
+                        connection,
+                        id,
+                        &params,
+                        &text_store,
+                        &mut tree_store,
+                    )?;
+                    info!(
+                        "Code lens request serviced in {}ms",
+                        start.elapsed().as_millis()
+                    );
+                } else if let Ok((id, lens)) = cast_req::<CodeLensResolve>(req.clone()) {
+                    handle_code_lens_resolve_request(
+                        connection,
+                        id,
+                        lens,
+                        &text_store,
+                        &mut tree_store,
+                    )?;
+                    info!(
+                        "Code lens resolve request serviced in {}ms",
+                        start.elapsed().as_millis()
+                    );
+                } else if let Ok((id, params)) = cast_req::<SelectionRangeRequest>(req.clone()) {
+                    handle_selection_range_request(
+                        connection,
+                        id,
+                        &params,
+                        &text_store,
+                        &mut tree_store,
+                    )?;
+                    info!(
+                        "Selection range request serviced in {}ms",
+                        start.elapsed().as_millis()
+                    );
+                } else if let Ok((id, params)) = cast_req::<DocumentHighlightRequest>(req.clone()) {
+                    handle_document_highlight_request(
+                        connection,
+                        id,
+                        &params,
+                        &text_store,
+                        &mut tree_store,
+                        &names_to_info.registers,
+                    )?;
+                    info!(
+                        "Document highlight request serviced in {}ms",
+                        start.elapsed().as_millis()
+                    );
                 } else if let Ok((id, params)) = cast_req::<SignatureHelpRequest>(req.clone()) {
                     handle_signature_help_request(
                         connection,
@@ -358,6 +892,7 @@ fn main_loop(
                         &text_store,
                         &mut tree_store,
                         &names_to_info.instructions,
+                        config,
                     )?;
                     info!(
                         "Signature help request serviced in {}ms",
@@ -375,9 +910,60 @@ fn main_loop(
                         "References request serviced in {}ms",
                         start.elapsed().as_millis()
                     );
+                } else if let Ok((id, params)) = cast_req::<CallHierarchyPrepare>(req.clone()) {
+                    handle_prepare_call_hierarchy_request(
+                        connection,
+                        id,
+                        &params,
+                        &text_store,
+                        &mut tree_store,
+                    )?;
+                    info!(
+                        "Call hierarchy request serviced in {}ms",
+                        start.elapsed().as_millis()
+                    );
+                } else if let Ok((id, params)) = cast_req::<CallHierarchyIncomingCalls>(req.clone())
+                {
+                    handle_incoming_calls_request(
+                        connection,
+                        id,
+                        &params,
+                        &text_store,
+                        &mut tree_store,
+                    )?;
+                    info!(
+                        "Incoming calls request serviced in {}ms",
+                        start.elapsed().as_millis()
+                    );
+                } else if let Ok((id, params)) = cast_req::<CallHierarchyOutgoingCalls>(req.clone())
+                {
+                    handle_outgoing_calls_request(
+                        connection,
+                        id,
+                        &params,
+                        &text_store,
+                        &mut tree_store,
+                    )?;
+                    info!(
+                        "Outgoing calls request serviced in {}ms",
+                        start.elapsed().as_millis()
+                    );
                 } else if let Ok((_id, params)) = cast_req::<DocumentDiagnosticRequest>(req.clone())
                 {
-                    handle_diagnostics(connection, &params.text_document.uri, compile_cmds)?;
+                    if is_large_file(&text_store, &params.text_document.uri, config) {
+                        pending_diagnostics.schedule(params.text_document.uri, start);
+                        continue;
+                    }
+                    handle_diagnostics(
+                        connection,
+                        &params.text_document.uri,
+                        compile_cmds,
+                        &text_store,
+                        &mut tree_store,
+                        &names_to_info.instructions,
+                        &names_to_info.registers,
+                        config,
+                    )?;
                     info!(
                         "Diagnostics request serviced in {}ms",
                         start.elapsed().as_millis()
@@ -393,6 +979,12 @@ fn main_loop(
                         &mut text_store,
                         &mut tree_store,
                     );
+                    update_included_files(
+                        &params.text_document.uri,
+                        &text_store,
+                        &mut include_store,
+                        include_dirs,
+                    );
                     info!(
                         "Did open text document notification serviced in {}ms",
                         start.elapsed().as_millis()
@@ -403,18 +995,45 @@ fn main_loop(
                         &mut text_store,
                         &mut tree_store,
                     )?;
+                    update_included_files(
+                        &params.text_document.uri,
+                        &text_store,
+                        &mut include_store,
+                        include_dirs,
+                    );
                 } else if let Ok(params) = cast_notif::<DidCloseTextDocument>(notif.clone()) {
                     handle_did_close_text_document_notification(
                         &params,
                         &mut text_store,
                         &mut tree_store,
                     );
+                    include_store.remove(&params.text_document.uri);
                     info!(
                         "Did close text document notification serviced in {}ms",
                         start.elapsed().as_millis()
                     );
                 } else if let Ok(params) = cast_notif::<DidSaveTextDocument>(notif.clone()) {
-                    handle_diagnostics(connection, &params.text_document.uri, compile_cmds)?;
+                    // included files on disk may have changed along with the saved one
+                    update_included_files(
+                        &params.text_document.uri,
+                        &text_store,
+                        &mut include_store,
+                        include_dirs,
+                    );
+                    if is_large_file(&text_store, &params.text_document.uri, config) {
+                        pending_diagnostics.schedule(params.text_document.uri, start);
+                        continue;
+                    }
+                    handle_diagnostics(
+                        connection,
+                        &params.text_document.uri,
+                        compile_cmds,
+                        &text_store,
+                        &mut tree_store,
+                        &names_to_info.instructions,
+                        &names_to_info.registers,
+                        config,
+                    )?;
                     info!(
                         "Published diagnostics on save in {}ms",
                         start.elapsed().as_millis()
@@ -424,9 +1043,20 @@ fn main_loop(
             Message::Response(_resp) => {}
         }
     }
+    if pending_frequencies_save.take() {
+        save_frequencies(&frequencies);
+    }
     Ok(())
 }
 
+/// Returns whether the open document `uri` is too large for completions, and so has its
+/// diagnostics wait for saves to pause
+fn is_large_file(text_store: &TextDocuments, uri: &Uri, config: &TargetConfig) -> bool {
+    text_store
+        .get_document(uri)
+        .is_some_and(|doc| config.is_large_file(doc.get_content(None)))
+}
+
 fn cast_req<R>(req: Request) -> Result<(RequestId, R::Params)>
 where
     R: lsp_types::request::Request,
@@ -1,26 +1,44 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Result};
 use compile_commands::{CompilationDatabase, SourceFile};
+use log::warn;
 use lsp_server::{Connection, Message, RequestId, Response};
 use lsp_textdocument::TextDocuments;
 use lsp_types::{
     notification::{
-        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification,
-        PublishDiagnostics,
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification, Progress,
+        PublishDiagnostics, ShowMessage,
     },
-    CompletionItem, CompletionParams, Diagnostic, DidChangeTextDocumentParams,
-    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentSymbolParams,
-    DocumentSymbolResponse, GotoDefinitionParams, HoverParams, PublishDiagnosticsParams,
-    ReferenceParams, SignatureHelpParams, Uri,
+    request::GotoTypeDefinitionParams,
+    CallHierarchyIncomingCallsParams, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+    CodeActionParams, CodeLens, CodeLensParams, CompletionItem, CompletionList, CompletionParams,
+    CompletionResponse, Diagnostic, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DocumentHighlightParams, DocumentSymbolParams,
+    DocumentSymbolResponse, ExecuteCommandParams, FoldingRangeParams, GotoDefinitionParams,
+    HoverParams, MessageType, ProgressParams, ProgressParamsValue, ProgressToken,
+    PublishDiagnosticsParams, ReferenceParams, SelectionRangeParams, ShowMessageParams,
+    SignatureHelpParams, Uri, WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressEnd,
+    WorkDoneProgressReport,
 };
 use serde_json::json;
 use tree_sitter::Parser;
 
 use crate::{
-    apply_compile_cmd, get_comp_resp, get_document_symbols, get_goto_def_resp, get_hover_resp,
-    get_ref_resp, get_sig_help_resp, get_word_from_pos_params, text_doc_change_to_ts_edit,
-    NameToInfoMaps, NameToInstructionMap, TreeEntry, TreeStore,
+    add_record_completion_commands, apply_compile_cmd, code_lens_uri,
+    filter_suppressed_diagnostics, get_code_actions, get_code_lenses, get_comp_resp,
+    get_document_highlights, get_document_symbols, get_file_summary, get_folding_ranges,
+    get_goto_def_resp, get_hover_resp, get_included_files, get_incoming_calls,
+    get_long_mode_instr_diagnostics, get_outgoing_calls, get_privileged_instr_diagnostics,
+    get_ref_resp, get_selection_ranges, get_sig_help_resp, get_suffix_size_mismatch_diagnostics,
+    get_symbol_redefinition_diagnostics, get_type_definition_resp, get_word_from_pos_params,
+    prepare_call_hierarchy, resolve_code_lens, text_doc_change_to_ts_edit, CompletionFrequencies,
+    FileSummaryParams, IncludeStore, NameToDirectiveMap, NameToInfoMaps, NameToInstructionMap,
+    NameToRegisterMap, TargetConfig, TreeEntry, TreeStore, RECORD_COMPLETION_COMMAND,
 };
 
 /// Handles hover requests
@@ -32,13 +50,17 @@ use crate::{
 /// # Panics
 ///
 /// Panics if JSON encoding of a response fails
+#[allow(clippy::too_many_arguments)]
 pub fn handle_hover_request(
     connection: &Connection,
     id: RequestId,
     params: &HoverParams,
     text_store: &TextDocuments,
+    tree_store: &mut TreeStore,
     names_to_info: &NameToInfoMaps,
     include_dirs: &HashMap<SourceFile, Vec<PathBuf>>,
+    include_store: &IncludeStore,
+    config: &TargetConfig,
 ) -> Result<()> {
     let empty_resp = Response {
         id: id.clone(),
@@ -46,12 +68,12 @@ pub fn handle_hover_request(
         error: None,
     };
 
-    let (word, file_word) = if let Some(doc) =
-        text_store.get_document(&params.text_document_position_params.text_document.uri)
-    {
+    let uri = &params.text_document_position_params.text_document.uri;
+    let (doc, word, file_word) = if let Some(doc) = text_store.get_document(uri) {
         (
-            // get the word under the cursor
-            get_word_from_pos_params(doc, &params.text_document_position_params, ""),
+            doc,
+            // get the word under the cursor, including any relocation suffix (e.g. `@PLT`)
+            get_word_from_pos_params(doc, &params.text_document_position_params, "@"),
             // treat the word under the cursor as a filename and grab it as well
             get_word_from_pos_params(doc, &params.text_document_position_params, "."),
         )
@@ -65,10 +87,15 @@ pub fn handle_hover_request(
         params,
         word,
         file_word,
+        doc,
+        tree_store.get_mut(uri),
         &names_to_info.instructions,
         &names_to_info.registers,
         &names_to_info.directives,
+        &names_to_info.same_operations,
         include_dirs,
+        config,
+        include_store.get(uri).map_or(&[], Vec::as_slice),
     ) {
         let result = serde_json::to_value(hover_resp).unwrap();
         let result = Response {
@@ -101,6 +128,11 @@ pub fn handle_completion_request(
     instruction_completion_items: &[CompletionItem],
     directive_completion_items: &[CompletionItem],
     register_completion_items: &[CompletionItem],
+    names_to_instructions: &NameToInstructionMap,
+    names_to_directives: &NameToDirectiveMap,
+    include_store: &IncludeStore,
+    config: &TargetConfig,
+    frequencies: &CompletionFrequencies,
 ) -> Result<()> {
     let uri = &params.text_document_position.text_document.uri;
     if let Some(doc) = text_store.get_document(uri) {
@@ -112,7 +144,33 @@ pub fn handle_completion_request(
                 instruction_completion_items,
                 directive_completion_items,
                 register_completion_items,
+                names_to_instructions,
+                names_to_directives,
+                config,
+                include_store.get(uri).map_or(&[], Vec::as_slice),
             ) {
+                let comp_resp = match comp_resp {
+                    CompletionResponse::List(mut list) => {
+                        frequencies.boost(&mut list.items);
+                        add_record_completion_commands(&mut list.items);
+                        CompletionResponse::List(list)
+                    }
+                    CompletionResponse::Array(mut items) => {
+                        frequencies.boost(&mut items);
+                        add_record_completion_commands(&mut items);
+                        CompletionResponse::Array(items)
+                    }
+                };
+                // stream the items back in chunks if the client asked for partial results
+                let comp_resp = match (
+                    params.partial_result_params.partial_result_token.as_ref(),
+                    comp_resp,
+                ) {
+                    (Some(token), CompletionResponse::List(list)) => {
+                        CompletionResponse::List(send_partial_comp_resp(connection, token, list)?)
+                    }
+                    (_, comp_resp) => comp_resp,
+                };
                 let result = serde_json::to_value(comp_resp).unwrap();
                 let result = Response {
                     id,
@@ -133,6 +191,142 @@ pub fn handle_completion_request(
     Ok(connection.sender.send(Message::Response(empty_resp))?)
 }
 
+/// Handles `workspace/executeCommand` requests. The only command is
+/// `RECORD_COMPLETION_COMMAND`, which the client runs when it inserts a completion item,
+/// recording the item in `frequencies`. Saving them is left to the caller, see
+/// `PendingFrequenciesSave`
+///
+/// # Errors
+///
+/// Returns 'Err' if the response fails to send via `connection`
+pub fn handle_execute_command_request(
+    connection: &Connection,
+    id: RequestId,
+    params: &ExecuteCommandParams,
+    frequencies: &mut CompletionFrequencies,
+) -> Result<()> {
+    if params.command == RECORD_COMPLETION_COMMAND {
+        if let Some(label) = params.arguments.first().and_then(|arg| arg.as_str()) {
+            frequencies.record(label);
+        }
+    } else {
+        warn!("Unknown command {}", params.command);
+    }
+
+    let result = Response {
+        id,
+        result: Some(serde_json::Value::Null),
+        error: None,
+    };
+
+    Ok(connection.sender.send(Message::Response(result))?)
+}
+
+/// Number of completion items sent per partial result notification
+const COMPLETION_CHUNK_SIZE: usize = 200;
+
+/// Sends the items of `comp_resp` to the client as a series of `$/progress`
+/// notifications associated with `token`, `COMPLETION_CHUNK_SIZE` items at a time
+///
+/// Per the LSP spec, the final response must be empty in terms of result values
+/// once partial results have been reported, so the returned `CompletionList` only
+/// retains the `is_incomplete` flag of the original response
+///
+/// # Errors
+///
+/// Returns 'Err' if a notification fails to send via `connection`
+///
+/// # Panics
+///
+/// Panics if JSON encoding of a notification fails
+fn send_partial_comp_resp(
+    connection: &Connection,
+    token: &ProgressToken,
+    comp_resp: CompletionList,
+) -> Result<CompletionList> {
+    for chunk in comp_resp.items.chunks(COMPLETION_CHUNK_SIZE) {
+        let notif = lsp_server::Notification {
+            method: Progress::METHOD.to_string(),
+            params: json!({
+                "token": token,
+                "value": chunk,
+            }),
+        };
+        connection.sender.send(Message::Notification(notif))?;
+    }
+
+    Ok(CompletionList {
+        is_incomplete: comp_resp.is_incomplete,
+        items: Vec::new(),
+    })
+}
+
+/// Reports the progress of a long running operation to the client via the work done
+/// progress protocol. Does nothing if the client didn't supply a token to report with
+pub struct WorkDoneProgressReporter<'a> {
+    connection: &'a Connection,
+    token: Option<ProgressToken>,
+}
+
+impl<'a> WorkDoneProgressReporter<'a> {
+    #[must_use]
+    pub const fn new(connection: &'a Connection, token: Option<ProgressToken>) -> Self {
+        Self { connection, token }
+    }
+
+    /// Starts reporting progress under `title`
+    ///
+    /// # Errors
+    ///
+    /// Returns 'Err' if the notification fails to send via `connection`
+    pub fn begin(&self, title: &str) -> Result<()> {
+        self.send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: title.to_string(),
+            cancellable: Some(false),
+            message: None,
+            percentage: Some(0),
+        }))
+    }
+
+    /// Reports the step currently being worked on, along with the overall percentage done
+    ///
+    /// # Errors
+    ///
+    /// Returns 'Err' if the notification fails to send via `connection`
+    pub fn report(&self, message: &str, percentage: u32) -> Result<()> {
+        self.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+            cancellable: Some(false),
+            message: Some(message.to_string()),
+            percentage: Some(percentage),
+        }))
+    }
+
+    /// Signals that the operation is finished
+    ///
+    /// # Errors
+    ///
+    /// Returns 'Err' if the notification fails to send via `connection`
+    pub fn end(&self, message: &str) -> Result<()> {
+        self.send(WorkDoneProgress::End(WorkDoneProgressEnd {
+            message: Some(message.to_string()),
+        }))
+    }
+
+    fn send(&self, progress: WorkDoneProgress) -> Result<()> {
+        let Some(ref token) = self.token else {
+            return Ok(());
+        };
+        let notif = lsp_server::Notification::new(
+            Progress::METHOD.to_string(),
+            ProgressParams {
+                token: token.clone(),
+                value: ProgressParamsValue::WorkDone(progress),
+            },
+        );
+        Ok(self.connection.sender.send(Message::Notification(notif))?)
+    }
+}
+
 /// Handles go to definition requests
 ///
 /// # Errors
@@ -174,6 +368,134 @@ pub fn handle_goto_def_request(
     Ok(connection.sender.send(Message::Response(empty_resp))?)
 }
 
+/// Handles type definition requests
+///
+/// The register class of a register under the cursor is shown to the user via a
+/// `window/showMessage` notification, as there is no location to navigate to
+///
+/// # Errors
+///
+/// Returns 'Err' if the notification or response fails to send via `connection`
+///
+/// # Panics
+///
+/// Panics if JSON encoding of the notification fails
+pub fn handle_type_definition_request(
+    connection: &Connection,
+    id: RequestId,
+    params: &GotoTypeDefinitionParams,
+    text_store: &TextDocuments,
+    names_to_registers: &NameToRegisterMap,
+    config: &TargetConfig,
+) -> Result<()> {
+    let uri = &params.text_document_position_params.text_document.uri;
+    if let Some(doc) = text_store.get_document(uri) {
+        if let Some(message) = get_type_definition_resp(
+            doc,
+            &params.text_document_position_params,
+            names_to_registers,
+            config,
+        ) {
+            let notif = lsp_server::Notification {
+                method: ShowMessage::METHOD.to_string(),
+                params: serde_json::to_value(ShowMessageParams {
+                    typ: MessageType::INFO,
+                    message,
+                })
+                .unwrap(),
+            };
+            connection.sender.send(Message::Notification(notif))?;
+        }
+    }
+
+    let empty_resp = Response {
+        id,
+        result: Some(json!("")),
+        error: None,
+    };
+
+    Ok(connection.sender.send(Message::Response(empty_resp))?)
+}
+
+/// Handles custom `asm-lsp/fileSummary` requests
+///
+/// # Errors
+///
+/// Returns 'Err' if the response fails to send via `connection`
+///
+/// # Panics
+///
+/// Panics if JSON encoding of a response fails
+pub fn handle_file_summary_request(
+    connection: &Connection,
+    id: RequestId,
+    params: &FileSummaryParams,
+    text_store: &TextDocuments,
+    tree_store: &mut TreeStore,
+    names_to_instructions: &NameToInstructionMap,
+    config: &TargetConfig,
+) -> Result<()> {
+    let uri = &params.text_document.uri;
+    if let Some(doc) = text_store.get_document(uri) {
+        if let Some(tree_entry) = tree_store.get_mut(uri) {
+            let summary = get_file_summary(doc, tree_entry, names_to_instructions, config);
+            let result = serde_json::to_value(summary).unwrap();
+            let result = Response {
+                id,
+                result: Some(result),
+                error: None,
+            };
+            return Ok(connection.sender.send(Message::Response(result))?);
+        }
+    }
+
+    let empty_resp = Response {
+        id,
+        result: Some(json!("")),
+        error: None,
+    };
+
+    Ok(connection.sender.send(Message::Response(empty_resp))?)
+}
+
+/// Handles code action requests
+///
+/// # Errors
+///
+/// Returns 'Err' if the response fails to send via `connection`
+///
+/// # Panics
+///
+/// Panics if JSON encoding of a response fails
+pub fn handle_code_action_request(
+    connection: &Connection,
+    id: RequestId,
+    params: &CodeActionParams,
+    text_store: &TextDocuments,
+    config: &TargetConfig,
+) -> Result<()> {
+    if let Some(doc) = text_store.get_document(&params.text_document.uri) {
+        let actions = get_code_actions(doc, params, config);
+        if !actions.is_empty() {
+            let result = serde_json::to_value(actions).unwrap();
+            let result = Response {
+                id,
+                result: Some(result),
+                error: None,
+            };
+            return Ok(connection.sender.send(Message::Response(result))?);
+        }
+    }
+
+    let empty_resp = Response {
+        id,
+        result: Some(json!("")),
+        error: None,
+    };
+
+    Ok(connection.sender.send(Message::Response(empty_resp))?)
+}
+
 /// Handles document symbols requests
 ///
 /// # Errors
@@ -215,6 +537,158 @@ pub fn handle_document_symbols_request(
     Ok(connection.sender.send(Message::Response(empty_resp))?)
 }
 
+/// Handles folding range requests
+///
+/// # Errors
+///
+/// Returns 'Err' if the response fails to send via `connection`
+///
+/// # Panics
+///
+/// Panics if JSON encoding of a response fails
+pub fn handle_folding_range_request(
+    connection: &Connection,
+    id: RequestId,
+    params: &FoldingRangeParams,
+    text_store: &TextDocuments,
+    tree_store: &mut TreeStore,
+) -> Result<()> {
+    let uri = &params.text_document.uri;
+    if let Some(doc) = text_store.get_document(uri) {
+        if let Some(tree_entry) = tree_store.get_mut(uri) {
+            let ranges = get_folding_ranges(doc.get_content(None), tree_entry);
+            let result = serde_json::to_value(ranges).unwrap();
+            let result = Response {
+                id: id.clone(),
+                result: Some(result),
+                error: None,
+            };
+            return Ok(connection.sender.send(Message::Response(result))?);
+        }
+    }
+
+    let empty_resp = Response {
+        id,
+        result: Some(json!("")),
+        error: None,
+    };
+
+    Ok(connection.sender.send(Message::Response(empty_resp))?)
+}
+
+/// Handles code lens requests
+///
+/// # Errors
+///
+/// Returns 'Err' if the response fails to send via `connection`
+///
+/// # Panics
+///
+/// Panics if JSON encoding of a response fails
+pub fn handle_code_lens_request(
+    connection: &Connection,
+    id: RequestId,
+    params: &CodeLensParams,
+    text_store: &TextDocuments,
+    tree_store: &mut TreeStore,
+) -> Result<()> {
+    let uri = &params.text_document.uri;
+    if let Some(doc) = text_store.get_document(uri) {
+        if let Some(tree_entry) = tree_store.get_mut(uri) {
+            let lenses = get_code_lenses(doc.get_content(None), tree_entry, uri);
+            let result = serde_json::to_value(lenses).unwrap();
+            let result = Response {
+                id: id.clone(),
+                result: Some(result),
+                error: None,
+            };
+            return Ok(connection.sender.send(Message::Response(result))?);
+        }
+    }
+
+    let empty_resp = Response {
+        id,
+        result: Some(json!("")),
+        error: None,
+    };
+
+    Ok(connection.sender.send(Message::Response(empty_resp))?)
+}
+
+/// Handles code lens resolve requests, filling in the command of `lens`. Lenses whose
+/// document is no longer open are sent back unchanged
+///
+/// # Errors
+///
+/// Returns 'Err' if the response fails to send via `connection`
+///
+/// # Panics
+///
+/// Panics if JSON encoding of a response fails
+pub fn handle_code_lens_resolve_request(
+    connection: &Connection,
+    id: RequestId,
+    lens: CodeLens,
+    text_store: &TextDocuments,
+    tree_store: &mut TreeStore,
+) -> Result<()> {
+    let doc_and_tree = code_lens_uri(&lens).and_then(|uri| {
+        let doc = text_store.get_document(&uri)?;
+        Some((doc, tree_store.get_mut(&uri)?))
+    });
+    let lens = match doc_and_tree {
+        Some((doc, tree_entry)) => resolve_code_lens(lens, doc.get_content(None), tree_entry),
+        None => lens,
+    };
+
+    let result = Response {
+        id,
+        result: Some(serde_json::to_value(lens).unwrap()),
+        error: None,
+    };
+
+    Ok(connection.sender.send(Message::Response(result))?)
+}
+
+/// Handles selection range requests
+///
+/// # Errors
+///
+/// Returns 'Err' if the response fails to send via `connection`
+///
+/// # Panics
+///
+/// Panics if JSON encoding of a response fails
+pub fn handle_selection_range_request(
+    connection: &Connection,
+    id: RequestId,
+    params: &SelectionRangeParams,
+    text_store: &TextDocuments,
+    tree_store: &mut TreeStore,
+) -> Result<()> {
+    let uri = &params.text_document.uri;
+    if let Some(doc) = text_store.get_document(uri) {
+        if let Some(tree_entry) = tree_store.get_mut(uri) {
+            let ranges = get_selection_ranges(doc.get_content(None), tree_entry, &params.positions);
+            let result = serde_json::to_value(ranges).unwrap();
+            let result = Response {
+                id: id.clone(),
+                result: Some(result),
+                error: None,
+            };
+            return Ok(connection.sender.send(Message::Response(result))?);
+        }
+    }
+
+    let empty_resp = Response {
+        id,
+        result: Some(json!("")),
+        error: None,
+    };
+
+    Ok(connection.sender.send(Message::Response(empty_resp))?)
+}
+
 /// Handles signature help requests
 ///
 /// # Errors
@@ -231,6 +705,7 @@ pub fn handle_signature_help_request(
     text_store: &TextDocuments,
     tree_store: &mut TreeStore,
     names_to_instructions: &NameToInstructionMap,
+    config: &TargetConfig,
 ) -> Result<()> {
     let uri = &params.text_document_position_params.text_document.uri;
     if let Some(doc) = text_store.get_document(uri) {
@@ -240,6 +715,7 @@ pub fn handle_signature_help_request(
                 params,
                 tree_entry,
                 names_to_instructions,
+                config,
             );
 
             if let Some(sig) = sig_resp {
@@ -306,6 +782,257 @@ pub fn handle_references_request(
     Ok(connection.sender.send(Message::Response(empty_resp))?)
 }
 
+/// Handles call hierarchy preparation requests
+///
+/// # Errors
+///
+/// Returns 'Err' if the response fails to send via `connection`
+///
+/// # Panics
+///
+/// Panics if JSON encoding of a response fails
+pub fn handle_prepare_call_hierarchy_request(
+    connection: &Connection,
+    id: RequestId,
+    params: &CallHierarchyPrepareParams,
+    text_store: &TextDocuments,
+    tree_store: &mut TreeStore,
+) -> Result<()> {
+    let uri = &params.text_document_position_params.text_document.uri;
+    if let Some(doc) = text_store.get_document(uri) {
+        if let Some(tree_entry) = tree_store.get_mut(uri) {
+            if let Some(items) = prepare_call_hierarchy(params, doc, tree_entry) {
+                let result = serde_json::to_value(items).unwrap();
+                let result = Response {
+                    id: id.clone(),
+                    result: Some(result),
+                    error: None,
+                };
+                return Ok(connection.sender.send(Message::Response(result))?);
+            }
+        }
+    }
+
+    let empty_resp = Response {
+        id,
+        result: Some(json!("")),
+        error: None,
+    };
+
+    Ok(connection.sender.send(Message::Response(empty_resp))?)
+}
+
+/// Handles incoming call requests
+///
+/// # Errors
+///
+/// Returns 'Err' if the response fails to send via `connection`
+///
+/// # Panics
+///
+/// Panics if JSON encoding of a response fails
+pub fn handle_incoming_calls_request(
+    connection: &Connection,
+    id: RequestId,
+    params: &CallHierarchyIncomingCallsParams,
+    text_store: &TextDocuments,
+    tree_store: &mut TreeStore,
+) -> Result<()> {
+    let uri = &params.item.uri;
+    if let Some(doc) = text_store.get_document(uri) {
+        if let Some(tree_entry) = tree_store.get_mut(uri) {
+            let calls = get_incoming_calls(&params.item, doc.get_content(None), tree_entry);
+            let result = serde_json::to_value(calls).unwrap();
+            let result = Response {
+                id: id.clone(),
+                result: Some(result),
+                error: None,
+            };
+            return Ok(connection.sender.send(Message::Response(result))?);
+        }
+    }
+
+    let empty_resp = Response {
+        id,
+        result: Some(json!("")),
+        error: None,
+    };
+
+    Ok(connection.sender.send(Message::Response(empty_resp))?)
+}
+
+/// Handles outgoing call requests
+///
+/// # Errors
+///
+/// Returns 'Err' if the response fails to send via `connection`
+///
+/// # Panics
+///
+/// Panics if JSON encoding of a response fails
+pub fn handle_outgoing_calls_request(
+    connection: &Connection,
+    id: RequestId,
+    params: &CallHierarchyOutgoingCallsParams,
+    text_store: &TextDocuments,
+    tree_store: &mut TreeStore,
+) -> Result<()> {
+    let uri = &params.item.uri;
+    if let Some(doc) = text_store.get_document(uri) {
+        if let Some(tree_entry) = tree_store.get_mut(uri) {
+            let calls = get_outgoing_calls(&params.item, doc.get_content(None), tree_entry);
+            let result = serde_json::to_value(calls).unwrap();
+            let result = Response {
+                id: id.clone(),
+                result: Some(result),
+                error: None,
+            };
+            return Ok(connection.sender.send(Message::Response(result))?);
+        }
+    }
+
+    let empty_resp = Response {
+        id,
+        result: Some(json!("")),
+        error: None,
+    };
+
+    Ok(connection.sender.send(Message::Response(empty_resp))?)
+}
+
+/// Handles document highlight requests
+///
+/// # Errors
+///
+/// Returns 'Err' if the response fails to send via `connection`
+///
+/// # Panics
+///
+/// Panics if JSON encoding of a response fails
+pub fn handle_document_highlight_request(
+    connection: &Connection,
+    id: RequestId,
+    params: &DocumentHighlightParams,
+    text_store: &TextDocuments,
+    tree_store: &mut TreeStore,
+    names_to_registers: &NameToRegisterMap,
+) -> Result<()> {
+    let uri = &params.text_document_position_params.text_document.uri;
+    if let Some(doc) = text_store.get_document(uri) {
+        if let Some(tree_entry) = tree_store.get_mut(uri) {
+            if let Some(highlights) =
+                get_document_highlights(doc, tree_entry, params, names_to_registers)
+            {
+                let result = serde_json::to_value(highlights).unwrap();
+                let result = Response {
+                    id: id.clone(),
+                    result: Some(result),
+                    error: None,
+                };
+                return Ok(connection.sender.send(Message::Response(result))?);
+            }
+        }
+    }
+
+    let empty_resp = Response {
+        id,
+        result: Some(json!("")),
+        error: None,
+    };
+
+    Ok(connection.sender.send(Message::Response(empty_resp))?)
+}
+
+/// How long diagnostics for a large document wait for saves to pause before being
+/// produced, see `ConfigOptions::max_file_lines_for_completion`
+pub const LARGE_FILE_DIAGNOSTICS_DELAY: Duration = Duration::from_millis(500);
+
+/// Documents whose diagnostics are put off until a burst of saves is over, along with
+/// when they're due
+#[derive(Debug, Default)]
+pub struct PendingDiagnostics {
+    deadlines: HashMap<Uri, Instant>,
+}
+
+impl PendingDiagnostics {
+    /// Puts off diagnostics for `uri` until `LARGE_FILE_DIAGNOSTICS_DELAY` after `now`,
+    /// replacing any earlier deadline
+    pub fn schedule(&mut self, uri: Uri, now: Instant) {
+        self.deadlines
+            .insert(uri, now + LARGE_FILE_DIAGNOSTICS_DELAY);
+    }
+
+    /// Returns the earliest deadline, if any diagnostics are pending
+    #[must_use]
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.values().min().copied()
+    }
+
+    /// Removes and returns the documents whose diagnostics are due at `now`
+    pub fn take_due(&mut self, now: Instant) -> Vec<Uri> {
+        let due: Vec<Uri> = self
+            .deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(uri, _)| uri.clone())
+            .collect();
+        for uri in &due {
+            self.deadlines.remove(uri);
+        }
+        due
+    }
+}
+
+/// How long accepted completions are batched for before `CompletionFrequencies` are saved
+pub const COMPLETION_FREQUENCIES_SAVE_DELAY: Duration = Duration::from_secs(30);
+
+/// When the `CompletionFrequencies` recorded since they were last saved are due to be
+/// saved, if any are
+#[derive(Debug, Default)]
+pub struct PendingFrequenciesSave {
+    deadline: Option<Instant>,
+}
+
+impl PendingFrequenciesSave {
+    /// Puts off saving until `COMPLETION_FREQUENCIES_SAVE_DELAY` after `now`, unless a save
+    /// is already pending, so that a steady stream of completions still gets saved
+    pub fn schedule(&mut self, now: Instant) {
+        self.deadline
+            .get_or_insert(now + COMPLETION_FREQUENCIES_SAVE_DELAY);
+    }
+
+    /// Returns when the save is due, if one is pending
+    #[must_use]
+    pub const fn next_deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Returns whether a save is pending and due at `now`, clearing it if so
+    pub fn take_due(&mut self, now: Instant) -> bool {
+        if self.deadline.is_some_and(|deadline| deadline <= now) {
+            self.deadline = None;
+            return true;
+        }
+        false
+    }
+
+    /// Returns whether a save is pending regardless of its deadline, clearing it, e.g.
+    /// to save before shutting down
+    pub fn take(&mut self) -> bool {
+        self.deadline.take().is_some()
+    }
+}
+
+/// Saves `frequencies` to `path`, logging failures
+pub fn save_completion_frequencies(frequencies: &CompletionFrequencies, path: &Path) {
+    if let Err(e) = frequencies.save(path) {
+        warn!(
+            "Failed to save completion frequencies to {} -- Error: {e}",
+            path.display()
+        );
+    }
+}
+
 /// Produces diagnostics and sends a `PublishDiagnostics` notification to the client
 /// Diagnostics are only produced for the file specified by `uri`
 /// Returns 'Err' if the response fails to send via `connection`
@@ -313,10 +1040,16 @@ pub fn handle_references_request(
 /// # Panics
 ///
 /// Panics if JSON encoding of the notification fails
+#[allow(clippy::too_many_arguments)]
 pub fn handle_diagnostics(
     connection: &Connection,
     uri: &Uri,
     compile_cmds: &CompilationDatabase,
+    text_store: &TextDocuments,
+    tree_store: &mut TreeStore,
+    names_to_instructions: &NameToInstructionMap,
+    names_to_registers: &NameToRegisterMap,
+    config: &TargetConfig,
 ) -> Result<()> {
     let req_source_path = PathBuf::from(uri.as_str());
 
@@ -338,6 +1071,50 @@ pub fn handle_diagnostics(
         apply_compile_cmd(&mut diagnostics, entry);
     }
 
+    if config.targets_long_mode() {
+        if let (Some(doc), Some(tree_entry)) =
+            (text_store.get_document(uri), tree_store.get_mut(uri))
+        {
+            diagnostics.extend(get_long_mode_instr_diagnostics(
+                doc,
+                tree_entry,
+                names_to_instructions,
+            ));
+        }
+    }
+
+    if config.assemblers.gas {
+        if let Some(doc) = text_store.get_document(uri) {
+            diagnostics.extend(get_symbol_redefinition_diagnostics(doc));
+        }
+        if let (Some(doc), Some(tree_entry)) =
+            (text_store.get_document(uri), tree_store.get_mut(uri))
+        {
+            diagnostics.extend(get_suffix_size_mismatch_diagnostics(
+                doc,
+                tree_entry,
+                names_to_instructions,
+                names_to_registers,
+            ));
+        }
+    }
+
+    if config.opts.user_mode {
+        if let (Some(doc), Some(tree_entry)) =
+            (text_store.get_document(uri), tree_store.get_mut(uri))
+        {
+            diagnostics.extend(get_privileged_instr_diagnostics(
+                doc,
+                tree_entry,
+                names_to_instructions,
+            ));
+        }
+    }
+
+    if let Some(doc) = text_store.get_document(uri) {
+        filter_suppressed_diagnostics(&mut diagnostics, doc, &config.opts.disabled_diagnostics);
+    }
+
     let params = PublishDiagnosticsParams {
         uri: uri.clone(),
         diagnostics,
@@ -426,6 +1203,32 @@ pub fn handle_did_change_text_document_notification(
 /// # Panics
 ///
 /// Panics if JSON encoding of `params` fails
+/// Follows the `.include`s of the open document at `uri` again, along with those of the
+/// open documents including it, so that they see its latest contents
+pub fn update_included_files(
+    uri: &Uri,
+    text_store: &TextDocuments,
+    include_store: &mut IncludeStore,
+    include_dirs: &HashMap<SourceFile, Vec<PathBuf>>,
+) {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let path = canonical(Path::new(uri.path().as_str()));
+    let mut stale: Vec<Uri> = include_store
+        .iter()
+        .filter(|(_, included)| included.iter().any(|file| canonical(&file.path) == path))
+        .map(|(including, _)| including.clone())
+        .collect();
+    stale.push(uri.clone());
+
+    for uri in stale {
+        if let Some(doc) = text_store.get_document(&uri) {
+            let included =
+                get_included_files(&uri, doc.get_content(None), text_store, include_dirs);
+            include_store.insert(uri, included);
+        }
+    }
+}
+
 pub fn handle_did_close_text_document_notification(
     params: &DidCloseTextDocumentParams,
     text_store: &mut TextDocuments,
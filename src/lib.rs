@@ -1,12 +1,18 @@
 pub mod handle;
 pub mod lsp;
+pub mod syscalls;
 mod test;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 pub mod types;
 pub mod x86_parser;
+pub mod z80;
 
 pub use lsp::*;
 pub use types::*;
 pub use x86_parser::{
+    data_hash, load_directives, load_instructions, populate_custom_instructions,
     populate_directives, populate_instructions, populate_name_to_directive_map,
     populate_name_to_instruction_map, populate_name_to_register_map, populate_registers,
+    relink_z80_docs, ParseError,
 };
@@ -1,64 +1,532 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::TryFrom;
 use std::fs::{create_dir_all, File};
+use std::hash::Hash;
 use std::io::BufRead;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::str::FromStr;
+use std::sync::Mutex;
 
 use anyhow::{anyhow, Result};
 use compile_commands::{CompilationDatabase, CompileCommand, SourceFile};
 use dirs::config_dir;
-use log::{error, info, log, log_enabled, warn};
-use lsp_textdocument::FullTextDocument;
+use log::{debug, error, info, log, log_enabled, warn};
+use lsp_textdocument::{FullTextDocument, TextDocuments};
 use lsp_types::{
-    CompletionItem, CompletionItemKind, CompletionList, CompletionParams, CompletionTriggerKind,
-    Diagnostic, DocumentSymbol, DocumentSymbolParams, Documentation, GotoDefinitionParams,
-    GotoDefinitionResponse, Hover, HoverContents, HoverParams, InitializeParams, Location,
-    MarkupContent, MarkupKind, Position, Range, ReferenceParams, SignatureHelp,
-    SignatureHelpParams, SignatureInformation, SymbolKind, TextDocumentContentChangeEvent,
-    TextDocumentPositionParams, Uri,
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall,
+    CallHierarchyPrepareParams, CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+    CodeLens, CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionList,
+    CompletionParams, CompletionResponse, CompletionTriggerKind, Diagnostic, DiagnosticSeverity,
+    DocumentHighlight, DocumentHighlightKind, DocumentHighlightParams, DocumentSymbol,
+    DocumentSymbolParams, Documentation, FoldingRange, FoldingRangeKind, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, InitializeParams, InsertTextFormat,
+    Location, MarkupContent, MarkupKind, NumberOrString, Position, Range, ReferenceParams,
+    SelectionRange, SignatureHelp, SignatureHelpParams, SignatureInformation, SymbolKind,
+    TextDocumentContentChangeEvent, TextDocumentPositionParams, TextEdit, Uri, WorkspaceEdit,
 };
 use once_cell::sync::Lazy;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use symbolic::common::{Language, Name, NameMangling};
 use symbolic_demangle::{Demangle, DemangleOptions};
 use tree_sitter::InputEdit;
 
+use crate::syscalls::SyscallAbi;
 use crate::types::Column;
+use crate::z80;
 use crate::{
-    Arch, ArchOrAssembler, Assembler, Completable, Hoverable, Instruction, NameToInstructionMap,
-    TargetConfig, TreeEntry,
+    Arch, ArchOrAssembler, ArmState, Assembler, Completable, CompletionCase, ConfigOptions,
+    DiagnosticCode, Directive, FileSummary, FormsLayout, HoverKind, Hoverable, IncludedFile,
+    Instruction, InstructionForm, LineAnalysis, NameToDirectiveMap, NameToInfoMaps,
+    NameToInstructionMap, NameToRegisterMap, OperandKind, OperandType, PrefixIndex, Register,
+    RegisterType, RegisterWidth, SameOperationMap, TargetConfig, TreeEntry,
 };
 
-/// Find the start and end indices of a word inside the given line
+/// Architecture specific behavior for hover, completion and signature help
+///
+/// Each supported `Arch` has a provider (see `arch_providers`), and the request handlers
+/// dispatch through it rather than special-casing architectures inline. Syntax quirks
+/// of a new architecture (e.g. dotted or suffixed mnemonics) belong in its provider
+pub trait ArchProvider: Sync {
+    /// The architecture this provider describes
+    fn arch(&self) -> Arch;
+
+    /// Name of the architecture, as shown in signature help
+    fn display_name(&self) -> &'static str;
+
+    /// Adjusts the bounds `start..end` of the word under the cursor in `line`, for names
+    /// containing characters that aren't normally considered part of a word
+    fn extend_word(&self, _line: &str, start: Column, end: Column) -> (Column, Column) {
+        (start, end)
+    }
+
+    /// Prefix of register names, which triggers register completion when typed
+    fn register_prefix(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Prefix of immediate operands, which triggers completion of named constants
+    fn immediate_prefix(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Returns whether `form` is written as `name` in this architecture's syntax
+    fn form_has_name(&self, form: &InstructionForm, name: &str) -> bool;
+
+    /// Suffixes written after a `.` following `mnemonic`, along with their descriptions
+    /// (e.g. the condition in AArch64's `b.eq`)
+    fn mnemonic_suffixes(&self, _mnemonic: &str) -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
+
+    /// Base mnemonics which are followed by a condition code (e.g. the `set` of `setne`),
+    /// each along with its condition codes and their descriptions
+    fn conditional_mnemonics(&self) -> &'static [ConditionalMnemonic] {
+        &[]
+    }
+
+    /// Assembles `instr` with `operands` into its opcode bytes, if the form they select
+    /// can be resolved
+    fn encode(&self, _instr: &Instruction, _operands: &[&str]) -> Option<String> {
+        None
+    }
+
+    /// Describes `operand` if it's written in a syntax specific to this architecture
+    /// which isn't covered by its register docs (e.g. AArch64's `v0.4s`)
+    fn describe_operand(&self, _operand: &str) -> Option<String> {
+        None
+    }
+
+    /// Snippets setting up and tearing down a function's stack frame under the
+    /// architecture's usual calling convention
+    fn frame_snippets(&self) -> Option<&'static FrameSnippets> {
+        None
+    }
+
+    /// Whether the architecture is one of ARM's, whose code is assembled in the ARM or
+    /// Thumb state selected by `.arm`/`.thumb` (see `get_arm_state`)
+    fn is_arm(&self) -> bool {
+        false
+    }
+}
+
+/// Function prologue and epilogue snippets of a calling convention, in LSP snippet syntax
+/// with the stack frame's size as the first placeholder
+pub struct FrameSnippets {
+    /// Calling convention the snippets follow, e.g. "System V"
+    pub abi: &'static str,
+    pub prologue: &'static str,
+    pub epilogue: &'static str,
+}
+
+/// Provider for x86 and x86-64, as written in GAS (AT&T) or Go assembly
+struct X86Provider {
+    arch: Arch,
+    name: &'static str,
+}
+
+impl ArchProvider for X86Provider {
+    fn arch(&self) -> Arch {
+        self.arch
+    }
+
+    fn display_name(&self) -> &'static str {
+        self.name
+    }
+
+    fn extend_word(&self, line: &str, start: Column, end: Column) -> (Column, Column) {
+        extend_fpu_stack_reg(line, start, end)
+    }
+
+    fn register_prefix(&self) -> Option<&'static str> {
+        Some("%")
+    }
+
+    fn immediate_prefix(&self) -> Option<&'static str> {
+        Some("$")
+    }
+
+    fn conditional_mnemonics(&self) -> &'static [ConditionalMnemonic] {
+        &[
+            ("set", X86_CONDITIONS),
+            ("j", X86_CONDITIONS),
+            ("cmov", X86_CONDITIONS),
+        ]
+    }
+
+    fn frame_snippets(&self) -> Option<&'static FrameSnippets> {
+        match self.arch {
+            Arch::X86 => Some(&FrameSnippets {
+                abi: "cdecl",
+                prologue: "pushl\t%ebp\nmovl\t%esp, %ebp\nsubl\t\\$${1:16}, %esp\n$0",
+                epilogue: "leave\nret",
+            }),
+            Arch::X86_64 => Some(&FrameSnippets {
+                abi: "System V",
+                prologue: "pushq\t%rbp\nmovq\t%rsp, %rbp\nsubq\t\\$${1:16}, %rsp\n$0",
+                epilogue: "leave\nret",
+            }),
+            _ => None,
+        }
+    }
+
+    fn form_has_name(&self, form: &InstructionForm, name: &str) -> bool {
+        // forms with a GAS name are only matched by it, even if they have a Go name too
+        if let Some(ref gas_name) = form.gas_name {
+            name.eq_ignore_ascii_case(gas_name)
+        } else if let Some(ref go_name) = form.go_name {
+            name.eq_ignore_ascii_case(go_name)
+        } else {
+            false
+        }
+    }
+}
+
+/// Provider for z80
+struct Z80Provider;
+
+impl ArchProvider for Z80Provider {
+    fn arch(&self) -> Arch {
+        Arch::Z80
+    }
+
+    fn display_name(&self) -> &'static str {
+        "z80"
+    }
+
+    fn form_has_name(&self, form: &InstructionForm, name: &str) -> bool {
+        form.z80_name
+            .as_ref()
+            .is_some_and(|z80_name| name.eq_ignore_ascii_case(z80_name))
+    }
+
+    fn extend_word(&self, line: &str, start: Column, end: Column) -> (Column, Column) {
+        extend_indexed_operand(line, start, end)
+    }
+
+    fn encode(&self, instr: &Instruction, operands: &[&str]) -> Option<String> {
+        z80::assemble(&instr.forms, operands)
+    }
+
+    fn describe_operand(&self, operand: &str) -> Option<String> {
+        describe_indexed_operand(operand)
+    }
+
+    // `ix` as the frame pointer, as compilers such as SDCC do
+    fn frame_snippets(&self) -> Option<&'static FrameSnippets> {
+        Some(&FrameSnippets {
+            abi: "ix frame pointer",
+            prologue:
+                "push\tix\nld\tix, 0\nadd\tix, sp\nld\thl, -${1:16}\nadd\thl, sp\nld\tsp, hl\n$0",
+            epilogue: "ld\tsp, ix\npop\tix\nret",
+        })
+    }
+}
+
+/// Matches a z80 indexed operand (e.g. `(ix+5)`, `(iy-2)` or `(ix)`), capturing the
+/// index register, the offset's sign and the offset
+static INDEXED_OPERAND: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)^\(\s*(i[xy])\s*(?:([+-])\s*([\w$]+))?\s*\)$").unwrap());
+
+/// Indexed operands such as `(ix+5)` would be split into `ix` and `5` by
+/// `find_word_at_pos`. If the word spanning `start..end` is inside of such an operand,
+/// returns the bounds of the entire operand instead
+fn extend_indexed_operand(line: &str, start: Column, end: Column) -> (Column, Column) {
+    let (Some(open), Some(close)) = (line[..start].rfind('('), line[end..].find(')')) else {
+        return (start, end);
+    };
+    let close = end + close + 1;
+    if INDEXED_OPERAND.is_match(&line[open..close]) {
+        (open, close)
+    } else {
+        (start, end)
+    }
+}
+
+/// Describes a z80 indexed operand, e.g. `(ix+5)` as "Indexed addressing: IX base + 5
+/// offset", noting offsets which don't fit the signed 8-bit displacement
+fn describe_indexed_operand(operand: &str) -> Option<String> {
+    let caps = INDEXED_OPERAND.captures(operand)?;
+    let reg = caps[1].to_ascii_uppercase();
+    let (sign, offset) = match (caps.get(2), caps.get(3)) {
+        (Some(sign), Some(offset)) => (sign.as_str(), offset.as_str()),
+        _ => ("+", "0"),
+    };
+
+    let mut description = format!("Indexed addressing: {reg} base {sign} {offset} offset");
+    if let Some(value) = parse_gas_int(offset) {
+        let fits = if sign == "-" {
+            value <= 128
+        } else {
+            value <= 127
+        };
+        description += &format!(
+            "\n\nThe byte at the address in {reg} {} {value}",
+            if sign == "-" { "minus" } else { "plus" }
+        );
+        if !fits {
+            description +=
+                "\n\n**The offset doesn't fit the signed 8-bit displacement (-128 to 127)**";
+        }
+    } else {
+        description += &format!(
+            "\n\nThe byte at the address in {reg} offset by `{sign}{offset}`, a signed 8-bit displacement (-128 to 127)"
+        );
+    }
+
+    Some(description)
+}
+
+/// Provider for AArch64. Only its system registers are bundled so far, whose names
+/// (e.g. `TTBR0_EL1`) are plain words. Conditional branches take their condition as a
+/// `.` suffix (e.g. `b.ne`), as do SIMD operands their arrangement (e.g. `v0.4s`)
+struct AArch64Provider;
+
+impl ArchProvider for AArch64Provider {
+    fn arch(&self) -> Arch {
+        Arch::AArch64
+    }
+
+    fn display_name(&self) -> &'static str {
+        "aarch64"
+    }
+
+    fn form_has_name(&self, _form: &InstructionForm, _name: &str) -> bool {
+        false
+    }
+
+    fn extend_word(&self, line: &str, start: Column, end: Column) -> (Column, Column) {
+        extend_vector_arrangement(line, start, end)
+    }
+
+    fn immediate_prefix(&self) -> Option<&'static str> {
+        Some("#")
+    }
+
+    fn mnemonic_suffixes(&self, mnemonic: &str) -> &'static [(&'static str, &'static str)] {
+        match mnemonic {
+            "b" | "bc" => AARCH64_CONDITIONS,
+            _ => &[],
+        }
+    }
+
+    fn conditional_mnemonics(&self) -> &'static [ConditionalMnemonic] {
+        &[("b.", AARCH64_CONDITIONS), ("bc.", AARCH64_CONDITIONS)]
+    }
+
+    fn describe_operand(&self, operand: &str) -> Option<String> {
+        describe_vector_arrangement(operand)
+    }
+
+    fn is_arm(&self) -> bool {
+        true
+    }
+
+    // the frame size has to stay a multiple of 16 to keep `sp` aligned
+    fn frame_snippets(&self) -> Option<&'static FrameSnippets> {
+        Some(&FrameSnippets {
+            abi: "AAPCS64",
+            prologue: "stp\tx29, x30, [sp, #-${1:16}]!\nmov\tx29, sp\n$0",
+            epilogue: "ldp\tx29, x30, [sp], #${1:16}\nret",
+        })
+    }
+}
+
+/// Matches an AArch64 SIMD register with its arrangement specifier (e.g. `v0.4s`, or
+/// `v1.s` when indexing a single element), capturing the register number, the number of
+/// lanes and the element size
+static VECTOR_ARRANGEMENT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)^v(\d{1,2})\.(\d{0,2})([bhsdq])$").unwrap());
+
+/// SIMD operands such as `v0.4s` would be split into `v0` and `4s` by `find_word_at_pos`.
+/// If the word spanning `start..end` is either piece of such an operand, returns the
+/// bounds of the entire operand instead
+fn extend_vector_arrangement(line: &str, start: Column, end: Column) -> (Column, Column) {
+    let word_end = |from: Column| {
+        line[from..]
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .map_or(line.len(), |idx| from + idx)
+    };
+    let word_start = |to: Column| {
+        line[..to]
+            .rfind(|c: char| !c.is_ascii_alphanumeric())
+            .map_or(0, |idx| idx + 1)
+    };
+    let is_arrangement = |from: Column, to: Column| VECTOR_ARRANGEMENT.is_match(&line[from..to]);
+
+    // cursor on the register
+    if line[end..].starts_with('.') {
+        let arrangement_end = word_end(end + 1);
+        if is_arrangement(start, arrangement_end) {
+            return (start, arrangement_end);
+        }
+    }
+    // cursor on the arrangement
+    if start > 0 && line[..start].ends_with('.') {
+        let reg_start = word_start(start - 1);
+        if is_arrangement(reg_start, end) {
+            return (reg_start, end);
+        }
+    }
+
+    (start, end)
+}
+
+/// Describes an AArch64 SIMD register with its arrangement specifier, e.g. `v0.4s` as
+/// "Vector register v0 viewed as 4 single-precision (32-bit) elements"
+fn describe_vector_arrangement(operand: &str) -> Option<String> {
+    let caps = VECTOR_ARRANGEMENT.captures(operand)?;
+    let reg: u8 = caps[1].parse().ok()?;
+    if reg > 31 {
+        return None;
+    }
+    let (element, bits) = match caps[3].to_ascii_lowercase().as_str() {
+        "b" => ("byte", 8),
+        "h" => ("half-precision", 16),
+        "s" => ("single-precision", 32),
+        "d" => ("double-precision", 64),
+        _ => ("quadword", 128),
+    };
+
+    // a bare element size is used when indexing a single element (e.g. `v0.s[1]`)
+    if caps[2].is_empty() {
+        return Some(format!(
+            "Vector register v{reg} viewed as {element} ({bits}-bit) elements"
+        ));
+    }
+    let lanes: u32 = caps[2].parse().ok()?;
+    // arrangements fill either the lower 64 bits or all 128 bits of the register
+    if lanes == 0 || !matches!(lanes * bits, 64 | 128) {
+        return None;
+    }
+
+    Some(format!(
+        "Vector register v{reg} viewed as {lanes} {element} ({bits}-bit) element{}",
+        if lanes == 1 { "" } else { "s" }
+    ))
+}
+
+/// A base mnemonic along with the condition codes appended to it, and their descriptions
+type ConditionalMnemonic = (&'static str, &'static [(&'static str, &'static str)]);
+
+/// x86 condition codes, as used by `setcc`, `jcc` and `cmovcc`, including the aliases
+/// sharing an encoding (e.g. `e` and `z`)
+const X86_CONDITIONS: &[(&str, &str)] = &[
+    ("o", "Overflow (OF=1)"),
+    ("no", "Not overflow (OF=0)"),
+    ("b", "Below, unsigned (CF=1)"),
+    ("c", "Carry (CF=1)"),
+    ("nae", "Not above or equal, unsigned (CF=1)"),
+    ("ae", "Above or equal, unsigned (CF=0)"),
+    ("nb", "Not below, unsigned (CF=0)"),
+    ("nc", "Not carry (CF=0)"),
+    ("e", "Equal (ZF=1)"),
+    ("z", "Zero (ZF=1)"),
+    ("ne", "Not equal (ZF=0)"),
+    ("nz", "Not zero (ZF=0)"),
+    ("be", "Below or equal, unsigned (CF=1 or ZF=1)"),
+    ("na", "Not above, unsigned (CF=1 or ZF=1)"),
+    ("a", "Above, unsigned (CF=0 and ZF=0)"),
+    ("nbe", "Not below or equal, unsigned (CF=0 and ZF=0)"),
+    ("s", "Sign (SF=1)"),
+    ("ns", "Not sign (SF=0)"),
+    ("p", "Parity (PF=1)"),
+    ("pe", "Parity even (PF=1)"),
+    ("np", "Not parity (PF=0)"),
+    ("po", "Parity odd (PF=0)"),
+    ("l", "Less, signed (SF≠OF)"),
+    ("nge", "Not greater or equal, signed (SF≠OF)"),
+    ("ge", "Greater or equal, signed (SF=OF)"),
+    ("nl", "Not less, signed (SF=OF)"),
+    ("le", "Less or equal, signed (ZF=1 or SF≠OF)"),
+    ("ng", "Not greater, signed (ZF=1 or SF≠OF)"),
+    ("g", "Greater, signed (ZF=0 and SF=OF)"),
+    ("nle", "Not less or equal, signed (ZF=0 and SF=OF)"),
+];
+
+/// AArch64 condition codes, as used by conditional branches such as `b.ne`
+const AARCH64_CONDITIONS: &[(&str, &str)] = &[
+    ("eq", "Equal"),
+    ("ne", "Not equal"),
+    ("cs", "Carry set"),
+    ("hs", "Unsigned higher or same"),
+    ("cc", "Carry clear"),
+    ("lo", "Unsigned lower"),
+    ("mi", "Negative"),
+    ("pl", "Positive or zero"),
+    ("vs", "Overflow"),
+    ("vc", "No overflow"),
+    ("hi", "Unsigned higher"),
+    ("ls", "Unsigned lower or same"),
+    ("ge", "Signed greater than or equal"),
+    ("lt", "Signed less than"),
+    ("gt", "Signed greater than"),
+    ("le", "Signed less than or equal"),
+    ("al", "Always"),
+];
+
+static ARCH_PROVIDERS: [&dyn ArchProvider; 4] = [
+    &X86Provider {
+        arch: Arch::X86,
+        name: "x86",
+    },
+    &X86Provider {
+        arch: Arch::X86_64,
+        name: "x86_64",
+    },
+    &Z80Provider,
+    &AArch64Provider,
+];
+
+/// Returns the providers for every supported architecture, in the order their entries
+/// are listed in hover and signature help
+#[must_use]
+pub fn arch_providers() -> &'static [&'static dyn ArchProvider] {
+    &ARCH_PROVIDERS
+}
+
+/// Find the start and end byte indices of a word inside the given line, around the
+/// byte index `col`
 /// Borrowed from RLS
 /// `additional_chars` parameter allows specifying additional legal "word"
 /// characters besides the default alphanumeric and '_'
 #[must_use]
 pub fn find_word_at_pos(line: &str, col: Column, additional_chars: &str) -> (Column, Column) {
-    let line_ = format!("{line} ");
     let is_ident_char = |c: char| {
         c.is_alphanumeric() || c == '_' || additional_chars.chars().any(|word_char| word_char == c)
     };
+    let col = col.min(line.len());
+    if !line.is_char_boundary(col) {
+        return (col, col);
+    }
 
-    let start = line_
-        .chars()
-        .enumerate()
-        .take(col)
-        .filter(|&(_, c)| !is_ident_char(c))
-        .last()
-        .map_or(0, |(i, _)| i + 1);
+    let start = line[..col]
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !is_ident_char(c))
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let end = line[col..]
+        .char_indices()
+        .find(|&(_, c)| !is_ident_char(c))
+        .map_or(line.len(), |(i, _)| col + i);
 
-    #[allow(clippy::filter_next)]
-    let mut end = line_
-        .chars()
-        .enumerate()
-        .skip(col)
-        .filter(|&(_, c)| !is_ident_char(c));
+    (start, end)
+}
+
+/// Converts the `character` offset of an LSP position in `line`, counted in UTF-16 code
+/// units, to a byte offset into `line`. Offsets past the end of the line map to its length
+#[must_use]
+pub fn byte_col_of_utf16(line: &str, character: u32) -> Column {
+    let mut units = 0;
+    for (idx, c) in line.char_indices() {
+        if units >= character as usize {
+            return idx;
+        }
+        units += c.len_utf16();
+    }
 
-    let end = end.next();
-    (start, end.map_or(col, |(i, _)| i))
+    line.len()
 }
 
 /// Returns the word undernearth the cursor given the specified `TextDocumentPositionParams`
@@ -77,7 +545,6 @@ pub fn get_word_from_file_params(
 ) -> Result<String> {
     let uri = &pos_params.text_document.uri;
     let line = pos_params.position.line as usize;
-    let col = pos_params.position.character as usize;
 
     let filepath = PathBuf::from(uri.as_str());
     match filepath.canonicalize() {
@@ -89,6 +556,7 @@ pub fn get_word_from_file_params(
             let buf_reader = std::io::BufReader::new(file);
 
             let line_conts = buf_reader.lines().nth(line).unwrap().unwrap();
+            let col = byte_col_of_utf16(&line_conts, pos_params.position.character);
             let (start, end) = find_word_at_pos(&line_conts, col, extra_chars);
             Ok(String::from(&line_conts[start..end]))
         }
@@ -108,6 +576,39 @@ pub fn get_word_from_pos_params<'a>(
     pos_params: &TextDocumentPositionParams,
     extra_chars: &str,
 ) -> &'a str {
+    let (line_contents, word_start, word_end) = word_bounds(doc, pos_params, extra_chars);
+    &line_contents[word_start..word_end]
+}
+
+/// Returns the range of the word in doc specified by the position params, i.e. of the
+/// word `get_word_from_pos_params` returns given the same `extra_chars`
+#[must_use]
+pub fn get_word_range_from_pos_params(
+    doc: &FullTextDocument,
+    pos_params: &TextDocumentPositionParams,
+    extra_chars: &str,
+) -> Range {
+    let (line_contents, word_start, word_end) = word_bounds(doc, pos_params, extra_chars);
+    let line = pos_params.position.line;
+    Range {
+        start: Position {
+            line,
+            character: line_contents[..word_start].encode_utf16().count() as u32,
+        },
+        end: Position {
+            line,
+            character: line_contents[..word_end].encode_utf16().count() as u32,
+        },
+    }
+}
+
+/// Returns the line specified by the position params, along with the start and end of
+/// the word at its position
+fn word_bounds<'a>(
+    doc: &'a FullTextDocument,
+    pos_params: &TextDocumentPositionParams,
+    extra_chars: &str,
+) -> (&'a str, Column, Column) {
     let line_contents = doc.get_content(Some(Range {
         start: Position {
             line: pos_params.position.line,
@@ -119,12 +620,40 @@ pub fn get_word_from_pos_params<'a>(
         },
     }));
 
-    let (word_start, word_end) = find_word_at_pos(
-        line_contents,
-        pos_params.position.character as usize,
-        extra_chars,
-    );
-    &line_contents[word_start..word_end]
+    let col = byte_col_of_utf16(line_contents, pos_params.position.character);
+    let (word_start, word_end) = find_word_at_pos(line_contents, col, extra_chars);
+    let (word_start, word_end) = arch_providers()
+        .iter()
+        .fold((word_start, word_end), |(start, end), provider| {
+            provider.extend_word(line_contents, start, end)
+        });
+    (line_contents, word_start, word_end)
+}
+
+/// x87 FPU stack registers are written as `st(i)`, which `find_word_at_pos` would
+/// split into `st` and `i`. If the word spanning `start..end` is either piece of
+/// such a register, returns the bounds of the entire register instead
+fn extend_fpu_stack_reg(line: &str, start: Column, end: Column) -> (Column, Column) {
+    let is_stack_reg = |reg_start: Column| {
+        line.get(reg_start..reg_start + 5).is_some_and(|reg| {
+            let reg = reg.as_bytes();
+            reg[..2].eq_ignore_ascii_case(b"st")
+                && reg[2] == b'('
+                && (b'0'..=b'7').contains(&reg[3])
+                && reg[4] == b')'
+        })
+    };
+
+    // cursor on the `st` portion
+    if end == start + 2 && is_stack_reg(start) {
+        return (start, start + 5);
+    }
+    // cursor on the index portion
+    if end == start + 1 && start >= 3 && is_stack_reg(start - 3) {
+        return (start - 3, start + 2);
+    }
+
+    (start, end)
 }
 
 /// Fetches default include directories, as well as any additional directories
@@ -315,88 +844,511 @@ pub fn get_compile_cmds(params: &InitializeParams) -> Option<CompilationDatabase
     None
 }
 
-/// Attempts to run the given compile command and parses the resulting output. Any
-/// relevant output will be translated into a `Diagnostic` object and pushed into
-/// `diagnostics`
-pub fn apply_compile_cmd(diagnostics: &mut Vec<Diagnostic>, compile_cmd: &CompileCommand) {
-    // TODO: Consolidate this logic, a little tricky because we need to capture
-    // compile_cmd.arguments by reference, but we get an owned Vec out of args_from_cmd()...
-    if let Some(ref args) = compile_cmd.arguments {
-        if args.len() < 2 {
-            return;
-        }
-        let output = match Command::new(&args[0]).args(&args[1..]).output() {
-            Ok(result) => result,
-            Err(e) => {
-                error!("Failed to launch compile command process -- Error: {e}");
-                return;
-            }
-        };
-        if let Ok(output_str) = String::from_utf8(output.stderr) {
-            get_diagnostics(diagnostics, &output_str);
-        }
-    } else if let Some(args) = compile_cmd.args_from_cmd() {
-        if args.len() < 2 {
-            return;
-        }
-        let output = match Command::new(&args[0]).args(&args[1..]).output() {
-            Ok(result) => result,
-            Err(e) => {
-                error!("Failed to launch compile command process -- Error: {e}");
-                return;
-            }
-        };
-        if let Ok(output_str) = String::from_utf8(output.stderr) {
-            get_diagnostics(diagnostics, &output_str);
-        }
-    }
-}
+/// Runs `check` on the name of each instruction in `curr_doc`, producing a diagnostic
+/// covering the name for each `(severity, message)` it returns, reported as `code`
+fn get_instr_diagnostics(
+    curr_doc: &FullTextDocument,
+    tree_entry: &mut TreeEntry,
+    code: DiagnosticCode,
+    check: impl Fn(&str) -> Option<(DiagnosticSeverity, String)>,
+) -> Vec<Diagnostic> {
+    static QUERY_INSTR: Lazy<tree_sitter::Query> = Lazy::new(|| {
+        tree_sitter::Query::new(
+            tree_sitter_asm::language(),
+            "(instruction kind: (word) @instr_name)",
+        )
+        .unwrap()
+    });
 
-/// Attempts to parse `tool_output`, translating it into `Diagnostic` objects
-/// and placing them into `diagnostics`
-///
-/// Looks for diagnostics of the following form:
-///
-/// <file name>:<line number>: Error: <Error message>
-///
-/// As more assemblers are incorporated, this can be updated
-///
-/// # Panics
-fn get_diagnostics(diagnostics: &mut Vec<Diagnostic>, tool_output: &str) {
-    static DIAG_REG: Lazy<Regex> = Lazy::new(|| Regex::new(r"^.*:(\d+):\s+(.*)$").unwrap());
+    let mut diagnostics = Vec::new();
+    let doc = curr_doc.get_content(None);
+    tree_entry.tree = tree_entry.parser.parse(doc, tree_entry.tree.as_ref());
+    let Some(ref tree) = tree_entry.tree else {
+        return diagnostics;
+    };
 
-    for line in tool_output.lines() {
-        if let Some(caps) = DIAG_REG.captures(line) {
-            if caps.len() < 3 {
-                // the entire capture is always at the 0th index
-                continue;
-            }
-            let Ok(line_number) = caps[1].parse::<u32>() else {
+    let mut cursor = tree_sitter::QueryCursor::new();
+    for match_ in cursor.matches(&QUERY_INSTR, tree.root_node(), doc.as_bytes()) {
+        for cap in match_.captures {
+            let Ok(name) = cap.node.utf8_text(doc.as_bytes()) else {
                 continue;
             };
-            let err_msg = &caps[2];
-            diagnostics.push(Diagnostic::new_simple(
-                Range {
-                    start: Position {
-                        line: line_number - 1,
-                        character: 0,
-                    },
-                    end: Position {
-                        line: line_number - 1,
-                        character: 0,
-                    },
-                },
-                String::from(err_msg),
-            ));
+            if let Some((severity, message)) = check(name) {
+                diagnostics.push(node_diagnostic(cap.node, code, severity, message));
+            }
         }
     }
+
+    diagnostics
 }
 
-/// Function allowing us to connect tree sitter's logging with the log crate
-pub fn tree_sitter_logger(log_type: tree_sitter::LogType, message: &str) {
-    // map tree-sitter log types to log levels, for now set everything to Trace
-    let log_level = match log_type {
-        tree_sitter::LogType::Parse | tree_sitter::LogType::Lex => log::Level::Trace,
+/// Returns a diagnostic from asm-lsp covering `node`
+fn node_diagnostic(
+    node: tree_sitter::Node,
+    code: DiagnosticCode,
+    severity: DiagnosticSeverity,
+    message: String,
+) -> Diagnostic {
+    Diagnostic {
+        range: Range {
+            start: lsp_pos_of_point(node.start_position()),
+            end: lsp_pos_of_point(node.end_position()),
+        },
+        severity: Some(severity),
+        code: Some(NumberOrString::String(code.code())),
+        source: Some(String::from("asm-lsp")),
+        message,
+        ..Default::default()
+    }
+}
+
+/// Drops the diagnostics of the `disabled` kinds from `diagnostics`, along with those
+/// silenced by an `asm-lsp: ignore` comment on their line. The comment takes the kinds
+/// to silence, separated by commas or spaces (e.g. `# asm-lsp: ignore
+/// suffix-size-mismatch`), silencing every kind if none are listed
+///
+/// Diagnostics not produced by asm-lsp itself, e.g. those of a compile command, are
+/// always kept
+pub fn filter_suppressed_diagnostics(
+    diagnostics: &mut Vec<Diagnostic>,
+    curr_doc: &FullTextDocument,
+    disabled: &[DiagnosticCode],
+) {
+    static IGNORE_COMMENT: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"asm-lsp:\s*ignore\b([\w\s,-]*)").unwrap());
+
+    let doc = curr_doc.get_content(None);
+    let lines: Vec<&str> = doc.lines().collect();
+    diagnostics.retain(|diagnostic| {
+        let Some(code) = diagnostic.code.as_ref().and_then(|code| match code {
+            NumberOrString::String(code) => code
+                .strip_prefix("asm-lsp/")
+                .and_then(|code| DiagnosticCode::from_str(code).ok()),
+            NumberOrString::Number(_) => None,
+        }) else {
+            return true;
+        };
+        if disabled.contains(&code) {
+            return false;
+        }
+        let line = lines
+            .get(diagnostic.range.start.line as usize)
+            .copied()
+            .unwrap_or_default();
+        let Some(caps) = IGNORE_COMMENT.captures(line) else {
+            return true;
+        };
+        let mut ignored = caps[1]
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|name| !name.is_empty())
+            .peekable();
+        // a bare `asm-lsp: ignore` silences everything
+        ignored.peek().is_some() && !ignored.any(|name| name == code.as_ref())
+    });
+}
+
+/// Warns about the instruction `name` if it's privileged (ring 0) under any of `archs`
+fn check_privileged_instr(
+    name: &str,
+    archs: &[Arch],
+    instr_map: &NameToInstructionMap,
+) -> Option<(DiagnosticSeverity, String)> {
+    let is_privileged = archs.iter().any(|arch| {
+        instr_map
+            .get(&(*arch, name))
+            .is_some_and(|instr| instr.privileged)
+    });
+    is_privileged.then(|| {
+        (
+            DiagnosticSeverity::WARNING,
+            format!("`{name}` is a privileged (ring 0) instruction and will fault in user mode"),
+        )
+    })
+}
+
+/// Errors on the instruction `name` if it's invalid in 64-bit (long) mode
+fn check_long_mode_instr(
+    name: &str,
+    instr_map: &NameToInstructionMap,
+) -> Option<(DiagnosticSeverity, String)> {
+    // names the x86-64 data knows about are fine
+    if instr_map.contains_key(&(Arch::X86_64, name)) {
+        return None;
+    }
+    let instr = instr_map.get(&(Arch::X86, name))?;
+    let invalid_in_long_mode = !instr.forms.is_empty()
+        && instr
+            .forms
+            .iter()
+            .all(|form| form.modes.is_some_and(|modes| !modes.long));
+    invalid_in_long_mode.then(|| {
+        (
+            DiagnosticSeverity::ERROR,
+            format!("`{name}` is invalid in 64-bit mode"),
+        )
+    })
+}
+
+/// Produces a warning for each privileged (ring 0) instruction in `curr_doc`, as they
+/// fault when executed in user mode
+pub fn get_privileged_instr_diagnostics(
+    curr_doc: &FullTextDocument,
+    tree_entry: &mut TreeEntry,
+    instr_map: &NameToInstructionMap,
+) -> Vec<Diagnostic> {
+    get_instr_diagnostics(
+        curr_doc,
+        tree_entry,
+        DiagnosticCode::PrivilegedInstruction,
+        |name| check_privileged_instr(name, &[Arch::X86, Arch::X86_64], instr_map),
+    )
+}
+
+/// Produces an error for each instruction in `curr_doc` that is invalid in 64-bit (long)
+/// mode, e.g. `aaa` or `into`, for use on files targeting x86-64
+pub fn get_long_mode_instr_diagnostics(
+    curr_doc: &FullTextDocument,
+    tree_entry: &mut TreeEntry,
+    instr_map: &NameToInstructionMap,
+) -> Vec<Diagnostic> {
+    get_instr_diagnostics(
+        curr_doc,
+        tree_entry,
+        DiagnosticCode::InvalidInLongMode,
+        |name| check_long_mode_instr(name, instr_map),
+    )
+}
+
+/// Returns the operand width in bits implied by a GAS mnemonic's size suffix, e.g. 32
+/// for `movl`
+fn gas_suffix_bits(name: &str) -> Option<u32> {
+    match name.chars().last()? {
+        'b' => Some(8),
+        'w' => Some(16),
+        'l' => Some(32),
+        'q' => Some(64),
+        _ => None,
+    }
+}
+
+/// Returns the width in bits of a general purpose register
+const fn gp_register_bits(width: RegisterWidth) -> Option<u32> {
+    match width {
+        RegisterWidth::Bits8 | RegisterWidth::Upper8Lower16 | RegisterWidth::Lower8Lower16 => {
+            Some(8)
+        }
+        RegisterWidth::Bits16 => Some(16),
+        RegisterWidth::Bits32 => Some(32),
+        RegisterWidth::Bits64 => Some(64),
+        _ => None,
+    }
+}
+
+/// Returns the width in bits of the register an operand type accepts, if any
+const fn register_operand_bits(type_: &OperandType) -> Option<u32> {
+    match type_ {
+        OperandType::r8 | OperandType::r8l | OperandType::al | OperandType::cl => Some(8),
+        OperandType::r16 | OperandType::r16l | OperandType::ax => Some(16),
+        OperandType::r32 | OperandType::r32l | OperandType::eax => Some(32),
+        OperandType::r64 | OperandType::rax => Some(64),
+        _ => None,
+    }
+}
+
+/// Splits an `instruction` node into its mnemonic and operand nodes, skipping comments
+fn instruction_parts(
+    instr: tree_sitter::Node,
+) -> Option<(tree_sitter::Node, Vec<tree_sitter::Node>)> {
+    let kind = instr.child_by_field_name("kind")?;
+    let mut walker = instr.walk();
+    let operands = instr
+        .named_children(&mut walker)
+        .filter(|child| *child != kind && child.kind() != "line_comment")
+        .collect();
+    Some((kind, operands))
+}
+
+/// Checks the general purpose register `operands` of the GAS instruction `name`
+/// against its size suffix, returning the index of each mismatching operand along with
+/// an error message
+fn check_suffix_size_mismatches(
+    name: &str,
+    operands: &[&str],
+    instr_map: &NameToInstructionMap,
+    reg_map: &NameToRegisterMap,
+) -> Vec<(usize, String)> {
+    let mut mismatches = Vec::new();
+    let name = name.to_ascii_lowercase();
+    let Some(suffix_bits) = gas_suffix_bits(&name) else {
+        return mismatches;
+    };
+    // only forms the suffixed mnemonic names, to skip non-suffixed instructions
+    // that happen to end in one of the suffix characters
+    let forms: Vec<&InstructionForm> = [Arch::X86_64, Arch::X86]
+        .iter()
+        .filter_map(|arch| instr_map.get(&(*arch, name.as_str())))
+        .flat_map(|instr| instr.forms.iter())
+        .filter(|form| form.gas_name.as_deref() == Some(name.as_str()))
+        .collect();
+    if forms.is_empty() {
+        return mismatches;
+    }
+
+    for (i, operand) in operands.iter().enumerate() {
+        let Some(reg_name) = operand.strip_prefix('%') else {
+            continue;
+        };
+        let reg_name = reg_name.to_ascii_lowercase();
+        let Some(reg_bits) = [Arch::X86_64, Arch::X86]
+            .iter()
+            .find_map(|arch| reg_map.get(&(*arch, reg_name.as_str())))
+            .filter(|reg| reg.reg_type == Some(RegisterType::GeneralPurpose))
+            .and_then(|reg| reg.width)
+            .and_then(gp_register_bits)
+        else {
+            continue;
+        };
+        if reg_bits == suffix_bits {
+            continue;
+        }
+        // AT&T operands are in the reverse order of the forms' (Intel) operands
+        let form_idx = operands.len() - 1 - i;
+        let accepted = forms.iter().any(|form| {
+            form.operands.len() == operands.len()
+                && register_operand_bits(&form.operands[form_idx].type_) == Some(reg_bits)
+        });
+        if !accepted {
+            mismatches.push((
+                i,
+                format!(
+                    "`{name}` takes {suffix_bits}-bit operands, but `%{reg_name}` is a {reg_bits}-bit register"
+                ),
+            ));
+        }
+    }
+
+    mismatches
+}
+
+/// Produces an error for each GAS instruction in `curr_doc` whose size suffix (`b`,
+/// `w`, `l` or `q`) disagrees with the width of a general purpose register operand,
+/// e.g. `movl %rax, %rbx`
+///
+/// Operands are only flagged if no form of the mnemonic accepts a register of that
+/// width in that position, leaving instructions like `movzbl` and `shll %cl, ...` be
+pub fn get_suffix_size_mismatch_diagnostics(
+    curr_doc: &FullTextDocument,
+    tree_entry: &mut TreeEntry,
+    instr_map: &NameToInstructionMap,
+    reg_map: &NameToRegisterMap,
+) -> Vec<Diagnostic> {
+    static QUERY_INSTR: Lazy<tree_sitter::Query> = Lazy::new(|| {
+        tree_sitter::Query::new(tree_sitter_asm::language(), "(instruction) @instr").unwrap()
+    });
+
+    let mut diagnostics = Vec::new();
+    let doc = curr_doc.get_content(None);
+    tree_entry.tree = tree_entry.parser.parse(doc, tree_entry.tree.as_ref());
+    let Some(ref tree) = tree_entry.tree else {
+        return diagnostics;
+    };
+
+    let mut cursor = tree_sitter::QueryCursor::new();
+    for match_ in cursor.matches(&QUERY_INSTR, tree.root_node(), doc.as_bytes()) {
+        for cap in match_.captures {
+            let Some((kind, operands)) = instruction_parts(cap.node) else {
+                continue;
+            };
+            let Ok(name) = kind.utf8_text(doc.as_bytes()) else {
+                continue;
+            };
+            let operand_texts: Vec<&str> = operands
+                .iter()
+                .map(|operand| operand.utf8_text(doc.as_bytes()).unwrap_or_default())
+                .collect();
+            for (i, message) in
+                check_suffix_size_mismatches(name, &operand_texts, instr_map, reg_map)
+            {
+                diagnostics.push(node_diagnostic(
+                    operands[i],
+                    DiagnosticCode::SuffixSizeMismatch,
+                    DiagnosticSeverity::ERROR,
+                    message,
+                ));
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Analyzes a single line of `arch` assembly outside of any document, e.g. for a REPL,
+/// returning its mnemonic, the documented instruction it resolves to, its operands and
+/// its diagnostics
+///
+/// The diagnostics are those the server reports for x86 and x86-64 documents that only
+/// depend on the line itself: privileged instructions, GAS size suffix mismatches and,
+/// for x86-64, instructions that are invalid in 64-bit mode
+///
+/// # Panics
+///
+/// Panics if the tree-sitter parser fails to load the assembly grammar
+#[must_use]
+pub fn analyze_line<'a>(
+    arch: Arch,
+    line: &str,
+    names_to_info: &NameToInfoMaps<'a>,
+) -> LineAnalysis<'a> {
+    let mut analysis = LineAnalysis::default();
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(tree_sitter_asm::language()).unwrap();
+    let Some(tree) = parser.parse(line, None) else {
+        return analysis;
+    };
+    let mut walker = tree.root_node().walk();
+    let Some((kind, operands)) = tree
+        .root_node()
+        .children(&mut walker)
+        .find(|node| node.kind() == "instruction")
+        .and_then(instruction_parts)
+    else {
+        return analysis;
+    };
+    let Ok(name) = kind.utf8_text(line.as_bytes()) else {
+        return analysis;
+    };
+    let operand_texts: Vec<&str> = operands
+        .iter()
+        .map(|operand| operand.utf8_text(line.as_bytes()).unwrap_or_default())
+        .collect();
+
+    let instr_map = &names_to_info.instructions;
+    analysis.mnemonic = Some(name.to_string());
+    analysis.instruction = instr_map.get(&(arch, name)).copied().or_else(|| {
+        instr_map
+            .get(&(arch, name.to_lowercase().as_str()))
+            .copied()
+    });
+    analysis.operands = operand_texts.iter().map(ToString::to_string).collect();
+
+    if matches!(arch, Arch::X86 | Arch::X86_64) {
+        let checks = vec![
+            (
+                DiagnosticCode::InvalidInLongMode,
+                (arch == Arch::X86_64)
+                    .then(|| check_long_mode_instr(name, instr_map))
+                    .flatten(),
+            ),
+            (
+                DiagnosticCode::PrivilegedInstruction,
+                check_privileged_instr(name, &[arch], instr_map),
+            ),
+        ];
+        for (code, (severity, message)) in checks
+            .into_iter()
+            .filter_map(|(code, check)| Some((code, check?)))
+        {
+            analysis
+                .diagnostics
+                .push(node_diagnostic(kind, code, severity, message));
+        }
+        for (i, message) in
+            check_suffix_size_mismatches(name, &operand_texts, instr_map, &names_to_info.registers)
+        {
+            analysis.diagnostics.push(node_diagnostic(
+                operands[i],
+                DiagnosticCode::SuffixSizeMismatch,
+                DiagnosticSeverity::ERROR,
+                message,
+            ));
+        }
+    }
+
+    analysis
+}
+
+/// Attempts to run the given compile command and parses the resulting output. Any
+/// relevant output will be translated into a `Diagnostic` object and pushed into
+/// `diagnostics`
+pub fn apply_compile_cmd(diagnostics: &mut Vec<Diagnostic>, compile_cmd: &CompileCommand) {
+    // TODO: Consolidate this logic, a little tricky because we need to capture
+    // compile_cmd.arguments by reference, but we get an owned Vec out of args_from_cmd()...
+    if let Some(ref args) = compile_cmd.arguments {
+        if args.len() < 2 {
+            return;
+        }
+        let output = match Command::new(&args[0]).args(&args[1..]).output() {
+            Ok(result) => result,
+            Err(e) => {
+                error!("Failed to launch compile command process -- Error: {e}");
+                return;
+            }
+        };
+        if let Ok(output_str) = String::from_utf8(output.stderr) {
+            get_diagnostics(diagnostics, &output_str);
+        }
+    } else if let Some(args) = compile_cmd.args_from_cmd() {
+        if args.len() < 2 {
+            return;
+        }
+        let output = match Command::new(&args[0]).args(&args[1..]).output() {
+            Ok(result) => result,
+            Err(e) => {
+                error!("Failed to launch compile command process -- Error: {e}");
+                return;
+            }
+        };
+        if let Ok(output_str) = String::from_utf8(output.stderr) {
+            get_diagnostics(diagnostics, &output_str);
+        }
+    }
+}
+
+/// Attempts to parse `tool_output`, translating it into `Diagnostic` objects
+/// and placing them into `diagnostics`
+///
+/// Looks for diagnostics of the following form:
+///
+/// <file name>:<line number>: Error: <Error message>
+///
+/// As more assemblers are incorporated, this can be updated
+///
+/// # Panics
+fn get_diagnostics(diagnostics: &mut Vec<Diagnostic>, tool_output: &str) {
+    static DIAG_REG: Lazy<Regex> = Lazy::new(|| Regex::new(r"^.*:(\d+):\s+(.*)$").unwrap());
+
+    for line in tool_output.lines() {
+        if let Some(caps) = DIAG_REG.captures(line) {
+            if caps.len() < 3 {
+                // the entire capture is always at the 0th index
+                continue;
+            }
+            let Ok(line_number) = caps[1].parse::<u32>() else {
+                continue;
+            };
+            let err_msg = &caps[2];
+            diagnostics.push(Diagnostic::new_simple(
+                Range {
+                    start: Position {
+                        line: line_number - 1,
+                        character: 0,
+                    },
+                    end: Position {
+                        line: line_number - 1,
+                        character: 0,
+                    },
+                },
+                String::from(err_msg),
+            ));
+        }
+    }
+}
+
+/// Function allowing us to connect tree sitter's logging with the log crate
+pub fn tree_sitter_logger(log_type: tree_sitter::LogType, message: &str) {
+    // map tree-sitter log types to log levels, for now set everything to Trace
+    let log_level = match log_type {
+        tree_sitter::LogType::Parse | tree_sitter::LogType::Lex => log::Level::Trace,
     };
 
     // tree-sitter logs are incredibly verbose, only forward them to the logger
@@ -444,290 +1396,3157 @@ pub fn text_doc_change_to_ts_edit(
 }
 
 /// Given a `NameTo_SomeItem_` map, returns a `Vec<CompletionItem>` for the items
-/// contained within the map
+/// contained within the map, labelled with their names in `case`
+///
+/// Names shared by several architectures/assemblers (or differing only in case) are
+/// merged into a single item, documenting each of them
 #[must_use]
-pub fn get_completes<T: Completable, U: ArchOrAssembler>(
+pub fn get_completes<T: Completable, U: ArchOrAssembler + Serialize>(
     map: &HashMap<(U, &str), T>,
     kind: Option<CompletionItemKind>,
+    case: CompletionCase,
 ) -> Vec<CompletionItem> {
-    map.iter()
-        .map(|((_arch_or_asm, name), item_info)| {
-            let value = format!("{item_info}");
-
-            CompletionItem {
-                label: (*name).to_string(),
-                kind,
-                documentation: Some(Documentation::MarkupContent(MarkupContent {
-                    kind: MarkupKind::Markdown,
-                    value,
-                })),
-                ..Default::default()
-            }
+    // visit the entries in a stable order so merged items don't depend on the map's,
+    // keeping the first architecture's label and preferring lowercase ones
+    let mut entries: Vec<_> = map
+        .iter()
+        .map(|((arch_or_asm, name), item_info)| {
+            let label = case.apply(name);
+            let arch_or_asm = serde_json::to_value(arch_or_asm).ok();
+            (label.to_lowercase(), arch_or_asm, label, item_info)
         })
-        .collect()
+        .collect();
+    entries.sort_by_cached_key(|(key, arch_or_asm, label, _)| {
+        (
+            key.clone(),
+            arch_or_asm.as_ref().map(ToString::to_string),
+            std::cmp::Reverse(label.clone()),
+        )
+    });
+
+    let mut items: Vec<CompletionItem> = Vec::new();
+    let mut item_idxs: HashMap<String, usize> = HashMap::new();
+    for (key, arch_or_asm, label, item_info) in entries {
+        let value = format!("{item_info}");
+        if let Some(&idx) = item_idxs.get(&key) {
+            let item = &mut items[idx];
+            if let Some(Documentation::MarkupContent(ref mut content)) = item.documentation {
+                if !content.value.contains(&value) {
+                    content.value += &format!("\n\n{value}");
+                }
+            }
+            if let (Some(serde_json::Value::Array(archs)), Some(arch_or_asm)) =
+                (item.data.as_mut(), arch_or_asm)
+            {
+                if !archs.contains(&arch_or_asm) {
+                    archs.push(arch_or_asm);
+                }
+            }
+            continue;
+        }
+
+        item_idxs.insert(key, items.len());
+        items.push(CompletionItem {
+            label,
+            kind,
+            documentation: Some(Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            })),
+            // lets completion narrow the items down to an architecture
+            data: Some(serde_json::Value::Array(arch_or_asm.into_iter().collect())),
+            ..Default::default()
+        });
+    }
+
+    items
+}
+
+/// Renders the hover shown for the instruction or register `name` of `arch` as a
+/// standalone HTML fragment, e.g. for pasting into a wiki page
+///
+/// Returns `None` if `arch` doesn't define `name`
+#[must_use]
+pub fn get_hover_html(arch: Arch, name: &str, names_to_info: &NameToInfoMaps) -> Option<String> {
+    let name = name.to_lowercase();
+    let markdown = if let Some(instr) = names_to_info.instructions.get(&(arch, name.as_str())) {
+        instr.to_markdown(arch, None, None, FormsLayout::Verbose, false)
+    } else {
+        names_to_info
+            .registers
+            .get(&(arch, name.as_str()))?
+            .to_markdown(arch, None, None, FormsLayout::Verbose, false)
+    };
+
+    Some(markdown_to_html(&markdown))
+}
+
+/// Converts hover `markdown` to an HTML fragment, wrapped in a single `div` so it can be
+/// embedded as is
+#[must_use]
+pub fn markdown_to_html(markdown: &str) -> String {
+    let mut html = String::from("<div class=\"asm-lsp-hover\">\n");
+    pulldown_cmark::html::push_html(&mut html, pulldown_cmark::Parser::new(markdown));
+    html += "</div>\n";
+
+    html
 }
 
 #[must_use]
-pub fn get_hover_resp<T: Hoverable, U: Hoverable, V: Hoverable>(
+#[allow(clippy::too_many_arguments)]
+pub fn get_hover_resp<U: Hoverable>(
     params: &HoverParams,
     word: &str,
     file_word: &str,
-    instruction_map: &HashMap<(Arch, &str), T>,
+    curr_doc: &FullTextDocument,
+    tree_entry: Option<&mut TreeEntry>,
+    instruction_map: &NameToInstructionMap,
     register_map: &HashMap<(Arch, &str), U>,
-    directive_map: &HashMap<(Assembler, &str), V>,
+    directive_map: &NameToDirectiveMap,
+    same_operations: &SameOperationMap,
     include_dirs: &HashMap<SourceFile, Vec<PathBuf>>,
+    config: &TargetConfig,
+    included: &[IncludedFile],
 ) -> Option<Hover> {
-    let instr_lookup = lookup_hover_resp_by_arch(word, instruction_map);
-    if instr_lookup.is_some() {
-        return instr_lookup;
+    get_hover_resp_with_kind(
+        params,
+        word,
+        file_word,
+        curr_doc,
+        tree_entry,
+        instruction_map,
+        register_map,
+        directive_map,
+        same_operations,
+        include_dirs,
+        config,
+        included,
+    )
+    .map(|(_, hover)| hover)
+}
+
+/// Like [`get_hover_resp`], but also returns what the hover resolved to, for clients
+/// which present instructions, registers, etc. differently
+#[must_use]
+#[allow(clippy::too_many_arguments)]
+pub fn get_hover_resp_with_kind<U: Hoverable>(
+    params: &HoverParams,
+    word: &str,
+    file_word: &str,
+    curr_doc: &FullTextDocument,
+    tree_entry: Option<&mut TreeEntry>,
+    instruction_map: &NameToInstructionMap,
+    register_map: &HashMap<(Arch, &str), U>,
+    directive_map: &NameToDirectiveMap,
+    same_operations: &SameOperationMap,
+    include_dirs: &HashMap<SourceFile, Vec<PathBuf>>,
+    config: &TargetConfig,
+    included: &[IncludedFile],
+) -> Option<(HoverKind, Hover)> {
+    // symbols like `foo@PLT` should still resolve as `foo`, with a note about the relocation
+    let (word, reloc) = split_relocation_suffix(word);
+
+    // directive arguments such as the symbol in `.size foo, .-foo` name a label, so
+    // resolve them as one rather than as an instruction/register that happens to match
+    if let Some(tree_entry) = tree_entry {
+        if let Some(counter_resp) = get_location_counter_hover_resp(curr_doc, params, tree_entry) {
+            debug!("hover '{word}' resolved as the location counter");
+            return Some((HoverKind::None, counter_resp));
+        }
+        if is_directive_symbol_arg(curr_doc, params, directive_map) {
+            if let Some(label_resp) = get_label_hover_resp(curr_doc, tree_entry, word) {
+                debug!("hover '{word}' resolved as a label named by a directive argument");
+                return Some((HoverKind::None, label_resp));
+            }
+        }
+        if let Some(prefix_resp) = get_prefix_hover_resp(curr_doc, params, tree_entry, word) {
+            debug!("hover '{word}' resolved as an instruction prefix");
+            return Some((HoverKind::Instruction, prefix_resp));
+        }
     }
 
-    let directive_lookup = lookup_hover_resp_by_assembler(word, directive_map);
-    if directive_lookup.is_some() {
-        return directive_lookup;
+    if let Some(alignment_resp) = get_alignment_hover_resp(curr_doc, params, config) {
+        debug!("hover '{word}' resolved as an alignment directive argument");
+        return Some((HoverKind::Immediate, alignment_resp));
+    }
+
+    if let Some(repeat_resp) = get_repeat_hover_resp(curr_doc, params) {
+        debug!("hover '{word}' resolved as a repeated data directive argument");
+        return Some((HoverKind::Immediate, repeat_resp));
+    }
+
+    if config.opts.syscalls {
+        if let Some(syscall_resp) = get_syscall_hover_resp(curr_doc, params, word) {
+            debug!("hover '{word}' resolved as a system call number");
+            return Some((HoverKind::Immediate, syscall_resp));
+        }
+    }
+
+    if let Some(type_resp) = get_symbol_type_hover_resp(curr_doc, params) {
+        debug!("hover '{word}' resolved as a `.type` symbol type");
+        return Some((HoverKind::Directive, type_resp));
+    }
+
+    if let Some(loc_resp) = get_loc_hover_resp(curr_doc, params) {
+        debug!("hover '{word}' resolved as a `.loc` directive argument");
+        return Some((HoverKind::Directive, loc_resp));
+    }
+
+    if let Some(imm_resp) = get_immediate_hover_resp(curr_doc, params, config) {
+        debug!("hover '{word}' resolved as a numeric immediate");
+        return Some((HoverKind::Immediate, imm_resp));
+    }
+
+    if let Some(operand_resp) = get_operand_hover_resp(word, config) {
+        debug!("hover '{word}' resolved as an architecture specific operand");
+        return Some((HoverKind::Register, operand_resp));
+    }
+
+    if let Some(constant_resp) = get_constant_hover_resp(curr_doc.get_content(None), included, word)
+    {
+        debug!("hover '{word}' resolved as a constant");
+        return Some((HoverKind::Immediate, constant_resp));
+    }
+
+    let mut resp = lookup_hover_resp(
+        params,
+        word,
+        file_word,
+        instruction_map,
+        register_map,
+        directive_map,
+        include_dirs,
+        config,
+    );
+    // underline the instruction, register or directive the hover describes
+    if let Some((HoverKind::Instruction | HoverKind::Register | HoverKind::Directive, hover)) =
+        resp.as_mut()
+    {
+        hover.range = Some(get_word_range_from_pos_params(
+            curr_doc,
+            &params.text_document_position_params,
+            "@",
+        ));
     }
+    if let Some(HoverContents::Markup(ref mut content)) =
+        resp.as_mut().map(|(_, h)| &mut h.contents)
+    {
+        for encoding in get_encodings(curr_doc, params, word, instruction_map, config) {
+            content.value += &format!("\n\n**Encoding**: `{encoding}`");
+        }
+        if let Some(note) = get_same_operation_note(word, instruction_map, same_operations) {
+            content.value += &format!("\n\n{note}");
+        }
+        if config.opts.z80_timing_legend
+            && shows_z80_timings(word, instruction_map, config.opts.primary_arch)
+        {
+            content.value += &format!("\n\n{Z80_TIMING_LEGEND}");
+        }
+        if let Some(section) = get_data_directive_section(curr_doc, params, word) {
+            content.value += &format!("\n\n*Inside {section}*");
+        }
+    }
+    // the ARM state doesn't change what the hovered mnemonic is, only how it's encoded,
+    // and only matters to the instructions of ARM architectures
+    let shows_arm_instr = arch_providers().iter().any(|provider| {
+        provider.is_arm()
+            && shown_instruction(
+                word,
+                instruction_map,
+                config.opts.primary_arch,
+                provider.arch(),
+            )
+            .is_some()
+    });
+    if let Some((HoverKind::Instruction, hover)) = resp.as_mut().filter(|_| shows_arm_instr) {
+        let line = params.text_document_position_params.position.line as usize;
+        if let (HoverContents::Markup(content), Some(state)) = (
+            &mut hover.contents,
+            get_arm_state(curr_doc.get_content(None), line),
+        ) {
+            content.value += &format!("\n\n*Assembled in {state} state*");
+        }
+    }
+
+    match (resp, reloc) {
+        (Some((kind, mut hover)), Some((suffix, descr))) => {
+            if let HoverContents::Markup(ref mut content) = hover.contents {
+                content.value += &format!("\n\n**@{suffix}**: {descr}");
+            }
+            Some((kind, hover))
+        }
+        (None, Some((suffix, descr))) => Some((
+            HoverKind::None,
+            Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: format!("`{word}@{suffix}`\n\n**@{suffix}**: {descr}"),
+                }),
+                range: None,
+            },
+        )),
+        (resp, None) => resp,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn lookup_hover_resp<T: Hoverable, U: Hoverable, V: Hoverable>(
+    params: &HoverParams,
+    word: &str,
+    file_word: &str,
+    instruction_map: &HashMap<(Arch, &str), T>,
+    register_map: &HashMap<(Arch, &str), U>,
+    directive_map: &HashMap<(Assembler, &str), V>,
+    include_dirs: &HashMap<SourceFile, Vec<PathBuf>>,
+    config: &TargetConfig,
+) -> Option<(HoverKind, Hover)> {
+    let instr_lookup =
+        lookup_hover_resp_by_arch(word, "NameToInstructionMap", instruction_map, &config.opts);
+    if let Some(instr_resp) = instr_lookup {
+        return Some((HoverKind::Instruction, instr_resp));
+    }
+
+    let directive_lookup = lookup_hover_resp_by_assembler(word, directive_map);
+    if let Some(directive_resp) = directive_lookup {
+        return Some((HoverKind::Directive, directive_resp));
+    }
+
+    let reg_lookup =
+        lookup_hover_resp_by_arch(word, "NameToRegisterMap", register_map, &config.opts);
+    if let Some(reg_resp) = reg_lookup {
+        return Some((HoverKind::Register, reg_resp));
+    }
+
+    let demang = get_demangle_resp(word);
+    if let Some(demang_resp) = demang {
+        debug!("hover '{word}' resolved by demangling it");
+        return Some((HoverKind::Demangled, demang_resp));
+    }
+
+    let include_path = get_include_resp(
+        &params.text_document_position_params.text_document.uri,
+        file_word,
+        include_dirs,
+    );
+    if let Some(include_resp) = include_path {
+        debug!("hover '{file_word}' resolved as an include path");
+        return Some((HoverKind::None, include_resp));
+    }
+
+    None
+}
+
+/// Fills `same_operations` with a note for each `canonical` id shared by the instructions
+/// of several loaded architectures under different names, e.g.
+/// "x86, x86-64: `JMP` / z80: `jp` — same operation". Call once `instruction_map` is
+/// populated, hover then only looks the notes up
+pub fn populate_same_operation_map<'instruction>(
+    instruction_map: &NameToInstructionMap<'instruction>,
+    same_operations: &mut SameOperationMap<'instruction>,
+) {
+    // each instruction is in the map under all of its names, so keep one per arch
+    let mut by_canonical: HashMap<&str, HashMap<Arch, &Instruction>> = HashMap::new();
+    for ((arch, _), instr) in instruction_map {
+        if let Some(canonical) = instr.canonical.as_deref() {
+            by_canonical
+                .entry(canonical)
+                .or_default()
+                .entry(*arch)
+                .or_insert(instr);
+        }
+    }
+
+    for (canonical, instrs) in by_canonical {
+        let mut groups: Vec<(&str, Vec<Arch>)> = Vec::new();
+        for provider in arch_providers() {
+            let arch = provider.arch();
+            let Some(instr) = instrs.get(&arch) else {
+                continue;
+            };
+            match groups
+                .iter_mut()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&instr.name))
+            {
+                Some((_, archs)) => archs.push(arch),
+                None => groups.push((&instr.name, vec![arch])),
+            }
+        }
+        if groups.len() < 2 {
+            continue;
+        }
+
+        let entries: Vec<String> = groups
+            .iter()
+            .map(|(name, archs)| {
+                let archs: Vec<String> = archs.iter().map(ToString::to_string).collect();
+                format!("{}: `{name}`", archs.join(", "))
+            })
+            .collect();
+        same_operations.insert(
+            canonical,
+            format!("{} — same operation", entries.join(" / ")),
+        );
+    }
+}
+
+/// Returns the note relating the instruction `word` to the instructions of the other
+/// loaded architectures sharing its `canonical` id, see `populate_same_operation_map`
+fn get_same_operation_note<'a>(
+    word: &str,
+    instruction_map: &NameToInstructionMap,
+    same_operations: &'a SameOperationMap,
+) -> Option<&'a String> {
+    let canonical = arch_providers().iter().find_map(|provider| {
+        instruction_map
+            .get(&(provider.arch(), word))?
+            .canonical
+            .as_deref()
+    })?;
+    same_operations.get(canonical)
+}
+
+/// Matches an instruction loading an immediate into `rax`/`eax`, in AT&T or Intel syntax,
+/// capturing the immediate
+static SYSCALL_NUM_LOAD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^\s*mov[lq]?\s+(?:\$(\w+)\s*,\s*%[re]ax|[re]ax\s*,\s*(\w+))\s*(?:[#;].*)?$")
+        .unwrap()
+});
+
+/// Explains the columns of the z80 timing line shown for each instruction form
+const Z80_TIMING_LEGEND: &str = "*Timings are in T-states (clock cycles). Z80 + M1 adds \
+    the extra wait state inserted on each M1 (opcode fetch) cycle by e.g. the MSX, R800 is \
+    the MSX turbo R's CPU and R800 + Wait includes its memory wait states*";
+
+/// Returns the `arch` instruction named `word` if the hover for `word` includes it, i.e.
+/// it isn't hidden in favor of the primary architecture
+fn shown_instruction<'a>(
+    word: &str,
+    instruction_map: &NameToInstructionMap<'a>,
+    primary_arch: Option<Arch>,
+    arch: Arch,
+) -> Option<&'a Instruction> {
+    let instr = instruction_map.get(&(arch, word))?;
+    let hidden = primary_arch
+        .is_some_and(|primary| primary != arch && instruction_map.contains_key(&(primary, word)));
+
+    (!hidden).then_some(*instr)
+}
+
+/// Returns whether the hover for `word` includes z80 instruction forms with timings
+fn shows_z80_timings(
+    word: &str,
+    instruction_map: &NameToInstructionMap,
+    primary_arch: Option<Arch>,
+) -> bool {
+    shown_instruction(word, instruction_map, primary_arch, Arch::Z80)
+        .is_some_and(|instr| instr.forms.iter().any(|form| form.z80_timing.is_some()))
+}
+
+/// Returns the opcode bytes of the instruction on the hovered line, if `word` is its
+/// mnemonic, for each architecture that can resolve the form its operands select
+fn get_encodings(
+    curr_doc: &FullTextDocument,
+    params: &HoverParams,
+    word: &str,
+    instruction_map: &NameToInstructionMap,
+    config: &TargetConfig,
+) -> Vec<String> {
+    let line_num = params.text_document_position_params.position.line as usize;
+    let Some(line) = curr_doc.get_content(None).lines().nth(line_num) else {
+        return Vec::new();
+    };
+    let code = line.split(';').next().unwrap_or_default();
+    let mut tokens = code.trim().splitn(2, char::is_whitespace);
+    let mut mnemonic = tokens.next().unwrap_or_default();
+    let mut rest = tokens.next().unwrap_or_default();
+    // skip over a label preceding the instruction
+    if mnemonic.ends_with(':') {
+        let mut tokens = rest.trim().splitn(2, char::is_whitespace);
+        mnemonic = tokens.next().unwrap_or_default();
+        rest = tokens.next().unwrap_or_default();
+    }
+    if !mnemonic.eq_ignore_ascii_case(word) {
+        return Vec::new();
+    }
+    let mut operands = split_operands(rest, config.operand_syntax());
+    operands.retain(|op| !op.is_empty());
+
+    arch_providers()
+        .iter()
+        .filter_map(|provider| {
+            let instr = instruction_map.get(&(provider.arch(), word))?;
+            provider.encode(instr, &operands)
+        })
+        .collect()
+}
+
+/// Parses a GAS integer literal, in decimal, hex (`0x`), binary (`0b`) or octal (leading
+/// `0`), or in the trailing-`h` hex form of Intel/z80 syntax (e.g. `0FFh`)
+fn parse_gas_int(literal: &str) -> Option<u64> {
+    let lower = literal.to_ascii_lowercase();
+    if let Some(hex) = lower
+        .strip_suffix('h')
+        .filter(|hex| hex.starts_with(|c: char| c.is_ascii_digit()))
+    {
+        u64::from_str_radix(hex, 16).ok()
+    } else if let Some(hex) = lower.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        u64::from_str_radix(bin, 2).ok()
+    } else if lower.len() > 1 && lower.starts_with('0') {
+        u64::from_str_radix(&lower[1..], 8).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+/// Splits the directive statement on `line` into its name (without the `.`), its
+/// arguments and the index of the argument byte column `col` is in. Returns `None` if
+/// `col` isn't within the directive's arguments
+fn directive_arg_at(line: &str, col: usize) -> Option<(&str, &str, usize)> {
+    // skip past any label preceding the directive, and any trailing comment
+    let stmt_start = line.get(..col)?.rfind(':').map_or(0, |idx| idx + 1);
+    let stmt = line[stmt_start..].split(['#', ';']).next()?;
+    let (name, args) = stmt
+        .trim_start()
+        .strip_prefix('.')?
+        .split_once(char::is_whitespace)?;
+    // the whitespace between the directive's name and its arguments
+    let name_end = line.len() - line[stmt_start..].trim_start().len() + name.len() + 1;
+    if col <= name_end || col > stmt_start + stmt.len() {
+        return None;
+    }
+    let arg_idx = split_operands(&line[name_end..col], Assembler::Gas)
+        .len()
+        .saturating_sub(1);
+
+    Some((name, args, arg_idx))
+}
+
+/// Directives padding the location counter to an alignment
+const ALIGNMENT_DIRECTIVES: &[&str] = &[
+    "align", "balign", "balignw", "balignl", "p2align", "p2alignw", "p2alignl",
+];
+
+/// Returns a hover explaining the argument of an alignment directive (`.align`,
+/// `.balign[wl]` or `.p2align[wl]`) under the cursor, spelling out the alignment in
+/// bytes
+///
+/// `.p2align` always takes a power of two and `.balign` a byte count, while `.align`
+/// takes a byte count on x86 ELF targets but a power of two on ARM ones
+fn get_alignment_hover_resp(
+    curr_doc: &FullTextDocument,
+    params: &HoverParams,
+    config: &TargetConfig,
+) -> Option<Hover> {
+    let pos = params.text_document_position_params.position;
+    let line = curr_doc.get_content(None).lines().nth(pos.line as usize)?;
+    let (name, args, arg_idx) = directive_arg_at(line, byte_col_of_utf16(line, pos.character))?;
+    if !ALIGNMENT_DIRECTIVES.contains(&name) {
+        return None;
+    }
+    let arg = *split_operands(args, Assembler::Gas).get(arg_idx)?;
+    let value = parse_gas_int(arg)?;
+    let power_of_two = u32::try_from(value)
+        .ok()
+        .and_then(|exp| 1_u64.checked_shl(exp));
+    let descr = match arg_idx {
+        0 if name.starts_with("p2align") => {
+            format!("aligns to 2^{value} = {} bytes", power_of_two?)
+        }
+        0 if name.starts_with("balign") => {
+            let mut descr = format!("aligns to {value} bytes");
+            if !value.is_power_of_two() {
+                descr += ", but isn't a power of two";
+            }
+            descr
+        }
+        0 => {
+            let sets = &config.instruction_sets;
+            match (
+                sets.x86 || sets.x86_64 || sets.z80,
+                sets.aarch64,
+                power_of_two,
+            ) {
+                (false, true, Some(bytes)) => format!("aligns to 2^{value} = {bytes} bytes"),
+                (true, true, Some(bytes)) => format!(
+                    "aligns to {value} bytes on x86 ELF targets, or 2^{value} = {bytes} bytes \
+                     on ARM targets, where `.align` takes a power of two"
+                ),
+                _ => format!("aligns to {value} bytes"),
+            }
+        }
+        1 => {
+            let fill_size = match name.chars().last()? {
+                'w' => "2-byte",
+                'l' => "4-byte",
+                _ => "byte",
+            };
+            format!("{fill_size} value the padding is filled with")
+        }
+        2 => format!(
+            "maximum number of bytes skipped, the alignment isn't applied if reaching it would \
+             skip more than {value}"
+        ),
+        _ => return None,
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("`.{name} {}`\n\n`{arg}`: {descr}", args.trim()),
+        }),
+        range: None,
+    })
+}
+
+/// Directives emitting a run of repeated bytes, along with what each of their arguments
+/// means
+const REPEAT_DIRECTIVES: &[(&str, &[&str])] = &[
+    (
+        "fill",
+        &[
+            "number of copies of `value` emitted",
+            "size in bytes of each copy, at most 8 (defaults to 1)",
+            "value each copy holds (defaults to 0)",
+        ],
+    ),
+    (
+        "space",
+        &[
+            "number of bytes emitted",
+            "value each byte holds (defaults to 0)",
+        ],
+    ),
+    (
+        "skip",
+        &[
+            "number of bytes emitted",
+            "value each byte holds (defaults to 0)",
+        ],
+    ),
+];
+
+/// Returns a hover explaining the numeric argument under the cursor of a directive
+/// emitting repeated bytes (`.fill repeat, size, value`, or `.space`/`.skip size, fill`),
+/// whose argument orders are easily mixed up. When `.fill`'s `repeat` and `size` are
+/// plain numbers, the number of bytes it emits is spelled out as well
+fn get_repeat_hover_resp(curr_doc: &FullTextDocument, params: &HoverParams) -> Option<Hover> {
+    let pos = params.text_document_position_params.position;
+    let line = curr_doc.get_content(None).lines().nth(pos.line as usize)?;
+    let (name, args, arg_idx) = directive_arg_at(line, byte_col_of_utf16(line, pos.character))?;
+    let (_, descrs) = REPEAT_DIRECTIVES.iter().find(|(dir, _)| *dir == name)?;
+    let arg_list = split_operands(args, Assembler::Gas);
+    let arg = *arg_list.get(arg_idx)?;
+    parse_gas_int(arg)?;
+
+    let mut value = format!(
+        "`.{name} {}`\n\n`{arg}`: {}",
+        args.trim(),
+        descrs.get(arg_idx)?
+    );
+    if name == "fill" {
+        let repeat = arg_list.first().and_then(|arg| parse_gas_int(arg));
+        let size = arg_list.get(1).map_or(Some(1), |arg| parse_gas_int(arg));
+        if let (Some(repeat), Some(size)) = (repeat, size) {
+            let size = size.min(8);
+            value += &format!(
+                "\n\nEmits {repeat} × {size} = {} bytes",
+                repeat.saturating_mul(size)
+            );
+        }
+    }
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: None,
+    })
+}
+
+/// Returns a hover naming the system call if `word` is the call number loaded into
+/// `rax` on the cursor's line, and a `syscall` follows before `rax` is loaded again
+fn get_syscall_hover_resp(
+    curr_doc: &FullTextDocument,
+    params: &HoverParams,
+    word: &str,
+) -> Option<Hover> {
+    static LABEL: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*[\w.$]+:").unwrap());
+
+    let line_num = params.text_document_position_params.position.line as usize;
+    let mut lines = curr_doc.get_content(None).lines().skip(line_num);
+    let caps = SYSCALL_NUM_LOAD.captures(lines.next()?)?;
+    let imm = caps.get(1).or_else(|| caps.get(2))?.as_str();
+    if imm != word {
+        return None;
+    }
+    let number = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => word.parse().ok()?,
+    };
+
+    // the load only names a system call if it reaches a `syscall` in the same block
+    let reaches_syscall = lines
+        .take_while(|line| !LABEL.is_match(line) && !SYSCALL_NUM_LOAD.is_match(line))
+        .any(|line| {
+            line.split(['#', ';'])
+                .next()
+                .is_some_and(|code| code.trim().eq_ignore_ascii_case("syscall"))
+        });
+    if !reaches_syscall {
+        return None;
+    }
+
+    let abi = SyscallAbi::LinuxX86_64;
+    let name = abi.syscall_name(number)?;
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("`{name}`\n\n{abi} system call {number}"),
+        }),
+        range: None,
+    })
+}
+
+/// Matches a DWARF `.file` directive, capturing the file number and name, preceded by
+/// the directory if it's given separately as in DWARF 5 (`.file 1 "dir" "name"`)
+static DWARF_FILE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"^\s*\.file\s+(\d+)\s+"([^"]*)"(?:\s+"([^"]*)")?"#).unwrap());
+
+/// Returns a hover decoding the arguments of the `.loc` directive the cursor is on, e.g.
+/// `.loc 1 42 0` as file #1, line 42, column 0. The file number is resolved to its name
+/// through the last `.file` directive numbering it before the `.loc`, if any
+fn get_loc_hover_resp(curr_doc: &FullTextDocument, params: &HoverParams) -> Option<Hover> {
+    let pos = params.text_document_position_params.position;
+    let contents = curr_doc.get_content(None);
+    let line = contents.lines().nth(pos.line as usize)?;
+    let col = byte_col_of_utf16(line, pos.character);
+    let stmt = line.split(['#', ';']).next()?;
+    let args = stmt.trim_start().strip_prefix(".loc")?;
+    if !args.starts_with(char::is_whitespace) || col <= stmt.len() - args.len() || col > stmt.len()
+    {
+        return None;
+    }
+
+    let mut args = args.split_whitespace();
+    let file_num: u64 = args.next()?.parse().ok()?;
+    let line_num: u64 = args.next()?.parse().ok()?;
+    let mut descr = format!("File #{file_num}");
+    let file_name = contents
+        .lines()
+        .take(pos.line as usize)
+        .filter_map(|line| DWARF_FILE.captures(line))
+        .filter(|caps| caps[1].parse() == Ok(file_num))
+        .last()
+        .map(|caps| match caps.get(3) {
+            Some(name) if !caps[2].is_empty() => format!("{}/{}", &caps[2], name.as_str()),
+            Some(name) => name.as_str().to_string(),
+            None => caps[2].to_string(),
+        });
+    if let Some(name) = file_name {
+        descr += &format!(" (`{name}`)");
+    }
+    descr += &format!(", line {line_num}");
+    let mut options: Vec<&str> = args.collect();
+    if let Some(column) = options.first().and_then(|col| col.parse::<u64>().ok()) {
+        descr += &format!(", column {column}");
+        options.remove(0);
+    }
+    if !options.is_empty() {
+        descr += &format!("\n\nOptions: `{}`", options.join(" "));
+    }
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("`{}`\n\n{descr}", stmt.trim()),
+        }),
+        range: None,
+    })
+}
+
+/// ELF symbol types which can be given to a symbol with `.type`, along with the type
+/// they set in the symbol table and a short description of each
+const SYMBOL_TYPES: &[(&str, &str, &str)] = &[
+    ("function", "STT_FUNC", "The symbol names a function"),
+    (
+        "gnu_indirect_function",
+        "STT_GNU_IFUNC",
+        "The symbol names an indirect function, a resolver called by the dynamic linker \
+         to pick the implementation it refers to",
+    ),
+    (
+        "object",
+        "STT_OBJECT",
+        "The symbol names a data object, such as a variable or an array",
+    ),
+    (
+        "tls_object",
+        "STT_TLS",
+        "The symbol names a thread-local data object",
+    ),
+    (
+        "common",
+        "STT_COMMON",
+        "The symbol names an uninitialized common block",
+    ),
+    (
+        "notype",
+        "STT_NOTYPE",
+        "The symbol's type is left unspecified",
+    ),
+    (
+        "gnu_unique_object",
+        "STT_OBJECT",
+        "The symbol names a data object, bound uniquely (`STB_GNU_UNIQUE`) so the whole \
+         process shares a single definition of it",
+    ),
+];
+
+/// Returns a hover explaining the symbol type under the cursor in a `.type` directive,
+/// e.g. `@function` in `.type foo, @function`. Types may also be written with a `%`
+/// prefix, as on architectures where `@` starts a comment
+fn get_symbol_type_hover_resp(curr_doc: &FullTextDocument, params: &HoverParams) -> Option<Hover> {
+    let pos = params.text_document_position_params.position;
+    let line = curr_doc.get_content(None).lines().nth(pos.line as usize)?;
+    let col = byte_col_of_utf16(line, pos.character);
+    let stmt = line.split(['#', ';']).next()?;
+    let args = stmt.trim_start().strip_prefix(".type")?;
+    if !args.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    // the type is the argument after the symbol, so skip over the first comma
+    let mut arg_start = stmt.len() - args.len();
+    for (idx, arg) in args.split(',').enumerate() {
+        let arg_end = arg_start + arg.len();
+        if idx > 0 && (arg_start..=arg_end).contains(&col) {
+            let arg = arg.trim();
+            let name = arg.strip_prefix(['@', '%'])?;
+            let (_, elf_type, descr) = SYMBOL_TYPES.iter().find(|(type_, ..)| *type_ == name)?;
+            return Some(Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: format!("`{arg}` ({elf_type})\n\n{descr}"),
+                }),
+                range: None,
+            });
+        }
+        arg_start = arg_end + 1;
+    }
+
+    None
+}
+
+/// Returns a hover spelling out the numeric immediate under the cursor (e.g. `$0x10` or
+/// ARM's `#16`) in decimal, hex and binary. The value has to be written with the
+/// immediate prefix of an enabled architecture, after the start of an instruction, so
+/// that a `#` starting a comment isn't taken for one
+fn get_immediate_hover_resp(
+    curr_doc: &FullTextDocument,
+    params: &HoverParams,
+    config: &TargetConfig,
+) -> Option<Hover> {
+    let pos = params.text_document_position_params.position;
+    let line = curr_doc.get_content(None).lines().nth(pos.line as usize)?;
+    let col = byte_col_of_utf16(line, pos.character);
+    let (start, end) = find_word_at_pos(line, col, "");
+    let literal = &line[start..end];
+    let value = parse_gas_int(literal)?;
+
+    let imm_prefix = immediate_prefixes(config).find(|prefix| line[..start].ends_with(prefix))?;
+    let before_imm = &line[..start - imm_prefix.len()];
+    let is_comment = |code: &str| code.contains('#') || code.contains("//") || code.contains(';');
+    if before_imm.trim().is_empty() || is_comment(before_imm) {
+        return None;
+    }
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!(
+                "`{imm_prefix}{literal}`\n\n- Decimal: {value}\n- Hex: {value:#x}\n- Binary: {value:#b}"
+            ),
+        }),
+        range: None,
+    })
+}
+
+/// Returns a hover explaining GAS's location counter if the cursor in `params` sits on a
+/// standalone `.` (e.g. in `.size f, .-f` or `jmp .`), rather than on the `.` starting a
+/// directive or a name such as `.L1`
+fn get_location_counter_hover_resp(
+    curr_doc: &FullTextDocument,
+    params: &HoverParams,
+    tree_entry: &mut TreeEntry,
+) -> Option<Hover> {
+    let pos = params.text_document_position_params.position;
+    let doc = curr_doc.get_content(None);
+    let line = doc.lines().nth(pos.line as usize)?;
+    let col = byte_col_of_utf16(line, pos.character);
+    if !line[col..].starts_with('.') {
+        return None;
+    }
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '.' || c == '$';
+    let prev_is_name = line[..col].chars().next_back().is_some_and(is_name_char);
+    let next_is_name = line[col + 1..].chars().next().is_some_and(is_name_char);
+    if prev_is_name || next_is_name {
+        return None;
+    }
+
+    // a directive's name is its own node, even when the directive is otherwise empty
+    tree_entry.tree = tree_entry.parser.parse(doc, tree_entry.tree.as_ref());
+    let point = tree_sitter::Point {
+        row: pos.line as usize,
+        column: col,
+    };
+    let node = tree_entry
+        .tree
+        .as_ref()?
+        .root_node()
+        .descendant_for_point_range(point, point)?;
+    if node.kind() == "meta_ident" {
+        return None;
+    }
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: String::from(
+                "`.` (location counter)\n\nThe address the assembler is currently emitting \
+                 to within the current section, e.g. `.size f, .-f` sets the size of `f` \
+                 to the number of bytes emitted since `f`",
+            ),
+        }),
+        range: None,
+    })
+}
+
+/// x86 instruction prefixes written as separate tokens ahead of the instruction they
+/// modify, along with their encoding and meaning
+const INSTRUCTION_PREFIXES: &[(&str, &str, &str)] = &[
+    (
+        "rep",
+        "F3",
+        "Repeats the following string instruction (`movs`, `stos`, `lods`, `ins` or `outs`) \
+         the number of times held in `rcx`/`ecx`/`cx`, decrementing it after each iteration",
+    ),
+    (
+        "repe",
+        "F3",
+        "Repeats the following string comparison (`cmps` or `scas`) while the count in \
+         `rcx`/`ecx`/`cx` is nonzero and the compared values are equal (ZF is set)",
+    ),
+    (
+        "repz",
+        "F3",
+        "Repeats the following string comparison (`cmps` or `scas`) while the count in \
+         `rcx`/`ecx`/`cx` is nonzero and the compared values are equal (ZF is set)",
+    ),
+    (
+        "repne",
+        "F2",
+        "Repeats the following string comparison (`cmps` or `scas`) while the count in \
+         `rcx`/`ecx`/`cx` is nonzero and the compared values differ (ZF is clear)",
+    ),
+    (
+        "repnz",
+        "F2",
+        "Repeats the following string comparison (`cmps` or `scas`) while the count in \
+         `rcx`/`ecx`/`cx` is nonzero and the compared values differ (ZF is clear)",
+    ),
+    (
+        "lock",
+        "F0",
+        "Makes the following read-modify-write instruction atomic, e.g. `lock cmpxchg`. Only \
+         valid when the destination is memory, and only with `add`, `adc`, `and`, `btc`, \
+         `btr`, `bts`, `cmpxchg`, `cmpxchg8b`, `cmpxchg16b`, `dec`, `inc`, `neg`, `not`, \
+         `or`, `sbb`, `sub`, `xadd`, `xchg` and `xor`",
+    ),
+    (
+        "rex",
+        "40",
+        "Emits a REX prefix for the following instruction even if its operands don't need \
+         one. Only valid in 64-bit mode",
+    ),
+    (
+        "rex64",
+        "48",
+        "Emits a REX.W prefix, making the following instruction use a 64-bit operand size. \
+         Only valid in 64-bit mode",
+    ),
+];
+
+/// Returns a hover explaining the instruction prefix under the cursor in `params`, e.g.
+/// `lock` in `lock cmpxchg %ecx, (%rdx)`. The tree confirms the word is in the position
+/// of an instruction's mnemonic, and it must be followed by the mnemonic it prefixes,
+/// either on the same statement or after a `;`
+fn get_prefix_hover_resp(
+    curr_doc: &FullTextDocument,
+    params: &HoverParams,
+    tree_entry: &mut TreeEntry,
+    word: &str,
+) -> Option<Hover> {
+    let lower = word.to_lowercase();
+    let &(prefix, encoding, meaning) = INSTRUCTION_PREFIXES
+        .iter()
+        .find(|(prefix, _, _)| *prefix == lower)?;
+
+    let pos = params.text_document_position_params.position;
+    let doc = curr_doc.get_content(None);
+    let line = doc.lines().nth(pos.line as usize)?;
+    tree_entry.tree = tree_entry.parser.parse(doc, tree_entry.tree.as_ref());
+    let point = tree_sitter::Point {
+        row: pos.line as usize,
+        column: byte_col_of_utf16(line, pos.character),
+    };
+    let node = tree_entry
+        .tree
+        .as_ref()?
+        .root_node()
+        .descendant_for_point_range(point, point)?;
+    let instr = node.parent()?;
+    if instr.kind() != "instruction" || instr.child_by_field_name("kind")? != node {
+        return None;
+    }
+
+    let rest = line.get(node.end_position().column..)?;
+    let rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ';');
+    if !rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("`{prefix}` (instruction prefix, `{encoding}`)\n\n{meaning}"),
+        }),
+        range: None,
+    })
+}
+
+/// Returns true if the cursor in `params` sits on an argument of a directive which
+/// the directive's `symbol_args` mark as a symbol reference
+fn is_directive_symbol_arg(
+    curr_doc: &FullTextDocument,
+    params: &HoverParams,
+    directive_map: &NameToDirectiveMap,
+) -> bool {
+    let pos = params.text_document_position_params.position;
+    let Some(line) = curr_doc.get_content(None).lines().nth(pos.line as usize) else {
+        return false;
+    };
+    is_directive_symbol_arg_at(line, byte_col_of_utf16(line, pos.character), directive_map)
+}
+
+/// Returns true if byte column `col` of `line` is within an argument of a directive
+/// which the directive's `symbol_args` mark as a symbol reference
+fn is_directive_symbol_arg_at(line: &str, col: usize, directive_map: &NameToDirectiveMap) -> bool {
+    let prefix = line.get(..col).unwrap_or(line);
+    // skip past any label preceding the directive
+    let prefix = prefix.rsplit_once(':').map_or(prefix, |(_, rest)| rest);
+    let Some((name, args)) = prefix
+        .trim_start()
+        .strip_prefix('.')
+        .and_then(|dir| dir.split_once(char::is_whitespace))
+    else {
+        return false;
+    };
+    let arg_idx = args.matches(',').count();
+
+    directive_map
+        .iter()
+        .filter(|((_, dir_name), _)| *dir_name == name)
+        .any(|(_, directive)| directive.symbol_args.contains(&arg_idx))
+}
+
+/// Finds the definition of the label `word` in `curr_doc`
+fn find_label_def(doc: &str, tree: &tree_sitter::Tree, word: &str) -> Option<tree_sitter::Range> {
+    static QUERY_LABEL: Lazy<tree_sitter::Query> = Lazy::new(|| {
+        tree_sitter::Query::new(tree_sitter_asm::language(), "(label) @label").unwrap()
+    });
+
+    let is_not_ident_char = |c: char| !(c.is_alphanumeric() || c == '_');
+    let mut cursor = tree_sitter::QueryCursor::new();
+    let matches = cursor.matches(&QUERY_LABEL, tree.root_node(), doc.as_bytes());
+
+    for match_ in matches {
+        for cap in match_.captures {
+            let text = cap
+                .node
+                .utf8_text(doc.as_bytes())
+                .unwrap_or("")
+                .trim()
+                .trim_matches(is_not_ident_char);
+
+            if word.eq(text) {
+                return Some(cap.node.range());
+            }
+        }
+    }
+
+    None
+}
+
+/// Returns a hover describing where the label `word` is defined, if it is
+fn get_label_hover_resp(
+    curr_doc: &FullTextDocument,
+    tree_entry: &mut TreeEntry,
+    word: &str,
+) -> Option<Hover> {
+    let doc = curr_doc.get_content(None);
+    tree_entry.tree = tree_entry.parser.parse(doc, tree_entry.tree.as_ref());
+    let def = find_label_def(doc, tree_entry.tree.as_ref()?, word)?;
+    let mut value = format!(
+        "`{word}:`\n\nLabel defined on line {}",
+        def.start_point.row + 1
+    );
+    if let Some(section) = get_active_section(doc, def.start_point.row) {
+        value += &format!(", inside {}", describe_section(&section));
+    }
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: None,
+    })
+}
+
+/// Matches a directive switching the active section, capturing the directive and the
+/// section's name following `.section`
+static SECTION_DIRECTIVE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)^\s*\.(section|text|data|bss|rodata)\b\s*"?([\w.$]*)"#).unwrap()
+});
+
+/// Returns the name of the section active on `line` of `curr_doc` (e.g. `.text` or
+/// `.data.rel.ro`), which is the one switched to by the closest section directive at
+/// or before `line`. Returns `None` if there's no such directive
+#[must_use]
+pub fn get_active_section(curr_doc: &str, line: usize) -> Option<String> {
+    let lines: Vec<&str> = curr_doc.lines().take(line + 1).collect();
+    lines.iter().rev().find_map(|line| {
+        let caps = SECTION_DIRECTIVE.captures(line)?;
+        let directive = caps[1].to_lowercase();
+        if directive != "section" {
+            return Some(format!(".{directive}"));
+        }
+        let name = caps.get(2).map_or("", |name| name.as_str());
+        (!name.is_empty()).then(|| name.to_string())
+    })
+}
+
+/// Matches a directive switching the ARM instruction set state, capturing the directive
+/// and the width following `.code`. `.thumb_func` and x86's `.code32` don't match
+static ARM_STATE_DIRECTIVE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)^\s*\.(arm|thumb|code\s+(16|32))\b").unwrap());
+
+/// Returns the ARM instruction set state active on `line` of `curr_doc`, which is the one
+/// selected by the closest `.arm`, `.thumb` or `.code` directive at or before `line`,
+/// like `get_active_section`. Returns `None` if there's no such directive
+#[must_use]
+pub fn get_arm_state(curr_doc: &str, line: usize) -> Option<ArmState> {
+    let lines: Vec<&str> = curr_doc.lines().take(line + 1).collect();
+    lines.iter().rev().find_map(|line| {
+        let caps = ARM_STATE_DIRECTIVE.captures(line)?;
+        match (
+            caps[1].to_lowercase().as_str(),
+            caps.get(2).map(|width| width.as_str()),
+        ) {
+            ("thumb", _) | (_, Some("16")) => Some(ArmState::Thumb),
+            _ => Some(ArmState::Arm),
+        }
+    })
+}
+
+/// Describes `section` for hover, e.g. "`.text` (code)"
+fn describe_section(section: &str) -> String {
+    let kind = [
+        (".text", "code"),
+        (".rodata", "read-only data"),
+        (".data", "data"),
+        (".bss", "zero-initialized data"),
+    ]
+    .iter()
+    .find(|(prefix, _)| section == *prefix || section.starts_with(&format!("{prefix}.")))
+    .map(|(_, kind)| *kind);
+
+    match kind {
+        Some(kind) => format!("`{section}` ({kind})"),
+        None => format!("`{section}`"),
+    }
+}
+
+/// Returns the section active on the hovered line if `word` is the data directive
+/// on it (e.g. `.byte`), described for hover
+fn get_data_directive_section(
+    curr_doc: &FullTextDocument,
+    params: &HoverParams,
+    word: &str,
+) -> Option<String> {
+    static DATA_DIRECTIVE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"(?i)^\s*(?:[\w.$]+:\s*)?\.(byte|short|hword|word|int|long|quad|octa|ascii|asciz|string|zero|space|skip|fill|single|float|double)\b",
+        )
+        .unwrap()
+    });
+
+    let doc = curr_doc.get_content(None);
+    let line_num = params.text_document_position_params.position.line as usize;
+    let caps = DATA_DIRECTIVE.captures(doc.lines().nth(line_num)?)?;
+    if !caps[1].eq_ignore_ascii_case(word.trim_start_matches('.')) {
+        return None;
+    }
+
+    get_active_section(doc, line_num).map(|section| describe_section(&section))
+}
+
+/// ELF relocation suffixes which can be attached to a symbol reference, along
+/// with a short description of each
+const RELOCATION_SUFFIXES: &[(&str, &str)] = &[
+    ("PLT", "Reference through the Procedure Linkage Table"),
+    ("GOT", "Offset of the symbol's Global Offset Table entry"),
+    (
+        "GOTPCREL",
+        "PC-relative reference to the symbol's Global Offset Table entry",
+    ),
+    (
+        "GOTOFF",
+        "Offset of the symbol from the base of the Global Offset Table",
+    ),
+    ("GOTPC", "PC-relative offset of the Global Offset Table"),
+    (
+        "GOTTPOFF",
+        "PC-relative reference to a GOT entry holding the symbol's TLS offset (initial exec)",
+    ),
+    (
+        "GOTNTPOFF",
+        "GOT entry holding the symbol's negative TLS offset (initial exec)",
+    ),
+    (
+        "INDNTPOFF",
+        "Absolute address of a GOT entry holding the symbol's negative TLS offset (initial exec)",
+    ),
+    (
+        "TPOFF",
+        "Offset of the symbol from the thread pointer (local exec)",
+    ),
+    (
+        "NTPOFF",
+        "Negative offset of the symbol from the thread pointer (local exec)",
+    ),
+    (
+        "DTPOFF",
+        "Offset of the symbol within its module's TLS block (local dynamic)",
+    ),
+    (
+        "TLSGD",
+        "TLS descriptor for the general dynamic access model",
+    ),
+    ("TLSLD", "TLS descriptor for the local dynamic access model"),
+    (
+        "TLSLDM",
+        "TLS descriptor for the local dynamic access model",
+    ),
+];
+
+/// Splits a relocation suffix such as `@PLT` off of `word`, returning the bare
+/// symbol along with the suffix and its description
+///
+/// If `word` doesn't end in a known relocation suffix, it is returned unchanged
+fn split_relocation_suffix(word: &str) -> (&str, Option<(&'static str, &'static str)>) {
+    if let Some((symbol, suffix)) = word.rsplit_once('@') {
+        if !symbol.is_empty() {
+            if let Some(reloc) = RELOCATION_SUFFIXES
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(suffix))
+            {
+                return (symbol, Some(*reloc));
+            }
+        }
+    }
+
+    (word, None)
+}
+
+/// Returns a hover for `word` built from its entries in `map`, rendered according to the
+/// hover options of `opts`. `map_name` identifies the map in debug logs of which entries
+/// resolved the hover
+fn lookup_hover_resp_by_arch<T: Hoverable>(
+    word: &str,
+    map_name: &str,
+    map: &HashMap<(Arch, &str), T>,
+    opts: &ConfigOptions,
+) -> Option<Hover> {
+    let form_fields = opts.hover_form_fields.as_deref();
+    let resps: Vec<(&dyn ArchProvider, &T)> = arch_providers()
+        .iter()
+        .filter_map(|provider| {
+            map.get(&(provider.arch(), word))
+                .map(|resp| (*provider, resp))
+        })
+        .collect();
+    if resps.is_empty() {
+        // don't know of this word
+        return None;
+    }
+    if log_enabled!(log::Level::Debug) {
+        let keys: Vec<String> = resps
+            .iter()
+            .map(|(provider, _)| format!("{map_name}[({:?}, {word})]", provider.arch()))
+            .collect();
+        debug!("hover '{word}' resolved via {}", keys.join(", "));
+    }
+
+    // if the user prefers an architecture that defines this word, show only its entry
+    let primary_resp = opts.primary_arch.and_then(|primary| {
+        resps
+            .iter()
+            .find(|(provider, _)| provider.arch() == primary)
+    });
+    let value = if let Some((primary, primary_resp)) = primary_resp {
+        let others: Vec<String> = resps
+            .iter()
+            .filter(|(provider, _)| provider.arch() != primary.arch())
+            .map(|(provider, _)| provider.arch().to_string())
+            .collect();
+        let mut value = primary_resp.to_markdown(
+            primary.arch(),
+            opts.hover_width,
+            form_fields,
+            opts.hover_forms,
+            opts.extended_size_gloss,
+        );
+        if !others.is_empty() {
+            value += &format!("\n\n*(also in {})*", others.join(", "));
+        }
+        value
+    } else {
+        resps
+            .iter()
+            .map(|(provider, resp)| {
+                resp.to_markdown(
+                    provider.arch(),
+                    opts.hover_width,
+                    form_fields,
+                    opts.hover_forms,
+                    opts.extended_size_gloss,
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: None,
+    })
+}
+
+fn lookup_hover_resp_by_assembler<T: Hoverable>(
+    word: &str,
+    map: &HashMap<(Assembler, &str), T>,
+) -> Option<Hover> {
+    let (gas_resp, go_resp) = search_for_hoverable_by_assembler(word, map);
+    if log_enabled!(log::Level::Debug) {
+        let keys: Vec<String> = [
+            (Assembler::Gas, gas_resp.is_some()),
+            (Assembler::Go, go_resp.is_some()),
+        ]
+        .iter()
+        .filter(|(_, found)| *found)
+        .map(|(assembler, _)| format!("NameToDirectiveMap[({assembler:?}, {word})]"))
+        .collect();
+        if !keys.is_empty() {
+            debug!("hover '{word}' resolved via {}", keys.join(", "));
+        }
+    }
+
+    match (gas_resp.is_some(), go_resp.is_some()) {
+        (true, _) | (_, true) => {
+            let mut value = String::new();
+            if let Some(gas_resp) = gas_resp {
+                value += &format!("{gas_resp}");
+            }
+            if let Some(go_resp) = go_resp {
+                value += &format!(
+                    "{}{}",
+                    if gas_resp.is_some() { "\n\n" } else { "" },
+                    go_resp
+                );
+            }
+            Some(Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value,
+                }),
+                range: None,
+            })
+        }
+        _ => {
+            // don't know of this word
+            None
+        }
+    }
+}
+
+/// Least recently used cache of demangled symbol names, keyed by the mangled name, so
+/// hovering around the same (possibly long) C++ symbols doesn't demangle them again
+#[derive(Debug)]
+pub struct DemangleCache {
+    capacity: usize,
+    /// Mangled names and their demangling, most recently used first
+    entries: VecDeque<(String, Option<String>)>,
+}
+
+impl DemangleCache {
+    /// Creates an empty cache holding at most `capacity` names
+    #[must_use]
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Returns the demangled form of `mangled`, or `None` if it isn't a mangled name,
+    /// evicting the least recently used name if the cache is full
+    pub fn demangle(&mut self, mangled: &str) -> Option<String> {
+        if let Some(idx) = self.entries.iter().position(|(name, _)| name == mangled) {
+            let entry = self.entries.remove(idx)?;
+            let demangled = entry.1.clone();
+            self.entries.push_front(entry);
+            return demangled;
+        }
+
+        let name = Name::new(mangled, NameMangling::Mangled, Language::Unknown);
+        let demangled = name.demangle(DemangleOptions::complete());
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_back();
+        }
+        if self.capacity > 0 {
+            self.entries
+                .push_front((mangled.to_string(), demangled.clone()));
+        }
+
+        demangled
+    }
+
+    /// Number of names in the cache
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the cache holds no names
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Maximum number of names kept in `DEMANGLE_CACHE`
+const DEMANGLE_CACHE_SIZE: usize = 128;
+
+static DEMANGLE_CACHE: Mutex<DemangleCache> = Mutex::new(DemangleCache::new(DEMANGLE_CACHE_SIZE));
+
+fn get_demangle_resp(word: &str) -> Option<Hover> {
+    let demangled = match DEMANGLE_CACHE.lock() {
+        Ok(mut cache) => cache.demangle(word),
+        // another hover panicked mid-update, so don't trust the cache's contents
+        Err(_) => Name::new(word, NameMangling::Mangled, Language::Unknown)
+            .demangle(DemangleOptions::complete()),
+    };
+    if let Some(value) = demangled {
+        return Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: None,
+        });
+    }
+
+    None
+}
+
+fn get_include_resp(
+    source_file: &Uri,
+    filename: &str,
+    include_dirs: &HashMap<SourceFile, Vec<PathBuf>>,
+) -> Option<Hover> {
+    let mut paths = String::new();
+
+    let mut dir_iter: Box<dyn Iterator<Item = &PathBuf>> = match include_dirs.get(&SourceFile::All)
+    {
+        Some(dirs) => Box::new(dirs.iter()),
+        None => Box::new(std::iter::empty()),
+    };
+
+    if let Ok(src_path) = PathBuf::from(source_file.as_str()).canonicalize() {
+        if let Some(dirs) = include_dirs.get(&SourceFile::File(src_path)) {
+            dir_iter = Box::new(dir_iter.chain(dirs.iter()));
+        }
+    }
+
+    for dir in dir_iter {
+        match std::fs::read_dir(dir) {
+            Ok(dir_reader) => {
+                for file in dir_reader {
+                    match file {
+                        Ok(f) => {
+                            if f.file_name() == filename {
+                                paths += &format!("file://{}\n", f.path().display());
+                            }
+                        }
+                        Err(e) => {
+                            error!(
+                                "Failed to read item in {} - Error {e}",
+                                dir.as_path().display()
+                            );
+                        }
+                    };
+                }
+            }
+            Err(e) => {
+                error!(
+                    "Failed to create directory reader for {} - Error {e}",
+                    dir.as_path().display()
+                );
+            }
+        }
+    }
+
+    if paths.is_empty() {
+        None
+    } else {
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: paths,
+            }),
+            range: None,
+        })
+    }
+}
+
+/// Filter out duplicate completion suggestions
+fn filtered_comp_list(comps: &[CompletionItem]) -> Vec<CompletionItem> {
+    let mut seen = HashSet::new();
+
+    comps
+        .iter()
+        .filter(|comp_item| {
+            if seen.contains(&comp_item.label) {
+                false
+            } else {
+                seen.insert(&comp_item.label);
+                true
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// Register classes an instruction operand can be constrained to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RegisterClass {
+    Gpr,
+    Mmx,
+    Xmm,
+    Ymm,
+    Zmm,
+    Mask,
+}
+
+/// Maps an operand type to the class of register it accepts, if it accepts one
+const fn operand_register_class(type_: &OperandType) -> Option<RegisterClass> {
+    match type_ {
+        OperandType::al
+        | OperandType::cl
+        | OperandType::r8
+        | OperandType::r8l
+        | OperandType::ax
+        | OperandType::r16
+        | OperandType::r16l
+        | OperandType::eax
+        | OperandType::r32
+        | OperandType::r32l
+        | OperandType::rax
+        | OperandType::r64 => Some(RegisterClass::Gpr),
+        OperandType::mm => Some(RegisterClass::Mmx),
+        OperandType::xmm0 | OperandType::xmm | OperandType::xmm_k | OperandType::xmm_k_z => {
+            Some(RegisterClass::Xmm)
+        }
+        OperandType::ymm | OperandType::ymm_k | OperandType::ymm_k_z => Some(RegisterClass::Ymm),
+        OperandType::zmm | OperandType::zmm_k | OperandType::zmm_k_z => Some(RegisterClass::Zmm),
+        OperandType::k | OperandType::k_k => Some(RegisterClass::Mask),
+        _ => None,
+    }
+}
+
+/// Classifies an x86 register by its name
+fn register_name_class(name: &str) -> Option<RegisterClass> {
+    static GPR_REGEX: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"^(r([89]|1[0-5])[dwb]?|[re]?([abcd]x|si|di|sp|bp)|[abcd][lh]|(si|di|sp|bp)l)$")
+            .unwrap()
+    });
+    static VEC_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^([xyz]?mm|k)[0-9]+$").unwrap());
+
+    let name = name.trim_start_matches('%').to_lowercase();
+    if GPR_REGEX.is_match(&name) {
+        return Some(RegisterClass::Gpr);
+    }
+    if !VEC_REGEX.is_match(&name) {
+        return None;
+    }
+    match name.trim_end_matches(|c: char| c.is_ascii_digit()) {
+        "mm" => Some(RegisterClass::Mmx),
+        "xmm" => Some(RegisterClass::Xmm),
+        "ymm" => Some(RegisterClass::Ymm),
+        "zmm" => Some(RegisterClass::Zmm),
+        "k" => Some(RegisterClass::Mask),
+        _ => None,
+    }
+}
+
+/// Splits the cursor's line, up to the cursor, into the instruction's mnemonic and the
+/// operands typed so far. Returns `None` if the cursor isn't past the mnemonic
+fn split_instr_prefix(
+    curr_doc: &str,
+    cursor_line: usize,
+    cursor_char: usize,
+) -> Option<(&str, &str)> {
+    let line = curr_doc.lines().nth(cursor_line)?;
+    let prefix = line.get(..cursor_char).unwrap_or(line);
+    // skip past any label preceding the instruction
+    let prefix = prefix.rsplit_once(':').map_or(prefix, |(_, rest)| rest);
+    prefix.trim_start().split_once(char::is_whitespace)
+}
+
+/// Splits an instruction's operands (e.g. `%rax, 8(%rbx,%rcx,4)`) at the commas
+/// separating them, returning each operand trimmed. Commas nested in parentheses,
+/// brackets or braces (memory references, register lists) or quoted in a string or
+/// character literal don't separate operands
+///
+/// GAS character literals are a `'` followed by the character (e.g. `$',`), optionally
+/// closed by another `'`, which `syntax`es like Go's require. A trailing comma yields a
+/// trailing empty operand, so the last operand of a prefix of `line` is the one being typed
+#[must_use]
+pub fn split_operands(line: &str, syntax: Assembler) -> Vec<&str> {
+    /// Skips past the closing `quote` of a literal, honoring backslash escapes
+    fn skip_quoted(chars: &mut impl Iterator<Item = (usize, char)>, quote: char) {
+        while let Some((_, c)) = chars.next() {
+            if c == '\\' {
+                chars.next();
+            } else if c == quote {
+                break;
+            }
+        }
+    }
+
+    let mut operands = Vec::new();
+    if line.trim().is_empty() {
+        return operands;
+    }
+
+    let mut depth = 0_usize;
+    let mut start = 0;
+    let mut prev = None;
+    let mut chars = line.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                operands.push(line[start..idx].trim());
+                start = idx + 1;
+            }
+            '"' => skip_quoted(&mut chars, '"'),
+            '\'' => match syntax {
+                // a `'` ending a word is part of it, e.g. z80's shadow register `af'`
+                Assembler::Gas if !prev.is_some_and(char::is_alphanumeric) => {
+                    if chars.next().is_some_and(|(_, c)| c == '\\') {
+                        chars.next();
+                    }
+                    // newer versions of GAS also accept a closing `'`
+                    if chars.clone().next().is_some_and(|(_, c)| c == '\'') {
+                        chars.next();
+                    }
+                }
+                Assembler::Gas => {}
+                Assembler::Go => skip_quoted(&mut chars, '\''),
+            },
+            _ => {}
+        }
+        prev = Some(c);
+    }
+    operands.push(line[start..].trim());
+
+    operands
+}
+
+/// Returns true if the cursor is in an operand of an instruction whose operands are
+/// usually labels (e.g. `jmp`, `call`), per the instruction's [`OperandKind`]
+fn expects_label_operand(
+    curr_doc: &str,
+    cursor_line: usize,
+    cursor_char: usize,
+    instr_map: &NameToInstructionMap,
+) -> bool {
+    let Some((mnemonic, _)) = split_instr_prefix(curr_doc, cursor_line, cursor_char) else {
+        return false;
+    };
+
+    let mnemonic_lower = mnemonic.to_lowercase();
+    [Arch::X86, Arch::X86_64].iter().any(|&arch| {
+        instr_map
+            .get(&(arch, mnemonic))
+            .or_else(|| instr_map.get(&(arch, mnemonic_lower.as_str())))
+            .is_some_and(|instr| instr.operand_kind == Some(OperandKind::Label))
+    })
+}
+
+/// Returns completion items for the labels defined in `curr_doc`, detailed with the
+/// line they're defined on
+fn get_label_comps(curr_doc: &str, tree: &tree_sitter::Tree) -> Vec<CompletionItem> {
+    static QUERY_LABEL: Lazy<tree_sitter::Query> = Lazy::new(|| {
+        tree_sitter::Query::new(tree_sitter_asm::language(), "(label) @label").unwrap()
+    });
+
+    let is_not_ident_char = |c: char| !(c.is_alphanumeric() || c == '_' || c == '.');
+    let mut items: Vec<CompletionItem> = Vec::new();
+    let mut cursor = tree_sitter::QueryCursor::new();
+    for match_ in cursor.matches(&QUERY_LABEL, tree.root_node(), curr_doc.as_bytes()) {
+        for cap in match_.captures {
+            let Ok(text) = cap.node.utf8_text(curr_doc.as_bytes()) else {
+                continue;
+            };
+            let name = text.trim().trim_matches(is_not_ident_char);
+            if name.is_empty() || items.iter().any(|item| item.label == name) {
+                continue;
+            }
+            items.push(CompletionItem {
+                label: name.to_string(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some(format!(
+                    "label on line {}",
+                    cap.node.start_position().row + 1
+                )),
+                ..Default::default()
+            });
+        }
+    }
+
+    items
+}
+
+/// Returns the items of `comps` made by `get_completes` for one of `archs`
+fn comps_for_archs(comps: &[CompletionItem], archs: &[Arch]) -> Vec<CompletionItem> {
+    comps
+        .iter()
+        .filter(|comp| {
+            comp.data
+                .as_ref()
+                .and_then(|data| Vec::<Arch>::deserialize(data).ok())
+                .is_some_and(|comp_archs| comp_archs.iter().any(|arch| archs.contains(arch)))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Returns the partial register name typed so far if the cursor is inside the brackets
+/// of an Intel syntax memory operand, e.g. `rb` in `mov rax, [rb`, including after the
+/// `+`/`*` separating its base, index and scale
+///
+/// The grammar only covers simple `[reg + disp]` operands, so the brackets are matched
+/// on the line's text, with the tree ruling out comments
+fn intel_memory_operand_prefix<'a>(
+    curr_doc: &'a str,
+    tree: &tree_sitter::Tree,
+    cursor_line: usize,
+    cursor_char: usize,
+) -> Option<&'a str> {
+    let prefix = curr_doc.lines().nth(cursor_line)?.get(..cursor_char)?;
+    let word_start = prefix
+        .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_')
+        .len();
+    let before_word = prefix[..word_start].trim_end();
+    if !before_word.ends_with(['[', '+', '*']) {
+        return None;
+    }
+    let open = before_word.rfind('[')?;
+    if before_word[open..].contains(']') {
+        return None;
+    }
+
+    let bracket = tree_sitter::Point {
+        row: cursor_line,
+        column: open,
+    };
+    let mut node = tree
+        .root_node()
+        .descendant_for_point_range(bracket, bracket);
+    while let Some(curr) = node {
+        if curr.kind() == "line_comment" {
+            return None;
+        }
+        node = curr.parent();
+    }
+
+    Some(&prefix[word_start..])
+}
+
+/// Returns the architecture whose registers are written as bare identifiers (e.g. z80's
+/// `hl`, rather than x86's `%rax`) if the cursor is in an operand of one of its
+/// instructions
+fn bare_register_operand_arch(
+    curr_doc: &str,
+    cursor_line: usize,
+    cursor_char: usize,
+    instr_map: &NameToInstructionMap,
+) -> Option<Arch> {
+    let line = curr_doc.lines().nth(cursor_line)?;
+    let prefix = line.get(..cursor_char).unwrap_or(line);
+    // skip past any label preceding the instruction
+    let prefix = prefix.rsplit_once(':').map_or(prefix, |(_, rest)| rest);
+    let (mnemonic, operands) = prefix.trim_start().split_once(char::is_whitespace)?;
+    let operand = operands.rsplit([',', '(']).next().unwrap_or_default();
+    if !operand
+        .trim()
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '\'')
+    {
+        return None;
+    }
+
+    let mnemonic = mnemonic.to_lowercase();
+    arch_providers()
+        .iter()
+        .filter(|provider| provider.register_prefix().is_none())
+        .map(|provider| provider.arch())
+        .find(|arch| instr_map.contains_key(&(*arch, mnemonic.as_str())))
+}
+
+/// Resolves the instruction on the cursor's line and returns the register classes
+/// accepted by the operand the cursor is in, across all of the instruction's forms.
+///
+/// An empty set means the operand isn't constrained to any register class (or the
+/// instruction couldn't be resolved)
+fn expected_register_classes(
+    curr_doc: &str,
+    cursor_line: usize,
+    cursor_char: usize,
+    instr_map: &NameToInstructionMap,
+    syntax: Assembler,
+) -> HashSet<RegisterClass> {
+    let mut classes = HashSet::new();
+    let Some((mnemonic, operands)) = split_instr_prefix(curr_doc, cursor_line, cursor_char) else {
+        return classes;
+    };
+
+    let idx = split_operands(operands, syntax).len().saturating_sub(1);
+    // AT&T syntax lists operands in the reverse order of the (Intel ordered) forms
+    let is_att = operands.contains('%');
+
+    let mnemonic_lower = mnemonic.to_lowercase();
+    for arch in [Arch::X86, Arch::X86_64] {
+        let Some(instr) = instr_map
+            .get(&(arch, mnemonic))
+            .or_else(|| instr_map.get(&(arch, mnemonic_lower.as_str())))
+        else {
+            continue;
+        };
+        for form in &instr.forms {
+            if form.operands.len() <= idx {
+                continue;
+            }
+            let operand = if is_att {
+                &form.operands[form.operands.len() - 1 - idx]
+            } else {
+                &form.operands[idx]
+            };
+            if let Some(class) = operand_register_class(&operand.type_) {
+                classes.insert(class);
+            }
+        }
+    }
+
+    classes
+}
+
+/// Returns the register completions, ranking those belonging to one of `classes` first.
+/// Falls back to the unranked list if `classes` is empty
+fn ranked_reg_comp_list(
+    reg_comps: &[CompletionItem],
+    classes: &HashSet<RegisterClass>,
+) -> Vec<CompletionItem> {
+    let mut items = filtered_comp_list(reg_comps);
+    if classes.is_empty() {
+        return items;
+    }
+
+    let is_expected = |item: &CompletionItem| {
+        register_name_class(&item.label).is_some_and(|class| classes.contains(&class))
+    };
+    for item in &mut items {
+        let rank = if is_expected(item) { 0 } else { 1 };
+        item.sort_text = Some(format!("{rank}{}", item.label));
+    }
+    items.sort_by_key(|item| !is_expected(item));
+
+    items
+}
+
+/// Kinds of GAS blocks, each opened and closed by a pair of directives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockKind {
+    /// `.macro` ... `.endm`
+    Macro,
+    /// `.rept`/`.irp`/`.irpc` ... `.endr`
+    Repeat,
+    /// `.if`/`.ifdef`/`.ifndef`/... ... `.endif`, along with any `.else`/`.elseif` arms
+    Conditional,
+}
+
+/// Directives opening a conditional assembly block
+const CONDITIONAL_DIRECTIVES: &[&str] = &[
+    ".if",
+    ".ifb",
+    ".ifc",
+    ".ifdef",
+    ".ifeq",
+    ".ifeqs",
+    ".ifge",
+    ".ifgt",
+    ".ifle",
+    ".iflt",
+    ".ifnb",
+    ".ifnc",
+    ".ifndef",
+    ".ifne",
+    ".ifnes",
+    ".ifnotdef",
+];
+
+/// Directives continuing or closing a conditional assembly block
+const CONDITIONAL_CONTINUATIONS: &[&str] = &[".else", ".elseif", ".endif"];
+
+impl BlockKind {
+    /// Returns the kind of block opened by `directive`, if any
+    fn opened_by(directive: &str) -> Option<Self> {
+        match directive {
+            ".macro" => Some(Self::Macro),
+            ".rept" | ".irp" | ".irpc" => Some(Self::Repeat),
+            _ if CONDITIONAL_DIRECTIVES.contains(&directive) => Some(Self::Conditional),
+            _ => None,
+        }
+    }
+
+    /// Returns the kind of block closed by `directive`, if any
+    fn closed_by(directive: &str) -> Option<Self> {
+        match directive {
+            ".endm" => Some(Self::Macro),
+            ".endr" => Some(Self::Repeat),
+            ".endif" => Some(Self::Conditional),
+            _ => None,
+        }
+    }
+}
+
+/// A GAS block, spanning from the row of its opening directive to the row of its
+/// closing directive. `end` is `None` if the block is never closed
+#[derive(Debug, Clone)]
+struct Block {
+    kind: BlockKind,
+    directive: String,
+    start: usize,
+    end: Option<usize>,
+}
+
+/// Returns the `.macro`, `.rept`, `.irp` and `.irpc` blocks in `curr_doc`, ordered by
+/// their starting row
+fn get_blocks(curr_doc: &str, tree: &tree_sitter::Tree) -> Vec<Block> {
+    static QUERY_META: Lazy<tree_sitter::Query> = Lazy::new(|| {
+        tree_sitter::Query::new(
+            tree_sitter_asm::language(),
+            "(meta kind: (meta_ident) @kind)",
+        )
+        .unwrap()
+    });
+
+    let mut blocks: Vec<Block> = Vec::new();
+    // indices into `blocks` of the blocks that haven't been closed yet
+    let mut open: Vec<usize> = Vec::new();
+    let mut cursor = tree_sitter::QueryCursor::new();
+    for match_ in cursor.matches(&QUERY_META, tree.root_node(), curr_doc.as_bytes()) {
+        for cap in match_.captures {
+            let Ok(directive) = cap.node.utf8_text(curr_doc.as_bytes()) else {
+                continue;
+            };
+            let directive = directive.to_lowercase();
+            let row = cap.node.start_position().row;
+            if let Some(kind) = BlockKind::opened_by(&directive) {
+                open.push(blocks.len());
+                blocks.push(Block {
+                    kind,
+                    directive,
+                    start: row,
+                    end: None,
+                });
+            } else if let Some(kind) = BlockKind::closed_by(&directive) {
+                // close the innermost block of the matching kind, along with any
+                // unterminated blocks nested inside of it
+                if let Some(pos) = open.iter().rposition(|&idx| blocks[idx].kind == kind) {
+                    blocks[open[pos]].end = Some(row);
+                    open.truncate(pos);
+                }
+            }
+        }
+    }
+
+    blocks
+}
+
+/// Returns the parameter names declared by the `.macro` blocks and the loop variables
+/// declared by the `.irp`/`.irpc` blocks whose bodies contain `cursor_line`, from
+/// outermost to innermost, or `None` if the line isn't inside any such block
+fn get_enclosing_block_params(
+    curr_doc: &str,
+    tree: &tree_sitter::Tree,
+    cursor_line: usize,
+) -> Option<Vec<(String, BlockKind)>> {
+    let mut params = Vec::new();
+    let mut enclosed = false;
+    for block in get_blocks(curr_doc, tree) {
+        if block.kind == BlockKind::Conditional
+            || block.start >= cursor_line
+            || block.end.is_some_and(|end| end < cursor_line)
+        {
+            continue;
+        }
+        enclosed = true;
+
+        // `.macro name arg1, arg2=default, arg3:req` and `.irp var, val1, val2` --
+        // arguments are separated by commas and/or whitespace
+        let Some(header) = curr_doc.lines().nth(block.start) else {
+            continue;
+        };
+        let mut args = header
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .skip(1); // the directive itself
+        let declared: Vec<&str> = match block.directive.as_str() {
+            ".macro" => args.skip(1).collect(), // the macro's name
+            ".irp" | ".irpc" => args.next().into_iter().collect(),
+            _ => Vec::new(), // `.rept count` doesn't declare anything
+        };
+        params.extend(
+            declared
+                .into_iter()
+                .map(|arg| arg.split(['=', ':']).next().unwrap_or(arg))
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| (arg.to_string(), block.kind)),
+        );
+    }
+
+    if enclosed {
+        Some(params)
+    } else {
+        None
+    }
+}
+
+/// Returns the directive completions. Inside a conditional assembly block, the
+/// directives continuing or closing it (`.else`, `.elseif` and `.endif`) are ranked first
+fn ranked_dir_comp_list(
+    curr_doc: &str,
+    tree: Option<&tree_sitter::Tree>,
+    cursor_line: usize,
+    dir_comps: &[CompletionItem],
+) -> Vec<CompletionItem> {
+    // `.arm`/`.thumb` do nothing in the state they select, so leave out the active one's
+    let active_state = cursor_line
+        .checked_sub(1)
+        .and_then(|line| get_arm_state(curr_doc, line))
+        .map(|state| state.to_string().to_lowercase());
+    let mut items: Vec<CompletionItem> = filtered_comp_list(dir_comps)
+        .into_iter()
+        .filter(|item| active_state.as_deref() != Some(item.label.as_str()))
+        .collect();
+    let in_conditional = tree.is_some_and(|tree| {
+        get_blocks(curr_doc, tree).iter().any(|block| {
+            block.kind == BlockKind::Conditional
+                && block.start < cursor_line
+                && block.end.is_none_or(|end| end >= cursor_line)
+        })
+    });
+    if !in_conditional {
+        return items;
+    }
+
+    // completion labels leave out the leading "."
+    let is_continuation = |item: &CompletionItem| {
+        CONDITIONAL_CONTINUATIONS
+            .iter()
+            .any(|dir| dir.trim_start_matches('.') == item.label)
+    };
+    for item in &mut items {
+        let rank = if is_continuation(item) { 0 } else { 1 };
+        item.sort_text = Some(format!("{rank}{}", item.label));
+    }
+    items.sort_by_key(|item| !is_continuation(item));
+
+    items
+}
+
+/// Returns folding ranges for the `.macro`, `.rept`, `.irp`, `.irpc` and conditional
+/// assembly (`.if` ... `.endif`) blocks in the document
+pub fn get_folding_ranges(curr_doc: &str, tree_entry: &mut TreeEntry) -> Vec<FoldingRange> {
+    tree_entry.tree = tree_entry.parser.parse(curr_doc, tree_entry.tree.as_ref());
+    let Some(tree) = tree_entry.tree.as_ref() else {
+        return Vec::new();
+    };
+
+    get_blocks(curr_doc, tree)
+        .into_iter()
+        .filter_map(|block| {
+            let end = block.end?;
+            Some(FoldingRange {
+                start_line: block.start as u32,
+                end_line: end as u32,
+                kind: Some(FoldingRangeKind::Region),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Returns nested selection ranges for each of `positions`, expanding from the syntax
+/// node under the cursor through its ancestors (e.g. register → operand → instruction),
+/// then to the whole line, the enclosing section and finally the whole document
+pub fn get_selection_ranges(
+    curr_doc: &str,
+    tree_entry: &mut TreeEntry,
+    positions: &[Position],
+) -> Vec<SelectionRange> {
+    tree_entry.tree = tree_entry.parser.parse(curr_doc, tree_entry.tree.as_ref());
+    let Some(tree) = tree_entry.tree.as_ref() else {
+        return Vec::new();
+    };
+    let root = tree.root_node();
+    let lines: Vec<&str> = curr_doc.lines().collect();
+    let doc_range = Range {
+        start: lsp_pos_of_point(root.start_position()),
+        end: lsp_pos_of_point(root.end_position()),
+    };
+
+    positions
+        .iter()
+        .map(|pos| {
+            // innermost first
+            let mut ranges: Vec<Range> = Vec::new();
+            let mut push = |range: Range| {
+                let contained = ranges.last().is_none_or(|inner| {
+                    range.start <= inner.start && inner.end <= range.end && range != *inner
+                });
+                if contained {
+                    ranges.push(range);
+                }
+            };
+
+            let point = tree_sitter::Point {
+                row: pos.line as usize,
+                column: pos.character as usize,
+            };
+            let mut node = root.named_descendant_for_point_range(point, point);
+            while let Some(curr) = node {
+                if curr == root {
+                    break;
+                }
+                push(Range {
+                    start: lsp_pos_of_point(curr.start_position()),
+                    end: lsp_pos_of_point(curr.end_position()),
+                });
+                node = curr.parent();
+            }
+
+            let row = pos.line as usize;
+            if let Some(line) = lines.get(row) {
+                push(Range {
+                    start: Position {
+                        line: pos.line,
+                        character: 0,
+                    },
+                    end: Position {
+                        line: pos.line,
+                        character: line.len() as u32,
+                    },
+                });
+
+                // a section runs from its directive up to the next section directive
+                let start = (0..=row)
+                    .rev()
+                    .find(|&r| SECTION_DIRECTIVE.is_match(lines[r]));
+                if let Some(start) = start {
+                    let end = (row + 1..lines.len())
+                        .find(|&r| SECTION_DIRECTIVE.is_match(lines[r]))
+                        .map_or(lines.len() - 1, |next| next - 1);
+                    push(Range {
+                        start: Position {
+                            line: start as u32,
+                            character: 0,
+                        },
+                        end: Position {
+                            line: end as u32,
+                            character: lines[end].len() as u32,
+                        },
+                    });
+                }
+            }
+            push(doc_range);
+
+            ranges
+                .into_iter()
+                .rev()
+                .fold(None, |parent, range| {
+                    Some(SelectionRange {
+                        range,
+                        parent: parent.map(Box::new),
+                    })
+                })
+                .unwrap_or(SelectionRange {
+                    range: doc_range,
+                    parent: None,
+                })
+        })
+        .collect()
+}
+
+/// Returns completion items for the suffixes of the mnemonic just before a `.` typed at
+/// `cursor_char`, or `None` if the `.` doesn't follow a mnemonic with suffixes in any of
+/// the enabled architectures
+fn get_suffix_comps(
+    curr_doc: &str,
+    cursor_line: usize,
+    cursor_char: Column,
+    config: &TargetConfig,
+) -> Option<Vec<CompletionItem>> {
+    // the mnemonic is the first word on the line, after an optional label
+    static MNEMONIC_DOT: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^\s*(?:[\w.$]+:\s*)?([A-Za-z]\w*)\.$").unwrap());
+
+    let line = curr_doc.lines().nth(cursor_line)?;
+    let caps = MNEMONIC_DOT.captures(line.get(..cursor_char)?)?;
+    let mnemonic = caps.get(1)?.as_str().to_lowercase();
+
+    let enabled_archs = config.enabled_archs();
+    let items: Vec<CompletionItem> = arch_providers()
+        .iter()
+        .filter(|provider| enabled_archs.contains(&provider.arch()))
+        .flat_map(|provider| provider.mnemonic_suffixes(&mnemonic))
+        .map(|(suffix, descr)| CompletionItem {
+            label: (*suffix).to_string(),
+            kind: Some(CompletionItemKind::ENUM_MEMBER),
+            detail: Some((*descr).to_string()),
+            ..Default::default()
+        })
+        .collect();
+
+    if items.is_empty() {
+        None
+    } else {
+        Some(items)
+    }
+}
+
+/// Returns the mnemonic typed so far up to `cursor_char` on `line` if it contains a `.`
+/// (e.g. AArch64's `b.ne`), which tree-sitter can't parse as an instruction
+fn dotted_mnemonic_prefix(line: &str, cursor_char: Column) -> Option<&str> {
+    // the mnemonic is the first word on the line, after an optional label
+    static DOTTED_MNEMONIC: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^\s*(?:[\w.$]+:\s*)?([A-Za-z]\w*\.\w*)$").unwrap());
+
+    let caps = DOTTED_MNEMONIC.captures(line.get(..cursor_char)?)?;
+    caps.get(1).map(|mnemonic| mnemonic.as_str())
+}
+
+/// Returns the instruction completions for the mnemonic `typed` so far, if it starts with
+/// a conditional base mnemonic of an enabled architecture (e.g. `set`, `jn` or `b.`). Each of
+/// the base's condition code variants starting with `typed` is offered first, described
+/// by its condition, followed by the rest of `instr_comps`
+fn get_conditional_comps(
+    typed: &str,
+    instr_comps: &[CompletionItem],
+    config: &TargetConfig,
+) -> Option<Vec<CompletionItem>> {
+    let typed = typed.to_lowercase();
+    let enabled_archs = config.enabled_archs();
+    let mut items: Vec<CompletionItem> = Vec::new();
+    for (base, conditions) in arch_providers()
+        .iter()
+        .filter(|provider| enabled_archs.contains(&provider.arch()))
+        .flat_map(|provider| provider.conditional_mnemonics())
+        .filter(|(base, _)| typed.starts_with(base))
+    {
+        for (cond, descr) in *conditions {
+            let label = format!("{base}{cond}");
+            if !label.starts_with(&typed) || items.iter().any(|item| item.label == label) {
+                continue;
+            }
+            let mut item = instr_comps
+                .iter()
+                .find(|comp| comp.label == label)
+                .cloned()
+                .unwrap_or_else(|| CompletionItem {
+                    label: label.clone(),
+                    kind: Some(CompletionItemKind::OPERATOR),
+                    ..Default::default()
+                });
+            item.detail = Some((*descr).to_string());
+            item.sort_text = Some(format!("0{label}"));
+            // the client's word starts after a `.`, so only the condition is typed over
+            if base.ends_with('.') {
+                item.insert_text = Some((*cond).to_string());
+                item.filter_text = Some((*cond).to_string());
+            }
+            items.push(item);
+        }
+    }
+    if items.is_empty() {
+        return None;
+    }
+
+    let rest: Vec<CompletionItem> = filtered_comp_list(instr_comps)
+        .into_iter()
+        .filter(|comp| !items.iter().any(|item| item.label == comp.label))
+        .collect();
+    items.extend(rest);
+
+    Some(items)
+}
+
+/// Returns function prologue snippets if the cursor is at the start of a label's body, or
+/// epilogue snippets if it's on a line following an instruction, for each enabled
+/// architecture (only `primary_arch` if set). Nothing but the start of a mnemonic may be
+/// typed before the cursor, and nothing is offered unless `frame_snippets` is set
+fn get_frame_snippet_comps(
+    curr_doc: &str,
+    cursor_line: usize,
+    cursor_char: Column,
+    config: &TargetConfig,
+) -> Vec<CompletionItem> {
+    static LABEL_LINE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[\w.$]+:$").unwrap());
+
+    let typed = curr_doc
+        .lines()
+        .nth(cursor_line)
+        .and_then(|line| line.get(..cursor_char))
+        .unwrap_or_default();
+    if !config.opts.frame_snippets || !typed.trim_start().chars().all(char::is_alphabetic) {
+        return Vec::new();
+    }
+    let Some(prev_line) = curr_doc
+        .lines()
+        .take(cursor_line)
+        .map(|line| line.split(['#', ';']).next().unwrap_or_default().trim())
+        .filter(|code| !code.is_empty())
+        .last()
+    else {
+        return Vec::new();
+    };
+    let (name, descr) = if LABEL_LINE.is_match(prev_line) {
+        ("prologue", "Function prologue")
+    } else if !prev_line.starts_with('.') && !prev_line.ends_with(':') {
+        ("epilogue", "Function epilogue")
+    } else {
+        return Vec::new();
+    };
+
+    let enabled_archs = config.enabled_archs();
+    arch_providers()
+        .iter()
+        .filter(|provider| match config.opts.primary_arch {
+            Some(arch) => provider.arch() == arch,
+            None => enabled_archs.contains(&provider.arch()),
+        })
+        .filter_map(|provider| {
+            let snippets = provider.frame_snippets()?;
+            let body = if name == "prologue" {
+                snippets.prologue
+            } else {
+                snippets.epilogue
+            };
+            Some(CompletionItem {
+                label: name.to_string(),
+                label_details: Some(CompletionItemLabelDetails {
+                    detail: None,
+                    description: Some(provider.display_name().to_string()),
+                }),
+                kind: Some(CompletionItemKind::SNIPPET),
+                detail: Some(format!(
+                    "{descr} ({} {})",
+                    provider.display_name(),
+                    snippets.abi
+                )),
+                insert_text: Some(body.to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Matches a constant definition via `.equ`, `.set` or `.equiv`, capturing the
+/// directive, the constant's name and its value
+static CONSTANT_DEF: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^\s*\.(equ|equiv|set)\s+([A-Za-z_.][\w.$]*)\s*,\s*([^#;]*?)\s*(?:[#;].*)?$")
+        .unwrap()
+});
+
+/// Maximum depth of nested `.include`s followed when gathering symbols from included files
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Returns the files included by the document at `uri`, directly or through nested
+/// `.include`s up to `MAX_INCLUDE_DEPTH` deep, in the order they're included
+///
+/// Included paths are resolved against the including file's directory, then against
+/// `include_dirs`. Documents open in `text_store` are read from there so unsaved edits
+/// are seen. Each file is only visited once, so include cycles are harmless, and
+/// includes that can't be found or read are skipped
+#[must_use]
+pub fn get_included_files(
+    uri: &Uri,
+    curr_doc: &str,
+    text_store: &TextDocuments,
+    include_dirs: &HashMap<SourceFile, Vec<PathBuf>>,
+) -> Vec<IncludedFile> {
+    static INCLUDE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r#"(?i)^\s*\.include\s+"([^"]+)""#).unwrap());
+
+    let src_path = PathBuf::from(uri.path().as_str());
+    let mut search_dirs: Vec<&PathBuf> = include_dirs
+        .get(&SourceFile::All)
+        .into_iter()
+        .flatten()
+        .collect();
+    if let Ok(canonical) = src_path.canonicalize() {
+        search_dirs.extend(
+            include_dirs
+                .get(&SourceFile::File(canonical))
+                .into_iter()
+                .flatten(),
+        );
+    }
+
+    let read_file = |path: &PathBuf| -> Option<String> {
+        let open_doc = text_store
+            .documents()
+            .iter()
+            .find(|(uri, _)| Path::new(uri.path().as_str()) == path);
+        if let Some((_, doc)) = open_doc {
+            return Some(doc.get_content(None).to_string());
+        }
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Some(contents),
+            Err(e) => {
+                warn!(
+                    "Failed to read included file {} - Error {e}",
+                    path.display()
+                );
+                None
+            }
+        }
+    };
+
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+    visited.insert(src_path.canonicalize().unwrap_or_else(|_| src_path.clone()));
+    let mut included = Vec::new();
+    // (file doing the including, its contents, its depth)
+    let mut pending: Vec<(PathBuf, String, usize)> = vec![(src_path, curr_doc.to_string(), 0)];
+    while let Some((including, contents, depth)) = pending.pop() {
+        if depth >= MAX_INCLUDE_DEPTH {
+            continue;
+        }
+        let mut nested = Vec::new();
+        for caps in contents.lines().filter_map(|line| INCLUDE.captures(line)) {
+            let name = &caps[1];
+            let Some(path) = including
+                .parent()
+                .into_iter()
+                .chain(search_dirs.iter().map(|dir| dir.as_path()))
+                .map(|dir| dir.join(name))
+                .find(|path| path.is_file())
+            else {
+                warn!("Failed to resolve included file \"{name}\"");
+                continue;
+            };
+            if !visited.insert(path.canonicalize().unwrap_or_else(|_| path.clone())) {
+                continue;
+            }
+            let Some(contents) = read_file(&path) else {
+                continue;
+            };
+            included.push(IncludedFile {
+                path: path.clone(),
+                contents: contents.clone(),
+            });
+            nested.push((path, contents, depth + 1));
+        }
+        // visit nested includes depth first, in the order they appear
+        pending.extend(nested.into_iter().rev());
+    }
+
+    included
+}
+
+/// A constant defined via `.equ`, `.set` or `.equiv`
+struct ConstantDef {
+    directive: String,
+    name: String,
+    value: String,
+    row: usize,
+}
+
+/// Returns the constants defined in `contents`, in order of definition
+fn get_constant_defs(contents: &str) -> impl Iterator<Item = ConstantDef> + '_ {
+    contents.lines().enumerate().filter_map(|(row, line)| {
+        let caps = CONSTANT_DEF.captures(line)?;
+        Some(ConstantDef {
+            directive: caps[1].to_lowercase(),
+            name: caps[2].to_string(),
+            value: caps[3].to_string(),
+            row,
+        })
+    })
+}
+
+/// How a symbol in the constant index was first defined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SymbolDefKind {
+    Label,
+    /// `.equ` or `.set`, which may be redefined freely
+    Set,
+    /// `.equiv`, which refuses to redefine an existing symbol
+    Equiv,
+}
+
+/// Produces diagnostics for symbols in `curr_doc` that are redefined against GAS'
+/// rules: `.equiv` errors if its symbol is already defined, and neither `.equ` nor
+/// `.set` may redefine a label. Redefining a `.equiv` constant with `.equ` or `.set`
+/// is accepted by the assembler but defeats the point of `.equiv`, so it's a warning
+#[must_use]
+pub fn get_symbol_redefinition_diagnostics(curr_doc: &FullTextDocument) -> Vec<Diagnostic> {
+    static LABEL_DEF: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*([A-Za-z_.$][\w.$]*):").unwrap());
+
+    let doc = curr_doc.get_content(None);
+    let mut defs: HashMap<&str, (SymbolDefKind, usize)> = HashMap::new();
+    let mut diagnostics = Vec::new();
+    for (row, line) in doc.lines().enumerate() {
+        if let Some(caps) = LABEL_DEF.captures(line) {
+            let name = caps.get(1).unwrap().as_str();
+            defs.entry(name).or_insert((SymbolDefKind::Label, row));
+            continue;
+        }
+        let Some(caps) = CONSTANT_DEF.captures(line) else {
+            continue;
+        };
+        let directive = caps[1].to_lowercase();
+        let name_match = caps.get(2).unwrap();
+        let name = name_match.as_str();
+        let kind = if directive == "equiv" {
+            SymbolDefKind::Equiv
+        } else {
+            SymbolDefKind::Set
+        };
+
+        let problem = match (defs.get(name), kind) {
+            (None, _) => None,
+            (Some(&(_, first_row)), SymbolDefKind::Equiv) => Some((
+                DiagnosticSeverity::ERROR,
+                format!(
+                    "`.equiv` redefines `{name}`, already defined on line {}",
+                    first_row + 1
+                ),
+            )),
+            (Some(&(SymbolDefKind::Label, first_row)), _) => Some((
+                DiagnosticSeverity::ERROR,
+                format!(
+                    "`.{directive}` redefines the label `{name}` defined on line {}",
+                    first_row + 1
+                ),
+            )),
+            (Some(&(SymbolDefKind::Equiv, first_row)), _) => Some((
+                DiagnosticSeverity::WARNING,
+                format!(
+                    "`.{directive}` redefines `{name}`, which was defined with `.equiv` on line {}",
+                    first_row + 1
+                ),
+            )),
+            (Some(&(SymbolDefKind::Set, _)), _) => None,
+        };
+
+        if let Some((severity, message)) = problem {
+            diagnostics.push(Diagnostic {
+                range: Range {
+                    start: Position {
+                        line: row as u32,
+                        character: name_match.start() as u32,
+                    },
+                    end: Position {
+                        line: row as u32,
+                        character: name_match.end() as u32,
+                    },
+                },
+                severity: Some(severity),
+                code: Some(NumberOrString::String(
+                    DiagnosticCode::SymbolRedefinition.code(),
+                )),
+                source: Some(String::from("asm-lsp")),
+                message,
+                ..Default::default()
+            });
+        }
+        defs.entry(name).or_insert((kind, row));
+    }
+
+    diagnostics
+}
+
+/// Returns completion items for the constants defined via `.equ`, `.set` or `.equiv`
+/// in `curr_doc` and the files it includes, detailed with their values. Constants from
+/// included files are described with the file's name
+fn get_constant_comps(curr_doc: &str, included: &[IncludedFile]) -> Vec<CompletionItem> {
+    let sources = included
+        .iter()
+        .map(|file| (file.contents.as_str(), file.path.file_name()))
+        .chain(std::iter::once((curr_doc, None)));
+
+    let mut items: Vec<CompletionItem> = Vec::new();
+    for (contents, file_name) in sources {
+        for def in get_constant_defs(contents) {
+            // `.set` may redefine a symbol, keep its latest value
+            items.retain(|item| item.label != def.name);
+            items.push(CompletionItem {
+                label: def.name,
+                kind: Some(CompletionItemKind::CONSTANT),
+                detail: Some(def.value),
+                label_details: file_name.map(|file_name| CompletionItemLabelDetails {
+                    detail: None,
+                    description: Some(file_name.to_string_lossy().into_owned()),
+                }),
+                ..Default::default()
+            });
+        }
+    }
+
+    items
+}
+
+/// Returns a hover describing `word` if it's an operand written in a syntax specific to
+/// one of the enabled architectures, such as AArch64's `v0.4s`
+fn get_operand_hover_resp(word: &str, config: &TargetConfig) -> Option<Hover> {
+    let enabled_archs = config.enabled_archs();
+    let description = arch_providers()
+        .iter()
+        .filter(|provider| enabled_archs.contains(&provider.arch()))
+        .find_map(|provider| provider.describe_operand(word))?;
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("`{word}`\n\n{description}"),
+        }),
+        range: None,
+    })
+}
+
+/// Returns a hover describing the constant `word` and where it's defined, if it's
+/// defined in `curr_doc` or one of the files it includes
+fn get_constant_hover_resp(curr_doc: &str, included: &[IncludedFile], word: &str) -> Option<Hover> {
+    let find_def = |contents: &str| {
+        get_constant_defs(contents)
+            .filter(|def| def.name == word)
+            .last()
+    };
+
+    let value = if let Some(def) = find_def(curr_doc) {
+        format!(
+            "`.{} {word}, {}`\n\nDefined on line {}",
+            def.directive,
+            def.value,
+            def.row + 1
+        )
+    } else {
+        let (file, def) = included
+            .iter()
+            .rev()
+            .find_map(|file| Some((file, find_def(&file.contents)?)))?;
+        format!(
+            "`.{} {word}, {}`\n\nDefined on line {} of `{}`",
+            def.directive,
+            def.value,
+            def.row + 1,
+            file.path.display()
+        )
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: None,
+    })
+}
+
+/// Returns true if the word ending at `cursor_char` on `cursor_line` is an immediate
+/// operand, i.e. starts with the immediate prefix of one of the enabled architectures
+/// (e.g. `$`)
+fn is_immediate_operand(
+    curr_doc: &str,
+    cursor_line: usize,
+    cursor_char: usize,
+    config: &TargetConfig,
+) -> bool {
+    let Some(line) = curr_doc.lines().nth(cursor_line) else {
+        return false;
+    };
+    let prefix = line.get(..cursor_char).unwrap_or(line);
+    let before_word = prefix.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_');
+    immediate_prefixes(config).any(|imm_prefix| before_word.ends_with(imm_prefix))
+}
+
+/// Immediate prefixes of the enabled architectures
+fn immediate_prefixes(config: &TargetConfig) -> impl Iterator<Item = &'static str> {
+    let enabled_archs = config.enabled_archs();
+    arch_providers()
+        .iter()
+        .filter(move |provider| enabled_archs.contains(&provider.arch()))
+        .filter_map(|provider| provider.immediate_prefix())
+}
+
+macro_rules! cursor_matches {
+    ($cursor_line:expr,$cursor_char:expr,$query_start:expr,$query_end:expr) => {{
+        $query_start.row == $cursor_line
+            && $query_end.row == $cursor_line
+            && $query_start.column <= $cursor_char
+            && $query_end.column >= $cursor_char
+    }};
+}
+
+/// Returns completion items for the cursor position judging only by the text before it on
+/// its line, for when the document couldn't be parsed: directives or instructions for the
+/// first word of a statement, registers for an instruction's operands
+fn get_fallback_comp_items(
+    curr_doc: &str,
+    cursor_line: usize,
+    cursor_char: usize,
+    instr_comps: &[CompletionItem],
+    dir_comps: &[CompletionItem],
+    reg_comps: &[CompletionItem],
+    reg_classes: &HashSet<RegisterClass>,
+) -> Option<Vec<CompletionItem>> {
+    let line = curr_doc.lines().nth(cursor_line)?;
+    let prefix = line.get(..cursor_char).unwrap_or(line);
+    // skip past any label preceding the statement
+    let prefix = prefix
+        .rsplit_once(':')
+        .map_or(prefix, |(_, rest)| rest)
+        .trim_start();
+
+    match (
+        prefix.starts_with('.'),
+        prefix.contains(char::is_whitespace),
+    ) {
+        (true, false) => Some(ranked_dir_comp_list(curr_doc, None, cursor_line, dir_comps)),
+        // a directive's arguments
+        (true, true) => None,
+        (false, false) => Some(filtered_comp_list(instr_comps)),
+        (false, true) => Some(ranked_reg_comp_list(reg_comps, reg_classes)),
+    }
+}
+
+/// Completion lists longer than this are filtered by the typed prefix on the server
+const COMPLETION_FILTER_THRESHOLD: usize = 500;
+
+/// Returns the completion items for the cursor position in `params`
+///
+/// Very large lists (e.g. every x86-64 instruction) are filtered by the word typed so far
+/// before being sent, and marked incomplete so the client asks again as the word grows.
+/// Smaller lists are sent whole and marked complete, leaving filtering to the client
+///
+/// Documents longer than `max_file_lines_for_completion` aren't parsed at all, getting
+/// an empty list marked incomplete to keep the editor responsive
+#[allow(clippy::too_many_arguments)]
+pub fn get_comp_resp(
+    curr_doc: &str,
+    tree_entry: &mut TreeEntry,
+    params: &CompletionParams,
+    instr_comps: &[CompletionItem],
+    dir_comps: &[CompletionItem],
+    reg_comps: &[CompletionItem],
+    instr_map: &NameToInstructionMap,
+    directive_map: &NameToDirectiveMap,
+    config: &TargetConfig,
+    included: &[IncludedFile],
+) -> Option<CompletionResponse> {
+    if config.is_large_file(curr_doc) {
+        return Some(CompletionResponse::List(CompletionList {
+            is_incomplete: true,
+            items: Vec::new(),
+        }));
+    }
+
+    let items = get_comp_items(
+        curr_doc,
+        tree_entry,
+        params,
+        instr_comps,
+        dir_comps,
+        reg_comps,
+        instr_map,
+        directive_map,
+        config,
+        included,
+    )?;
+
+    let position = params.text_document_position.position;
+    let typed = curr_doc
+        .lines()
+        .nth(position.line as usize)
+        .map_or("", |line| {
+            let prefix = &line[..byte_col_of_utf16(line, position.character)];
+            let start = prefix
+                .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .map_or(0, |idx| idx + 1);
+            &prefix[start..]
+        })
+        .to_lowercase();
 
-    let reg_lookup = lookup_hover_resp_by_arch(word, register_map);
-    if reg_lookup.is_some() {
-        return reg_lookup;
+    if items.len() <= COMPLETION_FILTER_THRESHOLD || typed.is_empty() {
+        return Some(CompletionResponse::List(CompletionList {
+            is_incomplete: false,
+            items,
+        }));
     }
 
-    let demang = get_demangle_resp(word);
-    if demang.is_some() {
-        return demang;
-    }
+    Some(CompletionResponse::List(CompletionList {
+        is_incomplete: true,
+        items: items
+            .into_iter()
+            .filter(|item| {
+                item.filter_text
+                    .as_ref()
+                    .unwrap_or(&item.label)
+                    .to_lowercase()
+                    .starts_with(&typed)
+            })
+            .collect(),
+    }))
+}
 
-    let include_path = get_include_resp(
-        &params.text_document_position_params.text_document.uri,
-        file_word,
-        include_dirs,
-    );
-    if include_path.is_some() {
-        return include_path;
-    }
+/// Name of the file in the cache directory holding `CompletionFrequencies`
+pub const COMPLETION_FREQUENCIES_FILE: &str = "completion_frequencies.json";
 
-    None
+/// Command attached to every completion item, which the client runs once it inserts the
+/// item, with the item's label as the only argument
+pub const RECORD_COMPLETION_COMMAND: &str = "asm-lsp.recordCompletion";
+
+/// Most labels `CompletionFrequencies` keeps counts for
+pub const MAX_COMPLETION_FREQUENCIES: usize = 1000;
+
+/// Number of times each completion item has been accepted, keyed by its label, used to
+/// sort frequently chosen items towards the top of future completion lists
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletionFrequencies {
+    counts: HashMap<String, u32>,
 }
 
-fn lookup_hover_resp_by_arch<T: Hoverable>(
-    word: &str,
-    map: &HashMap<(Arch, &str), T>,
-) -> Option<Hover> {
-    // switch over to vec?
-    let (x86_resp, x86_64_resp, z80_resp) = search_for_hoverable_by_arch(word, map);
-    match (
-        x86_resp.is_some(),
-        x86_64_resp.is_some(),
-        z80_resp.is_some(),
-    ) {
-        (true, _, _) | (_, true, _) | (_, _, true) => {
-            let mut value = String::new();
-            if let Some(x86_resp) = x86_resp {
-                value += &format!("{x86_resp}");
-            }
-            if let Some(x86_64_resp) = x86_64_resp {
-                value += &format!(
-                    "{}{}",
-                    if value.is_empty() { "" } else { "\n\n" },
-                    x86_64_resp
+impl CompletionFrequencies {
+    /// Loads the counts saved at `path`, starting over with no counts if the file
+    /// is missing or can't be parsed
+    #[must_use]
+    pub fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(conts) => serde_json::from_str(&conts).unwrap_or_else(|e| {
+                warn!(
+                    "Failed to parse completion frequencies at {} -- Error: {e}",
+                    path.display()
                 );
-            }
-            if let Some(z80_resp) = z80_resp {
-                value += &format!("{}{}", if value.is_empty() { "" } else { "\n\n" }, z80_resp);
-            }
-            Some(Hover {
-                contents: HoverContents::Markup(MarkupContent {
-                    kind: MarkupKind::Markdown,
-                    value,
-                }),
-                range: None,
-            })
-        }
-        _ => {
-            // don't know of this word
-            None
+                Self::default()
+            }),
+            Err(_) => Self::default(),
         }
     }
-}
 
-fn lookup_hover_resp_by_assembler<T: Hoverable>(
-    word: &str,
-    map: &HashMap<(Assembler, &str), T>,
-) -> Option<Hover> {
-    let (gas_resp, go_resp) = search_for_hoverable_by_assembler(word, map);
+    /// Saves the counts to `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the counts can't be serialized or written to `path`
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
 
-    match (gas_resp.is_some(), go_resp.is_some()) {
-        (true, _) | (_, true) => {
-            let mut value = String::new();
-            if let Some(gas_resp) = gas_resp {
-                value += &format!("{gas_resp}");
-            }
-            if let Some(go_resp) = go_resp {
-                value += &format!(
-                    "{}{}",
-                    if gas_resp.is_some() { "\n\n" } else { "" },
-                    go_resp
-                );
+    /// Records that the item labelled `label` was accepted, forgetting the least
+    /// accepted item if `MAX_COMPLETION_FREQUENCIES` are already counted
+    pub fn record(&mut self, label: &str) {
+        if !self.counts.contains_key(label) && self.counts.len() >= MAX_COMPLETION_FREQUENCIES {
+            let least = self
+                .counts
+                .iter()
+                .min_by(|a, b| a.1.cmp(b.1).then_with(|| a.0.cmp(b.0)))
+                .map(|(label, _)| label.clone());
+            if let Some(least) = least {
+                self.counts.remove(&least);
             }
-            Some(Hover {
-                contents: HoverContents::Markup(MarkupContent {
-                    kind: MarkupKind::Markdown,
-                    value,
-                }),
-                range: None,
-            })
-        }
-        _ => {
-            // don't know of this word
-            None
         }
+        let count = self.counts.entry(label.to_string()).or_insert(0);
+        *count = count.saturating_add(1);
     }
-}
 
-fn get_demangle_resp(word: &str) -> Option<Hover> {
-    let name = Name::new(word, NameMangling::Mangled, Language::Unknown);
-    let demangled = name.demangle(DemangleOptions::complete());
-    if let Some(demang) = demangled {
-        let value = demang.to_string();
-        return Some(Hover {
-            contents: HoverContents::Markup(MarkupContent {
-                kind: MarkupKind::Markdown,
-                value,
-            }),
-            range: None,
-        });
+    /// Number of times the item labelled `label` was accepted
+    #[must_use]
+    pub fn count(&self, label: &str) -> u32 {
+        self.counts.get(label).copied().unwrap_or(0)
     }
 
-    None
-}
-
-fn get_include_resp(
-    source_file: &Uri,
-    filename: &str,
-    include_dirs: &HashMap<SourceFile, Vec<PathBuf>>,
-) -> Option<Hover> {
-    let mut paths = String::new();
-
-    let mut dir_iter: Box<dyn Iterator<Item = &PathBuf>> = match include_dirs.get(&SourceFile::All)
-    {
-        Some(dirs) => Box::new(dirs.iter()),
-        None => Box::new(std::iter::empty()),
-    };
-
-    if let Ok(src_path) = PathBuf::from(source_file.as_str()).canonicalize() {
-        if let Some(dirs) = include_dirs.get(&SourceFile::File(src_path)) {
-            dir_iter = Box::new(dir_iter.chain(dirs.iter()));
+    /// Rewrites the `sort_text` of `items` so previously accepted items sort first,
+    /// most frequently accepted leading, with the existing order kept otherwise
+    ///
+    /// `items` is left untouched if none of them have been accepted before
+    pub fn boost(&self, items: &mut [CompletionItem]) {
+        if !items.iter().any(|item| self.count(&item.label) > 0) {
+            return;
         }
-    }
-
-    for dir in dir_iter {
-        match std::fs::read_dir(dir) {
-            Ok(dir_reader) => {
-                for file in dir_reader {
-                    match file {
-                        Ok(f) => {
-                            if f.file_name() == filename {
-                                paths += &format!("file://{}\n", f.path().display());
-                            }
-                        }
-                        Err(e) => {
-                            error!(
-                                "Failed to read item in {} - Error {e}",
-                                dir.as_path().display()
-                            );
-                        }
-                    };
-                }
-            }
-            Err(e) => {
-                error!(
-                    "Failed to create directory reader for {} - Error {e}",
-                    dir.as_path().display()
-                );
-            }
+        for item in items {
+            let base = item.sort_text.take().unwrap_or_else(|| item.label.clone());
+            item.sort_text = Some(match self.count(&item.label) {
+                0 => format!("1{base}"),
+                count => format!("0{:010}{base}", u32::MAX - count),
+            });
         }
     }
+}
 
-    if paths.is_empty() {
-        None
-    } else {
-        Some(Hover {
-            contents: HoverContents::Markup(MarkupContent {
-                kind: MarkupKind::Markdown,
-                value: paths,
-            }),
-            range: None,
-        })
+/// Attaches `RECORD_COMPLETION_COMMAND` to `items`, so that only the items actually
+/// inserted are counted by `CompletionFrequencies`
+pub fn add_record_completion_commands(items: &mut [CompletionItem]) {
+    for item in items {
+        item.command = Some(lsp_types::Command {
+            title: String::new(),
+            command: RECORD_COMPLETION_COMMAND.to_string(),
+            arguments: Some(vec![serde_json::Value::String(item.label.clone())]),
+        });
     }
 }
 
-/// Filter out duplicate completion suggestions
-fn filtered_comp_list(comps: &[CompletionItem]) -> Vec<CompletionItem> {
-    let mut seen = HashSet::new();
+/// Characters which trigger completion, other than the architectures' register and
+/// immediate prefixes
+const TRIGGER_CHARACTERS: [&str; 2] = [".", "\\"];
 
-    comps
+/// Returns the character a completion was triggered by, along with the column right
+/// after it, if any
+///
+/// Clients re-request incomplete completions as the word grows without repeating the
+/// trigger character, so it's recovered from the document instead: it's the character
+/// right before the word being typed (e.g. the `%` in `%ra`)
+fn get_completion_trigger<'a>(
+    params: &'a CompletionParams,
+    curr_doc: &'a str,
+    cursor_line: usize,
+    cursor_char: Column,
+) -> Option<(&'a str, Column)> {
+    let ctx = params.context.as_ref()?;
+    if ctx.trigger_kind == CompletionTriggerKind::TRIGGER_CHARACTER {
+        return Some((ctx.trigger_character.as_deref()?, cursor_char));
+    }
+    if ctx.trigger_kind != CompletionTriggerKind::TRIGGER_FOR_INCOMPLETE_COMPLETIONS {
+        return None;
+    }
+
+    let prefix = curr_doc.lines().nth(cursor_line)?.get(..cursor_char)?;
+    let word_start = prefix
+        .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_')
+        .len();
+    let before_word = &prefix[..word_start];
+    arch_providers()
         .iter()
-        .filter(|comp_item| {
-            if seen.contains(&comp_item.label) {
-                false
-            } else {
-                seen.insert(&comp_item.label);
-                true
-            }
+        .filter_map(|provider| provider.register_prefix())
+        .chain(TRIGGER_CHARACTERS.iter().copied())
+        .find(|trigger| before_word.ends_with(trigger))
+        .map(|trigger| {
+            (
+                &before_word[before_word.len() - trigger.len()..],
+                word_start,
+            )
         })
-        .cloned()
-        .collect()
-}
-
-macro_rules! cursor_matches {
-    ($cursor_line:expr,$cursor_char:expr,$query_start:expr,$query_end:expr) => {{
-        $query_start.row == $cursor_line
-            && $query_end.row == $cursor_line
-            && $query_start.column <= $cursor_char
-            && $query_end.column >= $cursor_char
-    }};
 }
 
-pub fn get_comp_resp(
+#[allow(clippy::too_many_arguments)]
+fn get_comp_items(
     curr_doc: &str,
     tree_entry: &mut TreeEntry,
     params: &CompletionParams,
     instr_comps: &[CompletionItem],
     dir_comps: &[CompletionItem],
     reg_comps: &[CompletionItem],
-) -> Option<CompletionList> {
+    instr_map: &NameToInstructionMap,
+    directive_map: &NameToDirectiveMap,
+    config: &TargetConfig,
+    included: &[IncludedFile],
+) -> Option<Vec<CompletionItem>> {
     let cursor_line = params.text_document_position.position.line as usize;
-    let cursor_char = params.text_document_position.position.character as usize;
+    let cursor_char = curr_doc.lines().nth(cursor_line).map_or(0, |line| {
+        byte_col_of_utf16(line, params.text_document_position.position.character)
+    });
+    let reg_classes = expected_register_classes(
+        curr_doc,
+        cursor_line,
+        cursor_char,
+        instr_map,
+        config.operand_syntax(),
+    );
 
-    if let Some(ctx) = params.context.as_ref() {
-        if ctx.trigger_kind == CompletionTriggerKind::TRIGGER_CHARACTER {
-            match ctx
-                .trigger_character
-                .as_ref()
-                .map(std::convert::AsRef::as_ref)
+    // immediates (e.g. `$0x10`, `$BUF_SIZE`) are never registers or instructions, so
+    // only offer named constants there
+    if is_immediate_operand(curr_doc, cursor_line, cursor_char, config) {
+        return Some(get_constant_comps(curr_doc, included));
+    }
+
+    // symbol arguments of directives such as `.globl` usually name the document's labels
+    let cursor_line_text = curr_doc.lines().nth(cursor_line).unwrap_or_default();
+    if is_directive_symbol_arg_at(cursor_line_text, cursor_char, directive_map) {
+        tree_entry.tree = tree_entry.parser.parse(curr_doc, tree_entry.tree.as_ref());
+        if let Some(ref tree) = tree_entry.tree {
+            return Some(get_label_comps(curr_doc, tree));
+        }
+    }
+
+    if let Some((trigger, trigger_end)) =
+        get_completion_trigger(params, curr_doc, cursor_line, cursor_char)
+    {
+        match trigger {
+            // register prefixes, e.g. "%" for GAS, only complete the registers of the
+            // architectures using them
+            trigger
+                if arch_providers()
+                    .iter()
+                    .any(|provider| provider.register_prefix() == Some(trigger)) =>
             {
-                // prepend GAS registers with "%"
-                Some("%") => {
-                    return Some(CompletionList {
-                        is_incomplete: true,
-                        items: filtered_comp_list(reg_comps),
-                    });
-                }
-                // prepend GAS directives with "."
-                Some(".") => {
-                    return Some(CompletionList {
-                        is_incomplete: true,
-                        items: filtered_comp_list(dir_comps),
-                    });
+                let archs: Vec<Arch> = arch_providers()
+                    .iter()
+                    .filter(|provider| provider.register_prefix() == Some(trigger))
+                    .map(|provider| provider.arch())
+                    .collect();
+                let reg_comps = comps_for_archs(reg_comps, &archs);
+                return Some(ranked_reg_comp_list(&reg_comps, &reg_classes));
+            }
+            // GAS macro parameters and `.irp`/`.irpc` variables are referenced as `\param`
+            "\\" => {
+                tree_entry.tree = tree_entry.parser.parse(curr_doc, tree_entry.tree.as_ref());
+                let params = tree_entry
+                    .tree
+                    .as_ref()
+                    .and_then(|tree| get_enclosing_block_params(curr_doc, tree, cursor_line))?;
+                return Some(
+                    params
+                        .into_iter()
+                        .map(|(param, kind)| CompletionItem {
+                            label: param,
+                            kind: Some(CompletionItemKind::VARIABLE),
+                            detail: match kind {
+                                BlockKind::Macro => Some("macro parameter"),
+                                BlockKind::Repeat => Some("loop variable"),
+                                // conditional blocks don't declare any symbols
+                                BlockKind::Conditional => None,
+                            }
+                            .map(String::from),
+                            ..Default::default()
+                        })
+                        .collect(),
+                );
+            }
+            // suffixes of the mnemonic right before the "." (e.g. `b.eq`), otherwise
+            // GAS directives, which start with "."
+            "." => {
+                if let Some(items) = get_suffix_comps(curr_doc, cursor_line, trigger_end, config) {
+                    return Some(items);
                 }
-                _ => {}
+                tree_entry.tree = tree_entry.parser.parse(curr_doc, tree_entry.tree.as_ref());
+                return Some(ranked_dir_comp_list(
+                    curr_doc,
+                    tree_entry.tree.as_ref(),
+                    cursor_line,
+                    dir_comps,
+                ));
+            }
+            // the immediate prefix of a disabled architecture, e.g. ARM's `#` starting a
+            // comment in x86 code, so there's nothing to complete
+            trigger
+                if arch_providers()
+                    .iter()
+                    .any(|provider| provider.immediate_prefix() == Some(trigger)) =>
+            {
+                return None;
             }
+            _ => {}
         }
     }
 
-    // TODO: filter register completions by width allowed by corresponding instruction
+    if let Some(items) = dotted_mnemonic_prefix(cursor_line_text, cursor_char)
+        .and_then(|typed| get_conditional_comps(typed, instr_comps, config))
+    {
+        return Some(items);
+    }
+
     tree_entry.tree = tree_entry.parser.parse(curr_doc, tree_entry.tree.as_ref());
     if let Some(ref tree) = tree_entry.tree {
+        // base and index registers of Intel syntax memory operands, e.g. `[rbp + rcx*8]`,
+        // narrowed down to the ones starting with what's been typed so far
+        if let Some(partial) = intel_memory_operand_prefix(curr_doc, tree, cursor_line, cursor_char)
+        {
+            let partial = partial.to_lowercase();
+            let reg_comps: Vec<CompletionItem> =
+                comps_for_archs(reg_comps, &[Arch::X86, Arch::X86_64])
+                    .into_iter()
+                    .filter(|item| item.label.to_lowercase().starts_with(&partial))
+                    .collect();
+            let classes = HashSet::from([RegisterClass::Gpr]);
+            return Some(ranked_reg_comp_list(&reg_comps, &classes));
+        }
+
+        // jump and call targets are usually labels, so offer those ahead of registers
+        if expects_label_operand(curr_doc, cursor_line, cursor_char, instr_map) {
+            let mut items = get_label_comps(curr_doc, tree);
+            for item in &mut items {
+                item.sort_text = Some(format!("0{}", item.label));
+            }
+            for mut item in ranked_reg_comp_list(reg_comps, &reg_classes) {
+                let sort_text = item.sort_text.take().unwrap_or_else(|| item.label.clone());
+                item.sort_text = Some(format!("1{sort_text}"));
+                items.push(item);
+            }
+            return Some(items);
+        }
+
+        // architectures like z80 don't prefix their registers, so any identifier in an
+        // operand of their instructions may be a register, 8-bit or a pair like `hl`
+        if let Some(arch) =
+            bare_register_operand_arch(curr_doc, cursor_line, cursor_char, instr_map)
+        {
+            let reg_comps = comps_for_archs(reg_comps, &[arch]);
+            return Some(ranked_reg_comp_list(&reg_comps, &reg_classes));
+        }
+
         let mut cursor = tree_sitter::QueryCursor::new();
         cursor.set_point_range(std::ops::Range {
             start: tree_sitter::Point {
@@ -739,7 +4558,6 @@ pub fn get_comp_resp(
                 column: usize::MAX,
             },
         });
-        let curr_doc = curr_doc.as_bytes();
 
         static QUERY_DIRECTIVE: Lazy<tree_sitter::Query> = Lazy::new(|| {
             tree_sitter::Query::new(
@@ -748,7 +4566,7 @@ pub fn get_comp_resp(
             )
             .unwrap()
         });
-        let matches_iter = cursor.matches(&QUERY_DIRECTIVE, tree.root_node(), curr_doc);
+        let matches_iter = cursor.matches(&QUERY_DIRECTIVE, tree.root_node(), curr_doc.as_bytes());
 
         for match_ in matches_iter {
             let caps = match_.captures;
@@ -756,11 +4574,8 @@ pub fn get_comp_resp(
                 let arg_start = cap.node.range().start_point;
                 let arg_end = cap.node.range().end_point;
                 if cursor_matches!(cursor_line, cursor_char, arg_start, arg_end) {
-                    let items = filtered_comp_list(dir_comps);
-                    return Some(CompletionList {
-                        is_incomplete: true,
-                        items,
-                    });
+                    let items = ranked_dir_comp_list(curr_doc, Some(tree), cursor_line, dir_comps);
+                    return Some(items);
                 }
             }
         }
@@ -803,7 +4618,7 @@ pub fn get_comp_resp(
             .unwrap()
         });
 
-        let matches_iter = cursor.matches(&QUERY_INSTR_ANY, tree.root_node(), curr_doc);
+        let matches_iter = cursor.matches(&QUERY_INSTR_ANY, tree.root_node(), curr_doc.as_bytes());
         for match_ in matches_iter {
             let caps = match_.captures;
             for (cap_num, cap) in caps.iter().enumerate() {
@@ -811,15 +4626,50 @@ pub fn get_comp_resp(
                 let arg_end = cap.node.range().end_point;
                 if cursor_matches!(cursor_line, cursor_char, arg_start, arg_end) {
                     // an instruction is always capture #0, any capture number after must be a register
-                    let items =
-                        filtered_comp_list(if cap_num == 0 { instr_comps } else { reg_comps });
-                    return Some(CompletionList {
-                        is_incomplete: true,
-                        items,
-                    });
+                    let items = if cap_num == 0 {
+                        let typed = cursor_line_text
+                            .get(arg_start.column..cursor_char)
+                            .unwrap_or_default();
+                        let mut items = get_conditional_comps(typed, instr_comps, config)
+                            .unwrap_or_else(|| filtered_comp_list(instr_comps));
+                        items.extend(get_frame_snippet_comps(
+                            curr_doc,
+                            cursor_line,
+                            cursor_char,
+                            config,
+                        ));
+                        items
+                    } else {
+                        ranked_reg_comp_list(reg_comps, &reg_classes)
+                    };
+                    return Some(items);
                 }
             }
         }
+    } else {
+        warn!("Failed to parse the document, falling back to line based completion");
+        return get_fallback_comp_items(
+            curr_doc,
+            cursor_line,
+            cursor_char,
+            instr_comps,
+            dir_comps,
+            reg_comps,
+            &reg_classes,
+        );
+    }
+
+    // the start of an instruction on an otherwise blank line
+    let snippets = get_frame_snippet_comps(curr_doc, cursor_line, cursor_char, config);
+    if !snippets.is_empty() {
+        let mut items = filtered_comp_list(instr_comps);
+        items.extend(snippets);
+        return Some(items);
+    }
+
+    // an empty operand slot of an instruction that expects a register there
+    if !reg_classes.is_empty() {
+        return Some(ranked_reg_comp_list(reg_comps, &reg_classes));
     }
 
     None
@@ -919,13 +4769,115 @@ pub fn get_document_symbols(
     }
 }
 
+/// Summarizes `curr_doc`: the functions it defines (labels followed by an
+/// instruction), the sections it uses, the external symbols it references and the
+/// instructions none of the enabled instruction sets document
+pub fn get_file_summary(
+    curr_doc: &FullTextDocument,
+    tree_entry: &mut TreeEntry,
+    instr_map: &NameToInstructionMap,
+    config: &TargetConfig,
+) -> FileSummary {
+    static PLT_REF: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)([A-Za-z_.$][\w.$]*)@PLT\b").unwrap());
+    static EXTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)^\s*\.extern\s+([^#;]*)").unwrap());
+
+    let mut summary = FileSummary::default();
+    let doc = curr_doc.get_content(None);
+    let push_unique = |items: &mut Vec<String>, item: &str| {
+        if !items.iter().any(|existing| existing == item) {
+            items.push(item.to_string());
+        }
+    };
+
+    for (row, line) in doc.lines().enumerate() {
+        if SECTION_DIRECTIVE.is_match(line) {
+            if let Some(section) = get_active_section(doc, row) {
+                push_unique(&mut summary.sections, &section);
+            }
+        }
+        if let Some(caps) = EXTERN.captures(line) {
+            for symbol in caps[1].split(',').map(str::trim) {
+                if !symbol.is_empty() {
+                    push_unique(&mut summary.external_symbols, symbol);
+                }
+            }
+        }
+        for caps in PLT_REF.captures_iter(line) {
+            push_unique(&mut summary.external_symbols, &caps[1]);
+        }
+    }
+
+    tree_entry.tree = tree_entry.parser.parse(doc, tree_entry.tree.as_ref());
+    let Some(ref tree) = tree_entry.tree else {
+        return summary;
+    };
+    let enabled_archs = config.enabled_archs();
+    let is_known = |name: &str| {
+        let lower = name.to_lowercase();
+        INSTRUCTION_PREFIXES
+            .iter()
+            .any(|(prefix, _, _)| *prefix == lower)
+            || enabled_archs.iter().any(|arch| {
+                instr_map.contains_key(&(*arch, name))
+                    || instr_map.contains_key(&(*arch, lower.as_str()))
+            })
+    };
+    // the label whose first statement hasn't been seen yet
+    let mut pending_label: Option<&str> = None;
+    let mut walker = tree.walk();
+    for node in tree.root_node().children(&mut walker) {
+        match node.kind() {
+            "label" => {
+                let name = node
+                    .utf8_text(doc.as_bytes())
+                    .unwrap_or_default()
+                    .trim_end_matches(':');
+                let is_local = name.starts_with(".L") || name.chars().all(|c| c.is_ascii_digit());
+                pending_label = (!is_local).then_some(name);
+            }
+            "instruction" => {
+                if let Some(label) = pending_label.take() {
+                    push_unique(&mut summary.functions, label);
+                }
+                let Some(Ok(name)) = node
+                    .child_by_field_name("kind")
+                    .map(|kind| kind.utf8_text(doc.as_bytes()))
+                else {
+                    continue;
+                };
+                if !is_known(name) {
+                    push_unique(&mut summary.unknown_instructions, name);
+                }
+            }
+            "meta" => pending_label = None,
+            _ => {}
+        }
+    }
+
+    summary
+}
+
 pub fn get_sig_help_resp(
     curr_doc: &str,
     params: &SignatureHelpParams,
     tree_entry: &mut TreeEntry,
     instr_info: &NameToInstructionMap,
+    config: &TargetConfig,
 ) -> Option<SignatureHelp> {
     let cursor_line = params.text_document_position_params.position.line as usize;
+    let cursor_char = curr_doc.lines().nth(cursor_line).map_or(0, |line| {
+        byte_col_of_utf16(
+            line,
+            params.text_document_position_params.position.character,
+        )
+    });
+    // the operand the cursor is in, if it's past the mnemonic
+    let operand_idx = split_instr_prefix(curr_doc, cursor_line, cursor_char).map(|(_, ops)| {
+        split_operands(ops, config.operand_syntax())
+            .len()
+            .saturating_sub(1)
+    });
 
     tree_entry.tree = tree_entry.parser.parse(curr_doc, tree_entry.tree.as_ref());
     if let Some(ref tree) = tree_entry.tree {
@@ -959,64 +4911,27 @@ pub fn get_sig_help_resp(
             if caps.len() == 1 {
                 if let Ok(instr_name) = caps[0].node.utf8_text(curr_doc) {
                     let mut value = String::new();
-                    let mut has_x86 = false;
-                    let mut has_x86_64 = false;
-                    let mut has_z80 = false;
-                    let (x86_info, x86_64_info, z80_info) =
-                        search_for_hoverable_by_arch(instr_name, instr_info);
-                    if let Some(sig) = x86_info {
-                        for form in &sig.forms {
-                            if let Some(ref gas_name) = form.gas_name {
-                                if instr_name.eq_ignore_ascii_case(gas_name) {
-                                    if !has_x86 {
-                                        value += "**x86**\n";
-                                        has_x86 = true;
-                                    }
-                                    value += &format!("{form}\n");
-                                }
-                            } else if let Some(ref go_name) = form.go_name {
-                                if instr_name.eq_ignore_ascii_case(go_name) {
-                                    if !has_x86 {
-                                        value += "**x86**\n";
-                                        has_x86 = true;
-                                    }
-                                    value += &format!("{form}\n");
-                                }
+                    for provider in arch_providers() {
+                        let Some(sig) = instr_info.get(&(provider.arch(), instr_name)) else {
+                            continue;
+                        };
+                        let mut forms: Vec<&InstructionForm> = sig
+                            .forms
+                            .iter()
+                            .filter(|form| provider.form_has_name(form, instr_name))
+                            .collect();
+                        // only list forms taking the operand being typed, unless none
+                        // document their operands
+                        if let Some(idx) = operand_idx {
+                            if forms.iter().any(|form| form.operands.len() > idx) {
+                                forms.retain(|form| form.operands.len() > idx);
                             }
                         }
-                    }
-                    if let Some(sig) = x86_64_info {
-                        for form in &sig.forms {
-                            if let Some(ref gas_name) = form.gas_name {
-                                if instr_name.eq_ignore_ascii_case(gas_name) {
-                                    if !has_x86_64 {
-                                        value += "**x86_64**\n";
-                                        has_x86_64 = true;
-                                    }
-                                    value += &format!("{form}\n");
-                                }
-                            } else if let Some(ref go_name) = form.go_name {
-                                if instr_name.eq_ignore_ascii_case(go_name) {
-                                    if !has_x86_64 {
-                                        value += "**x86_64**\n";
-                                        has_x86_64 = true;
-                                    }
-                                    value += &format!("{form}\n");
-                                }
-                            }
+                        if !forms.is_empty() {
+                            value += &format!("**{}**\n", provider.display_name());
                         }
-                    }
-                    if let Some(sig) = z80_info {
-                        for form in &sig.forms {
-                            if let Some(ref z80_name) = form.z80_name {
-                                if instr_name.eq_ignore_ascii_case(z80_name) {
-                                    if !has_z80 {
-                                        value += "**z80**\n";
-                                        has_z80 = true;
-                                    }
-                                    value += &format!("{form}\n");
-                                }
-                            }
+                        for form in forms {
+                            value += &format!("{form}\n");
                         }
                     }
                     if !value.is_empty() {
@@ -1045,47 +4960,26 @@ pub fn get_sig_help_resp(
 pub fn get_goto_def_resp(
     curr_doc: &FullTextDocument,
     tree_entry: &mut TreeEntry,
-    params: &GotoDefinitionParams,
-) -> Option<GotoDefinitionResponse> {
-    let doc = curr_doc.get_content(None);
-    tree_entry.tree = tree_entry.parser.parse(doc, tree_entry.tree.as_ref());
-
-    if let Some(ref tree) = tree_entry.tree {
-        static QUERY_LABEL: Lazy<tree_sitter::Query> = Lazy::new(|| {
-            tree_sitter::Query::new(tree_sitter_asm::language(), "(label) @label").unwrap()
-        });
-
-        let is_not_ident_char = |c: char| !(c.is_alphanumeric() || c == '_');
-        let mut cursor = tree_sitter::QueryCursor::new();
-        let matches = cursor.matches(&QUERY_LABEL, tree.root_node(), doc.as_bytes());
-
-        let word = get_word_from_pos_params(curr_doc, &params.text_document_position_params, "");
-
-        for match_ in matches {
-            for cap in match_.captures {
-                let text = cap
-                    .node
-                    .utf8_text(doc.as_bytes())
-                    .unwrap_or("")
-                    .trim()
-                    .trim_matches(is_not_ident_char);
+    params: &GotoDefinitionParams,
+) -> Option<GotoDefinitionResponse> {
+    let doc = curr_doc.get_content(None);
+    tree_entry.tree = tree_entry.parser.parse(doc, tree_entry.tree.as_ref());
 
-                if word.eq(text) {
-                    let start = cap.node.start_position();
-                    let end = cap.node.end_position();
-                    return Some(GotoDefinitionResponse::Scalar(Location {
-                        uri: params
-                            .text_document_position_params
-                            .text_document
-                            .uri
-                            .clone(),
-                        range: Range {
-                            start: lsp_pos_of_point(start),
-                            end: lsp_pos_of_point(end),
-                        },
-                    }));
-                }
-            }
+    if let Some(ref tree) = tree_entry.tree {
+        let word = get_word_from_pos_params(curr_doc, &params.text_document_position_params, "");
+
+        if let Some(def) = find_label_def(doc, tree, word) {
+            return Some(GotoDefinitionResponse::Scalar(Location {
+                uri: params
+                    .text_document_position_params
+                    .text_document
+                    .uri
+                    .clone(),
+                range: Range {
+                    start: lsp_pos_of_point(def.start_point),
+                    end: lsp_pos_of_point(def.end_point),
+                },
+            }));
         }
     }
 
@@ -1097,57 +4991,48 @@ pub fn get_ref_resp(
     curr_doc: &FullTextDocument,
     tree_entry: &mut TreeEntry,
 ) -> Vec<Location> {
-    let mut refs: Vec<Location> = Vec::new();
     let doc = curr_doc.get_content(None);
     tree_entry.tree = tree_entry.parser.parse(doc, tree_entry.tree.as_ref());
 
-    if let Some(ref tree) = tree_entry.tree {
-        static QUERY_LABEL: Lazy<tree_sitter::Query> = Lazy::new(|| {
-            tree_sitter::Query::new(
-                tree_sitter_asm::language(),
-                "(label (ident (reg (word)))) @label",
-            )
-            .unwrap()
-        });
+    let Some(ref tree) = tree_entry.tree else {
+        return Vec::new();
+    };
+    let word = get_word_from_pos_params(curr_doc, &params.text_document_position, "");
+    find_label_refs(
+        doc,
+        tree,
+        word,
+        &params.text_document_position.text_document.uri,
+        params.context.include_declaration,
+    )
+}
 
-        static QUERY_WORD: Lazy<tree_sitter::Query> = Lazy::new(|| {
-            tree_sitter::Query::new(tree_sitter_asm::language(), "(ident) @ident").unwrap()
-        });
+/// Finds the locations of the identifiers in `doc` referring to the label `word`,
+/// preceded by the label's definitions if `include_declaration` is set
+fn find_label_refs(
+    doc: &str,
+    tree: &tree_sitter::Tree,
+    word: &str,
+    uri: &Uri,
+    include_declaration: bool,
+) -> Vec<Location> {
+    static QUERY_LABEL: Lazy<tree_sitter::Query> = Lazy::new(|| {
+        tree_sitter::Query::new(
+            tree_sitter_asm::language(),
+            "(label (ident (reg (word)))) @label",
+        )
+        .unwrap()
+    });
 
-        let is_not_ident_char = |c: char| !(c.is_alphanumeric() || c == '_');
-        let word = get_word_from_pos_params(curr_doc, &params.text_document_position, "");
-        let uri = &params.text_document_position.text_document.uri;
+    static QUERY_WORD: Lazy<tree_sitter::Query> = Lazy::new(|| {
+        tree_sitter::Query::new(tree_sitter_asm::language(), "(ident) @ident").unwrap()
+    });
 
+    let mut refs: Vec<Location> = Vec::new();
+    let is_not_ident_char = |c: char| !(c.is_alphanumeric() || c == '_');
+    let mut push_matches = |query: &tree_sitter::Query| {
         let mut cursor = tree_sitter::QueryCursor::new();
-        if params.context.include_declaration {
-            let label_matches = cursor.matches(&QUERY_LABEL, tree.root_node(), doc.as_bytes());
-            for match_ in label_matches {
-                for cap in match_.captures {
-                    let text = cap
-                        .node
-                        .utf8_text(doc.as_bytes())
-                        .unwrap_or("")
-                        .trim()
-                        .trim_matches(is_not_ident_char);
-
-                    if word.eq(text) {
-                        let start = lsp_pos_of_point(cap.node.start_position());
-                        let end = lsp_pos_of_point(cap.node.end_position());
-                        // TODO: Use a Hashset here once lsptextdocument bumps its
-                        // dependency on lsp_types
-                        if !refs.iter().any(|loc| loc.range.start == start) {
-                            refs.push(Location {
-                                uri: uri.clone(),
-                                range: Range { start, end },
-                            });
-                        }
-                    }
-                }
-            }
-        }
-
-        let word_matches = cursor.matches(&QUERY_WORD, tree.root_node(), doc.as_bytes());
-        for match_ in word_matches {
+        for match_ in cursor.matches(query, tree.root_node(), doc.as_bytes()) {
             for cap in match_.captures {
                 let text = cap
                     .node
@@ -1170,25 +5055,579 @@ pub fn get_ref_resp(
                 }
             }
         }
+    };
+
+    if include_declaration {
+        push_matches(&QUERY_LABEL);
     }
+    push_matches(&QUERY_WORD);
 
     refs
 }
 
-// Note: Some issues here regarding entangled lifetimes
-// -- https://github.com/rust-lang/rust/issues/80389
-// If issue is resolved, can add a separate lifetime "'b" to "word"
-// parameter such that 'a: 'b
-// For now, using 'a for both isn't strictly necessary, but fits our use case
-fn search_for_hoverable_by_arch<'a, T: Hoverable>(
-    word: &'a str,
-    map: &'a HashMap<(Arch, &str), T>,
-) -> (Option<&'a T>, Option<&'a T>, Option<&'a T>) {
-    let x86_resp = map.get(&(Arch::X86, word));
-    let x86_64_resp = map.get(&(Arch::X86_64, word));
-    let z80_resp = map.get(&(Arch::Z80, word));
+/// Label a code lens is shown above, which the lens' command is resolved for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CodeLensData {
+    uri: Uri,
+    label: String,
+}
+
+/// Returns a code lens above each label definition in `curr_doc`. Counting a label's
+/// references scans the whole document, so the lenses' commands are left for
+/// `resolve_code_lens` to fill in once the client displays them
+pub fn get_code_lenses(curr_doc: &str, tree_entry: &mut TreeEntry, uri: &Uri) -> Vec<CodeLens> {
+    static QUERY_LABEL: Lazy<tree_sitter::Query> = Lazy::new(|| {
+        tree_sitter::Query::new(tree_sitter_asm::language(), "(label) @label").unwrap()
+    });
+
+    tree_entry.tree = tree_entry.parser.parse(curr_doc, tree_entry.tree.as_ref());
+    let Some(ref tree) = tree_entry.tree else {
+        return Vec::new();
+    };
+
+    let is_not_ident_char = |c: char| !(c.is_alphanumeric() || c == '_');
+    let mut cursor = tree_sitter::QueryCursor::new();
+    cursor
+        .matches(&QUERY_LABEL, tree.root_node(), curr_doc.as_bytes())
+        .flat_map(|match_| match_.captures)
+        .filter_map(|cap| {
+            let label = cap
+                .node
+                .utf8_text(curr_doc.as_bytes())
+                .ok()?
+                .trim()
+                .trim_matches(is_not_ident_char);
+            let data = CodeLensData {
+                uri: uri.clone(),
+                label: label.to_string(),
+            };
+            Some(CodeLens {
+                range: Range {
+                    start: lsp_pos_of_point(cap.node.start_position()),
+                    end: lsp_pos_of_point(cap.node.end_position()),
+                },
+                command: None,
+                data: serde_json::to_value(data).ok(),
+            })
+        })
+        .collect()
+}
+
+/// Returns the document a code lens from `get_code_lenses` was produced for
+#[must_use]
+pub fn code_lens_uri(lens: &CodeLens) -> Option<Uri> {
+    let data: CodeLensData = serde_json::from_value(lens.data.clone()?).ok()?;
+    Some(data.uri)
+}
+
+/// Fills in the command of a code lens from `get_code_lenses`, titled with the number of
+/// references to its label in `curr_doc`. Running the command shows those references,
+/// via the `editor.action.showReferences` command which clients commonly provide
+pub fn resolve_code_lens(
+    mut lens: CodeLens,
+    curr_doc: &str,
+    tree_entry: &mut TreeEntry,
+) -> CodeLens {
+    let Some(data) = lens
+        .data
+        .clone()
+        .and_then(|data| serde_json::from_value::<CodeLensData>(data).ok())
+    else {
+        return lens;
+    };
+    tree_entry.tree = tree_entry.parser.parse(curr_doc, tree_entry.tree.as_ref());
+    let Some(ref tree) = tree_entry.tree else {
+        return lens;
+    };
+
+    let mut refs = find_label_refs(curr_doc, tree, &data.label, &data.uri, false);
+    // the label's own name is an identifier too
+    refs.retain(|loc| loc.range.start != lens.range.start);
+    let title = match refs.len() {
+        1 => "1 reference".to_string(),
+        count => format!("{count} references"),
+    };
+    lens.command = Some(lsp_types::Command {
+        title,
+        command: "editor.action.showReferences".to_string(),
+        arguments: Some(vec![
+            serde_json::json!(data.uri),
+            serde_json::json!(lens.range.start),
+            serde_json::json!(refs),
+        ]),
+    });
+
+    lens
+}
+
+/// Mnemonics of the instructions that call a subroutine across the supported
+/// architectures, e.g. x86's `call`, ARM's `bl` and m68k/6502 style `jsr`
+const CALL_MNEMONICS: &[&str] = &["call", "callq", "calll", "callw", "bl", "blx", "jsr"];
+
+/// A label that starts a function, i.e. any label but local ones (`.L1`, `1`)
+#[derive(Debug, Clone)]
+struct FunctionLabel {
+    name: String,
+    range: Range,
+}
+
+/// A call to the symbol `callee`, made from the function at index `caller` of the
+/// document's function labels (`None` if the call precedes every label)
+#[derive(Debug, Clone)]
+struct FunctionCall {
+    caller: Option<usize>,
+    callee: String,
+    range: Range,
+}
+
+/// Returns the function labels in `curr_doc` along with the calls made between them.
+/// Every call belongs to the closest function label above it
+fn get_function_calls(
+    curr_doc: &str,
+    tree: &tree_sitter::Tree,
+) -> (Vec<FunctionLabel>, Vec<FunctionCall>) {
+    let mut labels: Vec<FunctionLabel> = Vec::new();
+    let mut calls: Vec<FunctionCall> = Vec::new();
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    for node in root.named_children(&mut cursor) {
+        match node.kind() {
+            "label" => {
+                let Ok(text) = node.utf8_text(curr_doc.as_bytes()) else {
+                    continue;
+                };
+                let name = text.trim().trim_end_matches(':');
+                let is_local = name.starts_with(".L") || name.chars().all(|c| c.is_ascii_digit());
+                if !name.is_empty() && !is_local {
+                    labels.push(FunctionLabel {
+                        name: name.to_string(),
+                        range: Range {
+                            start: lsp_pos_of_point(node.start_position()),
+                            end: lsp_pos_of_point(node.end_position()),
+                        },
+                    });
+                }
+            }
+            "instruction" => {
+                let is_call = node
+                    .child_by_field_name("kind")
+                    .and_then(|kind| kind.utf8_text(curr_doc.as_bytes()).ok())
+                    .is_some_and(|kind| CALL_MNEMONICS.contains(&kind.to_lowercase().as_str()));
+                if !is_call {
+                    continue;
+                }
+                let mut arg_cursor = node.walk();
+                let Some(target) = node
+                    .named_children(&mut arg_cursor)
+                    .find(|arg| arg.kind() == "ident")
+                else {
+                    continue;
+                };
+                let Ok(callee) = target.utf8_text(curr_doc.as_bytes()) else {
+                    continue;
+                };
+                calls.push(FunctionCall {
+                    caller: labels.len().checked_sub(1),
+                    callee: callee.trim().to_string(),
+                    range: Range {
+                        start: lsp_pos_of_point(target.start_position()),
+                        end: lsp_pos_of_point(target.end_position()),
+                    },
+                });
+            }
+            _ => {}
+        }
+    }
+
+    (labels, calls)
+}
+
+/// Builds the call hierarchy item for a function `label` of the document at `uri`
+fn call_hierarchy_item(label: &FunctionLabel, uri: &Uri) -> CallHierarchyItem {
+    CallHierarchyItem {
+        name: label.name.clone(),
+        kind: SymbolKind::FUNCTION,
+        tags: None,
+        detail: None,
+        uri: uri.clone(),
+        range: label.range,
+        selection_range: label.range,
+        data: None,
+    }
+}
+
+/// Returns the call hierarchy item for the function label under the cursor, which can
+/// either be the label's definition or a reference to it
+pub fn prepare_call_hierarchy(
+    params: &CallHierarchyPrepareParams,
+    curr_doc: &FullTextDocument,
+    tree_entry: &mut TreeEntry,
+) -> Option<Vec<CallHierarchyItem>> {
+    let doc = curr_doc.get_content(None);
+    tree_entry.tree = tree_entry.parser.parse(doc, tree_entry.tree.as_ref());
+    let word = get_word_from_pos_params(curr_doc, &params.text_document_position_params, "");
+    if word.is_empty() {
+        return None;
+    }
+
+    let (labels, _) = get_function_calls(doc, tree_entry.tree.as_ref()?);
+    let label = labels.iter().find(|label| label.name == word)?;
+    let uri = &params.text_document_position_params.text_document.uri;
+
+    Some(vec![call_hierarchy_item(label, uri)])
+}
+
+/// Returns the functions calling `item`, each with the ranges of its calls
+pub fn get_incoming_calls(
+    item: &CallHierarchyItem,
+    curr_doc: &str,
+    tree_entry: &mut TreeEntry,
+) -> Vec<CallHierarchyIncomingCall> {
+    tree_entry.tree = tree_entry.parser.parse(curr_doc, tree_entry.tree.as_ref());
+    let Some(tree) = tree_entry.tree.as_ref() else {
+        return Vec::new();
+    };
+
+    let (labels, calls) = get_function_calls(curr_doc, tree);
+    let mut incoming: Vec<(usize, Vec<Range>)> = Vec::new();
+    for call in calls.iter().filter(|call| call.callee == item.name) {
+        let Some(caller) = call.caller else {
+            continue;
+        };
+        match incoming.iter_mut().find(|(idx, _)| *idx == caller) {
+            Some((_, ranges)) => ranges.push(call.range),
+            None => incoming.push((caller, vec![call.range])),
+        }
+    }
+
+    incoming
+        .into_iter()
+        .map(|(caller, from_ranges)| CallHierarchyIncomingCall {
+            from: call_hierarchy_item(&labels[caller], &item.uri),
+            from_ranges,
+        })
+        .collect()
+}
+
+/// Returns the functions defined in the document that `item` calls, each with the
+/// ranges of its calls. Calls to symbols defined elsewhere are left out
+pub fn get_outgoing_calls(
+    item: &CallHierarchyItem,
+    curr_doc: &str,
+    tree_entry: &mut TreeEntry,
+) -> Vec<CallHierarchyOutgoingCall> {
+    tree_entry.tree = tree_entry.parser.parse(curr_doc, tree_entry.tree.as_ref());
+    let Some(tree) = tree_entry.tree.as_ref() else {
+        return Vec::new();
+    };
+
+    let (labels, calls) = get_function_calls(curr_doc, tree);
+    let Some(caller) = labels
+        .iter()
+        .position(|label| label.name == item.name && label.range == item.range)
+        .or_else(|| labels.iter().position(|label| label.name == item.name))
+    else {
+        return Vec::new();
+    };
+
+    let mut outgoing: Vec<(usize, Vec<Range>)> = Vec::new();
+    for call in calls.iter().filter(|call| call.caller == Some(caller)) {
+        let Some(callee) = labels.iter().position(|label| label.name == call.callee) else {
+            continue;
+        };
+        match outgoing.iter_mut().find(|(idx, _)| *idx == callee) {
+            Some((_, ranges)) => ranges.push(call.range),
+            None => outgoing.push((callee, vec![call.range])),
+        }
+    }
+
+    outgoing
+        .into_iter()
+        .map(|(callee, from_ranges)| CallHierarchyOutgoingCall {
+            to: call_hierarchy_item(&labels[callee], &item.uri),
+            from_ranges,
+        })
+        .collect()
+}
+
+/// Returns highlights for every occurrence of the word under the cursor in the
+/// current document
+///
+/// Registers are matched regardless of casing, all other symbols (e.g. labels)
+/// must match exactly. Label definitions and the destination operand of an
+/// instruction are reported as `DocumentHighlightKind::WRITE`, everything else
+/// as `DocumentHighlightKind::READ`
+pub fn get_document_highlights(
+    curr_doc: &FullTextDocument,
+    tree_entry: &mut TreeEntry,
+    params: &DocumentHighlightParams,
+    names_to_registers: &NameToRegisterMap,
+) -> Option<Vec<DocumentHighlight>> {
+    let doc = curr_doc.get_content(None);
+    tree_entry.tree = tree_entry.parser.parse(doc, tree_entry.tree.as_ref());
+
+    let word = get_word_from_pos_params(curr_doc, &params.text_document_position_params, "");
+    if word.is_empty() {
+        return None;
+    }
+    let is_reg = arch_providers().iter().any(|provider| {
+        names_to_registers.contains_key(&(provider.arch(), word))
+            || names_to_registers.contains_key(&(provider.arch(), word.to_lowercase().as_str()))
+    });
+
+    static IDENT_KIND_ID: Lazy<u16> =
+        Lazy::new(|| tree_sitter_asm::language().id_for_node_kind("ident", true));
+    static REG_KIND_ID: Lazy<u16> =
+        Lazy::new(|| tree_sitter_asm::language().id_for_node_kind("reg", true));
+    static LABEL_KIND_ID: Lazy<u16> =
+        Lazy::new(|| tree_sitter_asm::language().id_for_node_kind("label", true));
+    static INSTR_KIND_ID: Lazy<u16> =
+        Lazy::new(|| tree_sitter_asm::language().id_for_node_kind("instruction", true));
+    static PTR_KIND_ID: Lazy<u16> =
+        Lazy::new(|| tree_sitter_asm::language().id_for_node_kind("ptr", true));
+
+    /// Determines whether the symbol at `node` is being written to
+    fn highlight_kind(node: tree_sitter::Node, doc: &str) -> DocumentHighlightKind {
+        // walk up to the operand directly beneath the enclosing label/instruction
+        let mut operand = node;
+        while let Some(parent) = operand.parent() {
+            if parent.kind_id() == *LABEL_KIND_ID {
+                return DocumentHighlightKind::WRITE;
+            }
+            if parent.kind_id() == *INSTR_KIND_ID {
+                // registers inside of memory operands are only ever read
+                if operand.kind_id() == *PTR_KIND_ID {
+                    break;
+                }
+                let mut cursor = parent.walk();
+                let operands: Vec<tree_sitter::Node> = parent
+                    .named_children(&mut cursor)
+                    .filter(|child| parent.child_by_field_name("kind") != Some(*child))
+                    .collect();
+                if operands.len() < 2 {
+                    break;
+                }
+                // AT&T syntax places the destination last, Intel syntax places it first
+                let is_att = parent
+                    .utf8_text(doc.as_bytes())
+                    .is_ok_and(|instr| instr.contains('%'));
+                let dest = if is_att {
+                    operands.last()
+                } else {
+                    operands.first()
+                };
+                if dest == Some(&operand) {
+                    return DocumentHighlightKind::WRITE;
+                }
+                break;
+            }
+            operand = parent;
+        }
+
+        DocumentHighlightKind::READ
+    }
+
+    fn collect_highlights(
+        node: tree_sitter::Node,
+        doc: &str,
+        word: &str,
+        is_reg: bool,
+        res: &mut Vec<DocumentHighlight>,
+    ) {
+        let is_symbol = node.kind_id() == *IDENT_KIND_ID
+            || (node.kind_id() == *REG_KIND_ID
+                && node.parent().map(|parent| parent.kind_id()) != Some(*IDENT_KIND_ID));
+        if is_symbol {
+            let text = node.utf8_text(doc.as_bytes()).unwrap_or("").trim();
+            let name = text.trim_matches(|c: char| !(c.is_alphanumeric() || c == '_'));
+            let is_match = if is_reg {
+                name.eq_ignore_ascii_case(word)
+            } else {
+                name.eq(word)
+            };
+            if is_match {
+                res.push(DocumentHighlight {
+                    range: Range {
+                        start: lsp_pos_of_point(node.start_position()),
+                        end: lsp_pos_of_point(node.end_position()),
+                    },
+                    kind: Some(highlight_kind(node, doc)),
+                });
+            }
+            return;
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            collect_highlights(child, doc, word, is_reg, res);
+        }
+    }
+
+    if let Some(ref tree) = tree_entry.tree {
+        let mut highlights = Vec::new();
+        collect_highlights(tree.root_node(), doc, word, is_reg, &mut highlights);
+        if !highlights.is_empty() {
+            return Some(highlights);
+        }
+    }
+
+    None
+}
+
+/// Returns a summary of the register class of the register under the cursor, listing
+/// every register of its architecture sharing its `RegisterType` and width, e.g.
+/// "General Purpose Register, 32 bits (x86-64): eax, ebp, ebx, ..."
+///
+/// Serves `textDocument/typeDefinition`, whose responses can only hold locations, so
+/// the summary is shown to the user as a message rather than navigated to. If the user
+/// prefers an architecture that defines the register, only its class is listed
+#[must_use]
+pub fn get_type_definition_resp(
+    curr_doc: &FullTextDocument,
+    params: &TextDocumentPositionParams,
+    names_to_registers: &NameToRegisterMap,
+    config: &TargetConfig,
+) -> Option<String> {
+    let word = get_word_from_pos_params(curr_doc, params, "");
+    if word.is_empty() {
+        return None;
+    }
+    let lower = word.to_lowercase();
+    let regs: Vec<&Register> = arch_providers()
+        .iter()
+        .filter_map(|provider| {
+            names_to_registers
+                .get(&(provider.arch(), word))
+                .or_else(|| names_to_registers.get(&(provider.arch(), lower.as_str())))
+                .copied()
+        })
+        .filter(|reg| reg.reg_type.is_some())
+        .collect();
+    let regs = match config.opts.primary_arch {
+        Some(primary) if regs.iter().any(|reg| reg.arch == Some(primary)) => regs
+            .into_iter()
+            .filter(|reg| reg.arch == Some(primary))
+            .collect(),
+        _ => regs,
+    };
+
+    let classes: Vec<String> = regs
+        .iter()
+        .filter_map(|reg| {
+            let arch = reg.arch?;
+            let mut siblings: Vec<&str> = names_to_registers
+                .iter()
+                .filter(|((sibling_arch, _), sibling)| {
+                    *sibling_arch == arch
+                        && sibling.reg_type == reg.reg_type
+                        && sibling.width == reg.width
+                })
+                .map(|(_, sibling)| sibling.name.as_str())
+                .collect();
+            siblings.sort_unstable();
+            siblings.dedup();
+            let class = reg.reg_type.as_ref()?.to_string();
+            let class = match reg.width {
+                Some(width) => format!("{class}, {width}"),
+                None => class,
+            };
+            Some(format!("{class} ({arch}): {}", siblings.join(", ")))
+        })
+        .collect();
+
+    if classes.is_empty() {
+        None
+    } else {
+        Some(classes.join("\n"))
+    }
+}
+
+/// Hex literal written with a trailing `h`, as in Intel/z80 syntax, e.g. `0FFh`
+static SUFFIXED_HEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b[0-9][0-9a-fA-F]*[hH]\b").unwrap());
+
+/// Returns code actions reformatting the integer literal under the cursor between
+/// decimal and hex, e.g. `$16` <-> `$0x10` or `mov eax, 10h` <-> `mov eax, 16`
+///
+/// Only the digits are replaced, so immediate prefixes such as AT&T's `$` are kept. Hex
+/// is written with a trailing `h` if the document already writes hex that way, and
+/// with a `0x` prefix otherwise. Numbers in comments and at the start of a line (e.g.
+/// the local label `1:`) are left alone
+#[must_use]
+pub fn get_code_actions(
+    curr_doc: &FullTextDocument,
+    params: &CodeActionParams,
+    config: &TargetConfig,
+) -> Vec<CodeActionOrCommand> {
+    let pos = params.range.start;
+    let doc = curr_doc.get_content(None);
+    let Some(line) = doc.lines().nth(pos.line as usize) else {
+        return Vec::new();
+    };
+    let col = byte_col_of_utf16(line, pos.character);
+    let (start, end) = find_word_at_pos(line, col, "");
+    let literal = &line[start..end];
+    let Some(value) = parse_gas_int(literal) else {
+        return Vec::new();
+    };
+    let is_hex = SUFFIXED_HEX.is_match(literal) || literal.to_ascii_lowercase().starts_with("0x");
+
+    let before = &line[..start];
+    let before = immediate_prefixes(config)
+        .find_map(|prefix| before.strip_suffix(prefix))
+        .unwrap_or(before);
+    let is_comment = |code: &str| code.contains('#') || code.contains("//") || code.contains(';');
+    if before.trim().is_empty() || is_comment(before) {
+        return Vec::new();
+    }
+
+    let hex = if SUFFIXED_HEX
+        .find_iter(doc)
+        .any(|hex| hex.as_str() != literal)
+    {
+        let digits = format!("{value:X}");
+        if digits.starts_with(|c: char| c.is_ascii_digit()) {
+            format!("{digits}h")
+        } else {
+            format!("0{digits}h")
+        }
+    } else {
+        format!("{value:#x}")
+    };
+    let decimal = value.to_string();
+    let is_decimal = literal == decimal;
 
-    (x86_resp, x86_64_resp, z80_resp)
+    let range = Range {
+        start: Position {
+            line: pos.line,
+            character: line[..start].encode_utf16().count() as u32,
+        },
+        end: Position {
+            line: pos.line,
+            character: line[..end].encode_utf16().count() as u32,
+        },
+    };
+    vec![(!is_hex, "hex", hex), (!is_decimal, "decimal", decimal)]
+        .into_iter()
+        .filter(|(applies, _, _)| *applies)
+        .map(|(_, radix, new_text)| {
+            let edit = TextEdit {
+                range,
+                new_text: new_text.clone(),
+            };
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Convert `{literal}` to {radix} (`{new_text}`)"),
+                kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(
+                        params.text_document.uri.clone(),
+                        vec![edit],
+                    )])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        })
+        .collect()
 }
 
 fn search_for_hoverable_by_assembler<'a, T: Hoverable>(
@@ -1201,15 +5640,136 @@ fn search_for_hoverable_by_assembler<'a, T: Hoverable>(
     (gas_resp, go_resp)
 }
 
+/// Fills `index` with the items of `map` under their lowercased names. Call once `map` is
+/// populated, the prefix queries then only look up a range of the index
+pub fn populate_prefix_index<'a, K: Copy + Eq + Hash, T>(
+    map: &HashMap<(K, &'a str), &'a T>,
+    index: &mut PrefixIndex<'a, K, T>,
+) {
+    for ((key, name), item) in map {
+        let items = index
+            .entry(*key)
+            .or_default()
+            .entry(name.to_lowercase())
+            .or_default();
+        if !items.iter().any(|other| std::ptr::eq(*other, *item)) {
+            items.push(*item);
+        }
+    }
+}
+
+/// Returns every item of `index` registered under `key` with a name starting with
+/// `prefix` (ignoring case), sorted by name and without duplicates
+fn items_with_prefix<'a, K: Eq + Hash, T>(
+    index: &PrefixIndex<'a, K, T>,
+    key: K,
+    prefix: &str,
+    name_of: impl Fn(&T) -> &str,
+) -> Vec<&'a T> {
+    let Some(names) = index.get(&key) else {
+        return Vec::new();
+    };
+    let prefix = prefix.to_lowercase();
+    let mut items: Vec<&'a T> = names
+        .range(prefix.clone()..)
+        .take_while(|(name, _)| name.starts_with(&prefix))
+        .flat_map(|(_, items)| items.iter().copied())
+        .collect();
+
+    items.sort_by(|a, b| {
+        name_of(a)
+            .cmp(name_of(b))
+            .then_with(|| (*a as *const T).cmp(&(*b as *const T)))
+    });
+    items.dedup_by(|a, b| std::ptr::eq(*a, *b));
+
+    items
+}
+
+/// Returns the instructions for `arch` with a name (or alternate/assembler specific name)
+/// starting with `prefix`, ignoring case
+#[must_use]
+pub fn instructions_with_prefix<'a>(
+    index: &PrefixIndex<'a, Arch, Instruction>,
+    arch: Arch,
+    prefix: &str,
+) -> Vec<&'a Instruction> {
+    items_with_prefix(index, arch, prefix, |instr| &instr.name)
+}
+
+/// Returns the registers for `arch` with a name (or alternate name) starting with
+/// `prefix`, ignoring case
+#[must_use]
+pub fn registers_with_prefix<'a>(
+    index: &PrefixIndex<'a, Arch, Register>,
+    arch: Arch,
+    prefix: &str,
+) -> Vec<&'a Register> {
+    items_with_prefix(index, arch, prefix, |reg| &reg.name)
+}
+
+/// Returns the directives for `assembler` with a name (or alternate name) starting with
+/// `prefix`, ignoring case
+#[must_use]
+pub fn directives_with_prefix<'a>(
+    index: &PrefixIndex<'a, Assembler, Directive>,
+    assembler: Assembler,
+    prefix: &str,
+) -> Vec<&'a Directive> {
+    items_with_prefix(index, assembler, prefix, |dir| &dir.name)
+}
+
+/// Returns the instructions for `arch` whose summary contains `query`, ignoring case,
+/// e.g. "compare and exchange" finds `CMPXCHG`. Instructions named `query` are included
+/// even if their summary doesn't match, and are ranked ahead of the other results
+///
+/// Unlike the prefix queries, this is a linear scan over `map`
+#[must_use]
+pub fn search_instructions<'a>(
+    map: &NameToInstructionMap<'a>,
+    arch: Arch,
+    query: &str,
+) -> Vec<&'a Instruction> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let is_name_match = |instr: &Instruction| instr.name.to_lowercase() == query;
+    let mut instrs: Vec<&'a Instruction> = map
+        .iter()
+        .filter(|((instr_arch, _), instr)| {
+            *instr_arch == arch
+                && (is_name_match(instr) || instr.summary.to_lowercase().contains(&query))
+        })
+        .map(|(_, instr)| *instr)
+        .collect();
+
+    instrs.sort_by(|a, b| {
+        is_name_match(b)
+            .cmp(&is_name_match(a))
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| (*a as *const Instruction).cmp(&(*b as *const Instruction)))
+    });
+    instrs.dedup_by(|a, b| std::ptr::eq(*a, *b));
+
+    instrs
+}
+
 /// Searches for global config in ~/.config/asm-lsp, then the project's directory
 /// Project specific configs will override global configs
 #[must_use]
 pub fn get_target_config(params: &InitializeParams) -> TargetConfig {
-    match (get_global_config(), get_project_config(params)) {
+    let config = match (get_global_config(), get_project_config(params)) {
         (_, Some(proj_cfg)) => proj_cfg,
         (Some(global_cfg), None) => global_cfg,
         (None, None) => TargetConfig::default(), // default is to turn every non-z80 feature on
+    };
+    for warning in config.validate() {
+        warn!("{warning}");
     }
+
+    config
 }
 
 /// Checks ~/.config/asm-lsp for a config file, creating directories along the way as necessary
@@ -1255,10 +5815,9 @@ fn get_project_root(params: &InitializeParams) -> Option<PathBuf> {
     if let Some(folders) = &params.workspace_folders {
         // if there's multiple, just visit in order until we find a valid folder
         for folder in folders {
-            if let Ok(parsed) = PathBuf::from_str(folder.uri.path().as_str()) {
-                if let Ok(parsed_path) = parsed.canonicalize() {
-                    return Some(parsed_path);
-                }
+            let parsed = PathBuf::from(folder.uri.path().as_str());
+            if let Ok(parsed_path) = parsed.canonicalize() {
+                return Some(parsed_path);
             }
         }
     }
@@ -1266,10 +5825,9 @@ fn get_project_root(params: &InitializeParams) -> Option<PathBuf> {
     // if workspace folders weren't set or came up empty, we check the root_uri
     #[allow(deprecated)]
     if let Some(root_uri) = &params.root_uri {
-        if let Ok(parsed) = PathBuf::from_str(root_uri.path().as_str()) {
-            if let Ok(parsed_path) = parsed.canonicalize() {
-                return Some(parsed_path);
-            }
+        let parsed = PathBuf::from(root_uri.path().as_str());
+        if let Ok(parsed_path) = parsed.canonicalize() {
+            return Some(parsed_path);
         }
     }
 
@@ -1283,8 +5841,15 @@ fn get_project_config(params: &InitializeParams) -> Option<TargetConfig> {
         if let Ok(config) = std::fs::read_to_string(&path) {
             let path_s = path.display();
             match toml::from_str::<TargetConfig>(&config) {
-                Ok(config) => {
+                Ok(mut config) => {
                     info!("Parsing asm-lsp project config from file -> {path_s}\n");
+                    if let (Some(extra), Some(root)) =
+                        (config.opts.extra_directives.as_mut(), path.parent())
+                    {
+                        if extra.is_relative() {
+                            *extra = root.join(&*extra);
+                        }
+                    }
                     return Some(config);
                 }
                 Err(e) => {
@@ -1309,6 +5874,8 @@ pub fn instr_filter_targets(instr: &Instruction, config: &TargetConfig) -> Instr
                 || (form.go_name.is_some() && config.assemblers.go)
                 || (form.z80_name.is_some() && config.instruction_sets.z80)
         })
+        // drop forms requiring an ISA extension the target lacks
+        .filter(|form| form.isa.as_ref().is_none_or(|isa| config.isa_enabled(isa)))
         .map(|form| {
             let mut filtered = form.clone();
             // handle cases where gas and go both have names on the same form
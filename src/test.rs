@@ -1,222 +1,84 @@
 #[cfg(test)]
 mod tests {
     use core::panic;
-    use std::{collections::HashMap, str::FromStr};
+    use std::{
+        collections::{HashMap, HashSet},
+        str::FromStr,
+    };
 
     use anyhow::Result;
-    use lsp_textdocument::FullTextDocument;
+    use lsp_server::{Connection, Message, RequestId, Response};
+    use lsp_textdocument::{FullTextDocument, TextDocuments};
     use lsp_types::{
-        CompletionContext, CompletionItem, CompletionItemKind, CompletionParams,
-        CompletionTriggerKind, HoverContents, HoverParams, MarkupContent, MarkupKind,
-        PartialResultParams, Position, TextDocumentIdentifier, TextDocumentPositionParams, Uri,
-        WorkDoneProgressParams,
+        notification::{DidOpenTextDocument, Notification, Progress},
+        CallHierarchyPrepareParams, CodeActionContext, CodeActionOrCommand, CodeActionParams,
+        CompletionContext, CompletionItem, CompletionItemKind, CompletionList, CompletionParams,
+        CompletionResponse, CompletionTriggerKind, Diagnostic, DiagnosticSeverity,
+        DocumentHighlightKind, DocumentHighlightParams, Documentation, ExecuteCommandParams, Hover,
+        HoverContents, HoverParams, InsertTextFormat, MarkupContent, MarkupKind, NumberOrString,
+        PartialResultParams, Position, Range, SignatureHelpParams, TextDocumentIdentifier,
+        TextDocumentPositionParams, Uri, WorkDoneProgressParams,
     };
     use tree_sitter::Parser;
 
     use crate::{
-        get_comp_resp, get_completes, get_hover_resp, get_word_from_pos_params,
-        instr_filter_targets, populate_directives, populate_instructions,
-        populate_name_to_directive_map, populate_name_to_instruction_map,
-        populate_name_to_register_map, populate_registers, x86_parser::get_cache_dir, Arch,
-        Assembler, Assemblers, Directive, Instruction, InstructionSets, NameToDirectiveMap,
-        NameToInstructionMap, NameToRegisterMap, Register, TargetConfig, TreeEntry,
+        analyze_line, arch_providers, byte_col_of_utf16, data_hash, directives_with_prefix,
+        filter_suppressed_diagnostics, find_word_at_pos, get_active_section, get_code_actions,
+        get_code_lenses, get_comp_resp, get_completes, get_document_highlights, get_file_summary,
+        get_folding_ranges, get_hover_html, get_hover_resp_with_kind, get_included_files,
+        get_incoming_calls, get_long_mode_instr_diagnostics, get_outgoing_calls,
+        get_privileged_instr_diagnostics, get_selection_ranges, get_sig_help_resp,
+        get_suffix_size_mismatch_diagnostics, get_symbol_redefinition_diagnostics,
+        get_type_definition_resp, get_word_from_pos_params,
+        handle::{
+            handle_completion_request, handle_execute_command_request, update_included_files,
+            PendingDiagnostics, PendingFrequenciesSave, COMPLETION_FREQUENCIES_SAVE_DELAY,
+            LARGE_FILE_DIAGNOSTICS_DELAY,
+        },
+        instr_filter_targets, instructions_with_prefix, populate_directives, populate_instructions,
+        populate_registers, prepare_call_hierarchy, registers_with_prefix, resolve_code_lens,
+        search_instructions, split_operands,
+        test_utils::{init_global_info, init_store, test_config, GlobalInfo, GlobalVars},
+        x86_parser::get_cache_dir,
+        z80, Arch, Assembler, Assemblers, CompletionCase, CompletionFrequencies, CustomInstruction,
+        DemangleCache, DiagnosticCode, Directive, FileSummary, FormField, FormsLayout, HoverKind,
+        IncludeStore, IncludedFile, Instruction, InstructionForm, InstructionSets, LineAnalysis,
+        NameToInfoMaps, NameToInstructionMap, Operand, OperandType, Register, TargetConfig,
+        TreeEntry, TreeStore, ISA, MAX_COMPLETION_FREQUENCIES, RECORD_COMPLETION_COMMAND,
     };
 
-    #[derive(Debug)]
-    struct GlobalInfo {
-        x86_instructions: Vec<Instruction>,
-        x86_64_instructions: Vec<Instruction>,
-        x86_registers: Vec<Register>,
-        x86_64_registers: Vec<Register>,
-        z80_instructions: Vec<Instruction>,
-        z80_registers: Vec<Register>,
-        gas_directives: Vec<Directive>,
-    }
-
-    #[derive(Debug)]
-    struct GlobalVars<'a> {
-        names_to_instructions: NameToInstructionMap<'a>,
-        names_to_registers: NameToRegisterMap<'a>,
-        names_to_directives: NameToDirectiveMap<'a>,
-        instr_completion_items: Vec<CompletionItem>,
-        reg_completion_items: Vec<CompletionItem>,
-        directive_completion_items: Vec<CompletionItem>,
-    }
-
-    impl GlobalInfo {
-        fn new() -> Self {
-            Self {
-                x86_instructions: Vec::new(),
-                x86_64_instructions: Vec::new(),
-                x86_registers: Vec::new(),
-                x86_64_registers: Vec::new(),
-                z80_instructions: Vec::new(),
-                z80_registers: Vec::new(),
-                gas_directives: Vec::new(),
-            }
-        }
-    }
-
-    impl GlobalVars<'_> {
-        fn new() -> Self {
-            Self {
-                names_to_instructions: NameToInstructionMap::new(),
-                names_to_registers: NameToRegisterMap::new(),
-                names_to_directives: NameToDirectiveMap::new(),
-                instr_completion_items: Vec::new(),
-                reg_completion_items: Vec::new(),
-                directive_completion_items: Vec::new(),
-            }
-        }
-    }
-
-    fn init_global_info(config: Option<TargetConfig>) -> Result<GlobalInfo> {
-        let mut info = GlobalInfo::new();
-
-        let target_config = config.unwrap_or(TargetConfig {
-            version: "0.1".to_string(),
-            assemblers: Assemblers {
-                gas: true,
-                go: true,
-                z80: true,
-            },
-            instruction_sets: InstructionSets {
-                x86: true,
-                x86_64: true,
-                z80: true,
-            },
-        });
-
-        info.x86_instructions = {
-            let x86_instrs = include_bytes!("../docs_store/opcodes/serialized/x86");
-            bincode::deserialize::<Vec<Instruction>>(x86_instrs)?
-                .into_iter()
-                .map(|instruction| {
-                    // filter out assemblers by user config
-                    instr_filter_targets(&instruction, &target_config)
-                })
-                .filter(|instruction| !instruction.forms.is_empty())
-                .collect()
-        };
-
-        info.x86_64_instructions = {
-            let x86_64_instrs = include_bytes!("../docs_store/opcodes/serialized/x86_64");
-            bincode::deserialize::<Vec<Instruction>>(x86_64_instrs)?
-                .into_iter()
-                .map(|instruction| {
-                    // filter out assemblers by user config
-                    instr_filter_targets(&instruction, &target_config)
-                })
-                .filter(|instruction| !instruction.forms.is_empty())
-                .collect()
-        };
-
-        info.z80_instructions = {
-            let z80_instrs = include_bytes!("../docs_store/opcodes/serialized/z80");
-            bincode::deserialize::<Vec<Instruction>>(z80_instrs)?
-                .into_iter()
-                .map(|instruction| {
-                    // filter out assemblers by user config
-                    instr_filter_targets(&instruction, &target_config)
-                })
-                .filter(|instruction| !instruction.forms.is_empty())
-                .collect()
-        };
-
-        info.x86_registers = {
-            let regs_x86 = include_bytes!("../docs_store/registers/serialized/x86");
-            bincode::deserialize(regs_x86)?
-        };
-
-        info.x86_64_registers = {
-            let regs_x86_64 = include_bytes!("../docs_store/registers/serialized/x86_64");
-            bincode::deserialize(regs_x86_64)?
-        };
-
-        info.z80_registers = {
-            let regs_z80 = include_bytes!("../docs_store/registers/serialized/z80");
-            bincode::deserialize(regs_z80)?
-        };
-
-        info.gas_directives = {
-            let gas_dirs = include_bytes!("../docs_store/directives/serialized/gas");
-            bincode::deserialize(gas_dirs)?
-        };
-
-        return Ok(info);
-    }
-
-    fn init_test_store<'a>(info: &'a GlobalInfo) -> Result<GlobalVars<'a>> {
-        let mut store = GlobalVars::new();
-
+    fn init_test_store(info: &GlobalInfo) -> Result<GlobalVars<'_>> {
+        // start from a clean slate, without a cached copy of the x86 docs page
         let mut x86_cache_path = get_cache_dir().unwrap();
         x86_cache_path.push("x86_instr_docs.html");
         if x86_cache_path.is_file() {
             std::fs::remove_file(&x86_cache_path).unwrap();
         }
 
-        populate_name_to_instruction_map(
-            Arch::X86,
-            &info.x86_instructions,
-            &mut store.names_to_instructions,
-        );
-
-        populate_name_to_instruction_map(
-            Arch::X86_64,
-            &info.x86_64_instructions,
-            &mut store.names_to_instructions,
-        );
-
-        populate_name_to_instruction_map(
-            Arch::Z80,
-            &info.z80_instructions,
-            &mut store.names_to_instructions,
-        );
-
-        populate_name_to_register_map(
-            Arch::X86,
-            &info.x86_registers,
-            &mut store.names_to_registers,
-        );
-
-        populate_name_to_register_map(
-            Arch::X86_64,
-            &info.x86_64_registers,
-            &mut store.names_to_registers,
-        );
-
-        populate_name_to_register_map(
-            Arch::Z80,
-            &info.z80_registers,
-            &mut store.names_to_registers,
-        );
-
-        populate_name_to_directive_map(
-            Assembler::Gas,
-            &info.gas_directives,
-            &mut store.names_to_directives,
-        );
-
-        store.instr_completion_items = get_completes(
-            &store.names_to_instructions,
-            Some(CompletionItemKind::OPERATOR),
-        );
+        init_store(info)
+    }
 
-        store.reg_completion_items = get_completes(
-            &store.names_to_registers,
-            Some(CompletionItemKind::VARIABLE),
-        );
+    fn test_hover(source: &str, expected: &str) {
+        test_hover_with_config(source, expected, &test_config());
+    }
 
-        store.directive_completion_items = get_completes(
-            &store.names_to_directives,
-            Some(CompletionItemKind::OPERATOR),
-        );
+    fn test_hover_with_config(source: &str, expected: &str, config: &TargetConfig) {
+        let (_, resp) = get_test_hover_resp(source, config).unwrap();
 
-        return Ok(store);
+        if let HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: resp_text,
+        }) = resp.contents
+        {
+            let cleaned = resp_text.replace("\n\n\n", "\n\n"); // not sure what's going on here...
+            assert_eq!(expected, cleaned);
+        } else {
+            panic!("Invalid hover response contents: {:?}", resp.contents);
+        }
     }
 
-    fn test_hover(source: &str, expected: &str) {
-        let info = init_global_info(None).expect("Failed to load info");
+    fn get_test_hover_resp(source: &str, config: &TargetConfig) -> Option<(HoverKind, Hover)> {
+        let info = init_global_info(Some(config.clone())).expect("Failed to load info");
         let globals = init_test_store(&info).expect("Failed to initialize test store");
 
         let source_code = source.replace("<cursor>", "");
@@ -231,7 +93,8 @@ mod tests {
             if let Some((idx, _)) = line.match_indices("<cursor>").next() {
                 position = Some(Position {
                     line: line_num as u32,
-                    character: idx as u32,
+                    // LSP positions count UTF-16 code units
+                    character: line[..idx].encode_utf16().count() as u32,
                 });
                 break;
             }
@@ -251,10 +114,11 @@ mod tests {
             },
         };
 
-        let (word, file_word) = if let Some(ref doc) = curr_doc {
+        let (doc, word, file_word) = if let Some(ref doc) = curr_doc {
             (
-                // get the word under the cursor
-                get_word_from_pos_params(doc, &pos_params, ""),
+                doc,
+                // get the word under the cursor, including any relocation suffix (e.g. `@PLT`)
+                get_word_from_pos_params(doc, &pos_params, "@"),
                 // treat the word under the cursor as a filename and grab it as well
                 get_word_from_pos_params(doc, &pos_params, "."),
             )
@@ -262,42 +126,189 @@ mod tests {
             panic!("No document");
         };
 
-        let resp = get_hover_resp(
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        let tree = parser.parse(doc.get_content(None), None);
+        let mut tree_entry = TreeEntry { tree, parser };
+
+        get_hover_resp_with_kind(
             &hover_params,
-            &word,
-            &file_word,
+            word,
+            file_word,
+            doc,
+            Some(&mut tree_entry),
             &globals.names_to_instructions,
             &globals.names_to_registers,
             &globals.names_to_directives,
+            &globals.same_operations,
             &HashMap::new(),
+            config,
+            &[],
         )
-        .unwrap();
+    }
 
-        if let HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: resp_text,
-        }) = resp.contents
-        {
-            let cleaned = resp_text.replace("\n\n\n", "\n\n"); // not sure what's going on here...
-            assert_eq!(expected, cleaned);
-        } else {
-            panic!("Invalid hover response contents: {:?}", resp.contents);
+    /// Returns the markdown listing the forms in the signature help for the cursor
+    /// position in `source`, resolving instructions in `instr_map`
+    fn get_test_sig_help(source: &str, instr_map: &NameToInstructionMap) -> Option<String> {
+        let source_code = source.replace("<cursor>", "");
+        let (line, character) = source
+            .lines()
+            .enumerate()
+            .find_map(|(line_num, line)| {
+                line.find("<cursor>")
+                    .map(|idx| (line_num as u32, idx as u32))
+            })
+            .expect("No <cursor> marker found");
+        let params = SignatureHelpParams {
+            context: None,
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier {
+                    uri: Uri::from_str("file:///tmp/test.s").unwrap(),
+                },
+                position: Position { line, character },
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        let tree = parser.parse(&source_code, None);
+        let mut tree_entry = TreeEntry { tree, parser };
+
+        let resp = get_sig_help_resp(
+            &source_code,
+            &params,
+            &mut tree_entry,
+            instr_map,
+            &test_config(),
+        )?;
+        match resp.signatures.first()?.documentation.clone()? {
+            Documentation::MarkupContent(content) => Some(content.value),
+            Documentation::String(value) => Some(value),
         }
     }
 
-    fn test_autocomplete(
+    fn get_test_comp_resp(
         source: &str,
-        expected_kind: CompletionItemKind,
         trigger_kind: CompletionTriggerKind,
         trigger_character: Option<String>,
-    ) {
-        let info = init_global_info(None).expect("Failed to load info");
-        let globals = init_test_store(&info).expect("Failed to initialize test store");
+    ) -> CompletionList {
+        get_test_comp_resp_with_includes(source, trigger_kind, trigger_character, &[])
+    }
+
+    fn get_test_comp_resp_with_includes(
+        source: &str,
+        trigger_kind: CompletionTriggerKind,
+        trigger_character: Option<String>,
+        included: &[IncludedFile],
+    ) -> CompletionList {
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        get_test_comp_resp_with_parser(
+            source,
+            trigger_kind,
+            trigger_character,
+            included,
+            parser,
+            &test_config(),
+        )
+    }
 
+    /// Runs `handle_completion_request` on `source`, opened as a document, returning the
+    /// messages it sent
+    fn handle_test_comp_request(
+        globals: &GlobalVars,
+        source: &str,
+        partial_result_token: Option<NumberOrString>,
+        frequencies: &CompletionFrequencies,
+    ) -> Vec<Message> {
         let source_code = source.replace("<cursor>", "");
+        let uri = Uri::from_str("file:///completion.s").unwrap();
+        let mut text_store = TextDocuments::new();
+        text_store.listen(
+            DidOpenTextDocument::METHOD,
+            &serde_json::json!({
+                "textDocument": {
+                    "uri": uri,
+                    "languageId": "asm",
+                    "version": 1,
+                    "text": source_code,
+                }
+            }),
+        );
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        let tree = parser.parse(&source_code, None);
+        let mut tree_store = TreeStore::new();
+        tree_store.insert(uri.clone(), TreeEntry { tree, parser });
+
+        let (line, character) = source
+            .lines()
+            .enumerate()
+            .find_map(|(line_num, line)| Some((line_num, line.find("<cursor>")?)))
+            .expect("No <cursor> marker found");
+        let params = CompletionParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position {
+                    line: line as u32,
+                    character: character as u32,
+                },
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams {
+                partial_result_token,
+            },
+            context: Some(CompletionContext {
+                trigger_kind: CompletionTriggerKind::INVOKED,
+                trigger_character: None,
+            }),
+        };
+
+        let (server, client) = Connection::memory();
+        handle_completion_request(
+            &server,
+            RequestId::from(1),
+            &params,
+            &text_store,
+            &mut tree_store,
+            &globals.instr_completion_items,
+            &globals.directive_completion_items,
+            &globals.reg_completion_items,
+            &globals.names_to_instructions,
+            &globals.names_to_directives,
+            &IncludeStore::new(),
+            &test_config(),
+            frequencies,
+        )
+        .unwrap();
+        client.receiver.try_iter().collect()
+    }
 
+    fn get_test_comp_resp_with_config(
+        source: &str,
+        trigger_kind: CompletionTriggerKind,
+        trigger_character: Option<String>,
+        config: &TargetConfig,
+    ) -> CompletionList {
         let mut parser = Parser::new();
         parser.set_language(tree_sitter_asm::language()).unwrap();
+        get_test_comp_resp_with_parser(source, trigger_kind, trigger_character, &[], parser, config)
+    }
+
+    fn get_test_comp_resp_with_parser(
+        source: &str,
+        trigger_kind: CompletionTriggerKind,
+        trigger_character: Option<String>,
+        included: &[IncludedFile],
+        mut parser: Parser,
+        config: &TargetConfig,
+    ) -> CompletionList {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+
+        let source_code = source.replace("<cursor>", "");
+
         let tree = parser.parse(&source_code, None);
         let mut tree_entry = TreeEntry { tree, parser };
 
@@ -306,7 +317,8 @@ mod tests {
             if let Some((idx, _)) = line.match_indices("<cursor>").next() {
                 position = Some(Position {
                     line: line_num as u32,
-                    character: idx as u32,
+                    // LSP positions count UTF-16 code units
+                    character: line[..idx].encode_utf16().count() as u32,
                 });
                 break;
             }
@@ -335,15 +347,30 @@ mod tests {
             context: Some(comp_ctx),
         };
 
-        let resp = get_comp_resp(
+        match get_comp_resp(
             &source_code,
             &mut tree_entry,
             &params,
             &globals.instr_completion_items,
             &globals.directive_completion_items,
             &globals.reg_completion_items,
-        )
-        .unwrap();
+            &globals.names_to_instructions,
+            &globals.names_to_directives,
+            config,
+            included,
+        ) {
+            Some(CompletionResponse::List(list)) => list,
+            resp => panic!("Expected a completion list, got {:?}", resp),
+        }
+    }
+
+    fn test_autocomplete(
+        source: &str,
+        expected_kind: CompletionItemKind,
+        trigger_kind: CompletionTriggerKind,
+        trigger_character: Option<String>,
+    ) -> CompletionList {
+        let resp = get_test_comp_resp(source, trigger_kind, trigger_character);
 
         // - We currently have a very course-grained approach to completions,
         // - We just send all of the appropriate items (e.g. all instrucitons, all
@@ -353,46 +380,208 @@ mod tests {
         // but instead just that
         //      1) There are some items
         //      2) Said items are of the right type
-        // NOTE: Both instructions and directives use the OPERATOR complection type,
-        // so another means of verification should be added here
+        // Instructions use the OPERATOR completion type and directives use KEYWORD, so
+        // the two can be told apart
         assert!(!resp.items.is_empty());
         for comp in &resp.items {
             assert!(comp.kind == Some(expected_kind));
         }
+
+        resp
     }
 
     fn test_register_autocomplete(
         source: &str,
         trigger_kind: CompletionTriggerKind,
         trigger_character: Option<String>,
-    ) {
+    ) -> CompletionList {
         let expected_kind = CompletionItemKind::VARIABLE;
-        test_autocomplete(source, expected_kind, trigger_kind, trigger_character);
+        test_autocomplete(source, expected_kind, trigger_kind, trigger_character)
+    }
+
+    /// Checks that the register completions are ranked with the ones starting
+    /// with `expected_prefix` ahead of all others
+    fn test_ranked_register_autocomplete(
+        source: &str,
+        trigger_kind: CompletionTriggerKind,
+        trigger_character: Option<String>,
+        expected_prefix: &str,
+    ) {
+        let resp = test_register_autocomplete(source, trigger_kind, trigger_character);
+        let is_expected = |comp: &CompletionItem| {
+            let label = comp.label.to_lowercase();
+            label.starts_with(expected_prefix)
+                && label[expected_prefix.len()..]
+                    .chars()
+                    .all(|c| c.is_ascii_digit())
+        };
+        let num_expected = resp.items.iter().filter(|comp| is_expected(comp)).count();
+        assert!(num_expected > 0);
+        assert!(resp.items[..num_expected].iter().all(is_expected));
+        let mut sort_texts: Vec<_> = resp.items.iter().map(|c| c.sort_text.clone()).collect();
+        sort_texts.sort();
+        assert_eq!(
+            sort_texts
+                .first()
+                .cloned()
+                .flatten()
+                .unwrap()
+                .chars()
+                .next(),
+            Some('0')
+        );
+    }
+
+    /// Checks that each of `expected_labels` is among the completions, all of `expected_kind`
+    fn test_autocomplete_contains(
+        source: &str,
+        expected_kind: CompletionItemKind,
+        trigger_kind: CompletionTriggerKind,
+        trigger_character: Option<String>,
+        expected_labels: &[&str],
+    ) {
+        let resp = test_autocomplete(source, expected_kind, trigger_kind, trigger_character);
+        for label in expected_labels {
+            assert!(
+                resp.items.iter().any(|comp| comp.label == *label),
+                "Expected a completion for `{}`",
+                label
+            );
+        }
     }
 
     fn test_instruction_autocomplete(
         source: &str,
         trigger_kind: CompletionTriggerKind,
         trigger_character: Option<String>,
+        expected_labels: &[&str],
     ) {
         let expected_kind = CompletionItemKind::OPERATOR;
-        test_autocomplete(source, expected_kind, trigger_kind, trigger_character);
+        test_autocomplete_contains(
+            source,
+            expected_kind,
+            trigger_kind,
+            trigger_character,
+            expected_labels,
+        );
     }
 
     fn test_directive_autocomplete(
         source: &str,
         trigger_kind: CompletionTriggerKind,
         trigger_character: Option<String>,
+        expected_labels: &[&str],
     ) {
-        let expected_kind = CompletionItemKind::OPERATOR;
-        test_autocomplete(source, expected_kind, trigger_kind, trigger_character);
+        let expected_kind = CompletionItemKind::KEYWORD;
+        test_autocomplete_contains(
+            source,
+            expected_kind,
+            trigger_kind,
+            trigger_character,
+            expected_labels,
+        );
+    }
+
+    fn test_document_highlight(source: &str, expected: &[(u32, u32, u32, DocumentHighlightKind)]) {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+
+        let source_code = source.replace("<cursor>", "");
+        let curr_doc = FullTextDocument::new("asm".to_string(), 1, source_code.clone());
+
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        let tree = parser.parse(&source_code, None);
+        let mut tree_entry = TreeEntry { tree, parser };
+
+        let mut position: Option<Position> = None;
+        for (line_num, line) in source.lines().enumerate() {
+            if let Some((idx, _)) = line.match_indices("<cursor>").next() {
+                position = Some(Position {
+                    line: line_num as u32,
+                    // LSP positions count UTF-16 code units
+                    character: line[..idx].encode_utf16().count() as u32,
+                });
+                break;
+            }
+        }
+
+        let params = DocumentHighlightParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier {
+                    uri: Uri::from_str("file://").unwrap(),
+                },
+                position: position.expect("No <cursor> marker found"),
+            },
+            work_done_progress_params: WorkDoneProgressParams {
+                work_done_token: None,
+            },
+            partial_result_params: PartialResultParams {
+                partial_result_token: None,
+            },
+        };
+
+        let resp = get_document_highlights(
+            &curr_doc,
+            &mut tree_entry,
+            &params,
+            &globals.names_to_registers,
+        )
+        .unwrap();
+
+        let resp: Vec<(u32, u32, u32, DocumentHighlightKind)> = resp
+            .iter()
+            .map(|hl| {
+                (
+                    hl.range.start.line,
+                    hl.range.start.character,
+                    hl.range.end.character,
+                    hl.kind.unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(expected, resp.as_slice());
     }
 
     #[test]
     fn handle_autocomplete_x86_x86_64_it_provides_instr_comps_one_character_start() {
-        test_instruction_autocomplete("s<cursor>", CompletionTriggerKind::INVOKED, None);
+        test_instruction_autocomplete(
+            "s<cursor>",
+            CompletionTriggerKind::INVOKED,
+            None,
+            &["sub", "shl", "syscall"],
+        );
+    }
+    #[test]
+    fn handle_autocomplete_x86_x86_64_it_filters_large_comp_lists_by_typed_prefix() {
+        let resp = get_test_comp_resp("cvt<cursor>", CompletionTriggerKind::INVOKED, None);
+        assert!(resp.is_incomplete);
+        assert!(!resp.items.is_empty());
+        assert!(resp
+            .items
+            .iter()
+            .all(|comp| comp.label.to_lowercase().starts_with("cvt")));
+
+        // small lists are sent whole, leaving filtering to the client
+        let resp = get_test_comp_resp(
+            "pushq %<cursor>",
+            CompletionTriggerKind::TRIGGER_CHARACTER,
+            Some("%".to_string()),
+        );
+        assert!(!resp.is_incomplete);
     }
 
+    #[test]
+    fn handle_autocomplete_x86_64_it_provides_mask_reg_comps() {
+        let resp = test_register_autocomplete(
+            "	vaddps	%zmm1, %zmm2, %zmm3{%<cursor>",
+            CompletionTriggerKind::TRIGGER_CHARACTER,
+            Some("%".to_string()),
+        );
+        for mask_reg in ["k0", "k7"] {
+            assert!(resp.items.iter().any(|comp| comp.label == mask_reg));
+        }
+    }
     #[test]
     fn handle_autocomplete_x86_x86_64_it_provides_reg_comps_after_percent_symbol() {
         test_register_autocomplete(
@@ -402,21 +591,477 @@ mod tests {
         );
     }
     #[test]
-    fn handle_autocomplete_x86_x86_64_it_provides_reg_comps_in_existing_reg_arg_1() {
-        test_register_autocomplete("pushq %rb<cursor>", CompletionTriggerKind::INVOKED, None);
+    fn handle_autocomplete_x86_x86_64_it_ranks_reg_comps_by_operand_class_att() {
+        test_ranked_register_autocomplete(
+            "addps %xmm1, %<cursor>",
+            CompletionTriggerKind::TRIGGER_CHARACTER,
+            Some("%".to_string()),
+            "xmm",
+        );
     }
     #[test]
-    fn handle_autocomplete_x86_x86_64_it_provides_reg_comps_in_existing_reg_arg_2() {
-        test_register_autocomplete(
-            "	movq	%rs<cursor>, %rbp",
+    fn handle_autocomplete_x86_x86_64_it_ranks_reg_comps_by_operand_class_intel() {
+        test_ranked_register_autocomplete(
+            "addps <cursor>",
             CompletionTriggerKind::INVOKED,
             None,
+            "xmm",
         );
     }
     #[test]
-    fn handle_autocomplete_x86_x86_64_it_provides_reg_comps_in_existing_reg_arg_3() {
-        test_register_autocomplete(
-            "	movq	%rsp, %rb<cursor>",
+    fn handle_autocomplete_it_falls_back_without_a_tree() {
+        // a parser without a language never produces a tree
+        let comp_labels = |source: &str| -> Vec<String> {
+            let resp = get_test_comp_resp_with_parser(
+                source,
+                CompletionTriggerKind::INVOKED,
+                None,
+                &[],
+                Parser::new(),
+                &test_config(),
+            );
+            resp.items.into_iter().map(|comp| comp.label).collect()
+        };
+
+        let instrs = comp_labels("main:	mov<cursor>");
+        assert!(instrs.iter().any(|label| label == "movq"));
+        let regs = comp_labels("	movq	%rax, %r<cursor>");
+        assert!(regs.iter().any(|label| label == "rbx"));
+        assert!(!regs.iter().any(|label| label == "movq"));
+        let dirs = comp_labels("	.gl<cursor>");
+        assert!(dirs.iter().any(|label| label == "globl"));
+    }
+    #[test]
+    fn handle_autocomplete_x86_64_it_completes_condition_codes() {
+        let resp = get_test_comp_resp("	setn<cursor>", CompletionTriggerKind::INVOKED, None);
+        let setne = resp
+            .items
+            .iter()
+            .find(|comp| comp.label == "setne")
+            .unwrap();
+        assert_eq!(setne.detail.as_deref(), Some("Not equal (ZF=0)"));
+        assert!(setne.documentation.is_some());
+        // only the variants of the typed prefix are described and ranked first
+        let ranked: Vec<&str> = resp
+            .items
+            .iter()
+            .filter(|comp| comp.sort_text.is_some())
+            .map(|comp| comp.label.as_str())
+            .collect();
+        assert!(ranked.iter().all(|label| label.starts_with("setn")));
+        assert!(ranked.contains(&"setnle"));
+
+        // other instructions are still offered
+        let resp = get_test_comp_resp("	j<cursor>", CompletionTriggerKind::INVOKED, None);
+        let je = resp.items.iter().find(|comp| comp.label == "je").unwrap();
+        assert_eq!(je.detail.as_deref(), Some("Equal (ZF=1)"));
+        assert!(resp.items.iter().any(|comp| comp.label == "jmp"));
+    }
+    #[test]
+    fn handle_autocomplete_it_skips_files_over_the_line_limit() {
+        let mut config = test_config();
+        config.opts.max_file_lines_for_completion = Some(2);
+        let small = get_test_comp_resp_with_config(
+            "movq %r<cursor>",
+            CompletionTriggerKind::INVOKED,
+            None,
+            &config,
+        );
+        assert!(!small.items.is_empty());
+
+        let large = get_test_comp_resp_with_config(
+            "nop\nnop\nmovq %r<cursor>",
+            CompletionTriggerKind::INVOKED,
+            None,
+            &config,
+        );
+        assert!(large.items.is_empty());
+        assert!(large.is_incomplete);
+
+        let mut pending = PendingDiagnostics::default();
+        let uri = Uri::from_str("file:///large.s").unwrap();
+        let now = std::time::Instant::now();
+        pending.schedule(uri.clone(), now);
+        assert!(pending.take_due(now).is_empty());
+        // a later save puts the diagnostics off again
+        pending.schedule(uri.clone(), now + LARGE_FILE_DIAGNOSTICS_DELAY);
+        let deadline = pending.next_deadline().unwrap();
+        assert_eq!(deadline, now + LARGE_FILE_DIAGNOSTICS_DELAY * 2);
+        assert_eq!(pending.take_due(deadline), vec![uri]);
+        assert_eq!(pending.next_deadline(), None);
+    }
+    #[test]
+    fn handle_autocomplete_x86_64_it_offers_frame_snippets() {
+        let mut config = test_config();
+        config.opts.frame_snippets = true;
+        config.opts.primary_arch = Some(Arch::X86_64);
+        let snippets = |source: &str, config: &TargetConfig| -> Vec<CompletionItem> {
+            get_test_comp_resp_with_config(source, CompletionTriggerKind::INVOKED, None, config)
+                .items
+                .into_iter()
+                .filter(|comp| comp.kind == Some(CompletionItemKind::SNIPPET))
+                .collect()
+        };
+
+        let prologue = snippets("main:\n	pro<cursor>", &config);
+        assert_eq!(prologue.len(), 1);
+        assert_eq!(prologue[0].label, "prologue");
+        assert_eq!(
+            prologue[0].insert_text.as_deref(),
+            Some("pushq\t%rbp\nmovq\t%rsp, %rbp\nsubq\t\\$${1:16}, %rsp\n$0")
+        );
+        assert_eq!(
+            prologue[0].insert_text_format,
+            Some(InsertTextFormat::SNIPPET)
+        );
+
+        let epilogue = snippets("main:\n	pushq	%rbp\n	movq	%rbp, %rsp\n	<cursor>", &config);
+        assert_eq!(epilogue.len(), 1);
+        assert_eq!(epilogue[0].insert_text.as_deref(), Some("leave\nret"));
+
+        // nothing after directives, or once disabled
+        assert!(snippets("	.text\n	mo<cursor>", &config).is_empty());
+        config.opts.frame_snippets = false;
+        assert!(snippets("main:\n	pro<cursor>", &config).is_empty());
+    }
+    #[test]
+    fn handle_autocomplete_x86_64_it_completes_intel_memory_operand_regs() {
+        let comp_labels = |source: &str| -> Vec<String> {
+            let resp = get_test_comp_resp(source, CompletionTriggerKind::INVOKED, None);
+            resp.items.into_iter().map(|comp| comp.label).collect()
+        };
+
+        let mut labels: Vec<String> = comp_labels("	mov	rax, [rb<cursor>")
+            .iter()
+            .map(|label| label.to_lowercase())
+            .collect();
+        labels.sort();
+        labels.dedup();
+        assert_eq!(labels, ["rbp", "rbx"]);
+
+        // index registers after the base, and within closed brackets
+        let labels = comp_labels("	mov	rax, [rbp + rc<cursor>*8]");
+        assert!(labels
+            .iter()
+            .all(|label| label.to_lowercase().starts_with("rc")));
+        assert!(labels.iter().any(|label| label == "rcx"));
+
+        // general purpose registers are offered first for an empty operand
+        let resp = get_test_comp_resp("	lea	rax, [<cursor>", CompletionTriggerKind::INVOKED, None);
+        let rax = resp.items.iter().find(|comp| comp.label == "rax").unwrap();
+        assert_eq!(rax.sort_text.as_deref(), Some("0rax"));
+        assert!(resp.items[0]
+            .sort_text
+            .as_deref()
+            .is_some_and(|sort_text| sort_text.starts_with('0')));
+    }
+    #[test]
+    fn handle_autocomplete_gas_it_completes_symbol_directive_args_with_labels() {
+        for directive in [".globl", ".global", ".extern", ".type", ".size"] {
+            let resp = get_test_comp_resp(
+                &format!(
+                    "	{directive}	<cursor>
+main:
+	call	helper
+	ret
+helper:
+	ret"
+                ),
+                CompletionTriggerKind::INVOKED,
+                None,
+            );
+            let labels: Vec<&str> = resp.items.iter().map(|comp| comp.label.as_str()).collect();
+            assert_eq!(labels, ["main", "helper"], "{}", directive);
+        }
+    }
+    #[test]
+    fn handle_autocomplete_x86_x86_64_it_prioritizes_label_comps_for_jump_targets() {
+        let resp = get_test_comp_resp(
+            "loop_start:
+	decq	%rcx
+	jne	<cursor>
+done:
+	ret",
+            CompletionTriggerKind::INVOKED,
+            None,
+        );
+        let mut items = resp.items.clone();
+        items.sort_by(|a, b| a.sort_text.cmp(&b.sort_text));
+        let labels: Vec<(&str, Option<&str>)> = items
+            .iter()
+            .take_while(|comp| comp.kind == Some(CompletionItemKind::FUNCTION))
+            .map(|comp| (comp.label.as_str(), comp.detail.as_deref()))
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                ("done", Some("label on line 4")),
+                ("loop_start", Some("label on line 1"))
+            ]
+        );
+        // registers are still offered, after the labels
+        assert!(items[labels.len()..]
+            .iter()
+            .all(|comp| comp.kind == Some(CompletionItemKind::VARIABLE)));
+        assert!(items.len() > labels.len());
+    }
+    #[test]
+    fn handle_autocomplete_gas_it_provides_macro_param_comps() {
+        let resp = test_autocomplete(
+            ".macro push_two a, b=1, c:req
+	pushq	\\<cursor>
+.endm",
+            CompletionItemKind::VARIABLE,
+            CompletionTriggerKind::TRIGGER_CHARACTER,
+            Some("\\".to_string()),
+        );
+        let labels: Vec<&str> = resp.items.iter().map(|comp| comp.label.as_str()).collect();
+        assert_eq!(labels, ["a", "b", "c"]);
+    }
+    #[test]
+    fn handle_autocomplete_it_recovers_trigger_characters_for_incomplete_comps() {
+        let retrigger = CompletionTriggerKind::TRIGGER_FOR_INCOMPLETE_COMPLETIONS;
+
+        let resp = test_autocomplete(
+            ".macro push_two a, b=1, c:req
+	pushq	\\b<cursor>
+.endm",
+            CompletionItemKind::VARIABLE,
+            retrigger,
+            None,
+        );
+        let labels: Vec<&str> = resp.items.iter().map(|comp| comp.label.as_str()).collect();
+        assert_eq!(labels, ["a", "b", "c"]);
+
+        let resp = test_autocomplete(
+            "loop:	b.n<cursor>",
+            CompletionItemKind::ENUM_MEMBER,
+            retrigger,
+            None,
+        );
+        assert_eq!(resp.items.len(), 17);
+
+        test_register_autocomplete("pushq %rb<cursor>", retrigger, None);
+        test_directive_autocomplete("	.fi<cursor>", retrigger, None, &["file", "fill"]);
+    }
+    #[test]
+    fn handle_autocomplete_gas_it_provides_irp_var_comps() {
+        let resp = test_autocomplete(
+            ".macro push_all regs:vararg
+	.irp	reg, \\regs
+	pushq	\\<cursor>
+	.endr
+.endm",
+            CompletionItemKind::VARIABLE,
+            CompletionTriggerKind::TRIGGER_CHARACTER,
+            Some("\\".to_string()),
+        );
+        let items: Vec<(&str, Option<&str>)> = resp
+            .items
+            .iter()
+            .map(|comp| (comp.label.as_str(), comp.detail.as_deref()))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                ("regs", Some("macro parameter")),
+                ("reg", Some("loop variable"))
+            ]
+        );
+    }
+    #[test]
+    fn handle_autocomplete_gas_it_provides_constant_comps_for_immediates() {
+        let source = "	.equ	BUF_SIZE, 0x100
+	.set	FLAGS, 1
+	.set	FLAGS, 3 # redefined
+	movq	$<cursor>, %rax";
+        let resp = test_autocomplete(
+            source,
+            CompletionItemKind::CONSTANT,
+            CompletionTriggerKind::TRIGGER_CHARACTER,
+            Some("$".to_string()),
+        );
+        let items: Vec<(&str, Option<&str>)> = resp
+            .items
+            .iter()
+            .map(|comp| (comp.label.as_str(), comp.detail.as_deref()))
+            .collect();
+        assert_eq!(items, [("BUF_SIZE", Some("0x100")), ("FLAGS", Some("3"))]);
+
+        // no registers or instructions while typing out an immediate either
+        let resp = test_autocomplete(
+            &source.replace("$<cursor>", "$BU<cursor>"),
+            CompletionItemKind::CONSTANT,
+            CompletionTriggerKind::INVOKED,
+            None,
+        );
+        assert_eq!(resp.items.len(), 2);
+    }
+    #[test]
+    fn handle_autocomplete_it_streams_partial_results() -> Result<()> {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+        let complete = |partial_result_token| {
+            handle_test_comp_request(
+                &globals,
+                "	v<cursor>",
+                partial_result_token,
+                &CompletionFrequencies::default(),
+            )
+        };
+
+        // without a token, every item is in the response
+        let messages = complete(None);
+        let [Message::Response(resp)] = messages.as_slice() else {
+            panic!("Expected a single response, got {:?}", messages);
+        };
+        let full: CompletionList = serde_json::from_value(resp.result.clone().unwrap())?;
+        assert!(full.is_incomplete);
+        assert!(full.items.len() > 200);
+
+        // with one, the items are sent as `$/progress` chunks of 200, followed by an empty
+        // response which still tells the client to ask again as the word grows
+        let token = NumberOrString::String(String::from("partial"));
+        let mut messages = complete(Some(token.clone()));
+        let Some(Message::Response(resp)) = messages.pop() else {
+            panic!("Expected the response last, got {:?}", messages);
+        };
+        let last: CompletionList = serde_json::from_value(resp.result.unwrap())?;
+        assert!(last.is_incomplete);
+        assert!(last.items.is_empty());
+
+        let mut streamed: Vec<CompletionItem> = Vec::new();
+        for (idx, message) in messages.iter().enumerate() {
+            let Message::Notification(notif) = message else {
+                panic!("Expected a progress notification, got {:?}", message);
+            };
+            assert_eq!(notif.method, Progress::METHOD);
+            assert_eq!(notif.params["token"], serde_json::to_value(&token)?);
+            let chunk: Vec<CompletionItem> = serde_json::from_value(notif.params["value"].clone())?;
+            if idx + 1 < messages.len() {
+                assert_eq!(chunk.len(), 200);
+            }
+            streamed.extend(chunk);
+        }
+        assert_eq!(messages.len(), full.items.len().div_ceil(200));
+        assert_eq!(streamed, full.items);
+
+        Ok(())
+    }
+    #[test]
+    fn handle_autocomplete_gas_it_provides_constant_comps_from_included_files() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("asm-lsp-include-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let source = "	.include \"defs.inc\"
+	movq	$<cursor>, %rax";
+        std::fs::write(dir.join("main.s"), source.replace("<cursor>", ""))?;
+        // `regs.inc` includes `defs.inc` back, and a file that doesn't exist
+        std::fs::write(
+            dir.join("defs.inc"),
+            "	.include \"regs.inc\"\n	.equ	BUF_SIZE, 0x100\n",
+        )?;
+        std::fs::write(
+            dir.join("regs.inc"),
+            "	.include \"defs.inc\"\n	.include \"missing.inc\"\n	.set	STDOUT, 1\n",
+        )?;
+
+        let uri = Uri::from_str(&format!("file://{}", dir.join("main.s").display()))?;
+        let included = get_included_files(
+            &uri,
+            &source.replace("<cursor>", ""),
+            &TextDocuments::new(),
+            &HashMap::new(),
+        );
+        let included_names: Vec<_> = included
+            .iter()
+            .filter_map(|file| file.path.file_name())
+            .collect();
+        assert_eq!(included_names, ["defs.inc", "regs.inc"]);
+
+        let resp = get_test_comp_resp_with_includes(
+            source,
+            CompletionTriggerKind::TRIGGER_CHARACTER,
+            Some("$".to_string()),
+            &included,
+        );
+        let items: Vec<(&str, Option<&str>)> = resp
+            .items
+            .iter()
+            .map(|comp| {
+                let file_name = comp
+                    .label_details
+                    .as_ref()
+                    .and_then(|d| d.description.as_deref());
+                (comp.label.as_str(), file_name)
+            })
+            .collect();
+        assert_eq!(
+            items,
+            [("BUF_SIZE", Some("defs.inc")), ("STDOUT", Some("regs.inc"))]
+        );
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+    #[test]
+    fn it_updates_included_files_of_open_documents() -> Result<()> {
+        let dir =
+            std::env::temp_dir().join(format!("asm-lsp-include-store-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("defs.inc"), "	.equ	BUF_SIZE, 0x100\n")?;
+        let open = |text_store: &mut TextDocuments, name: &str, text: &str| -> Result<Uri> {
+            let uri = Uri::from_str(&format!("file://{}", dir.join(name).display()))?;
+            text_store.listen(
+                DidOpenTextDocument::METHOD,
+                &serde_json::json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": "asm",
+                        "version": 1,
+                        "text": text,
+                    }
+                }),
+            );
+            Ok(uri)
+        };
+        let contents = |include_store: &IncludeStore, uri: &Uri| -> Vec<String> {
+            include_store[uri]
+                .iter()
+                .map(|file| file.contents.clone())
+                .collect()
+        };
+
+        let mut text_store = TextDocuments::new();
+        let mut include_store = IncludeStore::new();
+        let main = open(&mut text_store, "main.s", "	.include \"defs.inc\"\n")?;
+        update_included_files(&main, &text_store, &mut include_store, &HashMap::new());
+        assert_eq!(contents(&include_store, &main), ["	.equ	BUF_SIZE, 0x100\n"]);
+
+        // edits to an open included file reach the documents including it
+        let defs = open(&mut text_store, "defs.inc", "	.equ	BUF_SIZE, 0x200\n")?;
+        update_included_files(&defs, &text_store, &mut include_store, &HashMap::new());
+        assert_eq!(contents(&include_store, &main), ["	.equ	BUF_SIZE, 0x200\n"]);
+        assert!(include_store[&defs].is_empty());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+    #[test]
+    fn handle_autocomplete_x86_x86_64_it_provides_reg_comps_in_existing_reg_arg_1() {
+        test_register_autocomplete("pushq %rb<cursor>", CompletionTriggerKind::INVOKED, None);
+    }
+    #[test]
+    fn handle_autocomplete_x86_x86_64_it_provides_reg_comps_in_existing_reg_arg_2() {
+        test_register_autocomplete(
+            "	movq	%rs<cursor>, %rbp",
+            CompletionTriggerKind::INVOKED,
+            None,
+        );
+    }
+    #[test]
+    fn handle_autocomplete_x86_x86_64_it_provides_reg_comps_in_existing_reg_arg_3() {
+        test_register_autocomplete(
+            "	movq	%rsp, %rb<cursor>",
             CompletionTriggerKind::INVOKED,
             None,
         );
@@ -611,147 +1256,2371 @@ Move Quadword
   + [mm]     input = true   output = false
 - *GAS*: movq | *GO*: MOVQ | *XMM*: SSE | *ISA*: SSE2
 
-  + [m64]    input = false  output = true
-  + [xmm]    input = true   output = false
+  + [m64]    input = false  output = true
+  + [xmm]    input = true   output = false
+
+More info: https://www.felixcloutier.com/x86/movq",
+        );
+    }
+
+    #[test]
+    fn handle_hover_x86_x86_64_it_provides_reg_info_normal() {
+        test_hover(
+            "	pushq	%r<cursor>bp",
+            "RBP [x86]
+Stack Base Pointer
+
+Type: General Purpose Register
+Width: 64 bits
+
+RBP [x86-64]
+Base Pointer (meant for stack frames)
+
+Type: General Purpose Register
+Width: 64 bits",
+        );
+    }
+    #[test]
+    fn handle_hover_x86_x86_64_it_provides_reg_info_sub_register() {
+        let mut config = test_config();
+        config.opts.primary_arch = Some(Arch::X86_64);
+        test_hover_with_config(
+            "	movb	$1, %a<cursor>l",
+            "AL [x86-64]
+Accumulator
+
+Type: General Purpose Register
+Width: 8 lower bits
+Position: bits 0–7 of AX/EAX/RAX
+
+*(also in x86)*",
+            &config,
+        );
+    }
+    #[test]
+    fn handle_hover_x86_64_it_provides_mask_reg_info_in_decorations() {
+        test_hover(
+            "	vaddps	%zmm1, %zmm2, %zmm3{%k<cursor>1}{z}",
+            "K1 [x86-64]
+An AVX-512 opmask register. Used as a write mask (e.g. `{k1}`), each bit selects whether the corresponding element of the destination is written.
+
+Type: Mask Register
+Width: 64 bits",
+        );
+    }
+    #[test]
+    fn handle_hover_x86_x86_64_it_provides_reg_info_primary_arch() {
+        let mut config = test_config();
+        config.opts.primary_arch = Some(Arch::X86_64);
+        test_hover_with_config(
+            "	pushq	%r<cursor>bp",
+            "RBP [x86-64]
+Base Pointer (meant for stack frames)
+
+Type: General Purpose Register
+Width: 64 bits
+
+*(also in x86)*",
+            &config,
+        );
+    }
+    #[test]
+    fn handle_hover_it_uses_compact_forms_for_narrow_hover_width() {
+        let mut config = test_config();
+        config.opts.primary_arch = Some(Arch::X86_64);
+        config.opts.hover_width = Some(30);
+        test_hover_with_config(
+            "	bsw<cursor>ap	%eax",
+            "BSWAP [x86-64]
+Byte Swap
+
+## Forms
+
+- *GAS*: bswap | r32 rw
+
+- *GAS*: bswap | r64 rw
+
+
+More info: https://www.felixcloutier.com/x86/bswap
+
+*(also in x86)*",
+            &config,
+        );
+    }
+    #[test]
+    fn it_renders_items_to_markdown_as_in_hover() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+
+        let bswap = globals
+            .names_to_instructions
+            .get(&(Arch::X86_64, "bswap"))
+            .unwrap();
+        let markdown = bswap.to_markdown(Arch::X86_64, Some(30), None, FormsLayout::Verbose, false);
+        assert_eq!(markdown, format!("{bswap:30}"));
+        assert!(markdown.starts_with("BSWAP [x86-64]\nByte Swap"));
+        for form in &bswap.forms {
+            assert!(markdown.contains(&form.to_markdown(Some(30), None, false)));
+        }
+        assert!(bswap.forms[0]
+            .to_markdown(Some(30), None, false)
+            .contains("*GAS*: bswap | r32 rw"));
+
+        // rendering under another architecture only changes the header
+        let as_x86 = bswap.to_markdown(Arch::X86, Some(30), None, FormsLayout::Verbose, false);
+        assert_eq!(as_x86.replacen("[x86]", "[x86-64]", 1), markdown);
+
+        let rax = globals
+            .names_to_registers
+            .get(&(Arch::X86_64, "rax"))
+            .unwrap();
+        assert_eq!(rax.to_markdown(Arch::X86_64), format!("{rax}"));
+    }
+    #[test]
+    fn it_renders_implicit_operands() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+
+        let mul = globals
+            .names_to_instructions
+            .get(&(Arch::X86_64, "mul"))
+            .unwrap();
+        let mulq = mul
+            .forms
+            .iter()
+            .find(|form| form.gas_name.as_deref() == Some("mulq"))
+            .unwrap();
+        assert!(mulq
+            .to_markdown(None, None, false)
+            .contains("\n  + Implicit: reads/writes RAX, writes RDX"));
+
+        // forms without implicit operands are rendered as before
+        let bswap = globals
+            .names_to_instructions
+            .get(&(Arch::X86_64, "bswap"))
+            .unwrap();
+        assert!(bswap.forms[0].implicit_operands.is_empty());
+        assert!(!bswap.forms[0]
+            .to_markdown(None, None, false)
+            .contains("Implicit"));
+    }
+    #[test]
+    fn it_renders_the_generation_forms_were_introduced_in() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+
+        let movbe = globals
+            .names_to_instructions
+            .get(&(Arch::X86_64, "movbe"))
+            .unwrap();
+        assert!(movbe
+            .forms
+            .iter()
+            .all(|form| form.introduced_in.as_deref() == Some("Haswell")));
+        assert!(movbe
+            .to_markdown(Arch::X86_64, None, None, FormsLayout::Verbose, false)
+            .contains("\n  + Introduced: Haswell"));
+
+        // forms without a known generation are rendered as before
+        let bswap = globals
+            .names_to_instructions
+            .get(&(Arch::X86_64, "bswap"))
+            .unwrap();
+        assert!(!bswap.forms[0]
+            .to_markdown(None, None, false)
+            .contains("Introduced"));
+    }
+    #[test]
+    fn it_parses_and_renders_instruction_notes() {
+        let xml = r#"<InstructionSet name="x86-64">
+  <Instruction name="CPUID" summary="CPU Identification">
+    <InstructionForm gas-name="cpuid" go-name="CPUID">
+      <ISA id="CPUID"/>
+    </InstructionForm>
+    <Note>Serializing
+      instruction</Note>
+    <Note>Faults in VMX non-root operation</Note>
+  </Instruction>
+</InstructionSet>"#;
+        let instrs = populate_instructions(xml).unwrap();
+        assert_eq!(
+            instrs[0].notes,
+            vec![
+                "Serializing instruction",
+                "Faults in VMX non-root operation"
+            ]
+        );
+        assert!(instrs[0]
+            .to_markdown(Arch::X86_64, None, None, FormsLayout::Verbose, false)
+            .contains("## Notes\n\n- Serializing instruction\n- Faults in VMX non-root operation"));
+    }
+    #[test]
+    fn it_parses_and_renders_multiple_instruction_urls() {
+        let xml = r#"<InstructionSet name="z80">
+  <Instruction name="NOP" summary="No Operation">
+    <InstructionForm z80name="NOP" form="NOP"/>
+    <Url>https://example.com/manual#nop</Url>
+    <Url>https://example.com/wiki/nop</Url>
+  </Instruction>
+</InstructionSet>"#;
+        let instrs = populate_instructions(xml).unwrap();
+        assert_eq!(
+            instrs[0].urls,
+            vec![
+                "https://example.com/manual#nop",
+                "https://example.com/wiki/nop"
+            ]
+        );
+        let markdown = instrs[0].to_markdown(Arch::Z80, None, None, FormsLayout::Verbose, false);
+        assert!(markdown.ends_with(
+            "\nMore info: https://example.com/manual#nop\n\nMore info: https://example.com/wiki/nop"
+        ));
+        assert!(markdown
+            .contains("\n  + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#NOP\n"));
+    }
+    #[test]
+    fn it_renders_x86_encoding_templates_when_picked() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+        let form = |name: &str, operands: &[&str]| -> InstructionForm {
+            globals
+                .names_to_instructions
+                .get(&(Arch::X86_64, name))
+                .unwrap()
+                .forms
+                .iter()
+                .find(|form| {
+                    form.operands
+                        .iter()
+                        .map(|op| op.type_.as_ref())
+                        .eq(operands.iter().copied())
+                })
+                .unwrap()
+                .clone()
+        };
+
+        let imul = form("imul", &["r64", "m64"]);
+        assert_eq!(imul.x86_encoding.as_deref(), Some("REX.W + 0F AF /r"));
+        assert!(imul
+            .to_markdown(None, Some(&[FormField::Encoding]), false)
+            .starts_with("- *Encoding*: REX.W + 0F AF /r\n"));
+        // only shown when picked
+        assert!(!imul.to_markdown(None, None, false).contains("Encoding"));
+
+        let encoding = |name: &str, operands: &[&str]| form(name, operands).x86_encoding;
+        assert_eq!(encoding("push", &["r64"]).as_deref(), Some("50+r"));
+        assert_eq!(encoding("jmp", &["rel8"]).as_deref(), Some("EB cb"));
+        assert_eq!(
+            encoding("vblendvps", &["ymm", "ymm", "ymm", "ymm"]).as_deref(),
+            Some("VEX.256.66.0F3A.W0 4A /r /is4")
+        );
+    }
+    #[test]
+    fn it_applies_the_completion_case_to_labels() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+
+        let upper = get_completes(
+            &globals.names_to_instructions,
+            Some(CompletionItemKind::OPERATOR),
+            CompletionCase::Upper,
+        );
+        assert!(upper.iter().any(|comp| comp.label == "BSWAP"));
+        assert!(upper
+            .iter()
+            .all(|comp| comp.label == comp.label.to_uppercase()));
+
+        let lower = get_completes(
+            &globals.names_to_registers,
+            Some(CompletionItemKind::VARIABLE),
+            CompletionCase::Lower,
+        );
+        assert!(lower.iter().any(|comp| comp.label == "ttbr0_el1"));
+        assert!(lower
+            .iter()
+            .all(|comp| comp.label == comp.label.to_lowercase()));
+
+        let config: TargetConfig = toml::from_str(
+            r#"
+            version = "0.1"
+            [assemblers]
+            gas = true
+            go = false
+            z80 = false
+            [instruction_sets]
+            x86 = false
+            x86_64 = true
+            z80 = false
+            [opts]
+            completion_case = "upper"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.opts.completion_case, CompletionCase::Upper);
+    }
+    #[test]
+    fn it_caches_demangled_names() {
+        let mut cache = DemangleCache::new(2);
+        let demangled = cache.demangle("_ZN3foo3barEv");
+        assert_eq!(demangled.as_deref(), Some("foo::bar()"));
+        assert_eq!(cache.demangle("_ZN3foo3barEv"), demangled);
+        assert_eq!(cache.len(), 1);
+
+        assert_eq!(cache.demangle("not_mangled"), None);
+        assert_eq!(
+            cache.demangle("_ZN3foo3bazEv").as_deref(),
+            Some("foo::baz()")
+        );
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.demangle("_ZN3foo3barEv"), demangled);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn handle_autocomplete_it_boosts_items_once_inserted() -> Result<()> {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+        let sorted_items = |frequencies: &CompletionFrequencies| -> Result<Vec<CompletionItem>> {
+            let messages = handle_test_comp_request(&globals, "	push<cursor>", None, frequencies);
+            let [Message::Response(resp)] = messages.as_slice() else {
+                panic!("Expected a single response, got {:?}", messages);
+            };
+            let mut list: CompletionList = serde_json::from_value(resp.result.clone().unwrap())?;
+            list.items.sort_by(|a, b| {
+                let key = |item: &CompletionItem| {
+                    item.sort_text.clone().unwrap_or_else(|| item.label.clone())
+                };
+                key(a).cmp(&key(b))
+            });
+            Ok(list.items)
+        };
+
+        let mut frequencies = CompletionFrequencies::default();
+        let items = sorted_items(&frequencies)?;
+        assert!(items.len() > 1);
+        let last = items.last().unwrap().clone();
+        // every item carries the command recording it, run by the client on insertion only
+        let command = last.command.clone().expect("Expected a record command");
+        assert_eq!(command.command, RECORD_COMPLETION_COMMAND);
+        assert_eq!(command.arguments, Some(vec![serde_json::json!(last.label)]));
+
+        // offering or resolving an item doesn't count as choosing it
+        assert_eq!(sorted_items(&frequencies)?, items);
+
+        let (server, client) = Connection::memory();
+        handle_execute_command_request(
+            &server,
+            RequestId::from(2),
+            &ExecuteCommandParams {
+                command: command.command,
+                arguments: command.arguments.unwrap(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            },
+            &mut frequencies,
+        )?;
+        assert!(matches!(
+            client.receiver.try_recv(),
+            Ok(Message::Response(Response { error: None, .. }))
+        ));
+        assert_eq!(sorted_items(&frequencies)?[0].label, last.label);
+
+        let mut pending = PendingFrequenciesSave::default();
+        let now = std::time::Instant::now();
+        pending.schedule(now);
+        // later insertions are saved along with the first
+        pending.schedule(now + COMPLETION_FREQUENCIES_SAVE_DELAY);
+        assert!(!pending.take_due(now));
+        assert!(pending.take_due(now + COMPLETION_FREQUENCIES_SAVE_DELAY));
+        assert!(!pending.take());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_boosts_frequently_chosen_completions() {
+        let item = |label: &str, sort_text: Option<&str>| CompletionItem {
+            label: label.to_string(),
+            sort_text: sort_text.map(ToString::to_string),
+            ..Default::default()
+        };
+        let mut items = vec![item("eax", Some("0eax")), item("ebx", Some("0ebx"))];
+        let mut frequencies = CompletionFrequencies::default();
+        frequencies.boost(&mut items);
+        assert_eq!(items[0].sort_text.as_deref(), Some("0eax"));
+
+        frequencies.record("ebx");
+        frequencies.record("ecx");
+        frequencies.record("ecx");
+        let mut items = vec![
+            item("eax", Some("0eax")),
+            item("ebx", None),
+            item("ecx", Some("1ecx")),
+        ];
+        frequencies.boost(&mut items);
+        items.sort_by(|a, b| a.sort_text.cmp(&b.sort_text));
+        let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["ecx", "ebx", "eax"]);
+
+        let path = std::env::temp_dir().join(format!(
+            "asm-lsp-completion-frequencies-{}.json",
+            std::process::id()
+        ));
+        frequencies.save(&path).unwrap();
+        let loaded = CompletionFrequencies::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, frequencies);
+        assert_eq!(loaded.count("ecx"), 2);
+        assert_eq!(CompletionFrequencies::load(&path).count("ecx"), 0);
+
+        // the least chosen items are forgotten once the counts are full
+        let mut frequencies = CompletionFrequencies::default();
+        frequencies.record("eax");
+        for idx in 1..MAX_COMPLETION_FREQUENCIES {
+            frequencies.record(&format!("label{idx}"));
+            frequencies.record(&format!("label{idx}"));
+        }
+        frequencies.record("ebx");
+        assert_eq!(frequencies.count("eax"), 0);
+        assert_eq!(frequencies.count("ebx"), 1);
+        assert_eq!(frequencies.count("label1"), 2);
+    }
+
+    #[test]
+    fn it_hashes_bundled_data_by_content() {
+        let gas_dirs_ser = include_bytes!("../docs_store/directives/serialized/gas");
+        let dirs = bincode::deserialize::<Vec<Directive>>(gas_dirs_ser).unwrap();
+        let hash = data_hash(&dirs).unwrap();
+        assert_eq!(hash, data_hash(&dirs.clone()).unwrap());
+
+        let mut changed = dirs;
+        changed[0].description.push('.');
+        assert_ne!(hash, data_hash(&changed).unwrap());
+        changed.pop();
+        assert_ne!(hash, data_hash(&changed).unwrap());
+    }
+
+    #[test]
+    fn it_merges_extra_directives_with_the_bundled_ones() {
+        let extra_path =
+            std::env::temp_dir().join(format!("asm-lsp-directives-{}.xml", std::process::id()));
+        std::fs::write(
+            &extra_path,
+            r#"<?xml version='1.0' encoding='utf-8'?>
+<Assembler name="Gas">
+    <Directive name="prologue" md_description="Sets up the stack frame.">
+        <Signatures>
+            <Signature sig=".prologue *frame-size*"></Signature>
+        </Signatures>
+    </Directive>
+    <Directive name="align" md_description="Aligns to our house style.">
+    </Directive>
+</Assembler>"#,
+        )
+        .unwrap();
+
+        let mut config = test_config();
+        config.opts.extra_directives = Some(extra_path.clone());
+        let info = init_global_info(Some(config)).expect("Failed to load info");
+        let globals = init_store(&info).expect("Failed to initialize store");
+        std::fs::remove_file(&extra_path).unwrap();
+
+        let directive = |name: &str| {
+            globals
+                .names_to_directives
+                .get(&(Assembler::Gas, name))
+                .copied()
+        };
+        assert_eq!(
+            directive("prologue").map(|dir| dir.description.as_str()),
+            Some("Sets up the stack frame.")
+        );
+        assert_eq!(
+            directive("ALIGN").map(|dir| dir.description.as_str()),
+            Some("Aligns to our house style.")
+        );
+        // the rest of the bundled directives are kept
+        assert!(directive("globl").is_some());
+        assert_eq!(
+            info.gas_directives
+                .iter()
+                .filter(|dir| dir.name == "align")
+                .count(),
+            1
+        );
+    }
+    #[test]
+    fn it_merges_completions_shared_by_archs() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+
+        let movq: Vec<&CompletionItem> = globals
+            .instr_completion_items
+            .iter()
+            .filter(|comp| comp.label.eq_ignore_ascii_case("movq"))
+            .collect();
+        assert_eq!(movq.len(), 1);
+        assert_eq!(
+            movq[0].data,
+            Some(serde_json::json!([Arch::X86, Arch::X86_64]))
+        );
+        let Some(Documentation::MarkupContent(ref docs)) = movq[0].documentation else {
+            panic!("Expected markdown documentation for movq");
+        };
+        assert!(docs.value.contains("[x86]"));
+        assert!(docs.value.contains("[x86-64]"));
+    }
+    #[test]
+    fn it_only_completes_registers_of_enabled_instruction_sets() {
+        let mut config = test_config();
+        config.instruction_sets = InstructionSets {
+            x86: false,
+            x86_64: false,
+            z80: true,
+            aarch64: false,
+        };
+        let info = init_global_info(Some(config)).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+
+        assert!(!globals.reg_completion_items.is_empty());
+        assert!(globals
+            .reg_completion_items
+            .iter()
+            .all(|comp| comp.data == Some(serde_json::json!([Arch::Z80]))));
+    }
+    #[test]
+    fn it_analyzes_a_single_line() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+        let names_to_info = NameToInfoMaps {
+            instructions: globals.names_to_instructions.clone(),
+            registers: globals.names_to_registers.clone(),
+            directives: globals.names_to_directives.clone(),
+            same_operations: globals.same_operations.clone(),
+            ..Default::default()
+        };
+
+        let analysis = analyze_line(Arch::X86_64, "\tmovl\t%rax, %ebx # copy", &names_to_info);
+        assert_eq!(Some("movl"), analysis.mnemonic.as_deref());
+        assert_eq!(
+            Some("MOV"),
+            analysis.instruction.map(|instr| instr.name.as_str())
+        );
+        assert_eq!(vec!["%rax", "%ebx"], analysis.operands);
+        assert_eq!(1, analysis.diagnostics.len());
+        let diag = &analysis.diagnostics[0];
+        assert_eq!(
+            (6, 10),
+            (diag.range.start.character, diag.range.end.character)
+        );
+        assert_eq!(
+            "`movl` takes 32-bit operands, but `%rax` is a 64-bit register",
+            diag.message
+        );
+
+        // only x86-64 rejects instructions dropped from long mode
+        let analysis = analyze_line(Arch::X86_64, "\taaa", &names_to_info);
+        assert_eq!(1, analysis.diagnostics.len());
+        assert!(analyze_line(Arch::X86, "\taaa", &names_to_info)
+            .diagnostics
+            .is_empty());
+
+        assert_eq!(
+            LineAnalysis::default(),
+            analyze_line(Arch::X86_64, "foo:", &names_to_info)
+        );
+    }
+
+    #[test]
+    fn it_renders_hover_as_html() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+        let names_to_info = NameToInfoMaps {
+            instructions: globals.names_to_instructions.clone(),
+            registers: globals.names_to_registers.clone(),
+            directives: globals.names_to_directives.clone(),
+            same_operations: globals.same_operations.clone(),
+            ..Default::default()
+        };
+
+        let html = get_hover_html(Arch::X86_64, "BSWAP", &names_to_info).unwrap();
+        assert!(html.starts_with("<div class=\"asm-lsp-hover\">\n<p>BSWAP [x86-64]\nByte Swap</p>"));
+        assert!(html.contains("<h2>Forms</h2>"));
+        assert!(html.contains("<li>\n<p><em>GAS</em>: bswap</p>"));
+        assert!(html.ends_with("</div>\n"));
+
+        assert!(get_hover_html(Arch::AArch64, "ttbr0_el1", &names_to_info).is_some());
+        assert!(get_hover_html(Arch::Z80, "bswap", &names_to_info).is_none());
+    }
+    #[test]
+    fn handle_hover_it_maps_utf16_columns_past_multibyte_text() {
+        test_hover(
+            "	mov	%rax, %rbx	# déjà vu: <cursor>bswap",
+            "BSWAP [x86]
+Byte Swap
+
+## Forms
+
+- *GAS*: bswap
+
+  + [r32]    input = true   output = true
+
+More info: https://www.felixcloutier.com/x86/bswap
+
+BSWAP [x86-64]
+Byte Swap
+
+## Forms
+
+- *GAS*: bswap
+
+  + [r32]    input = true   output = true
+- *GAS*: bswap
+
+  + [r64]    input = true   output = true
+
+More info: https://www.felixcloutier.com/x86/bswap",
+        );
+    }
+    #[test]
+    fn it_converts_utf16_columns_to_byte_offsets() {
+        let line = "\tmovq\t%rsp, %rbp\t# ☃ 𝄞 x";
+        assert_eq!(byte_col_of_utf16(line, 0), 0);
+        assert_eq!(byte_col_of_utf16(line, 1), 1);
+        // the snowman is 3 bytes but a single UTF-16 code unit
+        let snowman = line.find('☃').unwrap();
+        assert_eq!(byte_col_of_utf16(line, 19), snowman);
+        assert_eq!(byte_col_of_utf16(line, 20), snowman + 3);
+        // the clef is 4 bytes and a surrogate pair
+        assert_eq!(byte_col_of_utf16(line, 24), line.len() - 1);
+        assert_eq!(byte_col_of_utf16(line, 100), line.len());
+
+        let col = byte_col_of_utf16(line, 3);
+        assert_eq!(find_word_at_pos(line, col, ""), (1, 5));
+        let col = byte_col_of_utf16(line, 24);
+        let (start, end) = find_word_at_pos(line, col, "");
+        assert_eq!(&line[start..end], "x");
+    }
+    #[test]
+    fn it_splits_operands_outside_nested_delimiters() {
+        assert_eq!(
+            split_operands("%rax, 8(%rbx,%rcx,4)", Assembler::Gas),
+            vec!["%rax", "8(%rbx,%rcx,4)"]
+        );
+        assert_eq!(
+            split_operands("{x0, x1}, [sp, #16]", Assembler::Gas),
+            vec!["{x0, x1}", "[sp, #16]"]
+        );
+        assert_eq!(
+            split_operands("$',, %al", Assembler::Gas),
+            vec!["$',", "%al"]
+        );
+        assert_eq!(
+            split_operands("$',', %al", Assembler::Gas),
+            vec!["$','", "%al"]
+        );
+        assert_eq!(split_operands("af, af'", Assembler::Gas), vec!["af", "af'"]);
+        assert_eq!(
+            split_operands("$',', AL", Assembler::Go),
+            vec!["$','", "AL"]
+        );
+        assert_eq!(
+            split_operands("\"a,\\\"b\", 1", Assembler::Gas),
+            vec!["\"a,\\\"b\"", "1"]
+        );
+        // the operand being typed after a trailing comma is empty
+        assert_eq!(split_operands("%rax, ", Assembler::Gas), vec!["%rax", ""]);
+        assert!(split_operands("  ", Assembler::Gas).is_empty());
+    }
+    #[test]
+    fn handle_hover_x86_it_shows_mode_availability() {
+        test_hover(
+            "	a<cursor>aa",
+            "AAA [x86]
+ASCII Adjust After Addition
+
+## Forms
+
+- *GAS*: aaa | *GO*: AAA
+
+  + Valid in: 16/32-bit; invalid in 64-bit
+
+More info: https://www.felixcloutier.com/x86/aaa",
+        );
+    }
+    #[test]
+    fn handle_hover_x86_64_it_names_syscall_numbers() {
+        let mut config = test_config();
+        config.opts.syscalls = true;
+        test_hover_with_config(
+            "	movq	$6<cursor>0, %rax
+	xorl	%edi, %edi
+	syscall",
+            "`exit`
+
+Linux x86-64 system call 60",
+            &config,
+        );
+        test_hover_with_config(
+            "	mov	eax, 0x<cursor>e7
+	syscall",
+            "`exit_group`
+
+Linux x86-64 system call 231",
+            &config,
+        );
+    }
+    #[test]
+    fn handle_hover_x86_64_it_names_last_syscall_number_loaded() {
+        let mut config = test_config();
+        config.opts.syscalls = true;
+        test_hover_with_config(
+            "	movq	$60, %rax
+	movq	$<cursor>1, %rax
+	syscall",
+            "`write`
+
+Linux x86-64 system call 1",
+            &config,
+        );
+    }
+    #[test]
+    fn handle_hover_aarch64_it_provides_system_reg_info() {
+        test_hover(
+            "	mrs	x0, TTBR0_E<cursor>L1",
+            "TTBR0_EL1 [aarch64]
+Translation Table Base Register 0 (EL1). Holds the base address of the stage 1 translation table for the lower virtual address range (typically user space), along with its ASID.
+
+Type: System Register
+Width: 64 bits",
+        );
+    }
+    #[test]
+    fn handle_hover_aarch64_it_describes_vector_arrangements() {
+        test_hover(
+            "	add	v0.4s, v1.4<cursor>s, v2.4s",
+            "`v1.4s`
+
+Vector register v1 viewed as 4 single-precision (32-bit) elements",
+        );
+        test_hover(
+            "	ins	v<cursor>3.s[1], w0",
+            "`v3.s`
+
+Vector register v3 viewed as single-precision (32-bit) elements",
+        );
+    }
+    #[test]
+    fn handle_hover_z80_it_decodes_indexed_operands() {
+        test_hover(
+            "	ld	a, (i<cursor>x+5)",
+            "`(ix+5)`
+
+Indexed addressing: IX base + 5 offset
+
+The byte at the address in IX plus 5",
+        );
+        test_hover(
+            "	ld	(IY-0x9<cursor>0), b",
+            "`(IY-0x90)`
+
+Indexed addressing: IY base - 0x90 offset
+
+The byte at the address in IY minus 144
+
+**The offset doesn't fit the signed 8-bit displacement (-128 to 127)**",
+        );
+        test_hover(
+            "	inc	(ix+co<cursor>unt)",
+            "`(ix+count)`
+
+Indexed addressing: IX base + count offset
+
+The byte at the address in IX offset by `+count`, a signed 8-bit displacement (-128 to 127)",
+        );
+    }
+    #[test]
+    fn handle_hover_x86_x86_64_it_provides_reg_info_offset() {
+        test_hover(
+            "	movl	%edi, -20(%r<cursor>bp)",
+            "RBP [x86]
+Stack Base Pointer
+
+Type: General Purpose Register
+Width: 64 bits
+
+RBP [x86-64]
+Base Pointer (meant for stack frames)
+
+Type: General Purpose Register
+Width: 64 bits",
+        );
+    }
+    #[test]
+    fn handle_hover_x86_x86_64_it_provies_reg_info_relative_addressing() {
+        test_hover(
+            "	leaq	_ZSt4cout(%<cursor>rip), %rdi",
+            "RIP [x86]
+Instruction Pointer
+
+Type: Pointer Register
+Width: 64 bits
+
+RIP [x86-64]
+Instruction Pointer. Can only be used in RIP-relative addressing.
+
+Type: Pointer Register
+Width: 64 bits",
+        );
+    }
+
+    #[test]
+    fn handle_document_highlight_x86_x86_64_it_matches_regs_across_casings() {
+        test_document_highlight(
+            "_start:
+	movq	%r<cursor>ax, %rbx
+	addq	%rcx, %RAX
+	movq	(%rax), %rdx",
+            &[
+                (1, 6, 10, DocumentHighlightKind::READ),
+                (2, 13, 16, DocumentHighlightKind::WRITE),
+                (3, 7, 11, DocumentHighlightKind::READ),
+            ],
+        );
+    }
+    #[test]
+    fn handle_document_highlight_it_matches_labels_exactly() {
+        test_document_highlight(
+            "_st<cursor>art:
+	movq	%rax, %rbx
+	jmp	_START
+	jmp	_start",
+            &[
+                (0, 0, 6, DocumentHighlightKind::WRITE),
+                (3, 5, 11, DocumentHighlightKind::READ),
+            ],
+        );
+    }
+    #[test]
+    fn handle_hover_x86_x86_64_it_provides_reg_info_fpu_stack() {
+        test_hover(
+            "	fadd	%st<cursor>(1), %st",
+            "ST(1) [x86]
+x87 FPU data register, entry 1 below the top of the register stack. Holds an 80-bit double extended-precision floating point value.
+
+Type: FPU Register
+Width: 80 bits
+
+ST(1) [x86-64]
+x87 FPU data register, entry 1 below the top of the register stack. Holds an 80-bit double extended-precision floating point value.
+
+Type: FPU Register
+Width: 80 bits",
+        );
+    }
+    #[test]
+    fn handle_hover_x86_x86_64_it_provides_reg_info_fpu_stack_index() {
+        test_hover(
+            "	fadd	%st(<cursor>1), %st",
+            "ST(1) [x86]
+x87 FPU data register, entry 1 below the top of the register stack. Holds an 80-bit double extended-precision floating point value.
+
+Type: FPU Register
+Width: 80 bits
+
+ST(1) [x86-64]
+x87 FPU data register, entry 1 below the top of the register stack. Holds an 80-bit double extended-precision floating point value.
+
+Type: FPU Register
+Width: 80 bits",
+        );
+    }
+    #[test]
+    fn handle_hover_x86_x86_64_it_provides_reg_info_mmx() {
+        test_hover(
+            "	paddb	%mm<cursor>1, %mm0",
+            "MM1 [x86]
+MMX register. Aliases the low 64 bits (the mantissa) of x87 FPU data register R1, so MMX and x87 code should not be mixed without an intervening EMMS.
+
+Type: MMX Register
+Width: 64 bits
+
+MM1 [x86-64]
+MMX register. Aliases the low 64 bits (the mantissa) of x87 FPU data register R1, so MMX and x87 code should not be mixed without an intervening EMMS.
+
+Type: MMX Register
+Width: 64 bits",
+        );
+    }
+    #[test]
+    fn handle_autocomplete_gas_it_provides_directive_completes_1() {
+        test_directive_autocomplete(
+            "	.fi<cursor>",
+            CompletionTriggerKind::INVOKED,
+            None,
+            &["file", "fill"],
+        );
+    }
+    #[test]
+    fn handle_autocomplete_gas_it_provides_directive_completes_2() {
+        test_directive_autocomplete(
+            r#"	.fil<cursor>	"a.cpp""#,
+            CompletionTriggerKind::INVOKED,
+            None,
+            &["file", "fill"],
+        );
+    }
+    #[test]
+    fn handle_autocomplete_gas_it_provides_directive_completes_3() {
+        test_directive_autocomplete(
+            ".<cursor>",
+            CompletionTriggerKind::TRIGGER_CHARACTER,
+            Some(".".to_string()),
+            &["globl", "section", "text"],
+        );
+    }
+    #[test]
+    fn handle_autocomplete_gas_it_ranks_conditional_continuations_in_if_blocks() {
+        let resp = get_test_comp_resp(
+            ".ifdef DEBUG
+	nop
+.<cursor>",
+            CompletionTriggerKind::TRIGGER_CHARACTER,
+            Some(".".to_string()),
+        );
+        let mut first: Vec<&str> = resp.items[..3]
+            .iter()
+            .map(|comp| comp.label.as_str())
+            .collect();
+        first.sort_unstable();
+        assert_eq!(first, ["else", "elseif", "endif"]);
+
+        // outside of a conditional block, directives aren't ranked
+        let resp = get_test_comp_resp(
+            ".<cursor>",
+            CompletionTriggerKind::TRIGGER_CHARACTER,
+            Some(".".to_string()),
+        );
+        assert!(resp.items.iter().all(|comp| comp.sort_text.is_none()));
+    }
+    #[test]
+    fn handle_autocomplete_aarch64_it_completes_condition_codes() {
+        let resp = get_test_comp_resp("loop:	b.<cursor>", CompletionTriggerKind::INVOKED, None);
+        let ranked: Vec<&str> = resp
+            .items
+            .iter()
+            .filter(|comp| comp.sort_text.is_some())
+            .map(|comp| comp.label.as_str())
+            .collect();
+        assert_eq!(ranked.len(), 17);
+        assert!(ranked.iter().all(|label| label.starts_with("b.")));
+        let bne = resp.items.iter().find(|comp| comp.label == "b.ne").unwrap();
+        assert_eq!(bne.detail.as_deref(), Some("Not equal"));
+        assert_eq!(bne.insert_text.as_deref(), Some("ne"));
+
+        let resp = get_test_comp_resp("loop:	b.l<cursor>", CompletionTriggerKind::INVOKED, None);
+        let ranked: Vec<&str> = resp
+            .items
+            .iter()
+            .filter(|comp| comp.sort_text.is_some())
+            .map(|comp| comp.label.as_str())
+            .collect();
+        assert_eq!(ranked, ["b.lo", "b.ls", "b.lt", "b.le"]);
+    }
+    #[test]
+    fn handle_autocomplete_aarch64_it_provides_condition_suffixes_after_dot() {
+        let resp = test_autocomplete(
+            "loop:	b.<cursor>",
+            CompletionItemKind::ENUM_MEMBER,
+            CompletionTriggerKind::TRIGGER_CHARACTER,
+            Some(".".to_string()),
+        );
+        assert_eq!(resp.items.len(), 17);
+        assert_eq!(resp.items[0].label, "eq");
+        assert_eq!(resp.items[0].detail.as_deref(), Some("Equal"));
+    }
+
+    #[test]
+    fn handle_hover_gas_it_provides_directive_info_1() {
+        test_hover(r#"	.f<cursor>ile	"a.cpp"#, ".file [Gas]
+This version of the `.file` directive tells `as` that we are about to start a new logical file. When emitting DWARF2 line number information, `.file` assigns filenames to the `.debug_line` file name table.
+
+- .file *string*
+- .file *fileno filename*
+
+More info: https://sourceware.org/binutils/docs-2.41/as/File.html",
+            );
+    }
+    #[test]
+    fn handle_hover_gas_it_provides_directive_info_2() {
+        test_hover(".<cursor>text", ".text [Gas]
+Tells *as* to assemble the following statements onto the end of the text subsection numbered *subsection*, which is an absolute expression. If *subsection* is omitted, subsection number zero is used.
+
+- .text *subsection*
+
+More info: https://sourceware.org/binutils/docs-2.41/as/Text.html",
+            );
+    }
+    #[test]
+    fn handle_hover_gas_it_provides_directive_info_3() {
+        test_hover("	.glob<cursor>l	main", ".globl [Gas]
+`.globl` makes the symbol visible to `ld`. If you define symbol in your partial program, its value is made available to other partial programs that are linked with it.
+
+- .globl *symbol*
+
+More info: https://sourceware.org/binutils/docs-2.41/as/Global.html",
+            );
+    }
+    #[test]
+    fn handle_hover_gas_it_provides_directive_info_4() {
+        test_hover("	.syntax	unified\n	.th<cursor>umb", ".thumb [Gas]
+ARM only. This directive selects the Thumb instruction set for the following instructions, and is the same as `.code 16`.
+
+- .thumb
+
+More info: https://sourceware.org/binutils/docs-2.41/as/ARM-Directives.html",
+            );
+    }
+    #[test]
+    fn handle_hover_it_notes_the_active_arm_state() {
+        // no ARM instructions are bundled, so resolve a custom one under AArch64
+        let mut config = test_config();
+        config.opts.primary_arch = Some(Arch::AArch64);
+        config.opts.custom_instructions = vec![CustomInstruction {
+            name: String::from("ENTER_CRITICAL"),
+            description: String::from("Saves the interrupt flag and disables interrupts"),
+        }];
+        let state_note = |source: &str, config: &TargetConfig| {
+            let (_, resp) = get_test_hover_resp(source, config).unwrap();
+            let HoverContents::Markup(content) = resp.contents else {
+                panic!("Expected markdown hover contents");
+            };
+            content
+                .value
+                .rsplit_once("\n\n")
+                .map(|(_, last)| last.to_string())
+                .filter(|last| last.starts_with("*Assembled in"))
+        };
+
+        assert_eq!(
+            Some("*Assembled in Thumb state*"),
+            state_note("	.thumb\n	enter_crit<cursor>ical", &config).as_deref()
+        );
+        // the closest directive wins, and `.code 32` selects the ARM state
+        assert_eq!(
+            Some("*Assembled in ARM state*"),
+            state_note(
+                "	.thumb\n	enter_critical\n	.code 32\n	enter_crit<cursor>ical",
+                &config
+            )
+            .as_deref()
+        );
+        // `.thumb_func` marks a symbol without switching states
+        assert_eq!(
+            None,
+            state_note("	.thumb_func\n	enter_crit<cursor>ical", &config)
+        );
+
+        // x86 instructions aren't assembled in either state
+        assert_eq!(None, state_note("	.thumb\n	n<cursor>op", &test_config()));
+        config.opts.primary_arch = Some(Arch::X86_64);
+        assert_eq!(
+            None,
+            state_note("	.thumb\n	enter_crit<cursor>ical", &config)
+        );
+    }
+    #[test]
+    fn handle_autocomplete_gas_it_leaves_out_the_active_arm_state_directive() {
+        let labels = |source: &str| {
+            get_test_comp_resp(
+                source,
+                CompletionTriggerKind::TRIGGER_CHARACTER,
+                Some(".".to_string()),
+            )
+            .items
+            .into_iter()
+            .map(|comp| comp.label)
+            .collect::<Vec<String>>()
+        };
+
+        let after_arm = labels("	.arm\n	nop\n.<cursor>");
+        assert!(!after_arm.contains(&String::from("arm")));
+        assert!(after_arm.contains(&String::from("thumb")));
+
+        let after_thumb = labels("	.code 16\n.<cursor>");
+        assert!(after_thumb.contains(&String::from("arm")));
+        assert!(!after_thumb.contains(&String::from("thumb")));
+    }
+
+    #[test]
+    fn handle_hover_it_demangles_cpp_1() {
+        test_hover("	call	<cursor>_ZStlsISt11char_traitsIcEERSt13basic_ostreamIcT_ES5_PKc@PLT",
+            "std::basic_ostream<char, std::char_traits<char> >& std::operator<< <std::char_traits<char> >(std::basic_ostream<char, std::char_traits<char> >&, char const*)
+
+**@PLT**: Reference through the Procedure Linkage Table",
+            );
+    }
+    #[test]
+    fn handle_hover_it_demangles_cpp_2() {
+        test_hover("	leaq	_ZSt4c<cursor>out(%rip), %rdi", "std::cout");
+    }
+    #[test]
+    fn handle_hover_it_demangles_cpp_3() {
+        test_hover("	movq	_ZSt4endlIcSt<cursor>11char_traitsIcEERSt13basic_ostreamIT_T0_ES6_@GOTPCREL(%rip), %rax",
+        "std::basic_ostream<char, std::char_traits<char> >& std::endl<char, std::char_traits<char> >(std::basic_ostream<char, std::char_traits<char> >&)
+
+**@GOTPCREL**: PC-relative reference to the symbol's Global Offset Table entry",
+            );
+    }
+    #[test]
+    fn handle_hover_x86_x86_64_it_shows_instr_examples() {
+        test_hover(
+            "	lea<cursor>q	8(%rbp,%rcx,4), %rax",
+            "LEA [x86-64]
+Load Effective Address
+
+## Forms
+
+- *GAS*: leaw | *GO*: LEAW
+
+  + [r16]    input = false  output = true
+  + [m]      input = true   output = false
+- *GAS*: leal | *GO*: LEAL
+
+  + [r32]    input = false  output = true
+  + [m]      input = true   output = false
+- *GAS*: leaq | *GO*: LEAQ
+
+  + [r64]    input = false  output = true
+  + [m]      input = true   output = false
+
+## Examples
+
+```asm
+leaq 8(%rbp,%rcx,4), %rax
+leal (%rdi,%rdi,2), %eax
+leaq msg(%rip), %rsi
+```
+
+More info: https://www.felixcloutier.com/x86/lea",
+        );
+    }
+    #[test]
+    fn handle_hover_x86_x86_64_it_marks_privileged_instrs() {
+        test_hover(
+            "	hl<cursor>t",
+            "HLT [x86]
+**Privileged (ring 0)**
+Halt
+
+## Forms
+
+- *GAS*: hlt
+
+
+More info: https://www.felixcloutier.com/x86/hlt
+
+HLT [x86-64]
+**Privileged (ring 0)**
+Halt
+
+## Forms
+
+- *GAS*: hlt
+
+
+More info: https://www.felixcloutier.com/x86/hlt
+
+x86, x86-64: `HLT` / z80: `halt` — same operation",
+        );
+    }
+    #[test]
+    fn it_warns_about_privileged_instrs() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+
+        let source_code = "	cli
+	movq	%rax, %rbx
+	wrmsr
+";
+        let curr_doc = FullTextDocument::new("asm".to_string(), 1, source_code.to_string());
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        let tree = parser.parse(source_code, None);
+        let mut tree_entry = TreeEntry { tree, parser };
+
+        let diagnostics = get_privileged_instr_diagnostics(
+            &curr_doc,
+            &mut tree_entry,
+            &globals.names_to_instructions,
+        );
+        let lines: Vec<u32> = diagnostics
+            .iter()
+            .map(|diag| diag.range.start.line)
+            .collect();
+        assert_eq!(lines, [0, 2]);
+        assert_eq!(
+            diagnostics[1].message,
+            "`wrmsr` is a privileged (ring 0) instruction and will fault in user mode"
+        );
+    }
+    #[test]
+    fn it_summarizes_files() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+        let source_code = "	.text
+	.globl	main
+	.extern	helper, other
+main:
+	pushq	%rbp
+	call	puts@PLT
+.L2:
+	frobnicate	%rax
+	call	helper
+	ret
+	.section	.rodata
+msg:
+	.string	\"hi\"
+";
+        let curr_doc = FullTextDocument::new("asm".to_string(), 1, source_code.to_string());
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        let tree = parser.parse(source_code, None);
+        let mut tree_entry = TreeEntry { tree, parser };
+
+        let summary = get_file_summary(
+            &curr_doc,
+            &mut tree_entry,
+            &globals.names_to_instructions,
+            &test_config(),
+        );
+        assert_eq!(
+            summary,
+            FileSummary {
+                functions: vec![String::from("main")],
+                sections: vec![String::from(".text"), String::from(".rodata")],
+                external_symbols: vec![
+                    String::from("helper"),
+                    String::from("other"),
+                    String::from("puts")
+                ],
+                unknown_instructions: vec![String::from("frobnicate")],
+            }
+        );
+    }
+
+    #[test]
+    fn it_suppresses_diagnostics_by_code() {
+        let source_code = "	.equiv	LIMIT, 32
+	.set	LIMIT, 64 # asm-lsp: ignore symbol-redefinition
+	.equiv	LIMIT, 16 # asm-lsp: ignore suffix-size-mismatch
+start:
+	.equ	start, 0 # asm-lsp: ignore
+";
+        let curr_doc = FullTextDocument::new("asm".to_string(), 1, source_code.to_string());
+        let mut diagnostics = get_symbol_redefinition_diagnostics(&curr_doc);
+        assert_eq!(3, diagnostics.len());
+        assert_eq!(
+            Some(NumberOrString::String(String::from(
+                "asm-lsp/symbol-redefinition"
+            ))),
+            diagnostics[0].code
+        );
+        // diagnostics of other tools are kept
+        diagnostics.push(Diagnostic {
+            range: diagnostics[2].range,
+            message: String::from("Error: junk at end of line"),
+            ..Default::default()
+        });
+
+        let mut filtered = diagnostics.clone();
+        filter_suppressed_diagnostics(&mut filtered, &curr_doc, &[]);
+        let lines: Vec<u32> = filtered.iter().map(|diag| diag.range.start.line).collect();
+        assert_eq!(lines, [2, 4]);
+        assert_eq!(None, filtered[1].code);
+
+        filter_suppressed_diagnostics(
+            &mut diagnostics,
+            &curr_doc,
+            &[DiagnosticCode::SymbolRedefinition],
+        );
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(None, diagnostics[0].code);
+    }
+
+    #[test]
+    fn it_diagnoses_symbol_redefinitions() {
+        let source_code = "	.equ	SIZE, 4
+	.set	SIZE, 8
+	.equiv	SIZE, 16
+	.equiv	LIMIT, 32
+	.set	LIMIT, 64
+start:
+	.equ	start, 0
+";
+        let curr_doc = FullTextDocument::new("asm".to_string(), 1, source_code.to_string());
+
+        let diagnostics = get_symbol_redefinition_diagnostics(&curr_doc);
+        let found: Vec<(u32, u32, Option<DiagnosticSeverity>)> = diagnostics
+            .iter()
+            .map(|diag| {
+                (
+                    diag.range.start.line,
+                    diag.range.start.character,
+                    diag.severity,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (2, 8, Some(DiagnosticSeverity::ERROR)),
+                (4, 6, Some(DiagnosticSeverity::WARNING)),
+                (6, 6, Some(DiagnosticSeverity::ERROR)),
+            ]
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "`.equiv` redefines `SIZE`, already defined on line 1"
+        );
+        assert_eq!(
+            diagnostics[1].message,
+            "`.set` redefines `LIMIT`, which was defined with `.equiv` on line 4"
+        );
+        assert_eq!(
+            diagnostics[2].message,
+            "`.equ` redefines the label `start` defined on line 6"
+        );
+    }
+    #[test]
+    fn it_provides_call_hierarchy_over_labels() {
+        let source_code = "main:
+	call	helper
+	call	puts
+.Lloop:
+	call	helper
+	ret
+helper:
+	callq	leaf
+	ret
+leaf:
+	ret
+";
+        let curr_doc = FullTextDocument::new("asm".to_string(), 1, source_code.to_string());
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        let tree = parser.parse(source_code, None);
+        let mut tree_entry = TreeEntry { tree, parser };
+
+        // preparing from a call to `helper` resolves to its definition
+        let params = CallHierarchyPrepareParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier {
+                    uri: Uri::from_str("file://").unwrap(),
+                },
+                position: Position {
+                    line: 1,
+                    character: 8,
+                },
+            },
+            work_done_progress_params: WorkDoneProgressParams {
+                work_done_token: None,
+            },
+        };
+        let items = prepare_call_hierarchy(&params, &curr_doc, &mut tree_entry).unwrap();
+        assert_eq!(items.len(), 1);
+        let helper = &items[0];
+        assert_eq!(helper.name, "helper");
+        assert_eq!(helper.range.start.line, 6);
+
+        // both calls from `main` are grouped, including the one after a local label
+        let incoming = get_incoming_calls(helper, source_code, &mut tree_entry);
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].from.name, "main");
+        let lines: Vec<u32> = incoming[0]
+            .from_ranges
+            .iter()
+            .map(|range| range.start.line)
+            .collect();
+        assert_eq!(lines, [1, 4]);
+
+        let outgoing = get_outgoing_calls(helper, source_code, &mut tree_entry);
+        assert_eq!(outgoing.len(), 1);
+        assert_eq!(outgoing[0].to.name, "leaf");
+        assert_eq!(outgoing[0].from_ranges[0].start.line, 7);
+
+        // `puts` isn't defined in the document, so only `helper` is an outgoing call
+        let main = &incoming[0].from;
+        let outgoing = get_outgoing_calls(main, source_code, &mut tree_entry);
+        let callees: Vec<&str> = outgoing.iter().map(|call| call.to.name.as_str()).collect();
+        assert_eq!(callees, ["helper"]);
+    }
+    #[test]
+    fn it_provides_folding_ranges_for_blocks() {
+        let source_code = ".macro zero_regs
+	.irp	reg, %rax, %rbx
+	xorq	\\reg, \\reg
+	.endr
+.endm
+	.rept	3
+	nop
+	.endr
+	.irpc	c, 0123
+";
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        let tree = parser.parse(source_code, None);
+        let mut tree_entry = TreeEntry { tree, parser };
+
+        let ranges: Vec<(u32, u32)> = get_folding_ranges(source_code, &mut tree_entry)
+            .iter()
+            .map(|range| (range.start_line, range.end_line))
+            .collect();
+        // the unterminated `.irpc` block isn't folded
+        assert_eq!(ranges, [(0, 4), (1, 3), (5, 7)]);
+    }
+    #[test]
+    fn it_provides_folding_ranges_for_conditional_blocks() {
+        let source_code = ".ifdef DEBUG
+	.if	LEVEL > 1
+	nop
+	.else
+	int3
+	.endif
+.endif
+";
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        let tree = parser.parse(source_code, None);
+        let mut tree_entry = TreeEntry { tree, parser };
+
+        let ranges: Vec<(u32, u32)> = get_folding_ranges(source_code, &mut tree_entry)
+            .iter()
+            .map(|range| (range.start_line, range.end_line))
+            .collect();
+        assert_eq!(ranges, [(0, 6), (1, 5)]);
+    }
+    #[test]
+    fn it_provides_code_lenses_counting_label_references() {
+        let source_code = "_start:
+	call	helper
+	jmp	.Ldone
+helper:
+	call	helper
+	ret
+.Ldone:
+	hlt
+unused:
+	nop
+";
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        let tree = parser.parse(source_code, None);
+        let mut tree_entry = TreeEntry { tree, parser };
+        let uri = Uri::from_str("file://").unwrap();
+
+        let lenses = get_code_lenses(source_code, &mut tree_entry, &uri);
+        assert_eq!(lenses.len(), 4);
+        assert!(lenses.iter().all(|lens| lens.command.is_none()));
+
+        let titles: Vec<(u32, String)> = lenses
+            .into_iter()
+            .map(|lens| {
+                let lens = resolve_code_lens(lens, source_code, &mut tree_entry);
+                (lens.range.start.line, lens.command.unwrap().title)
+            })
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                (0, "0 references".to_string()),
+                (3, "2 references".to_string()),
+                (6, "1 reference".to_string()),
+                (8, "0 references".to_string()),
+            ]
+        );
+    }
+    #[test]
+    fn it_expands_selection_along_the_syntax_tree() {
+        let source_code = ".data
+msg:	.ascii	\"hi\"
+.text
+_start:
+	movq	$60, %rax
+	syscall
+";
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        let tree = parser.parse(source_code, None);
+        let mut tree_entry = TreeEntry { tree, parser };
+
+        let position = Position {
+            line: 4,
+            character: 13,
+        };
+        let ranges = get_selection_ranges(source_code, &mut tree_entry, &[position]);
+        assert_eq!(ranges.len(), 1);
+        let mut spans = Vec::new();
+        let mut range = ranges.first();
+        while let Some(selection) = range {
+            let (start, end) = (selection.range.start, selection.range.end);
+            spans.push(((start.line, start.character), (end.line, end.character)));
+            range = selection.parent.as_deref();
+        }
+        // register -> instruction -> line -> `.text` section -> document
+        assert_eq!(
+            spans,
+            [
+                ((4, 11), (4, 15)),
+                ((4, 1), (4, 15)),
+                ((4, 0), (4, 15)),
+                ((2, 0), (5, 8)),
+                ((0, 0), (6, 0)),
+            ]
+        );
+    }
+    #[test]
+    fn it_falls_back_to_default_assemblers_for_directives() {
+        let mut config = test_config();
+        assert_eq!(
+            config.directive_assemblers(),
+            [Assembler::Gas, Assembler::Go]
+        );
+
+        config.assemblers = Assemblers {
+            gas: false,
+            go: true,
+            z80: false,
+        };
+        assert_eq!(config.directive_assemblers(), [Assembler::Go]);
+
+        // no assembler with directives enabled, only the z80 instruction set
+        config.assemblers.go = false;
+        config.instruction_sets = InstructionSets {
+            x86: false,
+            x86_64: false,
+            z80: true,
+            aarch64: false,
+        };
+        assert_eq!(config.directive_assemblers(), [Assembler::Gas]);
+        assert_eq!(Arch::Z80.default_assemblers(), [Assembler::Gas]);
+    }
+    #[test]
+    fn it_flags_instrs_invalid_in_long_mode() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+
+        let source_code = "	aaa
+	movq	%rax, %rbx
+	into
+";
+        let curr_doc = FullTextDocument::new("asm".to_string(), 1, source_code.to_string());
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        let tree = parser.parse(source_code, None);
+        let mut tree_entry = TreeEntry { tree, parser };
+
+        let diagnostics = get_long_mode_instr_diagnostics(
+            &curr_doc,
+            &mut tree_entry,
+            &globals.names_to_instructions,
+        );
+        let lines: Vec<u32> = diagnostics
+            .iter()
+            .map(|diag| diag.range.start.line)
+            .collect();
+        assert_eq!(lines, [0, 2]);
+        assert_eq!(diagnostics[0].message, "`aaa` is invalid in 64-bit mode");
+    }
+    #[test]
+    fn it_flags_gas_suffix_size_mismatches() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+
+        let source_code = "	movl	%rax, %rbx
+	movq	%rax, %rbx
+	movzbl	%al, %eax
+	shll	%cl, %eax
+	addw	%ax, %r8d
+";
+        let curr_doc = FullTextDocument::new("asm".to_string(), 1, source_code.to_string());
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_asm::language()).unwrap();
+        let tree = parser.parse(source_code, None);
+        let mut tree_entry = TreeEntry { tree, parser };
+
+        let diagnostics = get_suffix_size_mismatch_diagnostics(
+            &curr_doc,
+            &mut tree_entry,
+            &globals.names_to_instructions,
+            &globals.names_to_registers,
+        );
+        let starts: Vec<(u32, u32)> = diagnostics
+            .iter()
+            .map(|diag| (diag.range.start.line, diag.range.start.character))
+            .collect();
+        assert_eq!(starts, [(0, 6), (0, 12), (4, 11)]);
+        assert_eq!(
+            diagnostics[0].message,
+            "`movl` takes 32-bit operands, but `%rax` is a 64-bit register"
+        );
+        assert_eq!(
+            diagnostics[2].message,
+            "`addw` takes 16-bit operands, but `%r8d` is a 32-bit register"
+        );
+    }
+    #[test]
+    fn it_only_lists_forms_taking_the_operand_being_typed_in_signature_help() {
+        let operand = |type_: OperandType| Operand {
+            type_,
+            input: Some(true),
+            output: None,
+            extended_size: None,
+        };
+        let form = |go_name: &str, operands: Vec<Operand>| InstructionForm {
+            gas_name: Some(String::from("add")),
+            go_name: Some(String::from(go_name)),
+            operands,
+            ..Default::default()
+        };
+        let add = Instruction {
+            name: String::from("add"),
+            summary: String::from("Add"),
+            forms: vec![
+                form("ADD1", vec![operand(OperandType::r64)]),
+                form(
+                    "ADD2",
+                    vec![operand(OperandType::r64), operand(OperandType::r64)],
+                ),
+                form(
+                    "ADD3",
+                    vec![operand(OperandType::imm32), operand(OperandType::r64)],
+                ),
+            ],
+            arch: Some(Arch::X86_64),
+            ..Default::default()
+        };
+        let instr_map = NameToInstructionMap::from([((Arch::X86_64, "add"), &add)]);
+
+        let listed = |source: &str| -> Vec<&str> {
+            let value = get_test_sig_help(source, &instr_map).expect("Expected signature help");
+            ["ADD1", "ADD2", "ADD3"]
+                .iter()
+                .copied()
+                .filter(|go_name| value.contains(&format!("*GO*: {go_name}")))
+                .collect()
+        };
+        assert_eq!(listed("	add <cursor>"), ["ADD1", "ADD2", "ADD3"]);
+        assert_eq!(listed("	add %rax, <cursor>"), ["ADD2", "ADD3"]);
+    }
+    #[test]
+    fn it_lists_all_forms_in_signature_help_when_none_document_operands() {
+        let form = |go_name: &str| InstructionForm {
+            gas_name: Some(String::from("add")),
+            go_name: Some(String::from(go_name)),
+            ..Default::default()
+        };
+        let add = Instruction {
+            name: String::from("add"),
+            summary: String::from("Add"),
+            forms: vec![form("ADD1"), form("ADD2")],
+            arch: Some(Arch::X86_64),
+            ..Default::default()
+        };
+        let instr_map = NameToInstructionMap::from([((Arch::X86_64, "add"), &add)]);
+
+        let value =
+            get_test_sig_help("	add %rax, <cursor>", &instr_map).expect("Expected signature help");
+        assert!(value.starts_with("**x86_64**\n"));
+        assert!(value.contains("*GO*: ADD1"));
+        assert!(value.contains("*GO*: ADD2"));
+
+        // z80 forms are described by their syntax rather than their operands
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+        let num_forms = |source: &str| {
+            get_test_sig_help(source, &globals.names_to_instructions)
+                .expect("Expected signature help")
+                .matches("- *Z80*: ")
+                .count()
+        };
+        assert!(num_forms("	ld <cursor>") > 0);
+        assert_eq!(num_forms("	ld a, <cursor>"), num_forms("	ld <cursor>"));
+    }
+    #[test]
+    fn it_has_a_provider_for_each_arch() {
+        let archs: Vec<Arch> = arch_providers()
+            .iter()
+            .map(|provider| provider.arch())
+            .collect();
+        assert_eq!(archs, [Arch::X86, Arch::X86_64, Arch::Z80, Arch::AArch64]);
+
+        let form = InstructionForm {
+            gas_name: Some(String::from("movq")),
+            go_name: Some(String::from("MOVQ")),
+            ..Default::default()
+        };
+        let x86_64 = arch_providers()[1];
+        assert!(x86_64.form_has_name(&form, "MOVQ"));
+        assert_eq!(x86_64.register_prefix(), Some("%"));
+        assert!(!arch_providers()[2].form_has_name(&form, "movq"));
+    }
+    #[test]
+    fn it_assembles_z80_instructions_from_their_operands() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+        let assemble = |name: &str, operands: &[&str]| {
+            let instr = globals
+                .names_to_instructions
+                .get(&(Arch::Z80, name))
+                .unwrap();
+            z80::assemble(&instr.forms, operands)
+        };
+
+        assert_eq!(assemble("ld", &["a", "b"]).as_deref(), Some("78"));
+        assert_eq!(assemble("ld", &["a", "ixl"]).as_deref(), Some("DD 7D"));
+        assert_eq!(
+            assemble("ld", &["(ix-2)", "0x10"]).as_deref(),
+            Some("DD 36 FE 10")
+        );
+        assert_eq!(
+            assemble("ld", &["hl", "1234h"]).as_deref(),
+            Some("21 34 12")
+        );
+        assert_eq!(assemble("ld", &["hl", "DATA"]).as_deref(), Some("21 nn nn"));
+        assert_eq!(
+            assemble("bit", &["7", "(iy+3)"]).as_deref(),
+            Some("FD CB 03 7E")
+        );
+        assert_eq!(assemble("set", &["2", "d"]).as_deref(), Some("CB D2"));
+        assert_eq!(assemble("rst", &["$38"]).as_deref(), Some("FF"));
+        assert_eq!(assemble("jr", &["nz", "loop"]).as_deref(), Some("20 o"));
+        // no form loads a 16-bit register pair into an 8-bit register
+        assert_eq!(assemble("ld", &["a", "hl"]), None);
+    }
+    #[test]
+    fn it_filters_instructions_by_isa_extension() {
+        let config: TargetConfig = toml::from_str(
+            r#"
+            version = "0.1"
+            enabled_isa_extensions = ["SSE", "SSE2", "AVX512-IFMA"]
+            [assemblers]
+            gas = true
+            go = false
+            z80 = false
+            [instruction_sets]
+            x86 = false
+            x86_64 = true
+            z80 = false
+            "#,
+        )
+        .expect("Failed to parse config");
+        assert!(config.isa_enabled(&ISA::AVX512IFMA));
+        assert!(!config.isa_enabled(&ISA::AVX512CD));
+
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+        let filter = |name: &str| {
+            let instr = globals
+                .names_to_instructions
+                .get(&(Arch::X86_64, name))
+                .unwrap();
+            instr_filter_targets(instr, &config)
+        };
+
+        // only the SSE2 forms remain, the MMX and AVX ones are dropped
+        let paddb = filter("paddb");
+        assert!(!paddb.forms.is_empty());
+        assert!(paddb.forms.iter().all(|form| form.isa == Some(ISA::SSE2)));
+        // every form requires AVX-512 CD
+        assert!(filter("vpconflictd").forms.is_empty());
+        // forms without an ISA extension are always kept
+        assert!(!filter("mov").forms.is_empty());
+
+        assert!(toml::from_str::<TargetConfig>(
+            "version = \"0.1\"\nenabled_isa_extensions = [\"AVX-9000\"]"
+        )
+        .is_err());
+    }
+    #[test]
+    fn it_rejects_unknown_config_fields() {
+        let err = toml::from_str::<TargetConfig>(
+            r#"
+            version = "0.1"
+            [assemblers]
+            gas = true
+            go = false
+            z80 = false
+            [instruction_set]
+            x86 = true
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown field `instruction_set`"));
+
+        let err = toml::from_str::<TargetConfig>(
+            r#"
+            version = "0.1"
+            [assemblers]
+            gas = true
+            go = false
+            z80 = false
+            nasm = true
+            [instruction_sets]
+            x86 = true
+            x86_64 = true
+            z80 = false
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown field `nasm`"));
+    }
+    #[test]
+    fn it_warns_about_instruction_sets_without_assemblers() {
+        assert!(TargetConfig::default().validate().is_empty());
+
+        let mut config = TargetConfig::default();
+        config.instruction_sets.z80 = true;
+        config.assemblers.gas = false;
+        config.assemblers.go = false;
+        let warnings = config.validate();
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].starts_with("The x86 instruction set is enabled"));
+        assert!(warnings[2].contains("enable z80 under [assemblers]"));
+    }
+    #[test]
+    fn it_finds_items_by_prefix() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+
+        let cmovs = instructions_with_prefix(&globals.instruction_prefixes, Arch::X86_64, "cmov");
+        assert!(!cmovs.is_empty());
+        assert!(cmovs.iter().all(|instr| instr.name.starts_with("CMOV")));
+        assert!(cmovs.windows(2).all(|pair| pair[0].name < pair[1].name));
+
+        let regs = registers_with_prefix(&globals.register_prefixes, Arch::X86_64, "XMM1");
+        let names: Vec<&str> = regs.iter().map(|reg| reg.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "xmm1", "xmm10", "xmm11", "xmm12", "xmm13", "xmm14", "xmm15", "xmm16", "xmm17",
+                "xmm18", "xmm19"
+            ]
+        );
+
+        let dirs = directives_with_prefix(&globals.directive_prefixes, Assembler::Gas, "glob");
+        let names: Vec<&str> = dirs.iter().map(|dir| dir.name.as_str()).collect();
+        assert_eq!(names, ["global", "globl"]);
+
+        assert!(registers_with_prefix(&globals.register_prefixes, Arch::Z80, "xmm").is_empty());
+    }
+    #[test]
+    fn it_searches_instructions_by_summary() {
+        let info = init_global_info(None).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+
+        let found = search_instructions(
+            &globals.names_to_instructions,
+            Arch::X86_64,
+            "Compare and Exchange",
+        );
+        let names: Vec<&str> = found.iter().map(|instr| instr.name.as_str()).collect();
+        assert!(names.contains(&"CMPXCHG"), "{:?}", names);
+
+        // exact name matches come first, even when the summary doesn't contain the query
+        let found = search_instructions(&globals.names_to_instructions, Arch::X86_64, "add");
+        assert_eq!(found[0].name, "ADD");
+        assert!(found.len() > 1);
+        assert!(found[1..]
+            .windows(2)
+            .all(|pair| pair[0].name <= pair[1].name));
+
+        assert!(search_instructions(&globals.names_to_instructions, Arch::X86_64, " ").is_empty());
+    }
+    #[test]
+    fn handle_hover_gas_it_resolves_size_directive_symbol_to_label() {
+        test_hover(
+            "	.globl	myfunc
+	.type	myfunc, @function
+myfunc:
+	ret
+	.size	myf<cursor>unc, .-myfunc",
+            "`myfunc:`
+
+Label defined on line 3",
+        );
+    }
+    #[test]
+    fn handle_hover_gas_it_explains_instruction_prefixes() {
+        test_hover(
+            "	lo<cursor>ck	cmpxchgq	%rcx, (%rdx)",
+            "`lock` (instruction prefix, `F0`)
+
+Makes the following read-modify-write instruction atomic, e.g. `lock cmpxchg`. Only valid when the destination is memory, and only with `add`, `adc`, `and`, `btc`, `btr`, `bts`, `cmpxchg`, `cmpxchg8b`, `cmpxchg16b`, `dec`, `inc`, `neg`, `not`, `or`, `sbb`, `sub`, `xadd`, `xchg` and `xor`",
+        );
+        test_hover(
+            "	<cursor>rep; movsb",
+            "`rep` (instruction prefix, `F3`)
+
+Repeats the following string instruction (`movs`, `stos`, `lods`, `ins` or `outs`) the number of times held in `rcx`/`ecx`/`cx`, decrementing it after each iteration",
+        );
+        test_hover(
+            "	REP<cursor>NE	scasb",
+            "`repne` (instruction prefix, `F2`)
+
+Repeats the following string comparison (`cmps` or `scas`) while the count in `rcx`/`ecx`/`cx` is nonzero and the compared values differ (ZF is clear)",
+        );
+    }
+    #[test]
+    fn handle_hover_it_reports_what_the_hover_resolved_to() {
+        let kind = |source: &str| get_test_hover_resp(source, &test_config()).unwrap().0;
+        assert_eq!(kind("	push<cursor>q	%rbp"), HoverKind::Instruction);
+        assert_eq!(
+            kind("	lo<cursor>ck addl	$1, (%rdi)"),
+            HoverKind::Instruction
+        );
+        assert_eq!(kind("	pushq	%rb<cursor>p"), HoverKind::Register);
+        assert_eq!(kind("	.glo<cursor>bl	main"), HoverKind::Directive);
+        assert_eq!(kind("	call	_ZN3foo3barEv<cursor>"), HoverKind::Demangled);
+        assert_eq!(kind("	.p2align	<cursor>4"), HoverKind::Immediate);
+        assert_eq!(kind("	.globl	ma<cursor>in\nmain:\n	ret"), HoverKind::None);
+    }
+    #[test]
+    fn handle_hover_it_spells_out_prefixed_immediates() {
+        test_hover(
+            "	addq	$0x<cursor>10, %rsp",
+            "`$0x10`
+
+- Decimal: 16
+- Hex: 0x10
+- Binary: 0b10000",
+        );
+        test_hover(
+            "	mov	x0, #2<cursor>55",
+            "`#255`
+
+- Decimal: 255
+- Hex: 0xff
+- Binary: 0b11111111",
+        );
+        // the trailing-`h` hex form reads the same as it does for the conversion action
+        test_hover(
+            "	mov	x0, #0F<cursor>Fh",
+            "`#0FFh`
+
+- Decimal: 255
+- Hex: 0xff
+- Binary: 0b11111111",
+        );
+
+        // `#` only marks an immediate for architectures using it
+        let mut x86_config = test_config();
+        x86_config.instruction_sets.aarch64 = false;
+        assert!(get_test_hover_resp("	mov	x0, #2<cursor>55", &x86_config).is_none());
+        // nor when it starts a comment
+        assert!(get_test_hover_resp("#1<cursor>6 byte stack frame", &test_config()).is_none());
+        assert!(get_test_hover_resp("	ret	# pops $1<cursor>6", &test_config()).is_none());
+    }
+
+    #[test]
+    fn handle_hover_gas_it_decodes_loc_directives() {
+        test_hover(
+            "	.file	1 \"foo.c\"
+	.loc	1 4<cursor>2 0",
+            "`.loc	1 42 0`
+
+File #1 (`foo.c`), line 42, column 0",
+        );
+        test_hover(
+            "	.file	0 \"/src\" \"main.c\" md5 0x0
+	.file	1 \"/src\" \"util.h\"
+	.loc	<cursor>1 7 3 prologue_end",
+            "`.loc	1 7 3 prologue_end`
+
+File #1 (`/src/util.h`), line 7, column 3
+
+Options: `prologue_end`",
+        );
+        test_hover(
+            "	.loc	2 1<cursor>0",
+            "`.loc	2 10`
+
+File #2, line 10",
+        );
+    }
+
+    #[test]
+    fn handle_hover_gas_it_explains_symbol_types() {
+        test_hover(
+            "	.type	sum, @func<cursor>tion",
+            "`@function` (STT_FUNC)
+
+The symbol names a function",
+        );
+        test_hover(
+            "	.type	counter,%tls_ob<cursor>ject",
+            "`%tls_object` (STT_TLS)
+
+The symbol names a thread-local data object",
+        );
+        // relocation suffixes aren't symbol types, even when named alike
+        let resp = get_test_hover_resp("	call	object@P<cursor>LT", &test_config()).unwrap();
+        let HoverContents::Markup(content) = resp.1.contents else {
+            panic!("Expected markup hover contents");
+        };
+        assert!(!content.value.contains("STT_"));
+    }
+
+    #[test]
+    fn handle_code_action_it_converts_between_hex_and_decimal() {
+        let config = test_config();
+        let actions = |source: &str| {
+            let line = source.lines().position(|line| line.contains("<cursor>"));
+            let line = line.expect("No <cursor> marker found");
+            let character = source.lines().nth(line).unwrap().find("<cursor>").unwrap();
+            let position = Position {
+                line: line as u32,
+                character: character as u32,
+            };
+            let doc = FullTextDocument::new("asm".to_string(), 1, source.replace("<cursor>", ""));
+            let params = CodeActionParams {
+                text_document: TextDocumentIdentifier {
+                    uri: Uri::from_str("file://").unwrap(),
+                },
+                range: Range {
+                    start: position,
+                    end: position,
+                },
+                context: CodeActionContext::default(),
+                work_done_progress_params: WorkDoneProgressParams {
+                    work_done_token: None,
+                },
+                partial_result_params: PartialResultParams {
+                    partial_result_token: None,
+                },
+            };
+            get_code_actions(&doc, &params, &config)
+                .into_iter()
+                .map(|action| {
+                    let CodeActionOrCommand::CodeAction(action) = action else {
+                        panic!("Expected a code action");
+                    };
+                    let edits = &action.edit.unwrap().changes.unwrap()[&params.text_document.uri];
+                    let edit = &edits[0];
+                    (
+                        edit.range.start.character,
+                        edit.range.end.character,
+                        edit.new_text.clone(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // AT&T immediates keep their prefix
+        assert_eq!(
+            vec![(7, 9, String::from("0x10"))],
+            actions("\tmovl\t$1<cursor>6, %eax")
+        );
+        assert_eq!(
+            vec![(7, 11, String::from("16"))],
+            actions("\tmovl\t$0x<cursor>10, %eax")
+        );
+        // Intel bare and trailing-`h` literals, with hex written as the document does
+        assert_eq!(
+            vec![(10, 14, String::from("255"))],
+            actions("\tmov\teax, 0F<cursor>Fh")
+        );
+        assert_eq!(
+            vec![(10, 12, String::from("10h"))],
+            actions("\tmov\teax, 0FFh\n\tmov\tebx, 1<cursor>6")
+        );
+        // binary converts both ways
+        assert_eq!(
+            vec![(7, 12, String::from("0x5")), (7, 12, String::from("5"))],
+            actions("\tmovl\t$0b<cursor>101, %eax")
+        );
+        // labels, registers and comments are left alone
+        assert!(actions("1<cursor>:\tjmp\t1b").is_empty());
+        assert!(actions("\tmovl\t$16, %e<cursor>ax").is_empty());
+        assert!(actions("\tnop # wait 1<cursor>6 cycles").is_empty());
+    }
+
+    #[test]
+    fn handle_type_definition_it_lists_the_register_class() {
+        let mut config = test_config();
+        config.opts.primary_arch = Some(Arch::X86_64);
+        let info = init_global_info(Some(config.clone())).expect("Failed to load info");
+        let globals = init_test_store(&info).expect("Failed to initialize test store");
+        let type_def = |source: &str| {
+            let line = source.lines().position(|line| line.contains("<cursor>"));
+            let line = line.expect("No <cursor> marker found");
+            let character = source.lines().nth(line).unwrap().find("<cursor>").unwrap();
+            let doc = FullTextDocument::new("asm".to_string(), 1, source.replace("<cursor>", ""));
+            let params = TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier {
+                    uri: Uri::from_str("file://").unwrap(),
+                },
+                position: Position {
+                    line: line as u32,
+                    character: character as u32,
+                },
+            };
+            get_type_definition_resp(&doc, &params, &globals.names_to_registers, &config)
+        };
+
+        let resp = type_def("\tmovl\t%e<cursor>ax, %ebx").expect("Expected a register class");
+        assert!(resp.starts_with("General Purpose Register, 32 bits (x86-64): "));
+        assert!(resp.contains("ebx, ecx, edi, edx"));
+        assert!(!resp.contains("rax"));
+        assert!(!resp.contains('\n'));
+
+        // nothing to list for instructions
+        assert_eq!(None, type_def("\tmo<cursor>vl\t%eax, %ebx"));
+    }
+
+    #[test]
+    fn handle_hover_it_reports_the_range_of_the_hovered_word() {
+        let config = test_config();
+        let range = |source: &str| {
+            let (_, hover) = get_test_hover_resp(source, &config).expect("Expected a hover");
+            hover
+                .range
+                .map(|range| (range.start.line, range.start.character, range.end.character))
+        };
+
+        assert_eq!(Some((1, 1, 6)), range("	.text\n	pus<cursor>hq	%rbp"));
+        assert_eq!(Some((0, 8, 11)), range("	pushq	%r<cursor>bp"));
+        assert_eq!(Some((0, 2, 6)), range("	.te<cursor>xt"));
+        // the range is in UTF-16 code units
+        assert_eq!(Some((0, 13, 15)), range("	movb	$'ü', %a<cursor>l"));
+        // hovers not describing the word itself have no range
+        assert_eq!(None, range("	movl	$0x<cursor>10, %eax"));
+    }
+
+    #[test]
+    fn handle_hover_it_documents_custom_instructions() {
+        let mut config = test_config();
+        config.opts.primary_arch = Some(Arch::X86_64);
+        config.opts.custom_instructions = vec![CustomInstruction {
+            name: String::from("ENTER_CRITICAL"),
+            description: String::from("Saves the interrupt flag and disables interrupts"),
+        }];
+
+        let (kind, resp) =
+            get_test_hover_resp("	enter_crit<cursor>ical", &config).expect("Expected a hover");
+        assert_eq!(kind, HoverKind::Instruction);
+        let HoverContents::Markup(content) = resp.contents else {
+            panic!("Expected markdown hover contents");
+        };
+        assert!(content.value.starts_with(
+            "ENTER_CRITICAL [x86-64]\nSaves the interrupt flag and disables interrupts"
+        ));
+        assert!(!content.value.contains("## Forms"));
+
+        // documented instructions aren't shadowed
+        config.opts.custom_instructions[0].name = String::from("push");
+        let (_, resp) = get_test_hover_resp("	pu<cursor>sh	%rax", &config).unwrap();
+        let HoverContents::Markup(content) = resp.contents else {
+            panic!("Expected markdown hover contents");
+        };
+        assert!(content.value.contains("## Forms"));
+    }
+
+    #[test]
+    fn handle_hover_it_notes_the_same_operation_on_other_archs() {
+        let note = |source: &str| {
+            let (_, resp) = get_test_hover_resp(source, &test_config()).unwrap();
+            let HoverContents::Markup(content) = resp.contents else {
+                panic!("Expected markdown hover contents");
+            };
+            content.value.contains("— same operation")
+        };
 
-More info: https://www.felixcloutier.com/x86/movq",
-        );
+        let (_, resp) = get_test_hover_resp("	j<cursor>mp	loop", &test_config()).unwrap();
+        let HoverContents::Markup(content) = resp.contents else {
+            panic!("Expected markdown hover contents");
+        };
+        assert!(content
+            .value
+            .ends_with("x86, x86-64: `JMP` / z80: `jp` — same operation"));
+        assert!(note("	j<cursor>p	loop"));
+        // `cpuid` has no z80 counterpart
+        assert!(!note("	cpu<cursor>id"));
     }
 
     #[test]
-    fn handle_hover_x86_x86_64_it_provides_reg_info_normal() {
+    fn handle_hover_gas_it_explains_repeated_data_args() {
         test_hover(
-            "	pushq	%r<cursor>bp",
-            "RBP [x86]
-Stack Base Pointer
+            "buf:	.fill	<cursor>16, 4, 0xff",
+            "`.fill 16, 4, 0xff`
 
-Type: General Purpose Register
-Width: 64 bits
+`16`: number of copies of `value` emitted
 
-RBP [x86-64]
-Base Pointer (meant for stack frames)
+Emits 16 × 4 = 64 bytes",
+        );
+        test_hover(
+            "	.fill	16, 4, 0xf<cursor>f",
+            "`.fill 16, 4, 0xff`
 
-Type: General Purpose Register
-Width: 64 bits",
+`0xff`: value each copy holds (defaults to 0)
+
+Emits 16 × 4 = 64 bytes",
+        );
+        test_hover(
+            "	.space	64, 0x9<cursor>0 # padding",
+            "`.space 64, 0x90`\n\n`0x90`: value each byte holds (defaults to 0)",
         );
     }
     #[test]
-    fn handle_hover_x86_x86_64_it_provides_reg_info_offset() {
+    fn handle_hover_gas_it_explains_alignment_args() {
         test_hover(
-            "	movl	%edi, -20(%r<cursor>bp)",
-            "RBP [x86]
-Stack Base Pointer
-
-Type: General Purpose Register
-Width: 64 bits
-
-RBP [x86-64]
-Base Pointer (meant for stack frames)
+            "	.p2align	<cursor>4, 0x90, 7",
+            "`.p2align 4, 0x90, 7`\n\n`4`: aligns to 2^4 = 16 bytes",
+        );
+        test_hover(
+            "	.balignw	8, 0x9<cursor>0",
+            "`.balignw 8, 0x90`\n\n`0x90`: 2-byte value the padding is filled with",
+        );
+        test_hover(
+            "	.balign	1<cursor>2",
+            "`.balign 12`\n\n`12`: aligns to 12 bytes, but isn't a power of two",
+        );
 
-Type: General Purpose Register
-Width: 64 bits",
+        let mut config = test_config();
+        config.instruction_sets.aarch64 = false;
+        test_hover_with_config(
+            "main:	.align	<cursor>16 # pad",
+            "`.align 16`\n\n`16`: aligns to 16 bytes",
+            &config,
+        );
+        config.instruction_sets = InstructionSets {
+            x86: false,
+            x86_64: false,
+            z80: false,
+            aarch64: true,
+        };
+        test_hover_with_config(
+            "	.align	<cursor>4",
+            "`.align 4`\n\n`4`: aligns to 2^4 = 16 bytes",
+            &config,
         );
     }
     #[test]
-    fn handle_hover_x86_x86_64_it_provies_reg_info_relative_addressing() {
+    fn handle_hover_gas_it_notes_the_active_section() {
         test_hover(
-            "	leaq	_ZSt4cout(%<cursor>rip), %rdi",
-            "RIP [x86]
-Instruction Pointer
+            "	.text
+	.globl	myfunc
+myfunc:
+	ret
+	.data
+value:	.quad	0
+	.size	myf<cursor>unc, .-myfunc",
+            "`myfunc:`
+
+Label defined on line 3, inside `.text` (code)",
+        );
+        test_hover(
+            "	.section .rodata.str1.1,\"aMS\",@progbits,1
+msg:	.as<cursor>ciz	\"hi\"",
+            ".asciz [Gas]
+`.asciz` is just like `.ascii`, but each string is followed by a zero byte. The \"z\" in '`.asciz`' stands for \"zero\".
 
-Type: Pointer Register
-Width: 64 bits
+- .asciz \"*string*\"...
 
-RIP [x86-64]
-Instruction Pointer. Can only be used in RIP-relative addressing.
+More info: https://sourceware.org/binutils/docs-2.41/as/Asciz.html
 
-Type: Pointer Register
-Width: 64 bits",
+*Inside `.rodata.str1.1` (read-only data)*",
         );
-    }
 
-    #[test]
-    fn handle_autocomplete_gas_it_provides_directive_completes_1() {
-        test_directive_autocomplete("	.fi<cursor>", CompletionTriggerKind::INVOKED, None);
-    }
-    #[test]
-    fn handle_autocomplete_gas_it_provides_directive_completes_2() {
-        test_directive_autocomplete(
-            r#"	.fil<cursor>	"a.cpp""#,
-            CompletionTriggerKind::INVOKED,
-            None,
+        assert_eq!(
+            get_active_section("\t.section \".data.rel.ro\", \"aw\"\n\t.quad 0", 1).as_deref(),
+            Some(".data.rel.ro")
         );
-    }
-    #[test]
-    fn handle_autocomplete_gas_it_provides_directive_completes_3() {
-        test_directive_autocomplete(
-            ".<cursor>",
-            CompletionTriggerKind::TRIGGER_CHARACTER,
-            Some(".".to_string()),
+        assert_eq!(
+            get_active_section("\t.bss\n\t.text 1\n\tret", 1).as_deref(),
+            Some(".text")
         );
+        assert_eq!(get_active_section("\tret", 0), None);
     }
-
     #[test]
-    fn handle_hover_gas_it_provides_directive_info_1() {
-        test_hover(r#"	.f<cursor>ile	"a.cpp"#, ".file [Gas]
-This version of the `.file` directive tells `as` that we are about to start a new logical file. When emitting DWARF2 line number information, `.file` assigns filenames to the `.debug_line` file name table.
-
-- .file *string*
-- .file *fileno filename*
+    fn handle_hover_gas_it_explains_the_location_counter() {
+        let expected = "`.` (location counter)
 
-More info: https://sourceware.org/binutils/docs-2.41/as/File.html",
-            );
+The address the assembler is currently emitting to within the current section, e.g. \
+`.size f, .-f` sets the size of `f` to the number of bytes emitted since `f`";
+        test_hover(
+            "myfunc:
+	ret
+	.size	myfunc, <cursor>.-myfunc",
+            expected,
+        );
+        test_hover("	jmp	<cursor>.", expected);
     }
     #[test]
-    fn handle_hover_gas_it_provides_directive_info_2() {
-        test_hover(".<cursor>text", ".text [Gas]
-Tells *as* to assemble the following statements onto the end of the text subsection numbered *subsection*, which is an absolute expression. If *subsection* is omitted, subsection number zero is used.
-
-- .text *subsection*
+    fn handle_hover_gas_it_resolves_type_directive_symbol_to_label() {
+        test_hover(
+            "	.type	ad<cursor>d, @function
+add:
+	ret",
+            "`add:`
 
-More info: https://sourceware.org/binutils/docs-2.41/as/Text.html",
-            );
+Label defined on line 2",
+        );
     }
     #[test]
-    fn handle_hover_gas_it_provides_directive_info_3() {
-        test_hover("	.glob<cursor>l	main", ".globl [Gas]
-`.globl` makes the symbol visible to `ld`. If you define symbol in your partial program, its value is made available to other partial programs that are linked with it.
-
-- .globl *symbol*
+    fn handle_hover_it_explains_relocation_suffix() {
+        test_hover(
+            "	call	pri<cursor>ntf@PLT",
+            "`printf@PLT`
 
-More info: https://sourceware.org/binutils/docs-2.41/as/Global.html",
-            );
+**@PLT**: Reference through the Procedure Linkage Table",
+        );
     }
 
     #[test]
-    fn handle_hover_it_demangles_cpp_1() {
-        test_hover("	call	<cursor>_ZStlsISt11char_traitsIcEERSt13basic_ostreamIcT_ES5_PKc@PLT",
-            "std::basic_ostream<char, std::char_traits<char> >& std::operator<< <std::char_traits<char> >(std::basic_ostream<char, std::char_traits<char> >&, char const*)",
-            );
-    }
-    #[test]
-    fn handle_hover_it_demangles_cpp_2() {
-        test_hover("	leaq	_ZSt4c<cursor>out(%rip), %rdi", "std::cout");
+    fn handle_autocomplete_z80_it_provides_instr_comps_one_character_start() {
+        test_instruction_autocomplete(
+            "L<cursor>",
+            CompletionTriggerKind::INVOKED,
+            None,
+            &["ld", "ldir"],
+        );
     }
+
     #[test]
-    fn handle_hover_it_demangles_cpp_3() {
-        test_hover("	movq	_ZSt4endlIcSt<cursor>11char_traitsIcEERSt13basic_ostreamIT_T0_ES6_@GOTPCREL(%rip), %rax",
-        "std::basic_ostream<char, std::char_traits<char> >& std::endl<char, std::char_traits<char> >(std::basic_ostream<char, std::char_traits<char> >&)",
-            );
+    fn handle_autocomplete_z80_it_provides_reg_comps_after_one_character() {
+        let resp = test_register_autocomplete(
+            "        PUSH H<cursor>",
+            CompletionTriggerKind::INVOKED,
+            None,
+        );
+        let labels: Vec<&str> = resp.items.iter().map(|comp| comp.label.as_str()).collect();
+        for pair in ["bc", "de", "hl", "ix", "iy"] {
+            assert!(labels.contains(&pair), "{}", pair);
+        }
+        assert!(labels.contains(&"a"));
+        assert!(!labels.contains(&"rax"));
     }
-
     #[test]
-    fn handle_autocomplete_z80_it_provides_instr_comps_one_character_start() {
-        test_instruction_autocomplete("L<cursor>", CompletionTriggerKind::INVOKED, None);
+    fn handle_autocomplete_z80_it_provides_reg_comps_in_empty_operand() {
+        let resp = test_register_autocomplete(
+            "        LD HL, <cursor>",
+            CompletionTriggerKind::INVOKED,
+            None,
+        );
+        let labels: Vec<&str> = resp.items.iter().map(|comp| comp.label.as_str()).collect();
+        assert!(labels.contains(&"de"));
+        assert!(!labels.contains(&"rax"));
     }
-
     #[test]
-    fn handle_autocomplete_z80_it_provides_reg_comps_after_one_character() {
-        test_register_autocomplete(
+    fn handle_autocomplete_x86_x86_64_it_only_provides_prefixed_regs_after_prefix() {
+        let resp = test_register_autocomplete(
             "pushq %<cursor>",
             CompletionTriggerKind::TRIGGER_CHARACTER,
             Some("%".to_string()),
         );
+        let labels: Vec<&str> = resp.items.iter().map(|comp| comp.label.as_str()).collect();
+        assert!(labels.contains(&"rax"));
+        assert!(!labels.contains(&"hl"));
     }
     #[test]
     fn handle_autocomplete_z80_it_provides_reg_comps_in_existing_reg_arg_1() {
@@ -782,14 +3651,153 @@ LoaD and Increment. Copies the byte pointed to by HL to the address pointed to b
 
 ## Forms
 
-- *Z80*: LDI
+- *Z80*: LDI | *Opcodes*: ED A0
 
   + Z80: 16, Z80 + M1: 18, R800: 4, R800 + Wait: 18
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LDI
-",
+
+**Encoding**: `ED A0`",
             );
     }
     #[test]
+    fn handle_hover_z80_it_explains_timings_when_configured() {
+        let mut config = test_config();
+        config.opts.z80_timing_legend = true;
+        test_hover_with_config(
+            "        LD<cursor>I",
+            "ldi [z80]
+LoaD and Increment. Copies the byte pointed to by HL to the address pointed to by DE, then adds 1 to DE and HL and subtracts 1 from BC. P/V is set to (BC!=0), i.e. set when non zero.
+
+## Forms
+
+- *Z80*: LDI | *Opcodes*: ED A0
+
+  + Z80: 16, Z80 + M1: 18, R800: 4, R800 + Wait: 18
+  + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LDI
+
+**Encoding**: `ED A0`
+
+*Timings are in T-states (clock cycles). Z80 + M1 adds the extra wait state inserted on each M1 (opcode fetch) cycle by e.g. the MSX, R800 is the MSX turbo R's CPU and R800 + Wait includes its memory wait states*",
+            &config,
+        );
+    }
+    #[test]
+    fn handle_hover_x86_x86_64_it_glosses_extended_sizes() {
+        let mut config = test_config();
+        config.opts.primary_arch = Some(Arch::X86_64);
+        config.opts.extended_size_gloss = true;
+        test_hover_with_config(
+            "push<cursor>q	%rbp",
+            "PUSH [x86-64]
+Push Value Onto the Stack
+
+## Forms
+
+- *GAS*: pushq | *GO*: PUSHQ
+
+  + [imm8]   extended-size = 8 (sign/zero-extended to 8 bytes)
+- *GAS*: pushq | *GO*: PUSHQ
+
+  + [imm32]  extended-size = 8 (sign/zero-extended to 8 bytes)
+- *GAS*: pushw | *GO*: PUSHW
+
+  + [r16]    input = true   output = false
+- *GAS*: pushq | *GO*: PUSHQ
+
+  + [r64]    input = true   output = false
+- *GAS*: pushw | *GO*: PUSHW
+
+  + [m16]    input = true   output = false
+- *GAS*: pushq | *GO*: PUSHQ
+
+  + [m64]    input = true   output = false
+
+More info: https://www.felixcloutier.com/x86/push
+
+*(also in x86)*",
+            &config,
+        );
+
+        // the compact layout of narrow popups glosses them too
+        config.opts.hover_width = Some(30);
+        let (_, resp) = get_test_hover_resp("push<cursor>q	%rbp", &config).unwrap();
+        let HoverContents::Markup(content) = resp.contents else {
+            panic!("Expected markdown hover contents");
+        };
+        assert!(content
+            .value
+            .contains("- *GAS*: pushq | *GO*: PUSHQ | imm8 (sign/zero-extended to 8 bytes)"));
+    }
+    #[test]
+    fn handle_hover_x86_x86_64_it_only_shows_configured_form_fields() {
+        let mut config = test_config();
+        config.opts.primary_arch = Some(Arch::X86_64);
+        config.opts.hover_form_fields = Some(vec![FormField::Gas]);
+        test_hover_with_config(
+            "push<cursor>q	%rbp",
+            "PUSH [x86-64]
+Push Value Onto the Stack
+
+## Forms
+
+- *GAS*: pushq
+
+  + [imm8]   extended-size = 8
+- *GAS*: pushq
+
+  + [imm32]  extended-size = 8
+- *GAS*: pushw
+
+  + [r16]    input = true   output = false
+- *GAS*: pushq
+
+  + [r64]    input = true   output = false
+- *GAS*: pushw
+
+  + [m16]    input = true   output = false
+- *GAS*: pushq
+
+  + [m64]    input = true   output = false
+
+More info: https://www.felixcloutier.com/x86/push
+
+*(also in x86)*",
+            &config,
+        );
+    }
+    #[test]
+    fn handle_hover_x86_x86_64_it_groups_forms_by_mnemonic() {
+        let mut config = test_config();
+        config.opts.primary_arch = Some(Arch::X86_64);
+        config.opts.hover_forms = FormsLayout::Grouped;
+        test_hover_with_config(
+            "push<cursor>q	%rbp",
+            "PUSH [x86-64]
+Push Value Onto the Stack
+
+## Forms
+
+- pushq / PUSHQ: [imm8], [imm32], [r64], [m64]
+- pushw / PUSHW: [r16], [m16]
+
+More info: https://www.felixcloutier.com/x86/push
+
+*(also in x86)*",
+            &config,
+        );
+    }
+    #[test]
+    fn handle_hover_z80_it_marks_undocumented_regs() {
+        test_hover(
+            "        LD A, IX<cursor>H",
+            "IXH [z80] (undocumented)
+Undocumented high byte of IX (bits 8-15), usable as an 8-bit register with most instructions that accept H or L when prefixed with DD.
+
+Type: General Purpose Register
+Width: 8 bits",
+        );
+    }
+    #[test]
     fn handle_hover_z80_it_provides_instr_info_one_reg_arg() {
         test_hover("        CP<cursor> (HL)         ;COMPARE MEMORY CONTENTS WITH",
             "cp [z80]
@@ -797,41 +3805,44 @@ ComPare. Sets the flags as if a SUB was performed but does not perform it. Legal
 
 ## Forms
 
-- *Z80*: CP (HL)
+- *Z80*: CP (HL) | *Opcode*: BE
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#CP%20%28HL%29
 
-- *Z80*: CP (IX+o)
+- *Z80*: CP (IX+o) | *Opcodes*: DD BE o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#CP%20%28IX%2Bo%29
 
-- *Z80*: CP (IY+o)
+- *Z80*: CP (IY+o) | *Opcodes*: FD BE o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#CP%20%28IY%2Bo%29
 
-- *Z80*: CP n
+- *Z80*: CP n | *Opcodes*: FE n
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#CP%20n
 
-- *Z80*: CP r
+- *Z80*: CP r | *Opcode*: B8+r
 
   + Z80: 4, Z80 + M1: 5, R800: 1, R800 + Wait: 5
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#CP%20r
 
-- *Z80*: CP IXp
+- *Z80*: CP IXp | *Opcodes*: DD B8+p
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#CP%20IXp
 
-- *Z80*: CP IYq
+- *Z80*: CP IYq | *Opcodes*: FD B8+q
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#CP%20IYq
-",
+
+**Encoding**: `BE`
+
+x86, x86-64: `CMP` / z80: `cp` — same operation",
             );
     }
     #[test]
@@ -842,456 +3853,459 @@ LoaD. The basic data load/transfer instruction. Transfers data from the location
 
 ## Forms
 
-- *Z80*: LD (BC), A
+- *Z80*: LD (BC), A | *Opcode*: 02
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28BC%29%2C%20A
 
-- *Z80*: LD (DE), A
+- *Z80*: LD (DE), A | *Opcode*: 12
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28DE%29%2C%20A
 
-- *Z80*: LD (HL), n
+- *Z80*: LD (HL), n | *Opcodes*: 36 n
 
   + Z80: 10, Z80 + M1: 11, R800: 3, R800 + Wait: 11
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28HL%29%2C%20n
 
-- *Z80*: LD (HL), r
+- *Z80*: LD (HL), r | *Opcode*: 70+r
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28HL%29%2C%20r
 
-- *Z80*: LD (IX+o), n
+- *Z80*: LD (IX+o), n | *Opcodes*: DD 36 o n
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28IX%2Bo%29%2C%20n
 
-- *Z80*: LD (IX+o), r
+- *Z80*: LD (IX+o), r | *Opcodes*: DD 70+r o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28IX%2Bo%29%2C%20r
 
-- *Z80*: LD (IY+o), n
+- *Z80*: LD (IY+o), n | *Opcodes*: FD 36 o n
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28IY%2Bo%29%2C%20n
 
-- *Z80*: LD (IY+o), r
+- *Z80*: LD (IY+o), r | *Opcodes*: FD 70+r o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28IY%2Bo%29%2C%20r
 
-- *Z80*: LD (nn), A
+- *Z80*: LD (nn), A | *Opcodes*: 32 nn nn
 
   + Z80: 13, Z80 + M1: 14, R800: 4, R800 + Wait: 14
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28nn%29%2C%20A
 
-- *Z80*: LD (nn), BC
+- *Z80*: LD (nn), BC | *Opcodes*: ED 43 nn nn
 
   + Z80: 20, Z80 + M1: 22, R800: 6, R800 + Wait: 22
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28nn%29%2C%20BC
 
-- *Z80*: LD (nn), DE
+- *Z80*: LD (nn), DE | *Opcodes*: ED 53 nn nn
 
   + Z80: 20, Z80 + M1: 22, R800: 6, R800 + Wait: 22
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28nn%29%2C%20DE
 
-- *Z80*: LD (nn), HL
+- *Z80*: LD (nn), HL | *Opcodes*: 22 nn nn
 
   + Z80: 16, Z80 + M1: 17, R800: 5, R800 + Wait: 17
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28nn%29%2C%20HL
 
-- *Z80*: LD (nn), IX
+- *Z80*: LD (nn), IX | *Opcodes*: DD 22 nn nn
 
   + Z80: 20, Z80 + M1: 22, R800: 6, R800 + Wait: 22
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28nn%29%2C%20IX
 
-- *Z80*: LD (nn), IY
+- *Z80*: LD (nn), IY | *Opcodes*: FD 22 nn nn
 
   + Z80: 20, Z80 + M1: 22, R800: 6, R800 + Wait: 22
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28nn%29%2C%20IY
 
-- *Z80*: LD (nn), SP
+- *Z80*: LD (nn), SP | *Opcodes*: ED 73 nn nn
 
   + Z80: 20, Z80 + M1: 22, R800: 6, R800 + Wait: 22
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20%28nn%29%2C%20SP
 
-- *Z80*: LD A, (BC)
+- *Z80*: LD A, (BC) | *Opcode*: 0A
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20A%2C%20%28BC%29
 
-- *Z80*: LD A, (DE)
+- *Z80*: LD A, (DE) | *Opcode*: 1A
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20A%2C%20%28DE%29
 
-- *Z80*: LD A, (HL)
+- *Z80*: LD A, (HL) | *Opcode*: 7E
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20A%2C%20%28HL%29
 
-- *Z80*: LD A, (IX+o)
+- *Z80*: LD A, (IX+o) | *Opcodes*: DD 7E o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20A%2C%20%28IX%2Bo%29
 
-- *Z80*: LD A, (IY+o)
+- *Z80*: LD A, (IY+o) | *Opcodes*: FD 7E o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20A%2C%20%28IY%2Bo%29
 
-- *Z80*: LD A, (nn)
+- *Z80*: LD A, (nn) | *Opcodes*: 3A nn nn
 
   + Z80: 13, Z80 + M1: 14, R800: 4, R800 + Wait: 14
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20A%2C%20%28nn%29
 
-- *Z80*: LD A, n
+- *Z80*: LD A, n | *Opcodes*: 3E n
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20A%2C%20n
 
-- *Z80*: LD A, r
+- *Z80*: LD A, r | *Opcode*: 78+r
 
   + Z80: 4, Z80 + M1: 5, R800: 1, R800 + Wait: 5
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20A%2C%20r
 
-- *Z80*: LD A, IXp
+- *Z80*: LD A, IXp | *Opcodes*: DD 78+p
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20A%2C%20IXp
 
-- *Z80*: LD A, IYq
+- *Z80*: LD A, IYq | *Opcodes*: FD 78+q
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20A%2C%20IYq
 
-- *Z80*: LD A, I
+- *Z80*: LD A, I | *Opcodes*: ED 57
 
   + Z80: 9, Z80 + M1: 11, R800: 2, R800 + Wait: 11
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20A%2C%20I
 
-- *Z80*: LD A, R
+- *Z80*: LD A, R | *Opcodes*: ED 5F
 
   + Z80: 9, Z80 + M1: 11, R800: 2, R800 + Wait: 11
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20A%2C%20R
 
-- *Z80*: LD B, (HL)
+- *Z80*: LD B, (HL) | *Opcode*: 46
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20B%2C%20%28HL%29
 
-- *Z80*: LD B, (IX+o)
+- *Z80*: LD B, (IX+o) | *Opcodes*: DD 46 o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20B%2C%20%28IX%2Bo%29
 
-- *Z80*: LD B, (IY+o)
+- *Z80*: LD B, (IY+o) | *Opcodes*: FD 46 o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20B%2C%20%28IY%2Bo%29
 
-- *Z80*: LD B, n
+- *Z80*: LD B, n | *Opcodes*: 06 n
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20B%2C%20n
 
-- *Z80*: LD B, r
+- *Z80*: LD B, r | *Opcode*: 40+r
 
   + Z80: 4, Z80 + M1: 5, R800: 1, R800 + Wait: 5
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20B%2C%20r
 
-- *Z80*: LD B, IXp
+- *Z80*: LD B, IXp | *Opcodes*: DD 40+p
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20B%2C%20IXp
 
-- *Z80*: LD B, IYq
+- *Z80*: LD B, IYq | *Opcodes*: FD 40+q
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20B%2C%20IYq
 
-- *Z80*: LD BC, (nn)
+- *Z80*: LD BC, (nn) | *Opcodes*: ED 4B nn nn
 
   + Z80: 20, Z80 + M1: 22, R800: 6, R800 + Wait: 22
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20BC%2C%20%28nn%29
 
-- *Z80*: LD BC, nn
+- *Z80*: LD BC, nn | *Opcodes*: 01 nn nn
 
   + Z80: 10, Z80 + M1: 11, R800: 3, R800 + Wait: 11
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20BC%2C%20nn
 
-- *Z80*: LD C, (HL)
+- *Z80*: LD C, (HL) | *Opcode*: 4E
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20C%2C%20%28HL%29
 
-- *Z80*: LD C, (IX+o)
+- *Z80*: LD C, (IX+o) | *Opcodes*: DD 4E o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20C%2C%20%28IX%2Bo%29
 
-- *Z80*: LD C, (IY+o)
+- *Z80*: LD C, (IY+o) | *Opcodes*: FD 4E o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20C%2C%20%28IY%2Bo%29
 
-- *Z80*: LD C, n
+- *Z80*: LD C, n | *Opcodes*: 0E n
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20C%2C%20n
 
-- *Z80*: LD C, r
+- *Z80*: LD C, r | *Opcode*: 48+r
 
   + Z80: 4, Z80 + M1: 5, R800: 1, R800 + Wait: 5
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20C%2C%20r
 
-- *Z80*: LD C, IXp
+- *Z80*: LD C, IXp | *Opcodes*: DD 48+p
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20C%2C%20IXp
 
-- *Z80*: LD C, IYq
+- *Z80*: LD C, IYq | *Opcodes*: FD 48+q
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20C%2C%20IYq
 
-- *Z80*: LD D, (HL)
+- *Z80*: LD D, (HL) | *Opcode*: 56
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20D%2C%20%28HL%29
 
-- *Z80*: LD D, (IX+o)
+- *Z80*: LD D, (IX+o) | *Opcodes*: DD 56 o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20D%2C%20%28IX%2Bo%29
 
-- *Z80*: LD D, (IY+o)
+- *Z80*: LD D, (IY+o) | *Opcodes*: FD 56 o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20D%2C%20%28IY%2Bo%29
 
-- *Z80*: LD D, n
+- *Z80*: LD D, n | *Opcodes*: 16 n
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20D%2C%20n
 
-- *Z80*: LD D, r
+- *Z80*: LD D, r | *Opcode*: 50+r
 
   + Z80: 4, Z80 + M1: 5, R800: 1, R800 + Wait: 5
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20D%2C%20r
 
-- *Z80*: LD D, IXp
+- *Z80*: LD D, IXp | *Opcodes*: DD 50+p
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20D%2C%20IXp
 
-- *Z80*: LD D, IYq
+- *Z80*: LD D, IYq | *Opcodes*: FD 50+q
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20D%2C%20IYq
 
-- *Z80*: LD DE, (nn)
+- *Z80*: LD DE, (nn) | *Opcodes*: ED 5B nn nn
 
   + Z80: 20, Z80 + M1: 22, R800: 6, R800 + Wait: 22
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20DE%2C%20%28nn%29
 
-- *Z80*: LD DE, nn
+- *Z80*: LD DE, nn | *Opcodes*: 11 nn nn
 
   + Z80: 10, Z80 + M1: 11, R800: 3, R800 + Wait: 11
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20DE%2C%20nn
 
-- *Z80*: LD E, (HL)
+- *Z80*: LD E, (HL) | *Opcode*: 5E
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20E%2C%20%28HL%29
 
-- *Z80*: LD E, (IX+o)
+- *Z80*: LD E, (IX+o) | *Opcodes*: DD 5E o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20E%2C%20%28IX%2Bo%29
 
-- *Z80*: LD E, (IY+o)
+- *Z80*: LD E, (IY+o) | *Opcodes*: FD 5E o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20E%2C%20%28IY%2Bo%29
 
-- *Z80*: LD E, n
+- *Z80*: LD E, n | *Opcodes*: 1E n
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20E%2C%20n
 
-- *Z80*: LD E, r
+- *Z80*: LD E, r | *Opcode*: 58+r
 
   + Z80: 4, Z80 + M1: 5, R800: 1, R800 + Wait: 5
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20E%2C%20r
 
-- *Z80*: LD E, IXp
+- *Z80*: LD E, IXp | *Opcodes*: DD 58+p
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20E%2C%20IXp
 
-- *Z80*: LD E, IYq
+- *Z80*: LD E, IYq | *Opcodes*: FD 58+q
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20E%2C%20IYq
 
-- *Z80*: LD H, (HL)
+- *Z80*: LD H, (HL) | *Opcode*: 66
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20H%2C%20%28HL%29
 
-- *Z80*: LD H, (IX+o)
+- *Z80*: LD H, (IX+o) | *Opcodes*: DD 66 o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20H%2C%20%28IX%2Bo%29
 
-- *Z80*: LD H, (IY+o)
+- *Z80*: LD H, (IY+o) | *Opcodes*: FD 66 o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20H%2C%20%28IY%2Bo%29
 
-- *Z80*: LD H, n
+- *Z80*: LD H, n | *Opcodes*: 26 n
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20H%2C%20n
 
-- *Z80*: LD H, r
+- *Z80*: LD H, r | *Opcode*: 60+r
 
   + Z80: 4, Z80 + M1: 5, R800: 1, R800 + Wait: 5
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20H%2C%20r
 
-- *Z80*: LD HL, (nn)
+- *Z80*: LD HL, (nn) | *Opcodes*: 2A nn nn
 
   + Z80: 16, Z80 + M1: 17, R800: 5, R800 + Wait: 17
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20HL%2C%20%28nn%29
 
-- *Z80*: LD HL, nn
+- *Z80*: LD HL, nn | *Opcodes*: 21 nn nn
 
   + Z80: 10, Z80 + M1: 11, R800: 3, R800 + Wait: 11
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20HL%2C%20nn
 
-- *Z80*: LD I, A
+- *Z80*: LD I, A | *Opcodes*: ED 47
 
   + Z80: 9, Z80 + M1: 11, R800: 2, R800 + Wait: 11
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20I%2C%20A
 
-- *Z80*: LD IX, (nn)
+- *Z80*: LD IX, (nn) | *Opcodes*: DD 2A nn nn
 
   + Z80: 20, Z80 + M1: 22, R800: 6, R800 + Wait: 22
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20IX%2C%20%28nn%29
 
-- *Z80*: LD IX, nn
+- *Z80*: LD IX, nn | *Opcodes*: DD 21 nn nn
 
   + Z80: 14, Z80 + M1: 16, R800: 4, R800 + Wait: 16
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20IX%2C%20nn
 
-- *Z80*: LD IXh, n
+- *Z80*: LD IXh, n | *Opcodes*: DD 26 n
 
   + Z80: 11, Z80 + M1: 13, R800: 3, R800 + Wait: 13
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20IXh%2C%20n
 
-- *Z80*: LD IXh, p
+- *Z80*: LD IXh, p | *Opcodes*: DD 60+p
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20IXh%2C%20p
 
-- *Z80*: LD IXl, n
+- *Z80*: LD IXl, n | *Opcodes*: DD 2E n
 
   + Z80: 11, Z80 + M1: 13, R800: 3, R800 + Wait: 13
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20IXl%2C%20n
 
-- *Z80*: LD IXl, p
+- *Z80*: LD IXl, p | *Opcodes*: DD 68+p
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20IXl%2C%20p
 
-- *Z80*: LD IY, (nn)
+- *Z80*: LD IY, (nn) | *Opcodes*: FD 2A nn nn
 
   + Z80: 20, Z80 + M1: 22, R800: 6, R800 + Wait: 22
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20IY%2C%20%28nn%29
 
-- *Z80*: LD IY, nn
+- *Z80*: LD IY, nn | *Opcodes*: FD 21 nn nn
 
   + Z80: 14, Z80 + M1: 16, R800: 4, R800 + Wait: 16
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20IY%2C%20nn
 
-- *Z80*: LD IYh, n
+- *Z80*: LD IYh, n | *Opcodes*: FD 26 n
 
   + Z80: 11, Z80 + M1: 13, R800: 3, R800 + Wait: 13
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20IYh%2C%20n
 
-- *Z80*: LD IYh, q
+- *Z80*: LD IYh, q | *Opcodes*: FD 60+q
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20IYh%2C%20q
 
-- *Z80*: LD IYl, n
+- *Z80*: LD IYl, n | *Opcodes*: FD 2E n
 
   + Z80: 11, Z80 + M1: 13, R800: 3, R800 + Wait: 13
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20IYl%2C%20n
 
-- *Z80*: LD IYl, q
+- *Z80*: LD IYl, q | *Opcodes*: FD 68+q
 
   + Z80: 8, Z80 + M1: 10, R800: 2, R800 + Wait: 10
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20IYl%2C%20q
 
-- *Z80*: LD L, (HL)
+- *Z80*: LD L, (HL) | *Opcode*: 6E
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20L%2C%20%28HL%29
 
-- *Z80*: LD L, (IX+o)
+- *Z80*: LD L, (IX+o) | *Opcodes*: DD 6E o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20L%2C%20%28IX%2Bo%29
 
-- *Z80*: LD L, (IY+o)
+- *Z80*: LD L, (IY+o) | *Opcodes*: FD 6E o
 
   + Z80: 19, Z80 + M1: 21, R800: 5, R800 + Wait: 21
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20L%2C%20%28IY%2Bo%29
 
-- *Z80*: LD L, n
+- *Z80*: LD L, n | *Opcodes*: 2E n
 
   + Z80: 7, Z80 + M1: 8, R800: 2, R800 + Wait: 8
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20L%2C%20n
 
-- *Z80*: LD L, r
+- *Z80*: LD L, r | *Opcode*: 68+r
 
   + Z80: 4, Z80 + M1: 5, R800: 1, R800 + Wait: 5
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20L%2C%20r
 
-- *Z80*: LD R, A
+- *Z80*: LD R, A | *Opcodes*: ED 4F
 
   + Z80: 9, Z80 + M1: 11, R800: 2, R800 + Wait: 11
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20R%2C%20A
 
-- *Z80*: LD SP, (nn)
+- *Z80*: LD SP, (nn) | *Opcodes*: ED 7B nn nn
 
   + Z80: 20, Z80 + M1: 22, R800: 6, R800 + Wait: 22
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20SP%2C%20%28nn%29
 
-- *Z80*: LD SP, HL
+- *Z80*: LD SP, HL | *Opcode*: F9
 
   + Z80: 6, Z80 + M1: 7, R800: 1, R800 + Wait: 7
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20SP%2C%20HL
 
-- *Z80*: LD SP, IX
+- *Z80*: LD SP, IX | *Opcodes*: DD F9
 
   + Z80: 10, Z80 + M1: 12, R800: 2, R800 + Wait: 12
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20SP%2C%20IX
 
-- *Z80*: LD SP, IY
+- *Z80*: LD SP, IY | *Opcodes*: FD F9
 
   + Z80: 10, Z80 + M1: 12, R800: 2, R800 + Wait: 12
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20SP%2C%20IY
 
-- *Z80*: LD SP, nn
+- *Z80*: LD SP, nn | *Opcodes*: 31 nn nn
 
   + Z80: 10, Z80 + M1: 11, R800: 3, R800 + Wait: 11
   + More info: https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20SP%2C%20nn
-"
+
+**Encoding**: `21 nn nn`
+
+x86, x86-64: `MOV` / z80: `ld` — same operation"
             );
     }
 
@@ -1416,6 +4430,34 @@ Width: 8 bits",
         }
     }
     #[test]
+    fn serialized_aarch64_registers_are_up_to_date() {
+        let mut cmp_map = HashMap::new();
+        let aarch64_regs_ser = include_bytes!("../docs_store/registers/serialized/aarch64");
+        let ser_vec = bincode::deserialize::<Vec<Register>>(aarch64_regs_ser).unwrap();
+
+        let aarch64_regs_raw = include_str!("../docs_store/registers/raw/aarch64.xml");
+        let raw_vec = populate_registers(aarch64_regs_raw).unwrap();
+
+        for reg in ser_vec {
+            *cmp_map.entry(reg.clone()).or_insert(0) += 1;
+        }
+        for reg in raw_vec {
+            let entry = cmp_map.get_mut(&reg).unwrap();
+            if *entry == 0 {
+                panic!(
+                    "Expected at least one more instruction entry for {:?}, but the count is 0",
+                    reg
+                );
+            }
+            *entry -= 1;
+        }
+        for (reg, count) in cmp_map.iter() {
+            if *count != 0 {
+                panic!("Expected count to be 0, found {count} for {:?}", reg);
+            }
+        }
+    }
+    #[test]
     fn serialized_x86_instructions_are_up_to_date() {
         let mut cmp_map = HashMap::new();
         let x86_instrs_ser = include_bytes!("../docs_store/opcodes/serialized/x86");
@@ -1427,10 +4469,10 @@ Width: 8 bits",
         // HACK: To work around the difference in extra info urls between testing
         // and production
         for instr in ser_vec.iter_mut() {
-            instr.url = None;
+            instr.urls.clear();
         }
         for instr in raw_vec.iter_mut() {
-            instr.url = None;
+            instr.urls.clear();
         }
 
         for instr in ser_vec {
@@ -1464,10 +4506,10 @@ Width: 8 bits",
         // HACK: To work around the difference in extra info urls between testing
         // and production
         for instr in ser_vec.iter_mut() {
-            instr.url = None;
+            instr.urls.clear();
         }
         for instr in raw_vec.iter_mut() {
-            instr.url = None;
+            instr.urls.clear();
         }
 
         for instr in ser_vec {
@@ -1490,6 +4532,61 @@ Width: 8 bits",
         }
     }
     #[test]
+    fn it_lists_instr_operand_types() {
+        let x86_64_instrs_ser = include_bytes!("../docs_store/opcodes/serialized/x86_64");
+        let instrs = bincode::deserialize::<Vec<Instruction>>(x86_64_instrs_ser).unwrap();
+        let push = instrs
+            .iter()
+            .find(|instr| instr.name.eq_ignore_ascii_case("push"))
+            .unwrap();
+        let operand_types = push.operand_types();
+
+        assert_eq!(operand_types.len(), push.forms.len());
+        assert!(operand_types.contains(&vec![OperandType::imm8]));
+        assert!(operand_types.contains(&vec![OperandType::r64]));
+        assert!(operand_types.contains(&vec![OperandType::m64]));
+        assert_eq!(OperandType::r64.to_string(), "r64");
+        assert_eq!(OperandType::xmm_k_z.to_string(), "xmm{k}{z}");
+        assert_eq!(
+            OperandType::m128_m32bcst.description(),
+            "128-bit memory or a broadcast 32-bit element"
+        );
+    }
+    #[test]
+    fn it_lists_instr_supported_assemblers() {
+        let x86_64_instrs_ser = include_bytes!("../docs_store/opcodes/serialized/x86_64");
+        let instrs = bincode::deserialize::<Vec<Instruction>>(x86_64_instrs_ser).unwrap();
+        let find = |name: &str| {
+            instrs
+                .iter()
+                .find(|instr| instr.name.eq_ignore_ascii_case(name))
+                .unwrap()
+        };
+        let mov = find("mov");
+        assert_eq!(
+            mov.supported_assemblers(),
+            HashSet::from([Assembler::Gas, Assembler::Go])
+        );
+
+        let mut gas_only = mov.clone();
+        for form in &mut gas_only.forms {
+            form.go_name = None;
+        }
+        assert_eq!(
+            gas_only.supported_assemblers(),
+            HashSet::from([Assembler::Gas])
+        );
+        gas_only.forms.clear();
+        assert!(gas_only.supported_assemblers().is_empty());
+
+        let z80_instrs_ser = include_bytes!("../docs_store/opcodes/serialized/z80");
+        let z80_instrs = bincode::deserialize::<Vec<Instruction>>(z80_instrs_ser).unwrap();
+        assert_eq!(
+            z80_instrs[0].supported_assemblers(),
+            HashSet::from([Assembler::Gas])
+        );
+    }
+    #[test]
     fn serialized_z80_instructions_are_up_to_date() {
         let mut cmp_map = HashMap::new();
         let z80_instrs_ser = include_bytes!("../docs_store/opcodes/serialized/z80");
@@ -0,0 +1,269 @@
+//! Loading of the bundled documentation into the stores the request handlers use,
+//! shared by the tests and the benchmarks
+
+use anyhow::Result;
+use lsp_types::{CompletionItem, CompletionItemKind};
+
+use crate::{
+    get_completes, instr_filter_targets, load_directives, populate_custom_instructions,
+    populate_name_to_directive_map, populate_name_to_instruction_map,
+    populate_name_to_register_map, populate_prefix_index, populate_same_operation_map, Arch,
+    Assembler, Assemblers, CompletionCase, ConfigOptions, Directive, Instruction, InstructionSets,
+    NameToDirectiveMap, NameToInstructionMap, NameToRegisterMap, PrefixIndex, Register,
+    SameOperationMap, TargetConfig,
+};
+
+#[derive(Debug)]
+pub struct GlobalInfo {
+    pub x86_instructions: Vec<Instruction>,
+    pub x86_64_instructions: Vec<Instruction>,
+    pub x86_registers: Vec<Register>,
+    pub x86_64_registers: Vec<Register>,
+    pub z80_instructions: Vec<Instruction>,
+    pub custom_instructions: Vec<Instruction>,
+    pub z80_registers: Vec<Register>,
+    pub aarch64_registers: Vec<Register>,
+    pub gas_directives: Vec<Directive>,
+}
+
+#[derive(Debug)]
+pub struct GlobalVars<'a> {
+    pub names_to_instructions: NameToInstructionMap<'a>,
+    pub names_to_registers: NameToRegisterMap<'a>,
+    pub names_to_directives: NameToDirectiveMap<'a>,
+    pub same_operations: SameOperationMap<'a>,
+    pub instruction_prefixes: PrefixIndex<'a, Arch, Instruction>,
+    pub register_prefixes: PrefixIndex<'a, Arch, Register>,
+    pub directive_prefixes: PrefixIndex<'a, Assembler, Directive>,
+    pub instr_completion_items: Vec<CompletionItem>,
+    pub reg_completion_items: Vec<CompletionItem>,
+    pub directive_completion_items: Vec<CompletionItem>,
+}
+
+impl GlobalInfo {
+    fn new() -> Self {
+        Self {
+            x86_instructions: Vec::new(),
+            x86_64_instructions: Vec::new(),
+            x86_registers: Vec::new(),
+            x86_64_registers: Vec::new(),
+            z80_instructions: Vec::new(),
+            custom_instructions: Vec::new(),
+            z80_registers: Vec::new(),
+            aarch64_registers: Vec::new(),
+            gas_directives: Vec::new(),
+        }
+    }
+}
+
+impl GlobalVars<'_> {
+    fn new() -> Self {
+        Self {
+            names_to_instructions: NameToInstructionMap::new(),
+            names_to_registers: NameToRegisterMap::new(),
+            names_to_directives: NameToDirectiveMap::new(),
+            same_operations: SameOperationMap::new(),
+            instruction_prefixes: PrefixIndex::new(),
+            register_prefixes: PrefixIndex::new(),
+            directive_prefixes: PrefixIndex::new(),
+            instr_completion_items: Vec::new(),
+            reg_completion_items: Vec::new(),
+            directive_completion_items: Vec::new(),
+        }
+    }
+}
+
+/// Returns a config enabling every assembler and instruction set
+#[must_use]
+pub fn test_config() -> TargetConfig {
+    TargetConfig {
+        version: "0.1".to_string(),
+        assemblers: Assemblers {
+            gas: true,
+            go: true,
+            z80: true,
+        },
+        instruction_sets: InstructionSets {
+            x86: true,
+            x86_64: true,
+            z80: true,
+            aarch64: true,
+        },
+        enabled_isa_extensions: None,
+        opts: ConfigOptions::default(),
+    }
+}
+
+/// Loads the bundled instructions, registers and directives, filtering the instructions
+/// by `config` (`test_config()` when `None`). Like the server, registers are only loaded
+/// for the enabled instruction sets
+///
+/// # Errors
+///
+/// Returns `Err` if any of the bundled data fails to deserialize
+pub fn init_global_info(config: Option<TargetConfig>) -> Result<GlobalInfo> {
+    let mut info = GlobalInfo::new();
+
+    let target_config = config.unwrap_or_else(test_config);
+
+    info.x86_instructions = {
+        let x86_instrs = include_bytes!("../docs_store/opcodes/serialized/x86");
+        bincode::deserialize::<Vec<Instruction>>(x86_instrs)?
+            .into_iter()
+            .map(|instruction| {
+                // filter out assemblers by user config
+                instr_filter_targets(&instruction, &target_config)
+            })
+            .filter(|instruction| !instruction.forms.is_empty())
+            .collect()
+    };
+
+    info.x86_64_instructions = {
+        let x86_64_instrs = include_bytes!("../docs_store/opcodes/serialized/x86_64");
+        bincode::deserialize::<Vec<Instruction>>(x86_64_instrs)?
+            .into_iter()
+            .map(|instruction| {
+                // filter out assemblers by user config
+                instr_filter_targets(&instruction, &target_config)
+            })
+            .filter(|instruction| !instruction.forms.is_empty())
+            .collect()
+    };
+
+    info.z80_instructions = {
+        let z80_instrs = include_bytes!("../docs_store/opcodes/serialized/z80");
+        bincode::deserialize::<Vec<Instruction>>(z80_instrs)?
+            .into_iter()
+            .map(|instruction| {
+                // filter out assemblers by user config
+                instr_filter_targets(&instruction, &target_config)
+            })
+            .filter(|instruction| !instruction.forms.is_empty())
+            .collect()
+    };
+
+    info.custom_instructions = target_config.custom_instructions();
+
+    info.x86_registers = if target_config.instruction_sets.x86 {
+        let regs_x86 = include_bytes!("../docs_store/registers/serialized/x86");
+        bincode::deserialize(regs_x86)?
+    } else {
+        Vec::new()
+    };
+
+    info.x86_64_registers = if target_config.instruction_sets.x86_64 {
+        let regs_x86_64 = include_bytes!("../docs_store/registers/serialized/x86_64");
+        bincode::deserialize(regs_x86_64)?
+    } else {
+        Vec::new()
+    };
+
+    info.z80_registers = if target_config.instruction_sets.z80 {
+        let regs_z80 = include_bytes!("../docs_store/registers/serialized/z80");
+        bincode::deserialize(regs_z80)?
+    } else {
+        Vec::new()
+    };
+
+    info.aarch64_registers = if target_config.instruction_sets.aarch64 {
+        let regs_aarch64 = include_bytes!("../docs_store/registers/serialized/aarch64");
+        bincode::deserialize(regs_aarch64)?
+    } else {
+        Vec::new()
+    };
+
+    info.gas_directives = {
+        let gas_dirs = include_bytes!("../docs_store/directives/serialized/gas");
+        load_directives(gas_dirs, target_config.opts.extra_directives.as_deref())?
+    };
+
+    Ok(info)
+}
+
+/// Builds the name maps and completion items over the data loaded into `info`
+///
+/// # Errors
+///
+/// Currently infallible, returns `Result` to match `init_global_info`
+pub fn init_store(info: &GlobalInfo) -> Result<GlobalVars<'_>> {
+    let mut store = GlobalVars::new();
+
+    populate_name_to_instruction_map(
+        Arch::X86,
+        &info.x86_instructions,
+        &mut store.names_to_instructions,
+    );
+
+    populate_name_to_instruction_map(
+        Arch::X86_64,
+        &info.x86_64_instructions,
+        &mut store.names_to_instructions,
+    );
+
+    populate_name_to_instruction_map(
+        Arch::Z80,
+        &info.z80_instructions,
+        &mut store.names_to_instructions,
+    );
+
+    populate_custom_instructions(&info.custom_instructions, &mut store.names_to_instructions);
+
+    populate_same_operation_map(&store.names_to_instructions, &mut store.same_operations);
+
+    populate_name_to_register_map(
+        Arch::X86,
+        &info.x86_registers,
+        &mut store.names_to_registers,
+    );
+
+    populate_name_to_register_map(
+        Arch::X86_64,
+        &info.x86_64_registers,
+        &mut store.names_to_registers,
+    );
+
+    populate_name_to_register_map(
+        Arch::Z80,
+        &info.z80_registers,
+        &mut store.names_to_registers,
+    );
+
+    populate_name_to_register_map(
+        Arch::AArch64,
+        &info.aarch64_registers,
+        &mut store.names_to_registers,
+    );
+
+    populate_name_to_directive_map(
+        Assembler::Gas,
+        &info.gas_directives,
+        &mut store.names_to_directives,
+    );
+
+    populate_prefix_index(
+        &store.names_to_instructions,
+        &mut store.instruction_prefixes,
+    );
+    populate_prefix_index(&store.names_to_registers, &mut store.register_prefixes);
+    populate_prefix_index(&store.names_to_directives, &mut store.directive_prefixes);
+
+    store.instr_completion_items = get_completes(
+        &store.names_to_instructions,
+        Some(CompletionItemKind::OPERATOR),
+        CompletionCase::AsIs,
+    );
+
+    store.reg_completion_items = get_completes(
+        &store.names_to_registers,
+        Some(CompletionItemKind::VARIABLE),
+        CompletionCase::AsIs,
+    );
+
+    store.directive_completion_items = get_completes(
+        &store.names_to_directives,
+        Some(CompletionItemKind::KEYWORD),
+        CompletionCase::AsIs,
+    );
+
+    Ok(store)
+}
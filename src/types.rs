@@ -1,11 +1,12 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::Display,
+    path::PathBuf,
     str::FromStr,
 };
 
-use lsp_types::Uri;
-use serde::{Deserialize, Serialize};
+use lsp_types::{request::Request, Diagnostic, TextDocumentIdentifier, Uri};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 use strum_macros::{AsRefStr, Display, EnumString};
 use tree_sitter::{Parser, Tree};
 
@@ -16,11 +17,37 @@ pub struct Instruction {
     pub alt_names: Vec<String>,
     pub summary: String,
     pub forms: Vec<InstructionForm>,
-    pub url: Option<String>,
+    /// Links to further documentation, e.g. a vendor manual and a community reference,
+    /// each shown as its own "More info" line in hover
+    pub urls: Vec<String>,
     pub arch: Option<Arch>,
+    /// Whether the instruction can only be executed at ring 0
+    pub privileged: bool,
+    /// What the instruction's operands usually are, when more specific than its forms
+    /// suggest (e.g. jump targets are labels rather than raw offsets)
+    pub operand_kind: Option<OperandKind>,
+    /// Identifies the operation across architectures, so instructions spelled differently
+    /// by each (e.g. x86's `JMP` and z80's `jp`) can be related
+    pub canonical: Option<String>,
+    /// Lines of example usage, shown in hover after the forms
+    pub examples: Vec<String>,
+    /// Caveats worth knowing when using the instruction (e.g. that it's serializing),
+    /// shown in hover after the forms
+    pub notes: Vec<String>,
 }
 
-impl Hoverable for &Instruction {}
+impl Hoverable for &Instruction {
+    fn to_markdown(
+        &self,
+        arch: Arch,
+        width: Option<usize>,
+        form_fields: Option<&[FormField]>,
+        layout: FormsLayout,
+        gloss_extended_sizes: bool,
+    ) -> String {
+        Instruction::to_markdown(self, arch, width, form_fields, layout, gloss_extended_sizes)
+    }
+}
 impl Completable for &Instruction {}
 
 impl Default for Instruction {
@@ -29,52 +56,206 @@ impl Default for Instruction {
         let alt_names = vec![];
         let summary = String::new();
         let forms = vec![];
-        let url = None;
+        let urls = vec![];
         let arch = None;
+        let privileged = false;
+        let operand_kind = None;
+        let canonical = None;
+        let examples = vec![];
+        let notes = vec![];
 
         Self {
             name,
             alt_names,
             summary,
             forms,
-            url,
+            urls,
             arch,
+            privileged,
+            operand_kind,
+            canonical,
+            examples,
+            notes,
         }
     }
 }
 
 impl std::fmt::Display for Instruction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.markdown(self.arch, f.width(), None, FormsLayout::Verbose, false)
+        )
+    }
+}
+
+impl Instruction {
+    /// Returns the operand types of each of the instruction's forms, in order
+    #[must_use]
+    pub fn operand_types(&self) -> Vec<Vec<OperandType>> {
+        self.forms
+            .iter()
+            .map(|form| {
+                form.operands
+                    .iter()
+                    .map(|operand| operand.type_.clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns the assemblers that can emit any of the instruction's forms, i.e. those
+    /// whose mnemonic is set on at least one form. Forms with a z80 mnemonic count
+    /// towards GAS, like in [`Arch::default_assemblers`]
+    #[must_use]
+    pub fn supported_assemblers(&self) -> HashSet<Assembler> {
+        let mut assemblers = HashSet::new();
+        for form in &self.forms {
+            if form.gas_name.is_some() || form.z80_name.is_some() {
+                assemblers.insert(Assembler::Gas);
+            }
+            if form.go_name.is_some() {
+                assemblers.insert(Assembler::Go);
+            }
+        }
+
+        assemblers
+    }
+
+    /// Renders the instruction as markdown, exactly as shown in hover for `arch`. Forms
+    /// are listed according to `layout`, laid out for a popup of `width` characters, if
+    /// given, and only annotated with `form_fields` if given. See
+    /// `InstructionForm::to_markdown` for `gloss_extended_sizes`
+    #[must_use]
+    pub fn to_markdown(
+        &self,
+        arch: Arch,
+        width: Option<usize>,
+        form_fields: Option<&[FormField]>,
+        layout: FormsLayout,
+        gloss_extended_sizes: bool,
+    ) -> String {
+        self.markdown(Some(arch), width, form_fields, layout, gloss_extended_sizes)
+    }
+
+    fn markdown(
+        &self,
+        arch: Option<Arch>,
+        width: Option<usize>,
+        form_fields: Option<&[FormField]>,
+        layout: FormsLayout,
+        gloss_extended_sizes: bool,
+    ) -> String {
         // basic fields
-        let header: String;
-        if let Some(arch) = &self.arch {
+        let mut header: String;
+        if let Some(arch) = arch {
             header = format!("{} [{}]", &self.name, arch.as_ref());
         } else {
             header = self.name.clone();
         }
+        if self.privileged {
+            header += "\n**Privileged (ring 0)**";
+        }
 
-        let mut v: Vec<&str> = vec![&header, &self.summary, "\n", "## Forms", "\n"];
+        let mut v: Vec<&str> = vec![&header, &self.summary];
+        // custom instructions have no forms
+        if !self.forms.is_empty() {
+            v.extend(["\n", "## Forms", "\n"]);
+        }
 
-        // instruction forms
-        let instruction_form_strs: Vec<String> =
-            self.forms.iter().map(|f| format!("{f}")).collect();
+        // instruction forms, passing along any requested width and annotations
+        let instruction_form_strs: Vec<String> = match layout {
+            FormsLayout::Verbose => self
+                .forms
+                .iter()
+                .map(|form| form.to_markdown(width, form_fields, gloss_extended_sizes))
+                .collect(),
+            FormsLayout::Grouped => {
+                self.grouped_forms_markdown(width, form_fields, gloss_extended_sizes)
+            }
+        };
         for item in &instruction_form_strs {
             v.push(item.as_str());
         }
 
-        // url
-        let more_info: String;
-        match &self.url {
-            Some(url) => {
-                more_info = format!("\nMore info: {url}");
-                v.push(&more_info);
+        // notes
+        let notes: String;
+        if !self.notes.is_empty() {
+            notes = self
+                .notes
+                .iter()
+                .map(|note| format!("- {note}"))
+                .collect::<Vec<String>>()
+                .join("\n");
+            v.extend(["\n## Notes\n", &notes]);
+        }
+
+        // examples
+        let examples: String;
+        if !self.examples.is_empty() {
+            examples = format!("```asm\n{}\n```", self.examples.join("\n"));
+            v.extend(["\n## Examples\n", &examples]);
+        }
+
+        // urls
+        let more_info: Vec<String> = more_info_lines(&self.urls)
+            .map(|line| format!("\n{line}"))
+            .collect();
+        v.extend(more_info.iter().map(String::as_str));
+
+        v.join("\n")
+    }
+
+    /// Renders the forms sharing a GAS/Go mnemonic as a single line listing each of
+    /// their operand signatures, e.g. "- pushq / PUSHQ: [imm8], [imm32], [r64], [m64]".
+    /// Forms without either mnemonic (e.g. z80's) are rendered as usual
+    fn grouped_forms_markdown(
+        &self,
+        width: Option<usize>,
+        form_fields: Option<&[FormField]>,
+        gloss_extended_sizes: bool,
+    ) -> Vec<String> {
+        let shown = |field: FormField| form_fields.is_none_or(|fields| fields.contains(&field));
+        // groups in the order their first form appears in
+        let mut groups: Vec<(_, Vec<String>)> = Vec::new();
+        let mut lines = Vec::new();
+        for (form, operand_types) in self.forms.iter().zip(self.operand_types()) {
+            let key = (form.gas_name.as_deref(), form.go_name.as_deref());
+            if key == (None, None) {
+                lines.push(form.to_markdown(width, form_fields, gloss_extended_sizes));
+                continue;
+            }
+            let signature = format!(
+                "[{}]",
+                operand_types
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
+            match groups.iter_mut().find(|(group_key, _)| *group_key == key) {
+                Some((_, signatures)) => signatures.push(signature),
+                None => groups.push((key, vec![signature])),
             }
-            None => {}
         }
 
-        let s = v.join("\n");
-        write!(f, "{s}")?;
-        Ok(())
+        let group_lines = groups.iter().map(|((gas_name, go_name), signatures)| {
+            let names: Vec<&str> = [
+                gas_name.filter(|_| shown(FormField::Gas)),
+                go_name.filter(|_| shown(FormField::Go)),
+            ]
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+            if names.is_empty() {
+                format!("- {}", signatures.join(", "))
+            } else {
+                format!("- {}: {}", names.join(" / "), signatures.join(", "))
+            }
+        });
+        group_lines.chain(lines).collect()
     }
 }
 
@@ -128,6 +309,10 @@ pub struct InstructionForm {
     pub nacl_zero_extends_outputs: Option<bool>,
     pub isa: Option<ISA>,
     pub operands: Vec<Operand>,
+    /// Registers the form reads or writes without naming them (e.g. `rax` for `mulq`)
+    pub implicit_operands: Vec<ImplicitOperand>,
+    /// Encoding template in Intel's notation (e.g. "REX.W + 0F AF /r"), if known
+    pub x86_encoding: Option<String>,
     // --- Z80-Specific Information ---
     pub z80_name: Option<String>,
     pub z80_form: Option<String>,
@@ -135,50 +320,197 @@ pub struct InstructionForm {
     pub z80_timing: Option<Z80Timing>,
     // --- Assembler/Architecture Agnostic Info ---
     pub urls: Vec<String>,
+    /// Operating modes the form can be executed in, `None` if unrestricted or unknown
+    pub modes: Option<Modes>,
+    /// CPU generation the form first appeared in (e.g. "Haswell"), if known
+    pub introduced_in: Option<String>,
+}
+
+/// Annotations of an instruction form which can be picked to show in hover, see
+/// `ConfigOptions::hover_form_fields`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FormField {
+    /// The form's GAS mnemonic
+    Gas,
+    /// The form's Go assembler mnemonic
+    Go,
+    /// Whether the form uses the MMX or FPU register state
+    Mmx,
+    /// Whether the form uses SSE or AVX encoding for XMM registers
+    Xmm,
+    /// The ISA extension the form requires
+    Isa,
+    /// The form's x86 encoding template. Unlike the other annotations, it's only shown
+    /// when explicitly picked
+    Encoding,
+}
+
+/// How an instruction's forms are listed in hover, see `ConfigOptions::hover_forms`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FormsLayout {
+    /// Each form is listed with its annotations and operand details
+    #[default]
+    Verbose,
+    /// Forms sharing a GAS/Go mnemonic are collapsed into a single line listing
+    /// their operand signatures
+    Grouped,
+}
+
+/// Case of the names offered by completion, see `ConfigOptions::completion_case`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompletionCase {
+    /// Names are offered as they're written in the bundled docs
+    #[default]
+    AsIs,
+    Upper,
+    Lower,
+}
+
+impl CompletionCase {
+    /// Returns `name` in this case
+    #[must_use]
+    pub fn apply(self, name: &str) -> String {
+        match self {
+            Self::AsIs => name.to_string(),
+            Self::Upper => name.to_uppercase(),
+            Self::Lower => name.to_lowercase(),
+        }
+    }
+}
+
+/// Documentation z80 instruction forms link to, see `ConfigOptions::z80_docs`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Z80DocsSource {
+    /// Zilog's Z80 CPU user manual, anchored at the form (e.g. `#LD%20A%2C%20n`)
+    #[default]
+    Zilog,
+    /// z80 Heaven's page on the form's mnemonic
+    Z80Heaven,
+    /// ClrHome's table of z80 opcodes
+    ClrHome,
 }
 
+impl Z80DocsSource {
+    /// Returns the URL documenting the z80 instruction form `form` (e.g. `LD A, n`)
+    #[must_use]
+    pub fn url(self, form: &str) -> String {
+        match self {
+            Self::Zilog => format!(
+                "https://www.zilog.com/docs/z80/z80cpu_um.pdf#{}",
+                url_escape::encode_www_form_urlencoded(form)
+            ),
+            Self::Z80Heaven => {
+                let mnemonic = form.split_whitespace().next().unwrap_or_default();
+                format!(
+                    "http://z80-heaven.wikidot.com/instructions-set:{}",
+                    mnemonic.to_lowercase()
+                )
+            }
+            Self::ClrHome => String::from("https://clrhome.org/table/"),
+        }
+    }
+}
+
+/// Narrowest width (in characters) the tabular operand layout fits in. When formatted
+/// with a smaller width (e.g. `{:30}`), forms switch to a compact one line layout
+pub const OPERAND_TABLE_WIDTH: usize = 42;
+
 impl std::fmt::Display for InstructionForm {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_markdown(f.width(), None, false))
+    }
+}
+
+impl InstructionForm {
+    /// Renders the form as markdown, as listed under an instruction's forms in hover.
+    /// Narrower `width`s than [`OPERAND_TABLE_WIDTH`] use the compact one line layout.
+    /// Only the annotations in `form_fields` are included, or all of them if `None`.
+    /// With `gloss_extended_sizes`, operands' extended sizes are followed by a note on
+    /// what they mean, see `ConfigOptions::extended_size_gloss`
+    #[must_use]
+    pub fn to_markdown(
+        &self,
+        width: Option<usize>,
+        form_fields: Option<&[FormField]>,
+        gloss_extended_sizes: bool,
+    ) -> String {
+        let compact = width.is_some_and(|width| width < OPERAND_TABLE_WIDTH);
+        let shown = |field: FormField| form_fields.is_none_or(|fields| fields.contains(&field));
         let mut s = String::new();
-        if let Some(val) = &self.gas_name {
+        if let Some(val) = self.gas_name.as_ref().filter(|_| shown(FormField::Gas)) {
             s += &format!("*GAS*: {val} | ");
         }
-        if let Some(val) = &self.go_name {
+        if let Some(val) = self.go_name.as_ref().filter(|_| shown(FormField::Go)) {
             s += &format!("*GO*: {val} | ");
         }
         if let Some(val) = &self.z80_form {
             s += &format!("*Z80*: {val} | ");
         }
 
-        if let Some(val) = &self.mmx_mode {
+        if let Some(val) = self.mmx_mode.as_ref().filter(|_| shown(FormField::Mmx)) {
             s += &(format!("*MMX*: {} | ", val.as_ref()));
         }
-        if let Some(val) = &self.xmm_mode {
+        if let Some(val) = self.xmm_mode.as_ref().filter(|_| shown(FormField::Xmm)) {
             s += &(format!("*XMM*: {} | ", val.as_ref()));
         }
         if let Some(val) = &self.z80_opcode {
-            if val.contains(',') {
+            if val.contains(' ') {
                 s += &format!("*Opcodes*: {val} | ");
             } else {
                 s += &format!("*Opcode*: {val} | ");
             }
         }
+        if let Some(val) = self
+            .x86_encoding
+            .as_ref()
+            .filter(|_| form_fields.is_some_and(|fields| fields.contains(&FormField::Encoding)))
+        {
+            s += &format!("*Encoding*: {val} | ");
+        }
 
         // cancelling inputs
         // nacl_version
         // nacl_zero_extends_outputs
 
         // ISA
-        if let Some(val) = &self.isa {
+        if let Some(val) = self.isa.as_ref().filter(|_| shown(FormField::Isa)) {
             s += &format!("*ISA*: {} | ", val.as_ref());
         }
 
+        // Operands, inline with the rest of the form in the compact layout
+        if compact && !self.operands.is_empty() {
+            let operands: Vec<String> = self
+                .operands
+                .iter()
+                .map(|op| {
+                    let access = match (op.input, op.output) {
+                        (Some(true), Some(true)) => " rw",
+                        (Some(true), _) => " r",
+                        (_, Some(true)) => " w",
+                        _ => "",
+                    };
+                    let gloss = match op.extended_size {
+                        Some(size) if gloss_extended_sizes => {
+                            format!(" (sign/zero-extended to {size} bytes)")
+                        }
+                        _ => String::new(),
+                    };
+                    format!("{}{access}{gloss}", op.type_.as_ref())
+                })
+                .collect();
+            s += &format!("{} | ", operands.join(", "));
+        }
+
         if !s.is_empty() {
             s = format!("- {}\n\n", &s[..s.len() - 3]);
         }
 
         // Operands
-        let operands_str: String = if !self.operands.is_empty() {
+        let operands_str: String = if !compact && !self.operands.is_empty() {
             self.operands
                 .iter()
                 .map(|op| {
@@ -191,6 +523,9 @@ impl std::fmt::Display for InstructionForm {
                     }
                     if let Some(extended_size) = op.extended_size {
                         s += &format!(" extended-size = {extended_size}");
+                        if gloss_extended_sizes {
+                            s += &format!(" (sign/zero-extended to {extended_size} bytes)");
+                        }
                     }
 
                     s.trim_end().to_owned()
@@ -203,15 +538,84 @@ impl std::fmt::Display for InstructionForm {
 
         s += &operands_str;
 
+        if !self.implicit_operands.is_empty() {
+            let implicit: Vec<String> = self
+                .implicit_operands
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            s += &format!("\n  + Implicit: {}", implicit.join(", "));
+        }
+
         if let Some(ref timing) = self.z80_timing {
             s += &format!("\n  + {timing}");
         }
 
-        for url in &self.urls {
-            s += &format!("\n  + More info: {url}\n");
+        if let Some(ref modes) = self.modes {
+            s += &format!("\n  + {modes}");
         }
 
-        write!(f, "{s}")?;
+        if let Some(ref introduced_in) = self.introduced_in {
+            s += &format!("\n  + Introduced: {introduced_in}");
+        }
+
+        for line in more_info_lines(&self.urls) {
+            s += &format!("\n  + {line}\n");
+        }
+
+        s
+    }
+}
+
+/// Renders each of `urls` as a "More info" line, for instructions and their forms alike
+fn more_info_lines(urls: &[String]) -> impl Iterator<Item = String> + '_ {
+    urls.iter().map(|url| format!("More info: {url}"))
+}
+
+/// x86 operating modes an instruction form is valid in: real (16-bit), protected
+/// (32-bit) and long (64-bit) mode
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct Modes {
+    pub real: bool,
+    pub protected: bool,
+    pub long: bool,
+}
+
+impl FromStr for Modes {
+    type Err = ();
+
+    /// Parses a comma separated list of mode bit widths, e.g. "16,32"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modes = Self::default();
+        for mode in s.split(',') {
+            match mode.trim() {
+                "16" => modes.real = true,
+                "32" => modes.protected = true,
+                "64" => modes.long = true,
+                _ => return Err(()),
+            }
+        }
+        Ok(modes)
+    }
+}
+
+impl Display for Modes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let widths = [(self.real, "16"), (self.protected, "32"), (self.long, "64")];
+        let join = |valid: bool| {
+            widths
+                .iter()
+                .filter(|(mode, _)| *mode == valid)
+                .map(|(_, width)| *width)
+                .collect::<Vec<&str>>()
+                .join("/")
+        };
+
+        let (valid, invalid) = (join(true), join(false));
+        write!(f, "Valid in: {valid}-bit")?;
+        if !invalid.is_empty() {
+            write!(f, "; invalid in {invalid}-bit")?;
+        }
         Ok(())
     }
 }
@@ -342,6 +746,8 @@ pub struct Directive {
     pub deprecated: bool,
     pub url: Option<String>,
     pub assembler: Option<Assembler>,
+    /// Zero-based positions of the arguments which reference a symbol
+    pub symbol_args: Vec<usize>,
 }
 
 impl Hoverable for &Directive {}
@@ -356,6 +762,7 @@ impl Default for Directive {
         let deprecated = false;
         let url = None;
         let assembler = None;
+        let symbol_args = vec![];
 
         Self {
             name,
@@ -364,6 +771,7 @@ impl Default for Directive {
             description,
             deprecated,
             url,
+            symbol_args,
             assembler,
         }
     }
@@ -473,9 +881,24 @@ pub struct Register {
     pub flag_info: Vec<RegisterBitInfo>,
     pub arch: Option<Arch>,
     pub url: Option<String>,
+    /// Whether the register is only accessible through undocumented behavior
+    pub undocumented: bool,
+    /// Where a sub-register sits within the wider registers containing it
+    pub position: Option<RegisterPosition>,
 }
 
-impl Hoverable for &Register {}
+impl Hoverable for &Register {
+    fn to_markdown(
+        &self,
+        arch: Arch,
+        _width: Option<usize>,
+        _form_fields: Option<&[FormField]>,
+        _layout: FormsLayout,
+        _gloss_extended_sizes: bool,
+    ) -> String {
+        Register::to_markdown(self, arch)
+    }
+}
 impl Completable for &Register {}
 
 impl Default for Register {
@@ -488,6 +911,8 @@ impl Default for Register {
         let flag_info = vec![];
         let arch = None;
         let url = None;
+        let undocumented = false;
+        let position = None;
 
         Self {
             name,
@@ -498,19 +923,36 @@ impl Default for Register {
             flag_info,
             arch,
             url,
+            undocumented,
+            position,
         }
     }
 }
 
 impl std::fmt::Display for Register {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.markdown(self.arch))
+    }
+}
+
+impl Register {
+    /// Renders the register as markdown, exactly as shown in hover for `arch`
+    #[must_use]
+    pub fn to_markdown(&self, arch: Arch) -> String {
+        self.markdown(Some(arch))
+    }
+
+    fn markdown(&self, arch: Option<Arch>) -> String {
         // basic fields
-        let header: String;
-        if let Some(arch) = &self.arch {
+        let mut header: String;
+        if let Some(arch) = arch {
             header = format!("{} [{}]", &self.name.to_uppercase(), arch.as_ref());
         } else {
             header = self.name.to_uppercase();
         }
+        if self.undocumented {
+            header += " (undocumented)";
+        }
 
         let mut v: Vec<String> = if let Some(description_) = &self.description {
             vec![header, description_.clone(), String::from("\n")]
@@ -530,6 +972,11 @@ impl std::fmt::Display for Register {
             v.push(reg_width);
         }
 
+        // Position within the containing registers
+        if let Some(position_) = &self.position {
+            v.push(format!("Position: {position_}"));
+        }
+
         // Bit-mask flag meanings if applicable
         if !self.flag_info.is_empty() {
             let flag_heading = String::from("\n## Flags:");
@@ -551,9 +998,7 @@ impl std::fmt::Display for Register {
             v.push(more_info);
         }
 
-        let s = v.join("\n");
-        write!(f, "{s}")?;
-        Ok(())
+        v.join("\n")
     }
 }
 
@@ -578,22 +1023,151 @@ impl<'own> Register {
 }
 
 // helper structs, types and functions ------------------------------------------------------------
+/// The result of analyzing a single line of assembly with `analyze_line`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineAnalysis<'a> {
+    /// The mnemonic of the line's instruction, if it has one
+    pub mnemonic: Option<String>,
+    /// The documented instruction the mnemonic resolves to
+    pub instruction: Option<&'a Instruction>,
+    /// The text of each operand, in source order
+    pub operands: Vec<String>,
+    /// Diagnostics for the line, with positions on line 0
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A high-level summary of a document, orienting the user in it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileSummary {
+    /// Labels followed by code, excluding local labels such as `.L1`
+    pub functions: Vec<String>,
+    /// Sections switched to, in the order they're first used
+    pub sections: Vec<String>,
+    /// Symbols referenced through the PLT (`foo@PLT`) or declared with `.extern`
+    pub external_symbols: Vec<String>,
+    /// Instruction mnemonics none of the enabled instruction sets document
+    pub unknown_instructions: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileSummaryParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// Custom `asm-lsp/fileSummary` request, returning a `FileSummary` of a document
+#[derive(Debug)]
+pub enum FileSummaryRequest {}
+
+impl Request for FileSummaryRequest {
+    type Params = FileSummaryParams;
+    type Result = FileSummary;
+    const METHOD: &'static str = "asm-lsp/fileSummary";
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct NameToInfoMaps<'a> {
     pub instructions: NameToInstructionMap<'a>,
     pub registers: NameToRegisterMap<'a>,
     pub directives: NameToDirectiveMap<'a>,
+    pub same_operations: SameOperationMap<'a>,
+    pub instruction_prefixes: PrefixIndex<'a, Arch, Instruction>,
+    pub register_prefixes: PrefixIndex<'a, Arch, Register>,
+    pub directive_prefixes: PrefixIndex<'a, Assembler, Directive>,
 }
 
 pub type NameToInstructionMap<'instruction> =
     HashMap<(Arch, &'instruction str), &'instruction Instruction>;
 
+/// Maps an instruction `canonical` id to the note relating its names across the loaded
+/// architectures, see `populate_same_operation_map`
+pub type SameOperationMap<'instruction> = HashMap<&'instruction str, String>;
+
 pub type NameToRegisterMap<'register> = HashMap<(Arch, &'register str), &'register Register>;
 
 pub type NameToDirectiveMap<'directive> =
     HashMap<(Assembler, &'directive str), &'directive Directive>;
 
-pub trait Hoverable: Display + Clone + Copy {}
+/// The items of a name map, keyed like it but by their lowercased names in order so
+/// the ones starting with a prefix form a range, see `populate_prefix_index`
+pub type PrefixIndex<'a, K, T> = HashMap<K, BTreeMap<String, Vec<&'a T>>>;
+
+/// ARM instruction set state, selected by the `.arm`/`.thumb` directives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display)]
+pub enum ArmState {
+    /// 32-bit ARM instructions, selected by `.arm` or `.code 32`
+    #[strum(serialize = "ARM")]
+    Arm,
+    /// Thumb instructions, selected by `.thumb` or `.code 16`
+    Thumb,
+}
+
+/// What a hover resolved to, see `get_hover_resp_with_kind`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HoverKind {
+    /// An instruction, or an instruction prefix such as `lock`
+    Instruction,
+    /// A register, including register operands with an architecture specific syntax
+    /// such as AArch64's `v0.4s`
+    Register,
+    Directive,
+    /// A mangled symbol
+    Demangled,
+    /// A number with a known meaning, e.g. a named constant's value, a system call
+    /// number or an alignment directive's argument
+    Immediate,
+    /// Anything else, e.g. a label, an include path or the location counter
+    None,
+}
+
+/// Kinds of diagnostics produced by asm-lsp itself. Each is reported as the `code` of
+/// its diagnostics (e.g. `asm-lsp/suffix-size-mismatch`), and can be disabled by name
+/// (e.g. `suffix-size-mismatch`), see `ConfigOptions::disabled_diagnostics`
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, AsRefStr, Display, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum DiagnosticCode {
+    /// A privileged (ring 0) instruction, in user mode code
+    PrivilegedInstruction,
+    /// An x86 instruction that is invalid in 64-bit mode
+    InvalidInLongMode,
+    /// A GAS size suffix disagreeing with the width of a register operand
+    SuffixSizeMismatch,
+    /// A symbol definition redefining a label or an `.equiv` symbol
+    SymbolRedefinition,
+}
+
+impl DiagnosticCode {
+    /// Returns the code reported for diagnostics of this kind
+    #[must_use]
+    pub fn code(self) -> String {
+        format!("asm-lsp/{self}")
+    }
+}
+
+pub trait Hoverable: Display + Clone + Copy {
+    /// Renders the item as markdown for a hover response under `arch`, laid out for a
+    /// popup of `width` characters if given. Instruction forms are listed according to
+    /// `layout` and only annotated with `form_fields`, if given, glossing operands'
+    /// extended sizes with `gloss_extended_sizes`. Items that don't depend on the
+    /// architecture are rendered as they're displayed
+    fn to_markdown(
+        &self,
+        _arch: Arch,
+        width: Option<usize>,
+        _form_fields: Option<&[FormField]>,
+        _layout: FormsLayout,
+        _gloss_extended_sizes: bool,
+    ) -> String {
+        match width {
+            Some(width) => format!("{self:width$}"),
+            None => format!("{self}"),
+        }
+    }
+}
 pub trait Completable: Display {}
 pub trait ArchOrAssembler {}
 
@@ -615,21 +1189,41 @@ pub enum MMXMode {
 pub enum Arch {
     #[default]
     #[strum(serialize = "x86")]
+    #[serde(rename = "x86")]
     X86,
     #[strum(serialize = "x86-64")]
+    #[serde(rename = "x86_64")]
     X86_64,
     #[strum(serialize = "z80")]
+    #[serde(rename = "z80")]
     Z80,
+    #[strum(serialize = "aarch64")]
+    #[serde(rename = "aarch64")]
+    AArch64,
 }
 
 impl ArchOrAssembler for Arch {}
 
+impl Arch {
+    /// Returns the assemblers typically used to target this architecture, most common first
+    ///
+    /// z80 maps to GAS (via its z80 target), as no z80 specific directive set is bundled
+    #[must_use]
+    pub const fn default_assemblers(self) -> &'static [Assembler] {
+        match self {
+            Self::X86 | Self::X86_64 => &[Assembler::Gas, Assembler::Go],
+            Self::Z80 | Self::AArch64 => &[Assembler::Gas],
+        }
+    }
+}
+
 impl std::fmt::Display for Arch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::X86 => write!(f, "x86")?,
             Self::X86_64 => write!(f, "x86-64")?,
             Self::Z80 => write!(f, "z80")?,
+            Self::AArch64 => write!(f, "aarch64")?,
         }
         Ok(())
     }
@@ -645,6 +1239,16 @@ pub enum Assembler {
 
 impl ArchOrAssembler for Assembler {}
 
+/// Kinds of operands an instruction usually takes, used to prioritize completions
+#[derive(
+    Debug, Hash, PartialEq, Eq, Clone, Copy, EnumString, AsRefStr, Display, Serialize, Deserialize,
+)]
+pub enum OperandKind {
+    /// A code label, e.g. the target of a jump or call
+    #[strum(serialize = "label")]
+    Label,
+}
+
 #[derive(
     Debug, Hash, PartialEq, Eq, Clone, Copy, EnumString, AsRefStr, Display, Serialize, Deserialize,
 )]
@@ -671,6 +1275,14 @@ pub enum RegisterType {
     Test,
     #[strum(serialize = "Protected Mode Register")]
     ProtectedMode,
+    #[strum(serialize = "FPU Register")]
+    FPU,
+    #[strum(serialize = "MMX Register")]
+    MMX,
+    #[strum(serialize = "System Register")]
+    System,
+    #[strum(serialize = "Mask Register")]
+    Mask,
 }
 
 #[derive(
@@ -683,6 +1295,8 @@ pub enum RegisterWidth {
     Bits256,
     #[strum(serialize = "128 bits")]
     Bits128,
+    #[strum(serialize = "80 bits")]
+    Bits80,
     #[strum(serialize = "32(64) bits")]
     Bits32Or64,
     #[strum(serialize = "64 bits")]
@@ -701,6 +1315,29 @@ pub enum RegisterWidth {
     Lower8Lower16,
 }
 
+/// Bits a sub-register occupies within the wider registers containing it, e.g. `al` is
+/// bits 0-7 of `ax`, `eax` and `rax`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RegisterPosition {
+    pub low_bit: u32,
+    pub high_bit: u32,
+    /// Containing registers, from narrowest to widest
+    pub parents: Vec<String>,
+}
+
+impl std::fmt::Display for RegisterPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parents: Vec<String> = self.parents.iter().map(|p| p.to_uppercase()).collect();
+        write!(
+            f,
+            "bits {}\u{2013}{} of {}",
+            self.low_bit,
+            self.high_bit,
+            parents.join("/")
+        )
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Default, Deserialize)]
 pub struct RegisterBitInfo {
     pub bit: u32,
@@ -730,6 +1367,7 @@ impl std::fmt::Display for RegisterBitInfo {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Assemblers {
     pub gas: bool,
     pub go: bool,
@@ -748,10 +1386,14 @@ impl Default for Assemblers {
 
 #[allow(non_snake_case)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct InstructionSets {
     pub x86: bool,
     pub x86_64: bool,
     pub z80: bool,
+    /// System registers only, no AArch64 instruction set is bundled yet
+    #[serde(default)]
+    pub aarch64: bool,
 }
 
 impl Default for InstructionSets {
@@ -760,15 +1402,266 @@ impl Default for InstructionSets {
             x86: true,
             x86_64: true,
             z80: false,
+            aarch64: false,
         }
     }
 }
 
+/// An instruction-like mnemonic defined by a project itself (e.g. a pseudo-op or a
+/// macro), see `ConfigOptions::custom_instructions`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomInstruction {
+    pub name: String,
+    /// Shown in hover in place of an instruction's summary
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigOptions {
+    /// When set, hover for a name defined by multiple architectures only shows
+    /// this architecture's entry, noting the others it's also defined in
+    pub primary_arch: Option<Arch>,
+    /// When set, diagnostics warn about privileged (ring 0) instructions, which
+    /// fault when executed in user mode
+    #[serde(default)]
+    pub user_mode: bool,
+    /// Width (in characters) of the client's hover popup. Instruction forms are
+    /// rendered in a compact one line layout when this is too narrow for the
+    /// operand table
+    pub hover_width: Option<usize>,
+    /// When set, hovering the call number loaded into `rax` ahead of a `syscall`
+    /// shows the name of the Linux x86-64 system call
+    #[serde(default)]
+    pub syscalls: bool,
+    /// When set, z80 instruction hover ends with a legend explaining the timing columns
+    #[serde(default)]
+    pub z80_timing_legend: bool,
+    /// When set, operands' `extended-size` in hover is followed by a short note that it's
+    /// the size (in bytes) the operand is sign/zero-extended to
+    #[serde(default)]
+    pub extended_size_gloss: bool,
+    /// Annotations shown on each instruction form in hover, out of `gas`, `go`, `mmx`,
+    /// `xmm`, `isa` and `encoding`. All of them but `encoding` are shown when unset
+    pub hover_form_fields: Option<Vec<FormField>>,
+    /// Case of the instruction and register names offered by completion, either
+    /// `as_is` (the default), `upper` or `lower`
+    #[serde(default)]
+    pub completion_case: CompletionCase,
+    /// When set, the server never accesses the network. Instruction hover still links
+    /// to the documentation URLs bundled with the server
+    #[serde(default)]
+    pub offline: bool,
+    /// How instruction forms are listed in hover, either `verbose` (the default) or
+    /// `grouped`, collapsing forms sharing a mnemonic into one line
+    #[serde(default)]
+    pub hover_forms: FormsLayout,
+    /// Raw directive XML file (in the format of `docs_store/directives/raw/gas.xml`)
+    /// whose directives are added to the bundled GAS ones, replacing any sharing a
+    /// name. Relative paths in a project config are resolved against the project root
+    pub extra_directives: Option<PathBuf>,
+    /// Mnemonics of a project's own pseudo-ops or macros, along with descriptions, which
+    /// are offered in hover and completion like the documented instructions
+    #[serde(default)]
+    pub custom_instructions: Vec<CustomInstruction>,
+    /// When set, completion offers function prologue snippets at the start of a label's
+    /// body, and epilogue snippets after its instructions, following each enabled
+    /// architecture's usual calling convention
+    #[serde(default)]
+    pub frame_snippets: bool,
+    /// Kinds of asm-lsp's own diagnostics that are never reported. Single lines can
+    /// silence them with an `asm-lsp: ignore <kind>` comment instead
+    #[serde(default)]
+    pub disabled_diagnostics: Vec<DiagnosticCode>,
+    /// Documents with more lines than this get no completions, and their diagnostics
+    /// are only published once saves pause, to keep the editor responsive
+    pub max_file_lines_for_completion: Option<usize>,
+    /// Documentation z80 instruction forms link to, either `zilog` (the default, Zilog's
+    /// user manual), `z80_heaven` or `clr_home`
+    #[serde(default)]
+    pub z80_docs: Z80DocsSource,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct TargetConfig {
     pub version: String,
     pub assemblers: Assemblers,
     pub instruction_sets: InstructionSets,
+    /// ISA extensions supported by the target CPU. When set, instruction forms requiring
+    /// any other extension are filtered out, while forms without one are always kept
+    #[serde(default, deserialize_with = "deserialize_isa_extensions")]
+    pub enabled_isa_extensions: Option<HashSet<ISA>>,
+    #[serde(default)]
+    pub opts: ConfigOptions,
+}
+
+/// Deserializes ISA extensions by the names used in the instruction docs (e.g.
+/// `"AVX512-IFMA"`), rather than by their variant names
+fn deserialize_isa_extensions<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<HashSet<ISA>>, D::Error> {
+    let Some(names) = Option::<Vec<String>>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    names
+        .iter()
+        .map(|name| {
+            ISA::from_str(name)
+                .map_err(|_| D::Error::custom(format!("unknown ISA extension `{name}`")))
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+impl TargetConfig {
+    /// Returns whether instruction forms requiring `isa` should be offered, which is the
+    /// case for every extension unless `enabled_isa_extensions` is set
+    #[must_use]
+    pub fn isa_enabled(&self, isa: &ISA) -> bool {
+        self.enabled_isa_extensions
+            .as_ref()
+            .is_none_or(|enabled| enabled.contains(isa))
+    }
+
+    /// Returns whether documents should be treated as x86-64 (long mode) code, either
+    /// because it's the configured primary architecture or because it's the only x86
+    /// instruction set enabled
+    #[must_use]
+    pub fn targets_long_mode(&self) -> bool {
+        match self.opts.primary_arch {
+            Some(arch) => arch == Arch::X86_64,
+            None => self.instruction_sets.x86_64 && !self.instruction_sets.x86,
+        }
+    }
+
+    /// Returns the assembler whose syntax instruction operands are parsed with, which is
+    /// Go's only if it's the sole enabled x86 assembler
+    #[must_use]
+    pub const fn operand_syntax(&self) -> Assembler {
+        if self.assemblers.go && !self.assemblers.gas {
+            Assembler::Go
+        } else {
+            Assembler::Gas
+        }
+    }
+
+    /// Returns whether `text` has more lines than `max_file_lines_for_completion`
+    #[must_use]
+    pub fn is_large_file(&self, text: &str) -> bool {
+        self.opts
+            .max_file_lines_for_completion
+            .is_some_and(|max_lines| text.lines().count() > max_lines)
+    }
+
+    /// Returns an instruction for each of the `custom_instructions`, under each enabled
+    /// architecture. They have no forms, only their name and description
+    #[must_use]
+    pub fn custom_instructions(&self) -> Vec<Instruction> {
+        self.enabled_archs()
+            .into_iter()
+            .flat_map(|arch| {
+                self.opts
+                    .custom_instructions
+                    .iter()
+                    .map(move |custom| Instruction {
+                        name: custom.name.clone(),
+                        alt_names: vec![custom.name.to_uppercase(), custom.name.to_lowercase()],
+                        summary: custom.description.clone(),
+                        arch: Some(arch),
+                        ..Default::default()
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns the architectures whose instruction sets are enabled
+    #[must_use]
+    pub fn enabled_archs(&self) -> Vec<Arch> {
+        [
+            (Arch::X86, self.instruction_sets.x86),
+            (Arch::X86_64, self.instruction_sets.x86_64),
+            (Arch::Z80, self.instruction_sets.z80),
+            (Arch::AArch64, self.instruction_sets.aarch64),
+        ]
+        .iter()
+        .filter_map(|&(arch, enabled)| enabled.then_some(arch))
+        .collect()
+    }
+
+    /// Checks for settings that parse fine but are likely mistaken, returning a warning
+    /// for each. Currently this flags enabled instruction sets without any of their
+    /// assemblers enabled, as no instruction forms would be offered for them
+    #[must_use]
+    pub fn validate(&self) -> Vec<String> {
+        let x86_assembler = self.assemblers.gas || self.assemblers.go;
+        [
+            (
+                Arch::X86,
+                self.instruction_sets.x86,
+                x86_assembler,
+                "gas or go",
+            ),
+            (
+                Arch::X86_64,
+                self.instruction_sets.x86_64,
+                x86_assembler,
+                "gas or go",
+            ),
+            (
+                Arch::Z80,
+                self.instruction_sets.z80,
+                self.assemblers.z80,
+                "z80",
+            ),
+            (
+                Arch::AArch64,
+                self.instruction_sets.aarch64,
+                self.assemblers.gas,
+                "gas",
+            ),
+        ]
+        .iter()
+        .filter(|&&(_, enabled, has_assembler, _)| enabled && !has_assembler)
+        .map(|(arch, _, _, assemblers)| {
+            format!(
+                "The {arch} instruction set is enabled, but none of its assemblers are \
+                 (enable {assemblers} under [assemblers])"
+            )
+        })
+        .collect()
+    }
+
+    /// Returns the assemblers whose directives should be offered in completion and hover
+    ///
+    /// These are the assemblers enabled in the config. If none of the assemblers with
+    /// directive support are enabled, the default assemblers of each enabled instruction
+    /// set are used instead, so that e.g. enabling only the z80 instruction set still
+    /// surfaces directives
+    #[must_use]
+    pub fn directive_assemblers(&self) -> Vec<Assembler> {
+        let mut assemblers = Vec::new();
+        if self.assemblers.gas {
+            assemblers.push(Assembler::Gas);
+        }
+        if self.assemblers.go {
+            assemblers.push(Assembler::Go);
+        }
+        if !assemblers.is_empty() {
+            return assemblers;
+        }
+
+        for arch in self.enabled_archs() {
+            for assembler in arch.default_assemblers() {
+                if !assemblers.contains(assembler) {
+                    assemblers.push(*assembler);
+                }
+            }
+        }
+
+        assemblers
+    }
 }
 
 impl Default for TargetConfig {
@@ -777,6 +1670,8 @@ impl Default for TargetConfig {
             version: String::from("0.1"),
             assemblers: Assemblers::default(),
             instruction_sets: InstructionSets::default(),
+            enabled_isa_extensions: None,
+            opts: ConfigOptions::default(),
         }
     }
 }
@@ -913,6 +1808,26 @@ pub struct Operand {
     pub extended_size: Option<usize>,
 }
 
+/// A register an instruction form accesses without it being one of its operands
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+pub struct ImplicitOperand {
+    pub id: String,
+    pub input: Option<bool>,
+    pub output: Option<bool>,
+}
+
+impl Display for ImplicitOperand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let access = match (self.input, self.output) {
+            (Some(true), Some(true)) => "reads/writes ",
+            (Some(true), _) => "reads ",
+            (_, Some(true)) => "writes ",
+            _ => "",
+        };
+        write!(f, "{access}{}", self.id.to_uppercase())
+    }
+}
+
 #[allow(non_camel_case_types)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, EnumString, AsRefStr, Serialize, Deserialize)]
 pub enum OperandType {
@@ -1044,6 +1959,103 @@ pub enum OperandType {
     tmm,
 }
 
+impl std::fmt::Display for OperandType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+impl OperandType {
+    /// Returns a short human readable description of the operand type
+    #[must_use]
+    pub const fn description(&self) -> &'static str {
+        match self {
+            Self::_1 => "the constant 1",
+            Self::_3 => "the constant 3",
+            Self::imm4 => "4-bit immediate",
+            Self::imm8 => "8-bit immediate",
+            Self::imm16 => "16-bit immediate",
+            Self::imm32 => "32-bit immediate",
+            Self::imm64 => "64-bit immediate",
+            Self::al => "the al register",
+            Self::cl => "the cl register",
+            Self::r8 | Self::r8l => "8-bit general purpose register",
+            Self::ax => "the ax register",
+            Self::r16 | Self::r16l => "16-bit general purpose register",
+            Self::eax => "the eax register",
+            Self::r32 | Self::r32l => "32-bit general purpose register",
+            Self::rax => "the rax register",
+            Self::r64 => "64-bit general purpose register",
+            Self::mm => "MMX register",
+            Self::xmm0 => "the xmm0 register",
+            Self::xmm => "XMM register",
+            Self::xmm_k => "XMM register with merge-masking",
+            Self::xmm_k_z => "XMM register with merge- or zero-masking",
+            Self::ymm => "YMM register",
+            Self::ymm_k => "YMM register with merge-masking",
+            Self::ymm_k_z => "YMM register with merge- or zero-masking",
+            Self::zmm => "ZMM register",
+            Self::zmm_k => "ZMM register with merge-masking",
+            Self::zmm_k_z => "ZMM register with merge- or zero-masking",
+            Self::k => "mask register",
+            Self::k_k => "mask register with merge-masking",
+            Self::moffs32 => "32-bit memory offset",
+            Self::moffs64 => "64-bit memory offset",
+            Self::m => "memory",
+            Self::m8 => "8-bit memory",
+            Self::m16 => "16-bit memory",
+            Self::m16_k => "16-bit memory with merge-masking",
+            Self::m16_k_z => "16-bit memory with merge- or zero-masking",
+            Self::m32 => "32-bit memory",
+            Self::m32_k => "32-bit memory with merge-masking",
+            Self::m32_k_z => "32-bit memory with merge- or zero-masking",
+            Self::m64 => "64-bit memory",
+            Self::m64_k => "64-bit memory with merge-masking",
+            Self::m64_k_z => "64-bit memory with merge- or zero-masking",
+            Self::m128 => "128-bit memory",
+            Self::m128_k => "128-bit memory with merge-masking",
+            Self::m128_k_z => "128-bit memory with merge- or zero-masking",
+            Self::m256 => "256-bit memory",
+            Self::m256_k => "256-bit memory with merge-masking",
+            Self::m256_k_z => "256-bit memory with merge- or zero-masking",
+            Self::m512 => "512-bit memory",
+            Self::m512_k => "512-bit memory with merge-masking",
+            Self::m512_k_z => "512-bit memory with merge- or zero-masking",
+            Self::m32_m16bcst => "32-bit memory or a broadcast 16-bit element",
+            Self::m64_m16bcst => "64-bit memory or a broadcast 16-bit element",
+            Self::m64_m32bcst => "64-bit memory or a broadcast 32-bit element",
+            Self::m128_m16bcst => "128-bit memory or a broadcast 16-bit element",
+            Self::m128_m32bcst => "128-bit memory or a broadcast 32-bit element",
+            Self::m128_m64bcst => "128-bit memory or a broadcast 64-bit element",
+            Self::m256_m16bcst => "256-bit memory or a broadcast 16-bit element",
+            Self::m256_m32bcst => "256-bit memory or a broadcast 32-bit element",
+            Self::m256_m64bcst => "256-bit memory or a broadcast 64-bit element",
+            Self::m512_m16bcst => "512-bit memory or a broadcast 16-bit element",
+            Self::m512_m32bcst => "512-bit memory or a broadcast 32-bit element",
+            Self::m512_m64bcst => "512-bit memory or a broadcast 64-bit element",
+            Self::vm32x => "vector of 32-bit indices in an XMM register",
+            Self::vm32x_k => "vector of 32-bit indices in an XMM register, with masking",
+            Self::vm64x => "vector of 64-bit indices in an XMM register",
+            Self::vm64xk => "vector of 64-bit indices in an XMM register, with masking",
+            Self::vm32y => "vector of 32-bit indices in a YMM register",
+            Self::vm32yk_ => "vector of 32-bit indices in a YMM register, with masking",
+            Self::vm64y => "vector of 64-bit indices in a YMM register",
+            Self::vm64y_k => "vector of 64-bit indices in a YMM register, with masking",
+            Self::vm32z => "vector of 32-bit indices in a ZMM register",
+            Self::vm32z_k => "vector of 32-bit indices in a ZMM register, with masking",
+            Self::vm64z => "vector of 64-bit indices in a ZMM register",
+            Self::vm64z_k => "vector of 64-bit indices in a ZMM register, with masking",
+            Self::rel8 => "8-bit relative offset",
+            Self::rel32 => "32-bit relative offset",
+            Self::rel32m => "32-bit relative offset or memory",
+            Self::er => "embedded rounding control",
+            Self::sae => "suppress all exceptions",
+            Self::sibmem => "memory addressed with a SIB byte",
+            Self::tmm => "AMX tile register",
+        }
+    }
+}
+
 // lsp types --------------------------------------------------------------------------------------
 
 /// Represents a text cursor between characters, pointing at the next character in the buffer.
@@ -1057,3 +2069,14 @@ pub struct TreeEntry {
 
 /// Associates URIs with their corresponding tree-sitter tree and parser
 pub type TreeStore = BTreeMap<Uri, TreeEntry>;
+
+/// Associates URIs with the files reached by following their `.include` directives, see
+/// `update_included_files`
+pub type IncludeStore = BTreeMap<Uri, Vec<IncludedFile>>;
+
+/// A file reached by following the `.include` directives of a document
+#[derive(Debug, Clone)]
+pub struct IncludedFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
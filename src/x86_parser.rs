@@ -1,26 +1,93 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env::args;
+use std::fmt;
 use std::fs;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::{self, FromStr};
 
 use crate::types::{
-    Arch, Assembler, Directive, Instruction, InstructionForm, MMXMode, NameToDirectiveMap,
-    NameToInstructionMap, NameToRegisterMap, Operand, OperandType, Register, RegisterBitInfo,
-    RegisterType, RegisterWidth, XMMMode, Z80Timing, Z80TimingInfo, ISA,
+    Arch, Assembler, Directive, ImplicitOperand, Instruction, InstructionForm, MMXMode, Modes,
+    NameToDirectiveMap, NameToInstructionMap, NameToRegisterMap, Operand, OperandKind, OperandType,
+    Register, RegisterBitInfo, RegisterPosition, RegisterType, RegisterWidth, XMMMode,
+    Z80DocsSource, Z80Timing, Z80TimingInfo, ISA,
 };
 
 use anyhow::{anyhow, Result};
 use log::{debug, error, info, warn};
+use once_cell::sync::Lazy;
 use quick_xml::escape::unescape;
 use quick_xml::events::attributes::Attribute;
-use quick_xml::events::Event;
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::name::QName;
 use quick_xml::Reader;
 use regex::Regex;
 use reqwest;
-use url_escape::encode_www_form_urlencoded;
+
+/// Failure kinds reported by [`populate_instructions`], [`populate_registers`] and
+/// [`populate_directives`].
+///
+/// Implements [`std::error::Error`], so `?` converts it into an [`anyhow::Error`] for callers
+/// that don't care about the specific kind.
+#[derive(Debug)]
+pub enum ParseError {
+    /// An `<Operand>` carried a `type` that doesn't name a known `OperandType`
+    UnknownOperandType(String),
+    /// An `<ISA>` carried an `id` that doesn't name a known `ISA`
+    UnknownIsa(String),
+    /// An attribute held a value outside of the set it allows
+    UnknownAttributeValue { attribute: String, value: String },
+    /// An attribute's value wasn't valid UTF-8
+    BadUtf8(str::Utf8Error),
+    /// The underlying XML reader failed at the given byte position
+    MalformedXml {
+        position: u64,
+        source: quick_xml::Error,
+    },
+}
+
+impl ParseError {
+    fn unknown_value(attribute: &str, value: &str) -> Self {
+        Self::UnknownAttributeValue {
+            attribute: attribute.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownOperandType(variant) => {
+                write!(f, "Unknown value for operand type -- Variant: {variant}")
+            }
+            Self::UnknownIsa(variant) => write!(f, "Unexpected ISA variant - {variant}"),
+            Self::UnknownAttributeValue { attribute, value } => {
+                write!(f, "Unknown value '{value}' for XML attribute {attribute}")
+            }
+            Self::BadUtf8(e) => write!(f, "Invalid UTF-8 in XML attribute: {e}"),
+            Self::MalformedXml { position, source } => {
+                write!(f, "Error at position {position}: {source}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::BadUtf8(e) => Some(e),
+            Self::MalformedXml { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<str::Utf8Error> for ParseError {
+    fn from(e: str::Utf8Error) -> Self {
+        Self::BadUtf8(e)
+    }
+}
 
 /// Parse the provided XML contents and return a vector of all the instructions based on that.
 /// If parsing fails, the appropriate error will be returned instead.
@@ -28,18 +95,40 @@ use url_escape::encode_www_form_urlencoded;
 /// Current function assumes that the XML file is already read and that it's been given a reference
 /// to its contents (`&str`).
 ///
+/// x86 instructions are linked to their online documentation, fetching the documentation's
+/// index page if it isn't cached yet. This is how the URLs get baked into the serialized
+/// instructions bundled with the server
+///
 /// # Errors
 ///
+/// Returns a [`ParseError`] describing the first malformed or unexpected input encountered
+///
+/// # Panics
+///
 /// This function is highly specialized to parse a handful of files and will panic or return
-/// `Err` for most mal-formed inputs
+/// `Err` for most mal-formed/unexpected inputs
+pub fn populate_instructions(xml_contents: &str) -> Result<Vec<Instruction>, ParseError> {
+    parse_instructions(xml_contents, false)
+}
+
+/// Like `populate_instructions`, but never accesses the network. x86 instructions are
+/// only linked to their online documentation if its index page is already cached
+///
+/// # Errors
+///
+/// Returns a [`ParseError`] describing the first malformed or unexpected input encountered
 ///
 /// # Panics
 ///
 /// This function is highly specialized to parse a handful of files and will panic or return
 /// `Err` for most mal-formed/unexpected inputs
-pub fn populate_instructions(xml_contents: &str) -> Result<Vec<Instruction>> {
-    // initialise the instruction set
-    let mut instructions_map = HashMap::<String, Instruction>::new();
+pub fn populate_instructions_offline(xml_contents: &str) -> Result<Vec<Instruction>, ParseError> {
+    parse_instructions(xml_contents, true)
+}
+
+fn parse_instructions(xml_contents: &str, offline: bool) -> Result<Vec<Instruction>, ParseError> {
+    // initialise the instruction set, keyed by architecture as a file may contain several
+    let mut instructions_map = HashMap::<(Option<Arch>, String), Instruction>::new();
 
     // iterate through the XML --------------------------------------------------------------------
     let mut reader = Reader::from_str(xml_contents);
@@ -48,6 +137,12 @@ pub fn populate_instructions(xml_contents: &str) -> Result<Vec<Instruction>> {
     let mut curr_instruction = Instruction::default();
     let mut curr_instruction_form = InstructionForm::default();
     let mut arch: Option<Arch> = None;
+    let mut in_example = false;
+    let mut in_note = false;
+    let mut in_url = false;
+    // parts of the x86 encoding template being read, `None` outside of the form's first
+    // `Encoding`
+    let mut encoding_parts: Option<Vec<String>> = None;
 
     debug!("Parsing instruction XML contents...");
     loop {
@@ -56,6 +151,7 @@ pub fn populate_instructions(xml_contents: &str) -> Result<Vec<Instruction>> {
             Ok(Event::Start(ref e)) => {
                 match e.name() {
                     QName(b"InstructionSet") => {
+                        arch = None;
                         for attr in e.attributes() {
                             let Attribute { key, value } = attr.unwrap();
                             if let Ok("name") = str::from_utf8(key.into_inner()) {
@@ -68,156 +164,84 @@ pub fn populate_instructions(xml_contents: &str) -> Result<Vec<Instruction>> {
                     }
                     QName(b"Instruction") => {
                         // start of a new instruction
-                        curr_instruction = Instruction::default();
-                        curr_instruction.arch = arch;
-
-                        // iterate over the attributes
-                        for attr in e.attributes() {
-                            let Attribute { key, value } = attr.unwrap();
-                            match str::from_utf8(key.into_inner()).unwrap() {
-                                "name" => {
-                                    let name =
-                                        String::from(unsafe { str::from_utf8_unchecked(&value) });
-                                    curr_instruction.alt_names.push(name.to_uppercase());
-                                    curr_instruction.alt_names.push(name.to_lowercase());
-                                    curr_instruction.name = name;
-                                }
-                                "summary" => {
-                                    curr_instruction.summary =
-                                        String::from(unsafe { str::from_utf8_unchecked(&value) });
-                                }
-                                _ => {}
-                            }
-                        }
+                        curr_instruction = instruction_from_tag(e, arch)?;
+                    }
+                    QName(b"Example") => in_example = true,
+                    QName(b"Note") => in_note = true,
+                    QName(b"Url") => in_url = true,
+                    // forms with alternative encodings only show the first
+                    QName(b"Encoding")
+                        if curr_instruction_form.z80_name.is_none()
+                            && curr_instruction_form.x86_encoding.is_none() =>
+                    {
+                        encoding_parts = Some(Vec::new());
                     }
                     QName(b"InstructionForm") => {
-                        // Read the attributes
-                        //
-                        // <xs:attribute name="gas-name" type="xs:string" use="required" />
-                        // <xs:attribute name="go-name" type="xs:string" />
-                        // <xs:attribute name="mmx-mode" type="MMXMode" />
-                        // <xs:attribute name="xmm-mode" type="XMMMode" />
-                        // <xs:attribute name="cancelling-inputs" type="xs:boolean" />
-                        // <xs:attribute name="nacl-version" type="NaClVersion" />
-                        // <xs:attribute name="nacl-zero-extends-outputs" type="xs:boolean" />
-
                         // new instruction form
-                        curr_instruction_form = InstructionForm::default();
-
-                        // iterate over the attributes
-                        for attr in e.attributes() {
-                            let Attribute { key, value } = attr.unwrap();
-                            match str::from_utf8(key.into_inner()).unwrap() {
-                                "gas-name" => {
-                                    curr_instruction_form.gas_name = Some(String::from(unsafe {
-                                        str::from_utf8_unchecked(&value)
-                                    }));
-                                }
-                                "go-name" => {
-                                    curr_instruction_form.go_name = Some(String::from(unsafe {
-                                        str::from_utf8_unchecked(&value)
-                                    }));
-                                }
-                                "mmx-mode" => {
-                                    let value_ = value.as_ref();
-                                    curr_instruction_form.mmx_mode =
-                                        Some(MMXMode::from_str(unsafe {
-                                            str::from_utf8_unchecked(value_)
-                                        })?);
-                                }
-                                "xmm-mode" => {
-                                    let value_ = value.as_ref();
-                                    curr_instruction_form.xmm_mode =
-                                        Some(XMMMode::from_str(unsafe {
-                                            str::from_utf8_unchecked(value_)
-                                        })?);
-                                }
-                                "cancelling-inputs" => match str::from_utf8(&value).unwrap() {
-                                    "true" => curr_instruction_form.cancelling_inputs = Some(true),
-                                    "false" => {
-                                        curr_instruction_form.cancelling_inputs = Some(false);
-                                    }
-                                    val => {
-                                        return Err(anyhow!(
-                                            "Unknown value '{val}' for XML attribute cancelling inputs"
-                                        ));
-                                    }
-                                },
-                                "nacl-version" => {
-                                    curr_instruction_form.nacl_version =
-                                        value.as_ref().first().copied();
-                                }
-                                "nacl-zero-extends-outputs" => {
-                                    match str::from_utf8(&value).unwrap() {
-                                        "true" => {
-                                            curr_instruction_form.nacl_zero_extends_outputs =
-                                                Some(true);
-                                        }
-                                        "false" => {
-                                            curr_instruction_form.nacl_zero_extends_outputs =
-                                                Some(false);
-                                        }
-                                        val => {
-                                            return Err(anyhow!(
-                                                "Unknown value '{val}' for XML attribute nacl-zero-extends-outputs",
-                                            ));
-                                        }
-                                    }
-                                }
-                                "z80name" => {
-                                    curr_instruction_form.z80_name = Some(String::from(unsafe {
-                                        str::from_utf8_unchecked(&value)
-                                    }));
-                                }
-                                "form" => {
-                                    let value_ = unsafe { str::from_utf8_unchecked(&value) };
-                                    curr_instruction_form.urls.push(format!(
-                                        "https://www.zilog.com/docs/z80/z80cpu_um.pdf#{}",
-                                        encode_www_form_urlencoded(value_)
-                                    ));
-                                    curr_instruction_form.z80_form = Some(value_.to_string());
-                                }
-                                _ => {}
-                            }
-                        }
+                        curr_instruction_form = instruction_form_from_tag(e)?;
                     }
-                    // TODO
-                    QName(b"Encoding") => {
+                    _ => {} // unknown event
+                }
+            }
+            Ok(Event::Empty(ref e)) => {
+                match e.name() {
+                    // a self-closing instruction has no forms, so it's finished right away
+                    QName(b"Instruction") => {
+                        let instruction = instruction_from_tag(e, arch)?;
+                        instructions_map
+                            .insert((instruction.arch, instruction.name.clone()), instruction);
+                    }
+                    // likewise a self-closing form has no operands or encoding
+                    QName(b"InstructionForm") => {
+                        curr_instruction.push_form(instruction_form_from_tag(e)?);
+                    }
+                    // z80 opcodes are shown as is, while x86 ones also depend on prefixes
+                    // and ModRM details, so they're part of the form's encoding template
+                    QName(b"Opcode") if curr_instruction_form.z80_name.is_some() => {
                         for attr in e.attributes() {
                             let Attribute { key, value } = attr.unwrap();
                             if str::from_utf8(key.into_inner()).unwrap() == "byte" {
-                                let disp_code =
-                                    unsafe { str::from_utf8_unchecked(&value) }.to_string() + " ";
+                                let mut byte = str::from_utf8(&value)?.to_string();
+                                // some bytes are written without their leading zero
+                                if byte.len() == 1 && byte.chars().all(|c| c.is_ascii_hexdigit()) {
+                                    byte.insert(0, '0');
+                                }
                                 if let Some(ref mut opcodes) = curr_instruction_form.z80_opcode {
-                                    opcodes.push_str(&disp_code);
+                                    opcodes.push(' ');
+                                    opcodes.push_str(&byte);
                                 } else {
-                                    curr_instruction_form.z80_opcode = Some(disp_code);
+                                    curr_instruction_form.z80_opcode = Some(byte);
                                 }
                             }
                         }
                     }
-                    _ => {} // unknown event
-                }
-            }
-            Ok(Event::Empty(ref e)) => {
-                match e.name() {
+                    name @ QName(
+                        b"Prefix" | b"REX" | b"VEX" | b"EVEX" | b"Opcode" | b"ModRM"
+                        | b"RegisterByte" | b"Immediate" | b"CodeOffset",
+                    ) => {
+                        if let Some(ref mut parts) = encoding_parts {
+                            let mut attrs = HashMap::new();
+                            for attr in e.attributes() {
+                                let Attribute { key, value } = attr.unwrap();
+                                attrs.insert(
+                                    str::from_utf8(key.into_inner())?.to_string(),
+                                    str::from_utf8(&value)?.to_string(),
+                                );
+                            }
+                            if let Some(part) = encoding_template_part(name.as_ref(), &attrs) {
+                                parts.push(part);
+                            }
+                        }
+                    }
                     QName(b"ISA") => {
                         for attr in e.attributes() {
                             let Attribute { key, value } = attr.unwrap();
                             if str::from_utf8(key.into_inner()).unwrap() == "id" {
-                                {
-                                    curr_instruction_form.isa =
-                                        Some(
-                                            ISA::from_str(unsafe {
-                                                str::from_utf8_unchecked(value.as_ref())
-                                            })
-                                            .unwrap_or_else(|_| {
-                                                panic!("Unexpected ISA variant - {}", unsafe {
-                                                    str::from_utf8_unchecked(&value)
-                                                })
-                                            }),
-                                        );
-                                }
+                                let value_ = str::from_utf8(&value)?;
+                                curr_instruction_form.isa = Some(
+                                    ISA::from_str(value_)
+                                        .map_err(|_| ParseError::UnknownIsa(value_.to_string()))?,
+                                );
                             }
                         }
                     }
@@ -231,30 +255,27 @@ pub fn populate_instructions(xml_contents: &str) -> Result<Vec<Instruction>> {
                             let Attribute { key, value } = attr.unwrap();
                             match str::from_utf8(key.into_inner()).unwrap() {
                                 "type" => {
-                                    type_ = match OperandType::from_str(str::from_utf8(&value)?) {
-                                        Ok(op_type) => op_type,
-                                        Err(_) => {
-                                            return Err(anyhow!(
-                                                "Unknown value for operand type -- Variant: {}",
-                                                str::from_utf8(&value)?
-                                            ));
-                                        }
-                                    }
+                                    let value_ = str::from_utf8(&value)?;
+                                    type_ = OperandType::from_str(value_).map_err(|_| {
+                                        ParseError::UnknownOperandType(value_.to_string())
+                                    })?;
                                 }
-                                "input" => match str::from_utf8(&value).unwrap() {
+                                "input" => match str::from_utf8(&value)? {
                                     "true" => input = Some(true),
                                     "false" => input = Some(false),
-                                    _ => return Err(anyhow!("Unknown value for operand type")),
+                                    val => return Err(ParseError::unknown_value("input", val)),
                                 },
-                                "output" => match str::from_utf8(&value).unwrap() {
+                                "output" => match str::from_utf8(&value)? {
                                     "true" => output = Some(true),
                                     "false" => output = Some(false),
-                                    _ => return Err(anyhow!("Unknown value for operand type")),
+                                    val => return Err(ParseError::unknown_value("output", val)),
                                 },
                                 "extended-size" => {
-                                    extended_size = Some(
-                                        str::from_utf8(value.as_ref()).unwrap().parse::<usize>()?,
-                                    );
+                                    let value_ = str::from_utf8(&value)?;
+                                    extended_size =
+                                        Some(value_.parse::<usize>().map_err(|_| {
+                                            ParseError::unknown_value("extended-size", value_)
+                                        })?);
                                 }
                                 _ => {} // unknown event
                             }
@@ -267,16 +288,41 @@ pub fn populate_instructions(xml_contents: &str) -> Result<Vec<Instruction>> {
                             extended_size,
                         });
                     }
+                    QName(b"ImplicitOperand") => {
+                        let mut id = String::new();
+                        let mut input = None;
+                        let mut output = None;
+
+                        for attr in e.attributes() {
+                            let Attribute { key, value } = attr.unwrap();
+                            match str::from_utf8(key.into_inner()).unwrap() {
+                                "id" => id = str::from_utf8(&value)?.to_string(),
+                                "input" => match str::from_utf8(&value)? {
+                                    "true" => input = Some(true),
+                                    "false" => input = Some(false),
+                                    val => return Err(ParseError::unknown_value("input", val)),
+                                },
+                                "output" => match str::from_utf8(&value)? {
+                                    "true" => output = Some(true),
+                                    "false" => output = Some(false),
+                                    val => return Err(ParseError::unknown_value("output", val)),
+                                },
+                                _ => {} // unknown event
+                            }
+                        }
+
+                        curr_instruction_form
+                            .implicit_operands
+                            .push(ImplicitOperand { id, input, output });
+                    }
                     QName(b"TimingZ80") => {
                         for attr in e.attributes() {
                             let Attribute { key, value } = attr.unwrap();
                             if str::from_utf8(key.into_inner()).unwrap() == "value" {
-                                let z80 = match Z80TimingInfo::from_str(unsafe {
-                                    str::from_utf8_unchecked(&value)
-                                }) {
-                                    Ok(timing) => timing,
-                                    Err(e) => return Err(anyhow!(e)),
-                                };
+                                let value_ = unsafe { str::from_utf8_unchecked(&value) };
+                                let z80 = Z80TimingInfo::from_str(value_).map_err(|_| {
+                                    ParseError::unknown_value("TimingZ80 value", value_)
+                                })?;
                                 if let Some(ref mut timing_entry) = curr_instruction_form.z80_timing
                                 {
                                     timing_entry.z80 = z80;
@@ -293,12 +339,11 @@ pub fn populate_instructions(xml_contents: &str) -> Result<Vec<Instruction>> {
                         for attr in e.attributes() {
                             let Attribute { key, value } = attr.unwrap();
                             if str::from_utf8(key.into_inner()).unwrap() == "value" {
-                                let z80_plus_m1 = match Z80TimingInfo::from_str(unsafe {
-                                    str::from_utf8_unchecked(&value)
-                                }) {
-                                    Ok(timing) => timing,
-                                    Err(e) => return Err(anyhow!(e)),
-                                };
+                                let value_ = unsafe { str::from_utf8_unchecked(&value) };
+                                let z80_plus_m1 =
+                                    Z80TimingInfo::from_str(value_).map_err(|_| {
+                                        ParseError::unknown_value("TimingZ80M1 value", value_)
+                                    })?;
                                 if let Some(ref mut timing_entry) = curr_instruction_form.z80_timing
                                 {
                                     timing_entry.z80_plus_m1 = z80_plus_m1;
@@ -315,12 +360,10 @@ pub fn populate_instructions(xml_contents: &str) -> Result<Vec<Instruction>> {
                         for attr in e.attributes() {
                             let Attribute { key, value } = attr.unwrap();
                             if str::from_utf8(key.into_inner()).unwrap() == "value" {
-                                let r800 = match Z80TimingInfo::from_str(unsafe {
-                                    str::from_utf8_unchecked(&value)
-                                }) {
-                                    Ok(timing) => timing,
-                                    Err(e) => return Err(anyhow!(e)),
-                                };
+                                let value_ = unsafe { str::from_utf8_unchecked(&value) };
+                                let r800 = Z80TimingInfo::from_str(value_).map_err(|_| {
+                                    ParseError::unknown_value("TimingR800 value", value_)
+                                })?;
                                 if let Some(ref mut timing_entry) = curr_instruction_form.z80_timing
                                 {
                                     timing_entry.r800 = r800;
@@ -337,12 +380,11 @@ pub fn populate_instructions(xml_contents: &str) -> Result<Vec<Instruction>> {
                         for attr in e.attributes() {
                             let Attribute { key, value } = attr.unwrap();
                             if str::from_utf8(key.into_inner()).unwrap() == "value" {
-                                let r800_plus_wait = match Z80TimingInfo::from_str(unsafe {
-                                    str::from_utf8_unchecked(&value)
-                                }) {
-                                    Ok(timing) => timing,
-                                    Err(e) => return Err(anyhow!(e)),
-                                };
+                                let value_ = unsafe { str::from_utf8_unchecked(&value) };
+                                let r800_plus_wait =
+                                    Z80TimingInfo::from_str(value_).map_err(|_| {
+                                        ParseError::unknown_value("TimingR800Wait value", value_)
+                                    })?;
                                 if let Some(ref mut timing_entry) = curr_instruction_form.z80_timing
                                 {
                                     timing_entry.r800_plus_wait = r800_plus_wait;
@@ -358,13 +400,51 @@ pub fn populate_instructions(xml_contents: &str) -> Result<Vec<Instruction>> {
                     _ => {} // unknown event
                 }
             }
+            // text event -------------------------------------------------------------------------
+            Ok(Event::Text(ref e)) if in_example => {
+                let example = e.unescape().map_err(|source| ParseError::MalformedXml {
+                    position: reader.buffer_position(),
+                    source,
+                })?;
+                curr_instruction.examples.push(example.into_owned());
+            }
+            Ok(Event::Text(ref e)) if in_note => {
+                let note = e.unescape().map_err(|source| ParseError::MalformedXml {
+                    position: reader.buffer_position(),
+                    source,
+                })?;
+                // notes may be wrapped over several lines in the XML
+                let note = note.split_whitespace().collect::<Vec<&str>>().join(" ");
+                curr_instruction.notes.push(note);
+            }
+            Ok(Event::Text(ref e)) if in_url => {
+                let url = e.unescape().map_err(|source| ParseError::MalformedXml {
+                    position: reader.buffer_position(),
+                    source,
+                })?;
+                curr_instruction.urls.push(url.trim().to_string());
+            }
             // end event --------------------------------------------------------------------------
             Ok(Event::End(ref e)) => {
                 match e.name() {
                     QName(b"Instruction") => {
                         // finish instruction
-                        instructions_map
-                            .insert(curr_instruction.name.clone(), curr_instruction.clone());
+                        instructions_map.insert(
+                            (curr_instruction.arch, curr_instruction.name.clone()),
+                            curr_instruction.clone(),
+                        );
+                    }
+                    // instructions after the set belong to no architecture
+                    QName(b"InstructionSet") => arch = None,
+                    QName(b"Example") => in_example = false,
+                    QName(b"Note") => in_note = false,
+                    QName(b"Url") => in_url = false,
+                    QName(b"Encoding") => {
+                        if let Some(parts) = encoding_parts.take() {
+                            if !parts.is_empty() {
+                                curr_instruction_form.x86_encoding = Some(parts.join(" "));
+                            }
+                        }
                     }
                     QName(b"InstructionForm") => {
                         curr_instruction.push_form(curr_instruction_form.clone());
@@ -373,40 +453,308 @@ pub fn populate_instructions(xml_contents: &str) -> Result<Vec<Instruction>> {
                 }
             }
             Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Err(e) => {
+                return Err(ParseError::MalformedXml {
+                    position: reader.buffer_position(),
+                    source: e,
+                })
+            }
             _ => {} // rest of events that we don't consider
         }
     }
 
-    if let Some(Arch::X86 | Arch::X86_64) = arch {
+    let has_x86 = instructions_map
+        .keys()
+        .any(|(arch, _)| matches!(arch, Some(Arch::X86 | Arch::X86_64)));
+    if has_x86 {
         let x86_online_docs = get_x86_docs_url();
-        let body = get_docs_body(&x86_online_docs).unwrap_or_default();
+        let body = get_docs_body(&x86_online_docs, offline).unwrap_or_default();
         let body_it = body.split("<td>").skip(1).step_by(2);
 
         // Parse this x86 page, grab the contents of the table + the URLs they are referring to
-        // Regex to match:
-        // <a href="./VSCATTERPF1DPS:VSCATTERPF1QPS:VSCATTERPF1DPD:VSCATTERPF1QPD.html">VSCATTERPF1QPS</a></td>
-        //
-        // let re = Regex::new(r"<a href=\"./(.*)">(.*)</a></td>")?;
-        // let re = Regex::new(r#"<a href="\./(.*?\.html)">(.*?)</a>.*</td>"#)?;
-        // let re = Regex::new(r"<a href='\/(.*?)'>(.*?)<\/a>.*<\/td>")?;
-        let re = Regex::new(r"<a href='\/x86\/(.*?)'>(.*?)<\/a>.*<\/td>")?;
-        for line in body_it {
-            // take it step by step.. match a small portion of the line first...
-            let caps = re.captures(line).unwrap();
-            let url_suffix = caps.get(1).map_or("", |m| m.as_str());
-            let instruction_name = caps.get(2).map_or("", |m| m.as_str());
+        let mut found_links = false;
+        for cell in body_it {
+            let Some((url_suffix, instruction_name)) = extract_docs_link(cell) else {
+                continue;
+            };
+            found_links = true;
 
             // add URL to the corresponding instruction
-            if let Some(instruction) = instructions_map.get_mut(instruction_name) {
-                instruction.url = Some(x86_online_docs.clone() + url_suffix);
+            for arch in [Arch::X86, Arch::X86_64] {
+                if let Some(instruction) =
+                    instructions_map.get_mut(&(Some(arch), instruction_name.to_string()))
+                {
+                    // the reference page leads any further links, and later links to
+                    // it for the same name replace earlier ones
+                    let url = x86_online_docs.clone() + url_suffix;
+                    match instruction.urls.first_mut() {
+                        Some(first) if first.starts_with(&x86_online_docs) => *first = url,
+                        _ => instruction.urls.insert(0, url),
+                    }
+                }
             }
         }
+        if !body.is_empty() && !found_links {
+            warn!("No instruction links found in the x86 documentation page, its markup may have changed");
+        }
     }
 
     Ok(instructions_map.into_values().collect())
 }
 
+/// Reads the attributes of the `<Instruction>` tag `e` into a new instruction of `arch`,
+/// with no forms yet
+fn instruction_from_tag(e: &BytesStart, arch: Option<Arch>) -> Result<Instruction, ParseError> {
+    let mut instruction = Instruction {
+        arch,
+        ..Default::default()
+    };
+
+    // iterate over the attributes
+    for attr in e.attributes() {
+        let Attribute { key, value } = attr.unwrap();
+        match str::from_utf8(key.into_inner()).unwrap() {
+            "name" => {
+                let name = String::from(unsafe { str::from_utf8_unchecked(&value) });
+                instruction.alt_names.push(name.to_uppercase());
+                instruction.alt_names.push(name.to_lowercase());
+                instruction.name = name;
+            }
+            "summary" => {
+                instruction.summary = String::from(unsafe { str::from_utf8_unchecked(&value) });
+            }
+            "privileged" => match str::from_utf8(&value)? {
+                "true" => instruction.privileged = true,
+                "false" => instruction.privileged = false,
+                val => {
+                    return Err(ParseError::unknown_value("privileged", val));
+                }
+            },
+            "canonical" => {
+                instruction.canonical = Some(str::from_utf8(&value)?.to_string());
+            }
+            "operand-kind" => {
+                let value_ = str::from_utf8(&value)?;
+                instruction.operand_kind = Some(
+                    OperandKind::from_str(value_)
+                        .map_err(|_| ParseError::unknown_value("operand-kind", value_))?,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    Ok(instruction)
+}
+
+/// Reads the attributes of the `<InstructionForm>` tag `e` into a new instruction form
+fn instruction_form_from_tag(e: &BytesStart) -> Result<InstructionForm, ParseError> {
+    // Read the attributes
+    //
+    // <xs:attribute name="gas-name" type="xs:string" use="required" />
+    // <xs:attribute name="go-name" type="xs:string" />
+    // <xs:attribute name="mmx-mode" type="MMXMode" />
+    // <xs:attribute name="xmm-mode" type="XMMMode" />
+    // <xs:attribute name="cancelling-inputs" type="xs:boolean" />
+    // <xs:attribute name="nacl-version" type="NaClVersion" />
+    // <xs:attribute name="nacl-zero-extends-outputs" type="xs:boolean" />
+    let mut form = InstructionForm::default();
+
+    // iterate over the attributes
+    for attr in e.attributes() {
+        let Attribute { key, value } = attr.unwrap();
+        match str::from_utf8(key.into_inner()).unwrap() {
+            "gas-name" => {
+                form.gas_name = Some(String::from(unsafe { str::from_utf8_unchecked(&value) }));
+            }
+            "go-name" => {
+                form.go_name = Some(String::from(unsafe { str::from_utf8_unchecked(&value) }));
+            }
+            "mmx-mode" => {
+                let value_ = unsafe { str::from_utf8_unchecked(&value) };
+                form.mmx_mode = Some(
+                    MMXMode::from_str(value_)
+                        .map_err(|_| ParseError::unknown_value("mmx-mode", value_))?,
+                );
+            }
+            "introduced-in" => {
+                form.introduced_in =
+                    Some(String::from(unsafe { str::from_utf8_unchecked(&value) }));
+            }
+            "modes" => {
+                let value_ = unsafe { str::from_utf8_unchecked(&value) };
+                form.modes = Some(
+                    Modes::from_str(value_)
+                        .map_err(|()| ParseError::unknown_value("modes", value_))?,
+                );
+            }
+            "xmm-mode" => {
+                let value_ = unsafe { str::from_utf8_unchecked(&value) };
+                form.xmm_mode = Some(
+                    XMMMode::from_str(value_)
+                        .map_err(|_| ParseError::unknown_value("xmm-mode", value_))?,
+                );
+            }
+            "cancelling-inputs" => match str::from_utf8(&value).unwrap() {
+                "true" => form.cancelling_inputs = Some(true),
+                "false" => {
+                    form.cancelling_inputs = Some(false);
+                }
+                val => {
+                    return Err(ParseError::unknown_value("cancelling-inputs", val));
+                }
+            },
+            "nacl-version" => {
+                form.nacl_version = value.as_ref().first().copied();
+            }
+            "nacl-zero-extends-outputs" => match str::from_utf8(&value).unwrap() {
+                "true" => {
+                    form.nacl_zero_extends_outputs = Some(true);
+                }
+                "false" => {
+                    form.nacl_zero_extends_outputs = Some(false);
+                }
+                val => {
+                    return Err(ParseError::unknown_value("nacl-zero-extends-outputs", val));
+                }
+            },
+            "z80name" => {
+                form.z80_name = Some(String::from(unsafe { str::from_utf8_unchecked(&value) }));
+            }
+            "form" => {
+                let value_ = unsafe { str::from_utf8_unchecked(&value) };
+                form.urls.push(Z80DocsSource::default().url(value_));
+                form.z80_form = Some(value_.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    Ok(form)
+}
+
+/// Renders an element of an x86 `Encoding` with the attributes `attrs` as its part of the
+/// encoding template, in Intel's notation: mandatory prefixes and opcode bytes in hex,
+/// `REX.W +` when REX.W is set, `VEX.128.66.0F38.W0`-style VEX/XOP/EVEX prefixes, `/digit`
+/// or `/r` for the ModRM byte, `/is4` for a register in the immediate, `+r` for a
+/// register added to the opcode, and `ib`/`iw`/`id`/`io` and `cb`/`cd` for immediates and
+/// code offsets by size. Returns `None` for elements that don't show in the template
+fn encoding_template_part(element: &[u8], attrs: &HashMap<String, String>) -> Option<String> {
+    let attr = |name: &str| attrs.get(name).map(String::as_str);
+    let size_suffix = |prefix: char| {
+        let size = match attr("size")? {
+            "1" => 'b',
+            "2" => 'w',
+            "4" => 'd',
+            "8" => 'o',
+            _ => return None,
+        };
+        Some(format!("{prefix}{size}"))
+    };
+    match element {
+        b"Prefix" if attr("mandatory") == Some("true") => attr("byte").map(str::to_uppercase),
+        b"REX" if attr("W") == Some("1") => Some(String::from("REX.W +")),
+        b"VEX" | b"EVEX" => {
+            let (kind, map, length) = if element == b"VEX" {
+                let map = match attr("m-mmmm")? {
+                    "00001" => "0F",
+                    "00010" => "0F38",
+                    "00011" => "0F3A",
+                    "01000" => "08",
+                    "01001" => "09",
+                    "01010" => "0A",
+                    _ => return None,
+                };
+                let length = match attr("L") {
+                    Some("0") => "128",
+                    Some("1") => "256",
+                    _ => "LIG",
+                };
+                (attr("type").unwrap_or("VEX"), map, length)
+            } else {
+                let map = match attr("mmm")? {
+                    "001" => "0F",
+                    "010" => "0F38",
+                    "011" => "0F3A",
+                    "101" => "MAP5",
+                    "110" => "MAP6",
+                    _ => return None,
+                };
+                let length = match attr("LL") {
+                    Some("00") => "128",
+                    Some("01") => "256",
+                    Some("10") => "512",
+                    _ => "LIG",
+                };
+                ("EVEX", map, length)
+            };
+            let mut fields = vec![kind, length];
+            match attr("pp") {
+                Some("01") => fields.push("66"),
+                Some("10") => fields.push("F3"),
+                Some("11") => fields.push("F2"),
+                _ => {}
+            }
+            fields.push(map);
+            fields.push(match attr("W") {
+                Some("0") => "W0",
+                Some("1") => "W1",
+                _ => "WIG",
+            });
+            Some(fields.join("."))
+        }
+        b"Opcode" => {
+            let byte = attr("byte")?.to_uppercase();
+            Some(if attr("addend").is_some() {
+                format!("{byte}+r")
+            } else {
+                byte
+            })
+        }
+        b"ModRM" => match attr("reg")? {
+            digit if digit.len() == 1 && digit.chars().all(|c| c.is_ascii_digit()) => {
+                Some(format!("/{digit}"))
+            }
+            _ => Some(String::from("/r")),
+        },
+        b"RegisterByte" => Some(String::from("/is4")),
+        b"Immediate" => size_suffix('i'),
+        b"CodeOffset" => size_suffix('c'),
+        _ => None,
+    }
+}
+
+/// Patterns for the instruction links in the x86 documentation page's table cells, tried
+/// in order. Each captures the link target relative to the docs root, then the
+/// instruction name. The page's markup has changed before, so variations in quote style
+/// and relative vs absolute links are accepted
+static DOCS_LINK_PATTERNS: Lazy<Vec<Regex>> = Lazy::new(|| {
+    [
+        // <a href='/x86/aaa'>AAA</a></td>
+        r"<a href='/x86/(.*?)'>(.*?)</a>",
+        // <a href="/x86/aaa">AAA</a></td>
+        r#"<a href="/x86/(.*?)">(.*?)</a>"#,
+        // <a href="./VSCATTERPF1DPS:VSCATTERPF1QPS.html">VSCATTERPF1QPS</a></td>
+        r#"<a href=["']\./(.*?)["']>(.*?)</a>"#,
+        // <a href="https://www.felixcloutier.com/x86/aaa">AAA</a></td>
+        r#"<a href=["']https?://[^/"']+/x86/(.*?)["']>(.*?)</a>"#,
+        // anything else: the last path segment of the first link's target
+        r#"<a\s[^>]*?href\s*=\s*["']?(?:[^"'>]*/)?([^"'/>]+)["']?[^>]*>(.*?)</a>"#,
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).unwrap())
+    .collect()
+});
+
+/// Extracts the link target (relative to the docs root) and instruction name from a
+/// table cell of the x86 documentation page
+fn extract_docs_link(cell: &str) -> Option<(&str, &str)> {
+    DOCS_LINK_PATTERNS.iter().find_map(|re| {
+        let caps = re.captures(cell)?;
+        Some((caps.get(1)?.as_str(), caps.get(2)?.as_str().trim()))
+    })
+}
+
 pub fn populate_name_to_instruction_map<'instruction>(
     arch: Arch,
     instructions: &'instruction Vec<Instruction>,
@@ -428,41 +776,21 @@ pub fn populate_name_to_instruction_map<'instruction>(
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::x86_parser::{get_cache_dir, populate_instructions};
-    #[test]
-    fn test_populate_instructions() {
-        let mut server = mockito::Server::new_with_port(8080);
-
-        let _ = server
-            .mock("GET", "/x86/")
-            .with_status(200)
-            .with_header("content-type", "text/html")
-            .with_body(include_str!(
-                "../docs_store/instr_info_cache/x86_instr_docs.html"
-            ))
-            .create();
-
-        // Need to clear the cache file (if there is one)
-        // to ensure a request is made for each test call
-        let mut x86_cache_path = get_cache_dir().unwrap();
-        x86_cache_path.push("x86_instr_docs.html");
-        if x86_cache_path.is_file() {
-            std::fs::remove_file(&x86_cache_path).unwrap();
-        }
-        let xml_conts_x86 = include_str!("../docs_store/opcodes/raw/x86.xml");
-        assert!(populate_instructions(xml_conts_x86).is_ok());
-
-        if x86_cache_path.is_file() {
-            std::fs::remove_file(&x86_cache_path).unwrap();
-        }
-        let xml_conts_x86_64 = include_str!("../docs_store/opcodes/raw/x86_64.xml");
-        assert!(populate_instructions(xml_conts_x86_64).is_ok());
-
-        // Clean things up so we don't have an empty cache file
-        if x86_cache_path.is_file() {
-            std::fs::remove_file(&x86_cache_path).unwrap();
+/// Adds `custom` instructions (see `TargetConfig::custom_instructions`) to
+/// `names_to_instructions` under their architecture. Documented instructions of the same
+/// name take precedence
+pub fn populate_custom_instructions<'instruction>(
+    custom: &'instruction [Instruction],
+    names_to_instructions: &mut NameToInstructionMap<'instruction>,
+) {
+    for instruction in custom {
+        let Some(arch) = instruction.arch else {
+            continue;
+        };
+        for name in instruction.get_primary_names() {
+            names_to_instructions
+                .entry((arch, name))
+                .or_insert(instruction);
         }
     }
 }
@@ -475,14 +803,13 @@ mod tests {
 ///
 /// # Errors
 ///
-/// This function is highly specialized to parse a handful of files and will panic or return
-/// `Err` for most mal-formed/unexpected inputs
+/// Returns a [`ParseError`] describing the first malformed or unexpected input encountered
 ///
 /// # Panics
 ///
 /// This function is highly specialized to parse a handful of files and will panic or return
 /// `Err` for most mal-formed/unexpected inputs
-pub fn populate_registers(xml_contents: &str) -> Result<Vec<Register>> {
+pub fn populate_registers(xml_contents: &str) -> Result<Vec<Register>, ParseError> {
     let mut registers_map = HashMap::<String, Register>::new();
 
     // iterate through the XML --------------------------------------------------------------------
@@ -512,6 +839,8 @@ pub fn populate_registers(xml_contents: &str) -> Result<Vec<Register>> {
                         // start of a new register
                         curr_register = Register::default();
                         curr_register.arch = arch;
+                        let mut bits: Option<(u32, u32)> = None;
+                        let mut parents: Vec<String> = Vec::new();
 
                         // iterate over the attributes
                         for attr in e.attributes() {
@@ -535,24 +864,54 @@ pub fn populate_registers(xml_contents: &str) -> Result<Vec<Register>> {
                                     }));
                                 }
                                 "type" => {
-                                    curr_register.reg_type = match RegisterType::from_str(unsafe {
+                                    curr_register.reg_type = RegisterType::from_str(unsafe {
                                         str::from_utf8_unchecked(&value)
-                                    }) {
-                                        Ok(reg) => Some(reg),
-                                        _ => None,
-                                    }
+                                    })
+                                    .ok();
                                 }
                                 "width" => {
-                                    curr_register.width = match RegisterWidth::from_str(unsafe {
+                                    curr_register.width = RegisterWidth::from_str(unsafe {
                                         str::from_utf8_unchecked(&value)
-                                    }) {
-                                        Ok(width) => Some(width),
-                                        _ => None,
+                                    })
+                                    .ok();
+                                }
+                                "undocumented" => match str::from_utf8(&value)? {
+                                    "true" => curr_register.undocumented = true,
+                                    "false" => curr_register.undocumented = false,
+                                    val => {
+                                        return Err(ParseError::unknown_value("undocumented", val));
                                     }
+                                },
+                                "bits" => {
+                                    let value_ = str::from_utf8(&value)?;
+                                    bits = Some(
+                                        value_
+                                            .split_once('-')
+                                            .and_then(|(low, high)| {
+                                                Some((low.parse().ok()?, high.parse().ok()?))
+                                            })
+                                            .ok_or_else(|| {
+                                                ParseError::unknown_value("bits", value_)
+                                            })?,
+                                    );
+                                }
+                                "parents" => {
+                                    parents = str::from_utf8(&value)?
+                                        .split(',')
+                                        .map(String::from)
+                                        .collect();
                                 }
                                 _ => {}
                             }
                         }
+
+                        if let Some((low_bit, high_bit)) = bits {
+                            curr_register.position = Some(RegisterPosition {
+                                low_bit,
+                                high_bit,
+                                parents,
+                            });
+                        }
                     }
                     QName(b"Flags") => {} // it's just a wrapper...
                     // Actual flag bit info
@@ -604,7 +963,12 @@ pub fn populate_registers(xml_contents: &str) -> Result<Vec<Register>> {
                 }
             }
             Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Err(e) => {
+                return Err(ParseError::MalformedXml {
+                    position: reader.buffer_position(),
+                    source: e,
+                })
+            }
             _ => {} // rest of events that we don't consider
         }
     }
@@ -636,14 +1000,13 @@ pub fn populate_name_to_register_map<'register>(
 ///
 /// # Errors
 ///
-/// This function is highly specialized to parse a handful of files and will panic or return
-/// `Err` for most mal-formed/unexpected inputs
+/// Returns a [`ParseError`] describing the first malformed or unexpected input encountered
 ///
 /// # Panics
 ///
 /// This function is highly specialized to parse a handful of files and will panic or return
 /// `Err` for most mal-formed/unexpected inputs
-pub fn populate_directives(xml_contents: &str) -> Result<Vec<Directive>> {
+pub fn populate_directives(xml_contents: &str) -> Result<Vec<Directive>, ParseError> {
     let mut directives_map = HashMap::<String, Directive>::new();
 
     // iterate through the XML --------------------------------------------------------------------
@@ -697,6 +1060,16 @@ pub fn populate_directives(xml_contents: &str) -> Result<Vec<Directive>> {
                                     })
                                     .unwrap();
                                 }
+                                "symbol_args" => {
+                                    let value_ = str::from_utf8(&value)?;
+                                    for arg in value_.split(',') {
+                                        curr_directive.symbol_args.push(
+                                            arg.trim().parse::<usize>().map_err(|_| {
+                                                ParseError::unknown_value("symbol_args", value_)
+                                            })?,
+                                        );
+                                    }
+                                }
                                 "url_fragment" => {
                                     curr_directive.url = Some(format!(
                                         "https://sourceware.org/binutils/docs-2.41/as/{}.html",
@@ -730,7 +1103,12 @@ pub fn populate_directives(xml_contents: &str) -> Result<Vec<Directive>> {
                 }
             }
             Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Err(e) => {
+                return Err(ParseError::MalformedXml {
+                    position: reader.buffer_position(),
+                    source: e,
+                })
+            }
             _ => {} // rest of events that we don't consider
         }
     }
@@ -750,13 +1128,16 @@ pub fn populate_name_to_directive_map<'directive>(
     }
 }
 
-fn get_docs_body(x86_online_docs: &str) -> Option<String> {
+fn get_docs_body(x86_online_docs: &str, offline: bool) -> Option<String> {
     // provide a URL example page -----------------------------------------------------------------
     // 1. If the cache refresh option is enabled or the cache doesn't exist, attempt to fetch the
-    //    data, write it to the cache, and then use it
+    //    data, write it to the cache, and then use it. This is skipped entirely when offline
     // 2. Otherwise, attempt to read the data from the cache
     // 3. If invalid data is read in, attempt to remove the cache file
     let cache_refresh = args().any(|arg| arg.contains("--cache-refresh"));
+    if offline && cache_refresh {
+        warn!("Ignoring --cache-refresh, the documentation isn't fetched in offline mode.");
+    }
     let mut x86_cache_path = match get_cache_dir() {
         Ok(cache_path) => Some(cache_path),
         Err(e) => {
@@ -775,7 +1156,12 @@ fn get_docs_body(x86_online_docs: &str) -> Option<String> {
         cache_exists = false;
     }
 
-    let body = if cache_refresh || !cache_exists {
+    if offline && !cache_exists {
+        info!("Offline mode, skipping the documentation fetch from {x86_online_docs}.");
+        return None;
+    }
+
+    let body = if !offline && (cache_refresh || !cache_exists) {
         match get_x86_docs_web(x86_online_docs) {
             Ok(docs) => {
                 if let Some(ref path) = x86_cache_path {
@@ -829,15 +1215,22 @@ fn get_docs_body(x86_online_docs: &str) -> Option<String> {
 
 /// Searches for the asm-lsp cache directory. First checks for the  `ASM_LSP_CACHE_DIR`
 /// environment variable. If this variable is present and points to a valid directory,
-/// this path is returned. Otherwise, the function returns `~/.config/asm-lsp/`
+/// this path is returned. Otherwise, the function returns `$XDG_CACHE_HOME/asm-lsp/` if
+/// `XDG_CACHE_HOME` is set to an absolute path, or `~/.cache/asm-lsp/`
 ///
 /// # Errors
 ///
-/// Returns `Err` if no directory can be found through `ASM_LSP_CACHE_DIR`, and
-/// then no home directory can be found on the system
+/// Returns `Err` if no directory can be found through `ASM_LSP_CACHE_DIR` or
+/// `XDG_CACHE_HOME`, and then no home directory can be found on the system
 pub fn get_cache_dir() -> Result<PathBuf> {
+    resolve_cache_dir(|var| std::env::var_os(var))
+}
+
+/// Resolves the cache directory as described by `get_cache_dir`, reading environment
+/// variables through `env`
+fn resolve_cache_dir(env: impl Fn(&str) -> Option<std::ffi::OsString>) -> Result<PathBuf> {
     // first check if the appropriate environment variable is set
-    if let Ok(path) = std::env::var("ASM_LSP_CACHE_DIR") {
+    if let Some(path) = env("ASM_LSP_CACHE_DIR") {
         let path = PathBuf::from(path);
         // ensure the path is valid
         if path.is_dir() {
@@ -845,18 +1238,173 @@ pub fn get_cache_dir() -> Result<PathBuf> {
         }
     }
 
-    // If the environment variable isn't set or gives an invalid path, grab the home directory and build off of that
-    let mut x86_cache_path = home::home_dir().ok_or(anyhow!("Home directory not found"))?;
+    // then the XDG base directory, which must be absolute to be considered
+    let cache_home = env("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute());
 
-    x86_cache_path.push(".cache");
+    // If neither environment variable gives a valid path, grab the home directory and build off of that
+    let mut x86_cache_path = match cache_home {
+        Some(path) => path,
+        None => {
+            let mut path = home::home_dir().ok_or(anyhow!("Home directory not found"))?;
+            path.push(".cache");
+            path
+        }
+    };
     x86_cache_path.push("asm-lsp");
 
-    // create the ~/.cache/asm-lsp directory if it's not already there
+    // create the asm-lsp cache directory if it's not already there
     fs::create_dir_all(&x86_cache_path)?;
 
     Ok(x86_cache_path)
 }
 
+/// Loads an instruction set, preferring a raw `<name>.xml` placed in `cache_dir` (e.g.
+/// `x86.xml`) over the `bundled` serialized instructions. This lets users track upstream
+/// instruction data without rebuilding. An override which fails to load is logged and
+/// the bundled instructions are used instead
+///
+/// When `offline` is set, loading an override never accesses the network. Its
+/// instructions are instead linked to the documentation URLs baked into `bundled`
+///
+/// # Errors
+///
+/// Returns `Err` if the bundled instructions fail to deserialize
+pub fn load_instructions(
+    name: &str,
+    bundled: &[u8],
+    cache_dir: Option<&Path>,
+    offline: bool,
+) -> Result<Vec<Instruction>> {
+    if let Some(dir) = cache_dir {
+        let override_path = dir.join(format!("{name}.xml"));
+        if override_path.is_file() {
+            let path_s = override_path.display();
+            let populate = if offline {
+                populate_instructions_offline
+            } else {
+                populate_instructions
+            };
+            match fs::read_to_string(&override_path)
+                .map_err(anyhow::Error::from)
+                .and_then(|xml| Ok(populate(&xml)?))
+            {
+                Ok(mut instrs) => {
+                    info!("Using {name} instructions from override -> {path_s}");
+                    if offline {
+                        let bundled = bincode::deserialize::<Vec<Instruction>>(bundled)?;
+                        fill_bundled_urls(&mut instrs, &bundled);
+                    }
+                    return Ok(instrs);
+                }
+                Err(e) => {
+                    error!("Failed to load {name} instruction override {path_s} - Error: {e}");
+                }
+            }
+        }
+    }
+
+    info!("Using bundled {name} instructions");
+    Ok(bincode::deserialize::<Vec<Instruction>>(bundled)?)
+}
+
+/// Loads the `bundled` directives, merged with those of the raw directive XML file at
+/// `extra` (in the format of `docs_store/directives/raw/gas.xml`), if given. Extra
+/// directives replace any bundled ones sharing a name with them. An extra file which
+/// fails to load is logged and only the bundled directives are used
+///
+/// # Errors
+///
+/// Returns `Err` if the bundled directives fail to deserialize
+pub fn load_directives(bundled: &[u8], extra: Option<&Path>) -> Result<Vec<Directive>> {
+    let mut directives = bincode::deserialize::<Vec<Directive>>(bundled)?;
+    let Some(path) = extra else {
+        return Ok(directives);
+    };
+
+    let path_s = path.display();
+    match fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|xml| Ok(populate_directives(&xml)?))
+    {
+        Ok(extra_dirs) => {
+            info!("Merging {} directives from {path_s}", extra_dirs.len());
+            let extra_names: HashSet<String> = extra_dirs
+                .iter()
+                .flat_map(Directive::get_associated_names)
+                .map(String::from)
+                .collect();
+            directives.retain(|dir| {
+                !dir.get_associated_names()
+                    .iter()
+                    .any(|name| extra_names.contains(*name))
+            });
+            directives.extend(extra_dirs);
+        }
+        Err(e) => {
+            error!("Failed to load extra directives {path_s} - Error: {e}");
+        }
+    }
+
+    Ok(directives)
+}
+
+/// Returns a content hash of `items`, for telling apart versions of the bundled data.
+/// It's the 64-bit FNV-1a hash of the items' bincode serialization, so it's stable
+/// across runs and platforms, unlike `std`'s randomly seeded hashers
+///
+/// # Errors
+///
+/// Returns `Err` if `items` fail to serialize
+pub fn data_hash<T: serde::Serialize>(items: &[T]) -> Result<u64> {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+    let bytes = bincode::serialize(items)?;
+    Ok(bytes.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    }))
+}
+
+/// Links each instruction in `instrs` to the documentation URLs of the instruction with
+/// the same architecture and name in `bundled` it's missing, if any, ahead of its own
+fn fill_bundled_urls(instrs: &mut [Instruction], bundled: &[Instruction]) {
+    let urls: HashMap<(Option<Arch>, &str), &Vec<String>> = bundled
+        .iter()
+        .map(|instr| ((instr.arch, instr.name.as_str()), &instr.urls))
+        .collect();
+    for instr in instrs.iter_mut() {
+        if let Some(bundled_urls) = urls.get(&(instr.arch, instr.name.as_str())) {
+            let missing: Vec<String> = bundled_urls
+                .iter()
+                .filter(|url| !instr.urls.contains(url))
+                .cloned()
+                .collect();
+            instr.urls.splice(0..0, missing);
+        }
+    }
+}
+
+/// Relinks the forms of the z80 instructions in `instrs` from the default documentation
+/// (Zilog's user manual), which parsing links them to, to `source`
+pub fn relink_z80_docs(instrs: &mut [Instruction], source: Z80DocsSource) {
+    if source == Z80DocsSource::default() {
+        return;
+    }
+    for form in instrs.iter_mut().flat_map(|instr| instr.forms.iter_mut()) {
+        let Some(ref z80_form) = form.z80_form else {
+            continue;
+        };
+        let default_url = Z80DocsSource::default().url(z80_form);
+        for url in &mut form.urls {
+            if *url == default_url {
+                *url = source.url(z80_form);
+            }
+        }
+    }
+}
+
 #[cfg(not(test))]
 fn get_x86_docs_url() -> String {
     String::from("https://www.felixcloutier.com/x86/")
@@ -907,3 +1455,263 @@ fn set_x86_docs_cache(contents: &str, x86_cache_path: &PathBuf) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{Arch, Z80DocsSource};
+    use crate::x86_parser::{
+        extract_docs_link, get_cache_dir, load_instructions, populate_instructions,
+        populate_registers, relink_z80_docs, resolve_cache_dir, ParseError,
+    };
+    #[test]
+    fn test_extract_docs_link_formats() {
+        let cells = [
+            "<a href='/x86/aaa'>AAA</a></td>",
+            "<a href=\"/x86/aaa\">AAA</a></td>",
+            "<a href='./aaa'>AAA</a></td>",
+            "<a href=\"https://www.felixcloutier.com/x86/aaa\">AAA</a></td>",
+            "<a class=\"instr\" href=\"../x86/aaa\">AAA</a></td>",
+        ];
+        for cell in cells {
+            assert_eq!(extract_docs_link(cell), Some(("aaa", "AAA")), "{}", cell);
+        }
+        assert_eq!(extract_docs_link("ASCII Adjust After Addition</td>"), None);
+    }
+    #[test]
+    fn test_populate_instructions() {
+        let mut server = mockito::Server::new_with_port(8080);
+
+        let _ = server
+            .mock("GET", "/x86/")
+            .with_status(200)
+            .with_header("content-type", "text/html")
+            .with_body(include_str!(
+                "../docs_store/instr_info_cache/x86_instr_docs.html"
+            ))
+            .create();
+
+        // Need to clear the cache file (if there is one)
+        // to ensure a request is made for each test call
+        let mut x86_cache_path = get_cache_dir().unwrap();
+        x86_cache_path.push("x86_instr_docs.html");
+        if x86_cache_path.is_file() {
+            std::fs::remove_file(&x86_cache_path).unwrap();
+        }
+        let xml_conts_x86 = include_str!("../docs_store/opcodes/raw/x86.xml");
+        assert!(populate_instructions(xml_conts_x86).is_ok());
+
+        if x86_cache_path.is_file() {
+            std::fs::remove_file(&x86_cache_path).unwrap();
+        }
+        let xml_conts_x86_64 = include_str!("../docs_store/opcodes/raw/x86_64.xml");
+        assert!(populate_instructions(xml_conts_x86_64).is_ok());
+
+        // Clean things up so we don't have an empty cache file
+        if x86_cache_path.is_file() {
+            std::fs::remove_file(&x86_cache_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_populate_reports_parse_error_kinds() {
+        let bad_operand = r#"<InstructionSet name="z80">
+<Instruction name="LD" summary="Load">
+<InstructionForm z80name="LD">
+<Operand type="bogus" />
+</InstructionForm>
+</Instruction>
+</InstructionSet>"#;
+        match populate_instructions(bad_operand) {
+            Err(ParseError::UnknownOperandType(variant)) => assert_eq!(variant, "bogus"),
+            other => panic!("Expected UnknownOperandType, got {:?}", other),
+        }
+
+        let bad_xml = r#"<InstructionSet name="z80"><Register name="a"></Flags></InstructionSet>"#;
+        assert!(matches!(
+            populate_registers(bad_xml),
+            Err(ParseError::MalformedXml { .. })
+        ));
+    }
+
+    #[test]
+    fn test_populate_instructions_scopes_each_instruction_set() {
+        let xml = r#"<InstructionSet name="z80">
+<Instruction name="NOP" summary="No Operation">
+<InstructionForm z80name="NOP">
+</InstructionForm>
+</Instruction>
+<Instruction name="HALT" summary="Halt">
+<InstructionForm z80name="HALT">
+</InstructionForm>
+</Instruction>
+</InstructionSet>
+<InstructionSet name="aarch64">
+<Instruction name="NOP" summary="No Operation">
+<InstructionForm gas-name="nop">
+</InstructionForm>
+</Instruction>
+</InstructionSet>"#;
+        let mut instrs: Vec<(String, Option<Arch>)> = populate_instructions(xml)
+            .unwrap()
+            .into_iter()
+            .map(|instr| (instr.name, instr.arch))
+            .collect();
+        instrs.sort_by_key(|(name, arch)| (name.clone(), arch.map(|arch| arch.to_string())));
+        assert_eq!(
+            instrs,
+            [
+                (String::from("HALT"), Some(Arch::Z80)),
+                (String::from("NOP"), Some(Arch::AArch64)),
+                (String::from("NOP"), Some(Arch::Z80)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_populate_instructions_keeps_self_closing_tags() {
+        let xml = r#"<InstructionSet name="z80">
+<Instruction name="NOP" summary="No Operation"/>
+<Instruction name="HALT" summary="Halt">
+<InstructionForm z80name="HALT" />
+</Instruction>
+</InstructionSet>"#;
+        let instrs = populate_instructions(xml).unwrap();
+        let nop = instrs.iter().find(|instr| instr.name == "NOP").unwrap();
+        assert_eq!(nop.summary, "No Operation");
+        assert_eq!(nop.arch, Some(Arch::Z80));
+        assert!(nop.forms.is_empty());
+        let halt = instrs.iter().find(|instr| instr.name == "HALT").unwrap();
+        assert_eq!(halt.forms.len(), 1);
+        assert_eq!(halt.forms[0].z80_name.as_deref(), Some("HALT"));
+    }
+
+    #[test]
+    fn test_load_instructions_prefers_cache_override() {
+        let bundled = include_bytes!("../docs_store/opcodes/serialized/z80");
+        let num_bundled = load_instructions("z80", bundled, None, false)
+            .unwrap()
+            .len();
+        assert!(num_bundled > 1);
+
+        let cache_dir =
+            std::env::temp_dir().join(format!("asm-lsp-override-{}", std::process::id()));
+        std::fs::create_dir_all(&cache_dir).unwrap();
+        let override_path = cache_dir.join("z80.xml");
+        std::fs::write(
+            &override_path,
+            r#"<InstructionSet name="z80">
+<Instruction name="NOP" summary="No Operation">
+<InstructionForm z80name="NOP">
+</InstructionForm>
+</Instruction>
+</InstructionSet>"#,
+        )
+        .unwrap();
+        let instrs = load_instructions("z80", bundled, Some(&cache_dir), false).unwrap();
+        assert_eq!(instrs.len(), 1);
+        assert_eq!(instrs[0].name, "NOP");
+
+        // a broken override falls back to the bundled instructions
+        std::fs::write(
+            &override_path,
+            "<InstructionSet name=\"z80\"></Instruction>",
+        )
+        .unwrap();
+        let instrs = load_instructions("z80", bundled, Some(&cache_dir), false).unwrap();
+        assert_eq!(instrs.len(), num_bundled);
+
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn test_relink_z80_docs() {
+        let bundled = include_bytes!("../docs_store/opcodes/serialized/z80");
+        let mut instrs = load_instructions("z80", bundled, None, true).unwrap();
+        let form_urls = |instrs: &[crate::types::Instruction], form: &str| {
+            instrs
+                .iter()
+                .flat_map(|instr| &instr.forms)
+                .find(|f| f.z80_form.as_deref() == Some(form))
+                .unwrap()
+                .urls
+                .clone()
+        };
+        assert_eq!(
+            form_urls(&instrs, "LD A, n"),
+            ["https://www.zilog.com/docs/z80/z80cpu_um.pdf#LD%20A%2C%20n"]
+        );
+
+        let mut clrhome_instrs = instrs.clone();
+        relink_z80_docs(&mut instrs, Z80DocsSource::Z80Heaven);
+        assert_eq!(
+            form_urls(&instrs, "LD A, n"),
+            ["http://z80-heaven.wikidot.com/instructions-set:ld"]
+        );
+        relink_z80_docs(&mut clrhome_instrs, Z80DocsSource::ClrHome);
+        assert_eq!(
+            form_urls(&clrhome_instrs, "LD A, n"),
+            ["https://clrhome.org/table/"]
+        );
+    }
+    #[test]
+    fn test_load_instructions_offline_uses_bundled_urls() {
+        let bundled = include_bytes!("../docs_store/opcodes/serialized/x86");
+        let cache_dir =
+            std::env::temp_dir().join(format!("asm-lsp-offline-{}", std::process::id()));
+        std::fs::create_dir_all(&cache_dir).unwrap();
+        std::fs::write(
+            cache_dir.join("x86.xml"),
+            r#"<InstructionSet name="x86">
+<Instruction name="AAA" summary="ASCII Adjust After Addition">
+<InstructionForm gas-name="aaa">
+</InstructionForm>
+</Instruction>
+<Instruction name="NOTANOP" summary="Not an instruction">
+<InstructionForm gas-name="notanop">
+</InstructionForm>
+</Instruction>
+</InstructionSet>"#,
+        )
+        .unwrap();
+
+        let mut instrs = load_instructions("x86", bundled, Some(&cache_dir), true).unwrap();
+        instrs.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(instrs.len(), 2);
+        assert_eq!(instrs[0].urls.len(), 1);
+        assert!(instrs[0].urls[0].ends_with("/x86/aaa"));
+        assert!(instrs[1].urls.is_empty());
+
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_dir_honors_xdg_cache_home() {
+        let cache_home = std::env::temp_dir().join(format!("asm-lsp-xdg-{}", std::process::id()));
+        std::fs::create_dir_all(&cache_home).unwrap();
+
+        let env = |var: &str| match var {
+            "XDG_CACHE_HOME" => Some(cache_home.clone().into_os_string()),
+            _ => None,
+        };
+        let cache_dir = resolve_cache_dir(env).unwrap();
+        assert_eq!(cache_dir, cache_home.join("asm-lsp"));
+        assert!(cache_dir.is_dir());
+
+        // `ASM_LSP_CACHE_DIR` takes precedence
+        let env = |var: &str| match var {
+            "ASM_LSP_CACHE_DIR" => Some(cache_home.clone().into_os_string()),
+            "XDG_CACHE_HOME" => Some(std::ffi::OsString::from("/nonexistent")),
+            _ => None,
+        };
+        assert_eq!(resolve_cache_dir(env).unwrap(), cache_home);
+
+        // relative paths are ignored, per the XDG base directory spec
+        let env = |var: &str| match var {
+            "XDG_CACHE_HOME" => Some(std::ffi::OsString::from("relative/cache")),
+            _ => None,
+        };
+        assert!(resolve_cache_dir(env).unwrap().ends_with(".cache/asm-lsp"));
+
+        std::fs::remove_dir_all(&cache_home).unwrap();
+    }
+}
@@ -0,0 +1,248 @@
+//! Resolution of the z80 instruction form selected by an instruction's operands, and
+//! assembly of that form's opcode bytes
+
+use crate::types::InstructionForm;
+
+/// 8-bit registers, indexed by their `r` code (6 selects `(HL)` rather than a register)
+const R_REGS: [&str; 8] = ["b", "c", "d", "e", "h", "l", "", "a"];
+/// Registers selectable by `p` in `DD`-prefixed forms, indexed by their code
+const P_REGS: [&str; 8] = ["b", "c", "d", "e", "ixh", "ixl", "", "a"];
+/// Registers selectable by `q` in `FD`-prefixed forms, indexed by their code
+const Q_REGS: [&str; 8] = ["b", "c", "d", "e", "iyh", "iyl", "", "a"];
+
+/// Register and condition names, which can never be a symbolic immediate
+const RESERVED: [&str; 29] = [
+    "a", "b", "c", "d", "e", "h", "l", "i", "r", "f", "af", "af'", "bc", "de", "hl", "sp", "ix",
+    "iy", "ixh", "ixl", "iyh", "iyl", "nz", "z", "nc", "po", "pe", "p", "m",
+];
+
+/// An immediate operand, which is `None` if it refers to a symbol that can't be resolved
+/// from the line alone
+type Imm = Option<i64>;
+
+/// Values bound to the placeholders of a form by an instruction's operands
+#[derive(Debug, Default)]
+struct Bindings {
+    r: Option<u8>,
+    p: Option<u8>,
+    q: Option<u8>,
+    b: Option<u8>,
+    n: Option<Imm>,
+    nn: Option<Imm>,
+    o: Option<Imm>,
+}
+
+/// Returns the opcode bytes of the form of `forms` selected by `operands`, e.g. `DD 8E 05`
+/// for `adc a, (ix+5)`. Bytes depending on symbols (such as a jump's target label) are
+/// left as their placeholder (`n`, `nn` or `o`)
+///
+/// Returns `None` if no form matches the operands, or if several match equally well
+#[must_use]
+pub fn assemble(forms: &[InstructionForm], operands: &[&str]) -> Option<String> {
+    let mut best: Option<(u32, &InstructionForm, Bindings)> = None;
+    let mut tied = false;
+    for form in forms {
+        let Some((score, bindings)) = form.z80_form.as_deref().and_then(|f| bind(f, operands))
+        else {
+            continue;
+        };
+        match best {
+            Some((best_score, _, _)) if score < best_score => {}
+            Some((best_score, _, _)) if score == best_score => tied = true,
+            _ => {
+                best = Some((score, form, bindings));
+                tied = false;
+            }
+        }
+    }
+    if tied {
+        return None;
+    }
+
+    let (_, form, bindings) = best?;
+    encode(form.z80_opcode.as_deref()?, &bindings)
+}
+
+/// Matches `operands` against the operand patterns of `form` (e.g. `LD A, (IX+o)`),
+/// returning how specific the match is along with the values bound by the operands
+fn bind(form: &str, operands: &[&str]) -> Option<(u32, Bindings)> {
+    let patterns: Vec<&str> = form
+        .split_once(' ')
+        .map(|(_, ops)| ops.split(',').map(str::trim).collect())
+        .unwrap_or_default();
+    if patterns.len() != operands.len() {
+        return None;
+    }
+
+    let mut bindings = Bindings::default();
+    let mut score = 0;
+    for (pattern, operand) in patterns.iter().zip(operands) {
+        let operand: String = operand
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_lowercase();
+        score += bind_operand(pattern, &operand, &mut bindings)?;
+    }
+
+    Some((score, bindings))
+}
+
+/// Matches a single lowercase `operand` against `pattern`, returning how specific the
+/// match is. Exact matches outrank register classes, which outrank immediates
+fn bind_operand(pattern: &str, operand: &str, bindings: &mut Bindings) -> Option<u32> {
+    let reg_code = |regs: &[&str; 8]| {
+        regs.iter()
+            .position(|reg| !reg.is_empty() && *reg == operand)
+            .map(|code| code as u8)
+    };
+
+    match pattern {
+        "r" => {
+            bindings.r = Some(reg_code(&R_REGS)?);
+            Some(3)
+        }
+        "p" | "IXp" => {
+            bindings.p = Some(reg_code(&P_REGS)?);
+            Some(2)
+        }
+        "q" | "IYq" => {
+            bindings.q = Some(reg_code(&Q_REGS)?);
+            Some(2)
+        }
+        "b" => {
+            let bit = parse_number(operand).filter(|bit| (0..8).contains(bit))?;
+            bindings.b = Some(bit as u8);
+            Some(3)
+        }
+        "n" => {
+            bindings.n = Some(parse_imm(operand)?);
+            Some(1)
+        }
+        "nn" => {
+            bindings.nn = Some(parse_imm(operand)?);
+            Some(1)
+        }
+        // a relative jump's offset depends on the instruction's own address
+        "o" => {
+            parse_imm(operand)?;
+            bindings.o = Some(None);
+            Some(1)
+        }
+        "(n)" | "(nn)" => {
+            let imm = parse_imm(operand.strip_prefix('(')?.strip_suffix(')')?)?;
+            if pattern == "(n)" {
+                bindings.n = Some(imm);
+            } else {
+                bindings.nn = Some(imm);
+            }
+            Some(1)
+        }
+        "(IX+o)" | "(IY+o)" => {
+            let base = pattern[..3].to_lowercase();
+            let offset = operand.strip_prefix(&base)?.strip_suffix(')')?;
+            bindings.o = Some(if offset.is_empty() {
+                Some(0)
+            } else if offset.starts_with(['+', '-']) {
+                parse_imm(offset.trim_start_matches('+'))?
+            } else {
+                return None;
+            });
+            Some(3)
+        }
+        literal => {
+            let literal = literal.to_lowercase();
+            let matches = literal == operand
+                || parse_number(&literal)
+                    .is_some_and(|lit| parse_number(operand).is_some_and(|num| num == lit));
+            matches.then_some(4)
+        }
+    }
+}
+
+/// Parses an immediate operand, which is either a number or a symbolic expression.
+/// Returns `None` if `operand` can't be an immediate (e.g. a register or memory operand)
+fn parse_imm(operand: &str) -> Option<Imm> {
+    if operand.is_empty() || operand.starts_with('(') || RESERVED.contains(&operand) {
+        return None;
+    }
+
+    Some(parse_number(operand))
+}
+
+/// Parses a numeric literal, written in decimal, hex (`0x1F`, `$1F` or `1Fh`) or binary
+/// (`0b101` or `%101`)
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix('$')) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(hex) = text.strip_suffix('h') {
+        // `ch` etc. are registers/names rather than hex numbers
+        if !hex.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = text.strip_prefix("0b").or_else(|| text.strip_prefix('%')) {
+        i64::from_str_radix(bin, 2).ok()?
+    } else {
+        text.parse().ok()?
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+/// Assembles the space-separated `opcode` bytes of a form (e.g. `DD 70+r o`), substituting
+/// the values in `bindings`
+fn encode(opcode: &str, bindings: &Bindings) -> Option<String> {
+    let byte = |imm: Imm, placeholder: &str, min: i64, max: i64| -> Option<String> {
+        match imm {
+            Some(val) if (min..=max).contains(&val) => Some(format!("{:02X}", val as u8)),
+            Some(_) => None,
+            None => Some(placeholder.to_string()),
+        }
+    };
+
+    let mut bytes = Vec::new();
+    let mut nn_bytes = 0;
+    for token in opcode.split_whitespace() {
+        let assembled = match token {
+            "n" => byte(bindings.n?, "n", -128, 255)?,
+            "o" => byte(bindings.o?, "o", -128, 127)?,
+            // 16-bit immediates are stored low byte first
+            "nn" => {
+                let nn = bindings.nn?;
+                if nn.is_some_and(|nn| !(-32768..=65535).contains(&nn)) {
+                    return None;
+                }
+                let half = nn.map(|nn| (nn >> (8 * nn_bytes)) & 0xFF);
+                nn_bytes += 1;
+                byte(half, "nn", 0, 255)?
+            }
+            _ => {
+                let mut terms = token.split('+');
+                let mut value = u8::from_str_radix(terms.next()?, 16).ok()?;
+                for term in terms {
+                    let (factor, placeholder) = match term.split_once('*') {
+                        Some((factor, placeholder)) => (factor.parse::<u8>().ok()?, placeholder),
+                        None => (1, term),
+                    };
+                    let code = match placeholder {
+                        "r" => bindings.r?,
+                        "p" => bindings.p?,
+                        "q" => bindings.q?,
+                        "b" => bindings.b?,
+                        _ => return None,
+                    };
+                    value = value.checked_add(factor.checked_mul(code)?)?;
+                }
+                format!("{value:02X}")
+            }
+        };
+        bytes.push(assembled);
+    }
+
+    Some(bytes.join(" "))
+}
@@ -27,6 +27,22 @@ def parse_descr_csv(filename:str) -> pd.DataFrame:
     df = pd.read_csv(filename)
     return df
 
+# Operations spelled differently by other architectures, keyed by the z80 mnemonic. The
+# x86 instruction sets tag the same operations with these `canonical` ids
+CANONICAL_IDS = {
+    "jp": "jump",
+    "ld": "move",
+    "cpl": "complement",
+    "halt": "halt",
+    "ex": "exchange",
+    "cp": "compare",
+    "ccf": "complement-carry",
+    "scf": "set-carry",
+    "sbc": "subtract-with-borrow",
+    "di": "disable-interrupts",
+    "ei": "enable-interrupts",
+}
+
 def make_xml(df: pd.DataFrame, descr_df: pd.DataFrame, dst_filename: str):
     root = ET.Element("InstructionSet", name="z80")
     instruction_list = df["instruction"].unique().tolist()
@@ -38,6 +54,8 @@ def make_xml(df: pd.DataFrame, descr_df: pd.DataFrame, dst_filename: str):
         else:
             description = ""
         instruction_elem = ET.Element("Instruction", name=instruction_name.lower(), summary=description)
+        if instruction_name.lower() in CANONICAL_IDS:
+            instruction_elem.set("canonical", CANONICAL_IDS[instruction_name.lower()])
         dff = df[df["instruction"] == instruction_name]
         for _, row in dff.iterrows():
             # Add spaces between comma separated args so we can